@@ -1,18 +1,35 @@
 // Application lifecycle management for CCCS
 use crate::{
-    claude_detector::ClaudeDetector, config_service::ConfigService, i18n_service::I18nService,
-    monitor_service::MonitorService, settings_service::SettingsService, tray_service::TrayService,
+    claude_detector::ClaudeDetector,
+    config_service::{ConfigAccess, ConfigService},
+    directory_rules,
+    i18n_service::I18nService,
+    interpolation,
+    mcp_service::McpService,
+    monitor_service::MonitorService,
+    org_policy, profile_history, profile_metadata,
+    schedule_service::ScheduleService,
+    secret_vault::SecretVault,
+    settings_scope::SettingsScope,
+    settings_service::SettingsService,
+    subscriptions, switch_hooks, switch_journal,
+    tray_actions::{self, ProfileAction},
+    tray_service::TrayService,
     AppError, AppResult,
 };
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, Listener, Manager};
+use tauri_plugin_notification::NotificationExt;
 
 pub struct App {
     config_service: Arc<Mutex<ConfigService>>,
     tray_service: Arc<Mutex<TrayService>>,
     monitor_service: Arc<Mutex<MonitorService>>,
+    schedule_service: Arc<Mutex<ScheduleService>>,
     settings_service: Arc<Mutex<SettingsService>>,
     i18n_service: Arc<Mutex<I18nService>>,
+    secret_vault: Arc<Mutex<SecretVault>>,
+    mcp_service: Arc<Mutex<McpService>>,
     app_handle: AppHandle,
     is_initialized: bool,
 }
@@ -24,6 +41,15 @@ impl App {
 
         let settings_service = Arc::new(Mutex::new(SettingsService::new()?));
         let i18n_service = Arc::new(Mutex::new(I18nService::new()));
+        // A corrupt or unreadable vault must not keep the app from starting
+        let secret_vault = match SecretVault::open_default() {
+            Ok(vault) => vault,
+            Err(e) => {
+                log::error!("Secret vault is unavailable: {}", e);
+                SecretVault::unavailable(SecretVault::default_vault_path()?, &e)
+            }
+        };
+        let secret_vault = Arc::new(Mutex::new(secret_vault));
 
         // Get settings for monitor interval
         let monitor_interval = {
@@ -35,13 +61,21 @@ impl App {
         let config_service = Arc::new(Mutex::new(ConfigService::new(std::env::temp_dir()))); // Placeholder
         let tray_service = Arc::new(Mutex::new(TrayService::new(app_handle.clone())));
         let monitor_service = Arc::new(Mutex::new(MonitorService::new(monitor_interval)));
+        let schedule_service = Arc::new(Mutex::new(ScheduleService::new()));
+        let mcp_service = Arc::new(Mutex::new(McpService::new(
+            std::env::temp_dir(),
+            std::env::temp_dir().join(".claude.json"),
+        ))); // Placeholder
 
         Ok(Self {
             config_service,
             tray_service,
             monitor_service,
+            schedule_service,
             settings_service,
             i18n_service,
+            secret_vault,
+            mcp_service,
             app_handle,
             is_initialized: false,
         })
@@ -74,23 +108,93 @@ impl App {
             Err(e) => return Err(e),
         };
 
-        // Step 2: Validate default configuration
+        // Step 2: Recover an interrupted switch, then validate default configuration
+        match switch_journal::recover(&claude_dir) {
+            Ok(report) => {
+                if let Some(operation) = &report.rolled_back {
+                    log::warn!("Rolled back interrupted operation: {}", operation);
+                }
+                if report.removed_files > 0 {
+                    log::info!("Removed {} stale temporary files", report.removed_files);
+                }
+            }
+            Err(e) => log::error!("Failed to recover interrupted switch: {}", e),
+        }
+        {
+            let settings = self.settings_service.lock().unwrap();
+            for project in &settings.get_current_settings().projects {
+                switch_journal::sweep_settings_dir(&project.join(".claude"));
+            }
+        }
         ClaudeDetector::validate_default_config(&claude_dir)?;
 
         // Step 3: Initialize configuration service with real Claude directory
         {
             let mut config_service = self.config_service.lock().unwrap();
             *config_service = ConfigService::new(claude_dir.clone());
+            config_service.set_secret_vault(Arc::clone(&self.secret_vault));
+            config_service.set_variables_path(interpolation::default_variables_path()?);
+            config_service.set_switch_hooks_path(switch_hooks::default_hooks_path()?);
+            config_service.set_history_dir(profile_history::default_history_dir()?);
+            config_service.set_subscriptions_dir(subscriptions::default_subscriptions_dir()?);
+            config_service.set_policy_path(org_policy::default_policy_path());
+            config_service.set_metadata_path(profile_metadata::default_metadata_path()?);
+            config_service.set_recent_profiles_path(profile_metadata::default_recent_profiles_path()?);
 
             // Scan for profiles
             config_service.scan_profiles()?;
+
+            let subscriptions = self
+                .settings_service
+                .lock()
+                .unwrap()
+                .get_subscriptions()
+                .to_vec();
+            if !subscriptions.is_empty() {
+                config_service.set_subscriptions(subscriptions)?;
+            }
+
+            let trusted_keys = self
+                .settings_service
+                .lock()
+                .unwrap()
+                .get_trusted_keys()
+                .to_vec();
+            if !trusted_keys.is_empty() {
+                config_service.set_trusted_keys(trusted_keys)?;
+            }
+
+            let git_store_enabled = self
+                .settings_service
+                .lock()
+                .unwrap()
+                .get_current_settings()
+                .git_store_enabled;
+            if git_store_enabled {
+                if let Err(e) = config_service.set_git_store_enabled(true) {
+                    log::warn!("Failed to open git profile store: {}", e);
+                }
+            }
+        }
+
+        // Step 3b: MCP server sets live next to the profiles and merge into ~/.claude.json
+        {
+            let mut mcp_service = self.mcp_service.lock().unwrap();
+            *mcp_service = McpService::new(
+                claude_dir.clone(),
+                ClaudeDetector::claude_json_path(&claude_dir),
+            );
         }
 
+        // Step 3c: Enforce directory rules on registered projects
+        apply_directory_rules(&self.app_handle, &self.config_service);
+
         // Step 4: Setup file monitoring
         self.setup_monitoring().await?;
 
-        // Step 5: Create system tray
+        // Step 5: Create system tray, then start scheduled and temporary switching
         self.setup_tray().await?;
+        self.setup_schedules()?;
 
         // Step 6: Setup event listeners
         self.setup_event_listeners().await?;
@@ -157,12 +261,120 @@ impl App {
         Ok(())
     }
 
+    /// Start evaluating profile schedules on the runtime the monitor runs on
+    fn setup_schedules(&self) -> AppResult<()> {
+        log::info!("Setting up profile schedules");
+
+        let config_service = Arc::clone(&self.config_service);
+        let tray_service = Arc::clone(&self.tray_service);
+        let i18n_service = Arc::clone(&self.i18n_service);
+        let app_handle = self.app_handle.clone();
+
+        // 从 Tauri 管理的设置服务读取，命令和托盘中的修改立即生效
+        let settings_handle = self.app_handle.clone();
+        let schedules = move || {
+            settings_handle
+                .try_state::<std::sync::Mutex<SettingsService>>()
+                .and_then(|state| {
+                    state
+                        .lock()
+                        .ok()
+                        .map(|settings| settings.get_profile_schedules().to_vec())
+                })
+                .unwrap_or_default()
+        };
+
+        let on_due = move |schedule: &crate::schedule_service::ProfileSchedule| {
+            let result = switch_profile_and_refresh(
+                &config_service,
+                &tray_service,
+                &i18n_service,
+                &schedule.profile,
+            );
+
+            let warning = match result {
+                Ok(()) => tray_service.lock().ok().and_then(|tray| tray.stale_sessions_label()),
+                Err(_) => None,
+            };
+            let (title, body) = {
+                let i18n = i18n_service.lock().unwrap();
+                match result {
+                    Ok(()) => {
+                        let body = i18n.get_text_with_args(
+                            "scheduled_switch",
+                            &[&schedule.name, &schedule.profile],
+                        );
+                        match warning {
+                            Some(warning) => (i18n.get_text("profile_switched"), format!("{}\n{}", body, warning)),
+                            None => (i18n.get_text("profile_switched"), body),
+                        }
+                    }
+                    Err(e) => (
+                        i18n.get_text("switch_failed"),
+                        format!("{}: {}", schedule.name, e),
+                    ),
+                }
+            };
+            notify(&app_handle, &title, &body);
+            let _ = app_handle.emit("profiles_changed", ());
+        };
+
+        let mut schedule_service = self.schedule_service.lock().unwrap();
+        schedule_service.start(schedules, on_due)?;
+
+        // Temporary switches: revert expired leases and keep the remaining time and the
+        // running session count in the tooltip current
+        let config_service = Arc::clone(&self.config_service);
+        let tray_service = Arc::clone(&self.tray_service);
+        let i18n_service = Arc::clone(&self.i18n_service);
+        let app_handle = self.app_handle.clone();
+        schedule_service.start_lease_timer(move || {
+            let has_lease = config_service.lock().unwrap().get_switch_lease().is_some();
+            if !has_lease {
+                let label = stale_sessions_warning(&config_service, &i18n_service);
+                if let Ok(mut tray) = tray_service.lock() {
+                    tray.set_stale_sessions_label(label);
+                    let _ = tray.refresh_tooltip();
+                }
+                return;
+            }
+            // 不持有锁调用，切换钩子运行期间配置服务保持可用
+            let result = config_service.as_ref().expire_switch_lease();
+            refresh_tray(&config_service, &tray_service, &i18n_service);
+
+            match result {
+                Ok(Some(lease)) => {
+                    let warning = tray_service.lock().ok().and_then(|tray| tray.stale_sessions_label());
+                    let body = i18n_service
+                        .lock()
+                        .unwrap()
+                        .get_text_with_args("lease_ended", &[&lease.profile]);
+                    let body = match warning {
+                        Some(warning) => format!("{}\n{}", body, warning),
+                        None => body,
+                    };
+                    notify(&app_handle, "CCCS", &body);
+                    let _ = app_handle.emit("profiles_changed", ());
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::error!("Failed to revert temporary switch: {}", e);
+                    let title = i18n_service.lock().unwrap().get_text("switch_failed");
+                    notify(&app_handle, &title, &e.to_string());
+                }
+            }
+        })
+    }
+
     /// Setup system tray
     async fn setup_tray(&self) -> AppResult<()> {
         log::info!("Setting up system tray");
 
+        let claude_dir = self.config_service.lock().unwrap().get_claude_dir().to_path_buf();
         let mut tray_service = self.tray_service.lock().unwrap();
         tray_service.create_tray()?;
+        tray_service.set_claude_dir(claude_dir);
+        tray_service.set_recent_profiles_path(profile_metadata::default_recent_profiles_path()?);
 
         // Update tray menu with initial profiles and detailed status
         let (profiles, statuses) = {
@@ -172,6 +384,7 @@ impl App {
             (profiles, statuses)
         };
 
+        tray_service.set_stale_sessions_label(stale_sessions_warning(&self.config_service, &self.i18n_service));
         tray_service.update_menu_with_detailed_status(&profiles, &statuses)?;
 
         // Set tooltip
@@ -201,7 +414,7 @@ impl App {
         let config_service_clone = Arc::clone(&config_service);
         let tray_service_clone = Arc::clone(&tray_service);
         let i18n_service_clone = Arc::clone(&i18n_service);
-        let _app_handle_for_switch = self.app_handle.clone();
+        let app_handle_for_switch = self.app_handle.clone();
 
         self.app_handle
             .listen("profile_switch_requested", move |event| {
@@ -209,55 +422,117 @@ impl App {
                 if let Ok(profile_name) = serde_json::from_str::<String>(event.payload()) {
                     log::info!("Profile switch requested: {}", profile_name);
 
-                    // Show switching status
-                    if let Ok(mut tray) = tray_service_clone.lock() {
-                        let _ = tray.update_profile_status(&profile_name, "❕");
-                    }
-
-                    // Perform switch
-                    let result = {
-                        let mut config = config_service_clone.lock().unwrap();
-                        config.switch_profile(&profile_name)
-                    };
-
-                    match result {
-                        Ok(()) => {
-                            log::info!("Profile switched successfully: {}", profile_name);
-
-                            // Update tray menu with detailed status
-                            if let (Ok(config), Ok(mut tray)) =
-                                (config_service_clone.lock(), tray_service_clone.lock())
-                            {
-                                let profiles = config.get_profiles();
-                                let statuses = config.compare_profiles();
-                                let _ = tray.update_menu_with_detailed_status(profiles, &statuses);
-
-                                // Update tooltip
-                                if let Ok(i18n) = i18n_service_clone.lock() {
-                                    let active_profile = profiles
-                                        .iter()
-                                        .enumerate()
-                                        .find(|(i, _)| {
-                                            matches!(statuses[*i], crate::ProfileStatus::FullMatch)
-                                        })
-                                        .map(|(_, p)| p.name.as_str());
-                                    let tooltip =
-                                        i18n.get_tray_tooltip(profiles.len(), active_profile);
-                                    let _ = tray.set_tooltip(&tooltip);
-                                }
+                    // 切换钩子可能运行较久，不在托盘事件线程上执行
+                    let config_service = Arc::clone(&config_service_clone);
+                    let tray_service = Arc::clone(&tray_service_clone);
+                    let i18n_service = Arc::clone(&i18n_service_clone);
+                    let app_handle = app_handle_for_switch.clone();
+                    std::thread::spawn(move || {
+                        let result = switch_profile_and_refresh(
+                            &config_service,
+                            &tray_service,
+                            &i18n_service,
+                            &profile_name,
+                        );
+
+                        match result {
+                            // A pre-switch hook, the organization policy or a bad signature can veto a tray click; tell the user why
+                            Err(e @ (AppError::HookError(_) | AppError::PolicyError(_) | AppError::SignatureError(_))) => {
+                                let title = i18n_service.lock().unwrap().get_text("switch_failed");
+                                notify(&app_handle, &title, &e.to_string());
                             }
+                            Ok(()) => warn_stale_sessions(&app_handle, &tray_service),
+                            Err(_) => {}
                         }
-                        Err(e) => {
-                            log::error!("Failed to switch profile {}: {}", profile_name, e);
+                    });
+                }
+            });
 
-                            // Reset status on error
-                            if let Ok(mut tray) = tray_service_clone.lock() {
-                                let _ = tray.update_profile_status(&profile_name, "");
-                            }
-                        }
-                    }
+        // Listen for temporary switch requests from tray
+        let config_service_clone = Arc::clone(&config_service);
+        let tray_service_clone = Arc::clone(&tray_service);
+        let i18n_service_clone = Arc::clone(&i18n_service);
+        let app_handle_for_lease = self.app_handle.clone();
+        self.app_handle.listen("lease_switch_requested", move |event| {
+            let Ok((profile_name, minutes)) = serde_json::from_str::<(String, u64)>(event.payload())
+            else {
+                return;
+            };
+            log::info!("Temporary switch requested: {} for {} minutes", profile_name, minutes);
+
+            let config_service = Arc::clone(&config_service_clone);
+            let tray_service = Arc::clone(&tray_service_clone);
+            let i18n_service = Arc::clone(&i18n_service_clone);
+            let app_handle = app_handle_for_lease.clone();
+            std::thread::spawn(move || {
+                let result = config_service.as_ref().switch_profile_temporarily(
+                    &profile_name,
+                    std::time::Duration::from_secs(minutes * 60),
+                );
+                refresh_tray(&config_service, &tray_service, &i18n_service);
+                match result {
+                    Ok(_) => warn_stale_sessions(&app_handle, &tray_service),
+                    Err(e) => log::error!("Failed to switch temporarily to {}: {}", profile_name, e),
+                }
+            });
+        });
+
+        let config_service_clone = Arc::clone(&config_service);
+        let tray_service_clone = Arc::clone(&tray_service);
+        let i18n_service_clone = Arc::clone(&i18n_service);
+        let app_handle_for_revert = self.app_handle.clone();
+        self.app_handle.listen("lease_revert_requested", move |_| {
+            log::info!("Temporary switch revert requested");
+
+            let config_service = Arc::clone(&config_service_clone);
+            let tray_service = Arc::clone(&tray_service_clone);
+            let i18n_service = Arc::clone(&i18n_service_clone);
+            let app_handle = app_handle_for_revert.clone();
+            std::thread::spawn(move || {
+                let result = config_service.as_ref().end_switch_lease(true);
+                refresh_tray(&config_service, &tray_service, &i18n_service);
+                match result {
+                    Ok(Some(_)) => warn_stale_sessions(&app_handle, &tray_service),
+                    Ok(None) => {}
+                    Err(e) => log::error!("Failed to revert temporary switch: {}", e),
                 }
             });
+        });
+
+        // Listen for schedule suspend/resume toggles from tray
+        let config_service_clone = Arc::clone(&config_service);
+        let tray_service_clone = Arc::clone(&tray_service);
+        let app_handle_for_schedules = self.app_handle.clone();
+        self.app_handle.listen("schedule_toggle_requested", move |event| {
+            let Ok(name) = serde_json::from_str::<String>(event.payload()) else {
+                return;
+            };
+            log::info!("Schedule toggle requested: {}", name);
+
+            let Some(state) =
+                app_handle_for_schedules.try_state::<std::sync::Mutex<SettingsService>>()
+            else {
+                return;
+            };
+            if let Ok(mut settings) = state.lock() {
+                let suspended = settings
+                    .get_profile_schedules()
+                    .iter()
+                    .any(|s| s.name == name && s.suspended);
+                if let Err(e) = settings.set_schedule_suspended(&name, !suspended) {
+                    log::error!("Failed to toggle schedule {}: {}", name, e);
+                    return;
+                }
+            }
+
+            if let (Ok(config), Ok(mut tray)) =
+                (config_service_clone.lock(), tray_service_clone.lock())
+            {
+                let profiles = config.get_profiles();
+                let statuses = config.compare_profiles();
+                let _ = tray.update_menu_with_detailed_status(profiles, &statuses);
+            }
+        });
 
         // Listen for settings menu clicks
         let app_handle_clone = self.app_handle.clone();
@@ -268,10 +543,11 @@ impl App {
             let _ = app_handle_clone.emit("open_settings_window", ());
         });
 
-        // Listen for open settings window requests
+        // Listen for open settings window requests; the payload names a profile to focus, if any
         let app_handle_clone2 = self.app_handle.clone();
-        self.app_handle.listen("open_settings_window", move |_| {
-            log::info!("Opening settings window");
+        self.app_handle.listen("open_settings_window", move |event| {
+            let profile = serde_json::from_str::<Option<String>>(event.payload()).ok().flatten();
+            log::info!("Opening settings window (profile: {:?})", profile);
 
             // Check if settings window already exists and is visible
             if let Some(window) = app_handle_clone2.get_webview_window("settings") {
@@ -282,6 +558,9 @@ impl App {
                         log::info!("Successfully showed existing settings window");
                         let _ = window.set_focus();
                         let _ = window.unminimize(); // In case it was minimized
+                        if let Some(profile) = &profile {
+                            let _ = window.emit("focus_profile", profile);
+                        }
                         return;
                     }
                     Err(e) => {
@@ -341,7 +620,7 @@ impl App {
             match tauri::WebviewWindowBuilder::new(
                 &app_handle_clone2,
                 "settings",
-                tauri::WebviewUrl::App("settings.html".into()),
+                tauri::WebviewUrl::App(tray_actions::settings_url(profile.as_deref()).into()),
             )
             .title("CCCS Settings")
             .inner_size(window_width, window_height)
@@ -384,6 +663,59 @@ impl App {
             }
         });
 
+        // Listen for Diff, Duplicate, Copy env and Reveal from a profile's tray submenu
+        let config_service_clone = Arc::clone(&config_service);
+        let tray_service_clone = Arc::clone(&tray_service);
+        let i18n_service_clone = Arc::clone(&i18n_service);
+        let app_handle_for_actions = self.app_handle.clone();
+        self.app_handle.listen("profile_action_requested", move |event| {
+            let Ok((action, profile_name)) =
+                serde_json::from_str::<(ProfileAction, String)>(event.payload())
+            else {
+                return;
+            };
+            log::info!("Profile action requested: {} {}", action.as_str(), profile_name);
+
+            let result = run_profile_action(&config_service_clone, &i18n_service_clone, action, &profile_name);
+            if action == ProfileAction::Duplicate {
+                refresh_tray(&config_service_clone, &tray_service_clone, &i18n_service_clone);
+            }
+            let (title, body) = {
+                let i18n = i18n_service_clone.lock().unwrap();
+                match result {
+                    Ok(Some(message)) => (
+                        i18n.get_text_with_args("profile_action_done", &[&profile_name]),
+                        message,
+                    ),
+                    Ok(None) => return,
+                    Err(e) => {
+                        log::error!("Profile action {} failed for {}: {}", action.as_str(), profile_name, e);
+                        (i18n.get_text("profile_action_failed"), e.to_string())
+                    }
+                }
+            };
+            notify(&app_handle_for_actions, &title, &body);
+        });
+
+        // Re-apply directory rules when the rules or the registered projects change
+        let config_service_clone = Arc::clone(&config_service);
+        let tray_service_clone = Arc::clone(&tray_service);
+        let i18n_service_clone = Arc::clone(&i18n_service);
+        let app_handle_for_rules = self.app_handle.clone();
+        self.app_handle.listen(crate::settings_service::DIRECTORY_RULES_CHANGED_EVENT, move |_| {
+            log::info!("Directory rules changed, re-applying");
+            let config_service = Arc::clone(&config_service_clone);
+            let tray_service = Arc::clone(&tray_service_clone);
+            let i18n_service = Arc::clone(&i18n_service_clone);
+            let app_handle = app_handle_for_rules.clone();
+            // 切换可能运行钩子，不阻塞发出事件的命令
+            std::thread::spawn(move || {
+                if apply_directory_rules(&app_handle, &config_service) {
+                    refresh_tray(&config_service, &tray_service, &i18n_service);
+                }
+            });
+        });
+
         // Listen for tray icon hover events
         let config_service = Arc::clone(&self.config_service);
         let tray_service = Arc::clone(&self.tray_service);
@@ -409,13 +741,17 @@ impl App {
 
         // Listen for app exit requests
         let monitor_service = Arc::clone(&self.monitor_service);
+        let schedule_service = Arc::clone(&self.schedule_service);
         self.app_handle.listen("app_exit_requested", move |_| {
             log::info!("Application exit requested");
 
-            // Stop monitoring
+            // Stop monitoring and schedules
             if let Ok(mut monitor) = monitor_service.lock() {
                 monitor.stop_monitoring();
             }
+            if let Ok(mut schedules) = schedule_service.lock() {
+                schedules.stop();
+            }
 
             log::info!("Application cleanup completed");
         });
@@ -444,6 +780,7 @@ impl App {
             let mut monitor_service = self.monitor_service.lock().unwrap();
             monitor_service.stop_monitoring();
         }
+        self.schedule_service.lock().unwrap().stop();
 
         // Clean up resources
         log::info!("Application shutdown completed");
@@ -462,6 +799,16 @@ impl App {
         Arc::clone(&self.config_service)
     }
 
+    /// Get reference to MCP service
+    pub fn get_mcp_service(&self) -> Arc<Mutex<McpService>> {
+        Arc::clone(&self.mcp_service)
+    }
+
+    /// Get reference to secret vault
+    pub fn get_secret_vault(&self) -> Arc<Mutex<SecretVault>> {
+        Arc::clone(&self.secret_vault)
+    }
+
     /// Get reference to settings service for testing
     #[cfg(test)]
     pub fn get_settings_service(&self) -> Arc<Mutex<SettingsService>> {
@@ -474,9 +821,11 @@ impl App {
         
         let config_service = Arc::clone(&self.config_service);
         let tray_service = Arc::clone(&self.tray_service);
+        let stale_sessions = stale_sessions_warning(&config_service, &self.i18n_service);
         
         if let Ok(config) = config_service.lock() {
             if let Ok(mut tray) = tray_service.lock() {
+                tray.set_stale_sessions_label(stale_sessions);
                 let profiles = config.get_profiles();
                 let statuses = config.compare_profiles();
                 if let Err(e) = tray.update_menu_with_detailed_status(profiles, &statuses) {
@@ -493,6 +842,196 @@ impl App {
     }
 }
 
+/// Carry out a tray profile action; returns the message to show, if any
+fn run_profile_action(
+    config_service: &Arc<Mutex<ConfigService>>,
+    i18n_service: &Arc<Mutex<I18nService>>,
+    action: ProfileAction,
+    profile_name: &str,
+) -> AppResult<Option<String>> {
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err(AppError::ConfigError("Failed to access configuration service".to_string()));
+        }
+    };
+    match action {
+        ProfileAction::Diff => Ok(Some(tray_actions::diff_summary(&config.diff_with_current(profile_name)?))),
+        ProfileAction::Duplicate => {
+            let new_name = config.duplicate_profile(profile_name)?;
+            drop(config);
+            let i18n = i18n_service.lock().unwrap();
+            Ok(Some(i18n.get_text_with_args("profile_duplicated", &[&new_name])))
+        }
+        ProfileAction::CopyEnv => {
+            let (script, count) = config.get_env_exports(profile_name)?;
+            drop(config); // 复制到剪贴板时不持有配置锁
+            tray_actions::copy_to_clipboard(&script)?;
+            let i18n = i18n_service.lock().unwrap();
+            Ok(Some(i18n.get_text_with_args("env_copied", &[&count.to_string()])))
+        }
+        ProfileAction::Reveal => {
+            let location = config.get_profile_location(profile_name)?;
+            drop(config);
+            tray_actions::reveal_in_file_manager(&location)?;
+            Ok(None)
+        }
+        // 切换和编辑由托盘直接处理
+        ProfileAction::Switch | ProfileAction::Edit => Ok(None),
+    }
+}
+
+/// Apply the directory rules of the Tauri-managed settings service to every registered
+/// project; returns whether any rule was applied
+fn apply_directory_rules(app_handle: &AppHandle, config_service: &Arc<Mutex<ConfigService>>) -> bool {
+    // 从 Tauri 管理的设置服务读取，命令中的修改立即生效
+    let Some(state) = app_handle.try_state::<std::sync::Mutex<SettingsService>>() else {
+        return false;
+    };
+    let planned = match state.lock() {
+        Ok(settings) => {
+            directory_rules::plan(settings.get_directory_rules(), settings.get_projects(), None)
+        }
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return false;
+        }
+    };
+    if planned.is_empty() {
+        return false;
+    }
+
+    let results = directory_rules::apply(config_service, &planned);
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    log::info!(
+        "Applied {} directory rules ({} failed)",
+        results.len() - failed,
+        failed
+    );
+    true
+}
+
+/// Switch the user profile and refresh the tray menu and tooltip; shared by tray clicks
+/// and scheduled switches
+fn switch_profile_and_refresh(
+    config_service: &Arc<Mutex<ConfigService>>,
+    tray_service: &Arc<Mutex<TrayService>>,
+    i18n_service: &Arc<Mutex<I18nService>>,
+    profile_name: &str,
+) -> AppResult<()> {
+    // Show switching status
+    if let Ok(mut tray) = tray_service.lock() {
+        let _ = tray.update_profile_status(profile_name, "❕");
+    }
+
+    // Perform switch
+    // 每一步单独加锁，钩子运行时不持有配置服务锁
+    let result = config_service.as_ref().switch_profile(profile_name);
+
+    match &result {
+        Ok(()) => {
+            log::info!("Profile switched successfully: {}", profile_name);
+            refresh_tray(config_service, tray_service, i18n_service);
+        }
+        Err(e) => {
+            log::error!("Failed to switch profile {}: {}", profile_name, e);
+
+            // Reset status on error
+            if let Ok(mut tray) = tray_service.lock() {
+                let _ = tray.update_profile_status(profile_name, "");
+            }
+        }
+    }
+
+    result
+}
+
+/// Rebuild the tray menu and tooltip from the current profile status
+fn refresh_tray(
+    config_service: &Arc<Mutex<ConfigService>>,
+    tray_service: &Arc<Mutex<TrayService>>,
+    i18n_service: &Arc<Mutex<I18nService>>,
+) {
+    // 每次刷新只扫描一次进程，菜单和提示都使用这个结果
+    let stale_sessions = stale_sessions_warning(config_service, i18n_service);
+
+    // Update tray menu with detailed status
+    if let (Ok(config), Ok(mut tray)) = (config_service.lock(), tray_service.lock()) {
+        tray.set_stale_sessions_label(stale_sessions);
+        let profiles = config.get_profiles();
+        let statuses = config.compare_profiles();
+        let _ = tray.update_menu_with_detailed_status(profiles, &statuses);
+
+        // Update tooltip
+        if let Ok(i18n) = i18n_service.lock() {
+            let active_profile = profiles
+                .iter()
+                .enumerate()
+                .find(|(i, _)| matches!(statuses[*i], crate::ProfileStatus::FullMatch))
+                .map(|(_, p)| p.name.as_str());
+            let tooltip = i18n.get_tray_tooltip(profiles.len(), active_profile);
+            let _ = tray.set_tooltip(&tooltip);
+        }
+    }
+}
+
+/// "N sessions still use the previous profile" for Claude Code sessions that started
+/// before the user settings changed
+fn stale_sessions_warning(
+    config_service: &Arc<Mutex<ConfigService>>,
+    i18n_service: &Arc<Mutex<I18nService>>,
+) -> Option<String> {
+    // 先释放配置锁再取 i18n 锁，与 refresh_tray 的加锁顺序保持一致
+    let count = config_service
+        .lock()
+        .ok()?
+        .stale_sessions(&SettingsScope::User)
+        .len();
+    let i18n = i18n_service.lock().ok()?;
+    match count {
+        0 => None,
+        1 => Some(i18n.get_text("stale_session")),
+        _ => Some(i18n.get_text_with_args("stale_sessions", &[&count.to_string()])),
+    }
+}
+
+/// Tell the user about sessions that keep running with the previous profile, as found
+/// by the last tray refresh
+fn warn_stale_sessions(app_handle: &AppHandle, tray_service: &Arc<Mutex<TrayService>>) {
+    let warning = tray_service.lock().ok().and_then(|tray| tray.stale_sessions_label());
+    if let Some(warning) = warning {
+        log::warn!("{}", warning);
+        notify(app_handle, "CCCS", &warning);
+    }
+}
+
+/// Show a desktop notification unless notifications are turned off in the settings
+fn notify(app_handle: &AppHandle, title: &str, body: &str) {
+    let enabled = app_handle
+        .try_state::<std::sync::Mutex<SettingsService>>()
+        .and_then(|state| {
+            state
+                .lock()
+                .ok()
+                .map(|settings| settings.get_current_settings().show_notifications)
+        })
+        .unwrap_or(true);
+    if !enabled {
+        return;
+    }
+
+    if let Err(e) = app_handle
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+    {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
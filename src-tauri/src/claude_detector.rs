@@ -22,6 +22,15 @@ impl ClaudeDetector {
         }
     }
     
+    /// Locate the global Claude config file (~/.claude.json) for a Claude directory.
+    /// A custom directory (CLAUDE_CONFIG_DIR) keeps the file inside itself.
+    pub fn claude_json_path(claude_dir: &Path) -> PathBuf {
+        match dirs::home_dir() {
+            Some(home_dir) if claude_dir == home_dir.join(".claude") => home_dir.join(".claude.json"),
+            _ => claude_dir.join(".claude.json"),
+        }
+    }
+
     /// Validate that the default settings.json file exists
     pub fn validate_default_config(claude_dir: &Path) -> AppResult<()> {
         let settings_file = claude_dir.join("settings.json");
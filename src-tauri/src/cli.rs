@@ -0,0 +1,384 @@
+// Command line subcommands, run instead of the tray app when cccs is started with arguments
+use crate::claude_detector::ClaudeDetector;
+use crate::directory_rules::RuleDryRun;
+use crate::effective_settings::{EffectiveSettings, SettingsResolver};
+use crate::file_permissions;
+use crate::profile_bundle::BUNDLE_SETTINGS_FILE;
+use crate::profile_signing::{self, SignatureState, TrustedKey};
+use crate::settings_service::SettingsService;
+use crate::{AppError, AppResult};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage:
+  cccs effective [--cwd <dir>] [--settings <file|json>] [--managed <file>] [--json] [--show-secrets]
+      Show the settings Claude Code runs with in <dir> (default: current directory)
+      and the layer each key comes from. --managed replaces the system managed-settings.json.
+  cccs rules [<path>] [--json]
+      Show which directory rule matches <path> (default: current directory) without applying it.
+  cccs keygen <key-file>
+      Create an ed25519 signing key (owner-only file) and print its public key.
+  cccs sign <profile> --key <key-file>
+      Write <profile>.sig. <profile> is a .settings.json file or a .profile directory.
+  cccs verify <profile> [--key <public-key>]...
+      Check a profile's signature against the given public keys (default: the trusted keys in settings).
+  cccs help";
+
+/// Run a CLI subcommand. Returns `None` when the arguments are not a CLI invocation
+/// (no subcommand), in which case the tray app should start.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+
+    let result = match command.as_str() {
+        "effective" => effective(rest),
+        "rules" => rules(rest),
+        "keygen" => keygen(rest),
+        "sign" => sign(rest),
+        "verify" => verify(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        // Arguments added by the OS or a launcher (e.g. macOS -psn_*) start the tray app
+        other if other.starts_with('-') => return None,
+        other => Err(AppError::ConfigError(format!(
+            "Unknown command '{}'\n\n{}",
+            other, USAGE
+        ))),
+    };
+
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("cccs: {}", e);
+            Some(1)
+        }
+    }
+}
+
+fn effective(args: &[String]) -> AppResult<()> {
+    let mut working_dir = None;
+    let mut cli_settings = None;
+    let mut managed_settings = None;
+    let mut as_json = false;
+    let mut show_secrets = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cwd" => working_dir = Some(PathBuf::from(required_value(&mut args, "--cwd")?)),
+            "--settings" => cli_settings = Some(required_value(&mut args, "--settings")?),
+            "--managed" => {
+                managed_settings = Some(PathBuf::from(required_value(&mut args, "--managed")?))
+            }
+            "--json" => as_json = true,
+            "--show-secrets" => show_secrets = true,
+            other => {
+                return Err(AppError::ConfigError(format!(
+                    "Unknown option '{}'\n\n{}",
+                    other, USAGE
+                )))
+            }
+        }
+    }
+
+    let working_dir = match working_dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let claude_dir = ClaudeDetector::detect_claude_installation()?;
+    let mut resolver = SettingsResolver::new(claude_dir);
+    if let Some(path) = managed_settings {
+        resolver = resolver.with_managed_settings_path(path);
+    }
+    let mut effective = resolver.resolve(&working_dir, cli_settings.as_deref())?;
+
+    if !show_secrets {
+        mask_secrets(&mut effective);
+    }
+
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&effective)?);
+    } else {
+        print_effective(&effective);
+    }
+    Ok(())
+}
+
+fn rules(args: &[String]) -> AppResult<()> {
+    let mut path = None;
+    let mut as_json = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            other if other.starts_with('-') => {
+                return Err(AppError::ConfigError(format!(
+                    "Unknown option '{}'\n\n{}",
+                    other, USAGE
+                )))
+            }
+            other => path = Some(PathBuf::from(other)),
+        }
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    let settings = SettingsService::new()?;
+    let result = settings.dry_run_directory_rules(&path);
+
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        print_dry_run(&result, &settings);
+    }
+    Ok(())
+}
+
+fn keygen(args: &[String]) -> AppResult<()> {
+    let [path] = args else {
+        return Err(AppError::ConfigError(format!("keygen needs a key file\n\n{}", USAGE)));
+    };
+    let public_key = profile_signing::generate_signing_key(Path::new(path))?;
+    println!("Private key written to {}", path);
+    println!("Public key (add it to the trusted keys): {}", public_key);
+    Ok(())
+}
+
+fn sign(args: &[String]) -> AppResult<()> {
+    let mut profile = None;
+    let mut key_file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key_file = Some(PathBuf::from(required_value(&mut args, "--key")?)),
+            other if other.starts_with('-') => {
+                return Err(AppError::ConfigError(format!(
+                    "Unknown option '{}'\n\n{}",
+                    other, USAGE
+                )))
+            }
+            other => profile = Some(PathBuf::from(other)),
+        }
+    }
+
+    let (Some(profile), Some(key_file)) = (profile, key_file) else {
+        return Err(AppError::ConfigError(format!("sign needs a profile and --key\n\n{}", USAGE)));
+    };
+    let key = profile_signing::load_signing_key(&key_file)?;
+    let (settings_path, bundle_dir) = profile_files(&profile);
+    let signature = profile_signing::sign_profile(&settings_path, bundle_dir.as_deref(), &key)?;
+    println!("Signature written to {}", signature.display());
+    Ok(())
+}
+
+fn verify(args: &[String]) -> AppResult<()> {
+    let mut profile = None;
+    let mut keys = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => keys.push(TrustedKey {
+                name: format!("--key #{}", keys.len() + 1),
+                public_key: required_value(&mut args, "--key")?,
+            }),
+            other if other.starts_with('-') => {
+                return Err(AppError::ConfigError(format!(
+                    "Unknown option '{}'\n\n{}",
+                    other, USAGE
+                )))
+            }
+            other => profile = Some(PathBuf::from(other)),
+        }
+    }
+
+    let Some(profile) = profile else {
+        return Err(AppError::ConfigError(format!("verify needs a profile\n\n{}", USAGE)));
+    };
+    if keys.is_empty() {
+        keys = SettingsService::new()?.get_trusted_keys().to_vec();
+    }
+    for key in &keys {
+        key.validate()?;
+    }
+    if keys.is_empty() {
+        return Err(AppError::ConfigError(
+            "No trusted keys configured; pass --key <public-key>".to_string(),
+        ));
+    }
+
+    let (settings_path, bundle_dir) = profile_files(&profile);
+    match profile_signing::verify_profile(&settings_path, bundle_dir.as_deref(), &keys)? {
+        SignatureState::Valid(name) => {
+            println!("{}: signature OK (key: {})", profile.display(), name);
+            Ok(())
+        }
+        SignatureState::Unsigned => Err(AppError::SignatureError(format!(
+            "{} is not signed",
+            profile.display()
+        ))),
+        SignatureState::Invalid(reason) => Err(AppError::SignatureError(format!(
+            "{}: {}",
+            profile.display(),
+            reason
+        ))),
+        SignatureState::NotChecked => Ok(()),
+    }
+}
+
+/// Settings file and bundle directory of a profile given as a file or a `.profile` directory
+fn profile_files(profile: &Path) -> (PathBuf, Option<PathBuf>) {
+    if profile.is_dir() {
+        (profile.join(BUNDLE_SETTINGS_FILE), Some(profile.to_path_buf()))
+    } else {
+        (profile.to_path_buf(), None)
+    }
+}
+
+fn print_dry_run(result: &RuleDryRun, settings: &SettingsService) {
+    let rules = settings.get_directory_rules();
+    println!("Path: {}", result.path.display());
+
+    for &index in &result.matching_rules {
+        let rule = &rules[index];
+        let marker = if Some(index) == result.rule { "*" } else { " " };
+        println!("  {} #{} {} -> {}", marker, index, rule.pattern, rule.profile);
+    }
+
+    if let (Some(profile), Some(scope)) = (&result.profile, &result.scope) {
+        println!("Would apply '{}' to {}", profile, scope.label());
+    }
+    if let Some(note) = &result.note {
+        println!("{}", note);
+    }
+}
+
+fn required_value(args: &mut std::slice::Iter<'_, String>, option: &str) -> AppResult<String> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| AppError::ConfigError(format!("{} needs a value", option)))
+}
+
+fn print_effective(effective: &EffectiveSettings) {
+    println!("Working directory: {}", effective.working_dir);
+    if let Some(project_dir) = &effective.project_dir {
+        println!("Project:           {}", project_dir);
+    }
+
+    println!("\nLayers (highest precedence first):");
+    for source in &effective.layers {
+        let state = match (&source.error, source.found) {
+            (Some(error), _) => format!("ignored: {}", error),
+            (None, true) => "loaded".to_string(),
+            (None, false) => "not found".to_string(),
+        };
+        let location = source.path.as_deref().unwrap_or("-");
+        println!("  {:<15} {:<10} {}", source.layer.label(), state, location);
+    }
+
+    println!("\nEffective settings:");
+    let width = effective.keys.iter().map(|k| k.key.len()).max().unwrap_or(0);
+    for key in &effective.keys {
+        let mut line = format!(
+            "  {:<width$}  {}  [{}]",
+            key.key,
+            key.value,
+            key.layer.label(),
+            width = width
+        );
+        if !key.overridden.is_empty() {
+            let overridden: Vec<&str> = key.overridden.iter().map(|l| l.label()).collect();
+            line.push_str(&format!(" (also in {})", overridden.join(", ")));
+        }
+        println!("{}", line);
+    }
+}
+
+/// Hide credential values; the CLI output often ends up in terminals and bug reports
+fn mask_secrets(effective: &mut EffectiveSettings) {
+    for key in &mut effective.keys {
+        let name = key.key.rsplit('.').next().unwrap_or(&key.key);
+        if file_permissions::is_secret_key(name) && key.value.is_string() {
+            key.value = serde_json::Value::String("********".to_string());
+        }
+    }
+    mask_value(&mut effective.settings);
+}
+
+fn mask_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, item) in map.iter_mut() {
+                if item.is_string() && file_permissions::is_secret_key(name) {
+                    *item = serde_json::Value::String("********".to_string());
+                } else {
+                    mask_value(item);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(mask_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_subcommand_starts_tray() {
+        assert_eq!(run_cli(&[]), None);
+        assert_eq!(run_cli(&["-psn_0_12345".to_string()]), None);
+        assert_eq!(run_cli(&["help".to_string()]), Some(0));
+        assert_eq!(run_cli(&["bogus".to_string()]), Some(1));
+        assert_eq!(
+            run_cli(&["rules".to_string(), "--bogus".to_string()]),
+            Some(1)
+        );
+        assert_eq!(
+            run_cli(&["effective".to_string(), "--cwd".to_string()]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_commands() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("platform.key");
+        let profile = temp_dir.path().join("work.settings.json");
+        std::fs::write(&profile, r#"{"model": "opus"}"#).unwrap();
+        let arg = |path: &Path| path.to_string_lossy().to_string();
+
+        assert_eq!(run_cli(&["keygen".to_string(), arg(&key_file)]), Some(0));
+        let public_key =
+            profile_signing::load_signing_key(&key_file).unwrap().verifying_key();
+        let public_key = base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            public_key.as_bytes(),
+        );
+        let verify_args = vec!["verify".to_string(), arg(&profile), "--key".to_string(), public_key];
+
+        assert_eq!(run_cli(&verify_args), Some(1));
+        assert_eq!(
+            run_cli(&["sign".to_string(), arg(&profile), "--key".to_string(), arg(&key_file)]),
+            Some(0)
+        );
+        assert_eq!(run_cli(&verify_args), Some(0));
+
+        std::fs::write(&profile, r#"{"model": "sonnet"}"#).unwrap();
+        assert_eq!(run_cli(&verify_args), Some(1));
+    }
+
+    #[test]
+    fn test_mask_value() {
+        let mut value = serde_json::json!({
+            "env": {"ANTHROPIC_AUTH_TOKEN": "sk-123", "ANTHROPIC_BASE_URL": "https://x"}
+        });
+        mask_value(&mut value);
+        assert_eq!(value["env"]["ANTHROPIC_AUTH_TOKEN"], "********");
+        assert_eq!(value["env"]["ANTHROPIC_BASE_URL"], "https://x");
+    }
+}
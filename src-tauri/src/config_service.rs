@@ -1,13 +1,41 @@
 // Configuration service for managing Claude Code profiles
+use crate::file_permissions;
+use crate::git_store::{GitCommit, GitStore};
+use crate::hooks_manager::{self, HookEntry, HooksRule};
+use crate::interpolation::{self, InterpolationContext, Placeholder};
+use crate::org_policy::{OrgPolicy, PolicyViolation};
+use crate::permissions_editor::ProfilePermissions;
+use crate::process_inspector::{self, ClaudeSession};
+use crate::profile_history::{self, ProfileDiff, ProfileHistory, ProfileVersion};
+use crate::profile_metadata::{self, MetadataStore, ProfileMetadata, RecentProfiles};
+use crate::profile_archive::{
+    self, ArchiveManifest, ArchivedProfile, ExportProfile, ImportAction, ImportCandidate, ImportChoice,
+    ImportPreview, SecretHandling,
+};
+use crate::profile_bundle::{self, BundleTransaction, ProfileBundle};
+use crate::profile_signing::{self, SignatureState, TrustedKey};
+use crate::profile_templates::{self, ProfileTemplate};
+use crate::secret_vault::SecretVault;
+use crate::settings_scope::SettingsScope;
+use crate::subscriptions::{self, ProfileSubscription, SubscriptionStatus, SubscriptionStore};
+use crate::switch_hooks::{self, HookContext, HookRun, HookStage, SwitchHooksConfig};
+use crate::switch_journal::SwitchJournal;
+use crate::switch_lease::{self, PreviousState, SwitchLease};
+use crate::tray_actions::{self, KeyChange};
 use crate::validation::JsonValidator;
 use crate::{
-    AppError, AppResult, FileMetadata, Profile, ProfileInfo, ProfileStatus, ValidationResult,
+    AppError, AppResult, FileMetadata, PermissionFixResult, Profile, ProfileInfo, ProfileStatus,
+    ValidationError, ValidationResult,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
+// 项目设置的备份放在 Claude 目录下，避免被提交到项目仓库
+const PROJECT_BACKUP_DIR: &str = ".cccs-project-backups";
+
 // Cache for profile metadata to improve performance
 #[derive(Clone, Debug)]
 struct ProfileCache {
@@ -26,6 +54,28 @@ pub struct ConfigService {
     cache_ttl: Duration,
     // Validation framework
     validator: JsonValidator,
+    // Vault used to resolve ${secret:name} references at switch time
+    secret_vault: Option<Arc<Mutex<SecretVault>>>,
+    // User variables file used to resolve ${var:name} placeholders
+    variables_path: Option<PathBuf>,
+    // Pre/post switch hook commands (switch_hooks.json)
+    switch_hooks_path: Option<PathBuf>,
+    last_hook_runs: Vec<HookRun>,
+    // Version history of saved profiles; disabled when not set
+    history_dir: Option<PathBuf>,
+    // Record profile changes and switches as commits in a git repository in claude_dir
+    git_store_enabled: bool,
+    // Shared team folders whose profiles are listed read-only next to the local ones
+    subscriptions: Vec<ProfileSubscription>,
+    subscriptions_dir: Option<PathBuf>,
+    // Organization policy every profile is checked against; none when not set
+    policy_path: Option<PathBuf>,
+    // Public keys whose signatures are accepted; when set, only signed profiles can be applied
+    trusted_keys: Vec<TrustedKey>,
+    // Display metadata (description, tags, color, icon, order) kept outside the profile files
+    metadata_path: Option<PathBuf>,
+    // Profiles recently switched to, listed at the top of the tray menu
+    recent_profiles_path: Option<PathBuf>,
 }
 
 impl ConfigService {
@@ -38,8 +88,576 @@ impl ConfigService {
             profile_cache: HashMap::new(),
             default_settings_cache: None,
             cache_ttl: Duration::from_secs(60), // 1 minute cache TTL
-            validator: JsonValidator::with_basic_rules(),
+            validator: Self::create_validator(),
+            secret_vault: None,
+            variables_path: None,
+            switch_hooks_path: None,
+            last_hook_runs: Vec::new(),
+            history_dir: None,
+            git_store_enabled: false,
+            subscriptions: Vec::new(),
+            subscriptions_dir: None,
+            policy_path: None,
+            trusted_keys: Vec::new(),
+            metadata_path: None,
+            recent_profiles_path: None,
+        }
+    }
+
+    fn create_validator() -> JsonValidator {
+        let mut validator = JsonValidator::with_basic_rules();
+        validator.add_rule(Box::new(HooksRule));
+        validator
+    }
+
+    /// Attach the secret vault used to resolve ${secret:name} references
+    pub fn set_secret_vault(&mut self, vault: Arc<Mutex<SecretVault>>) {
+        self.secret_vault = Some(vault);
+        self.clear_cache();
+    }
+
+    /// Set the user variables file used to resolve ${var:name} placeholders
+    pub fn set_variables_path(&mut self, path: PathBuf) {
+        self.variables_path = Some(path);
+        self.clear_cache();
+    }
+
+    /// Set the hooks file whose commands run around profile switches
+    pub fn set_switch_hooks_path(&mut self, path: PathBuf) {
+        self.switch_hooks_path = Some(path);
+    }
+
+    /// Set the directory where every saved profile version is kept
+    pub fn set_history_dir(&mut self, dir: PathBuf) {
+        self.history_dir = Some(dir);
+    }
+
+    fn history(&self) -> AppResult<ProfileHistory> {
+        self.history_dir
+            .clone()
+            .map(ProfileHistory::new)
+            .ok_or_else(|| AppError::ConfigError("Profile history is not configured".to_string()))
+    }
+
+    /// Turn the git-backed profile store on or off. Enabling it creates the repository
+    /// if needed and commits the existing profiles.
+    pub fn set_git_store_enabled(&mut self, enabled: bool) -> AppResult<()> {
+        if enabled {
+            let store = GitStore::open_or_init(&self.claude_dir)?;
+            let paths: Vec<PathBuf> = self
+                .profiles
+                .iter()
+                .flat_map(|p| Self::profile_git_paths(&p.name))
+                .collect();
+            store.commit(&paths, "Track existing profiles", false)?;
+        }
+        self.git_store_enabled = enabled;
+        log::info!("Git profile store {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    /// A profile is either a flat file or a bundle directory; both are staged so a
+    /// profile that changed form is recorded correctly
+    fn profile_git_paths(profile_name: &str) -> Vec<PathBuf> {
+        vec![
+            PathBuf::from(format!("{}.settings.json", profile_name)),
+            PathBuf::from(format!("{}{}", profile_name, profile_bundle::BUNDLE_SUFFIX)),
+        ]
+    }
+
+    /// Commit a change when the git store is enabled; failures never undo the change
+    fn git_commit(&self, profile_name: Option<&str>, message: &str) {
+        if !self.git_store_enabled {
+            return;
+        }
+        let paths = profile_name.map(Self::profile_git_paths).unwrap_or_default();
+        // 切换不改动配置文件，记录为空提交
+        let result = GitStore::open_or_init(&self.claude_dir)
+            .and_then(|store| store.commit(&paths, message, profile_name.is_none()));
+        if let Err(e) = result {
+            log::warn!("Failed to commit '{}': {}", message, e);
+        }
+    }
+
+    /// Commits of one profile, or of the whole store (including switches) when `None`
+    pub fn get_git_log(&self, profile_id: Option<&str>) -> AppResult<Vec<GitCommit>> {
+        let store = self.require_git_store()?;
+        let paths = profile_id.map(Self::profile_git_paths).unwrap_or_default();
+        store.log(&paths, 200)
+    }
+
+    /// Profile settings at an older revision, to be opened in the editor
+    pub fn get_profile_at_revision(&self, profile_id: &str, revision: &str) -> AppResult<String> {
+        let store = self.require_git_store()?;
+        store.show(
+            revision,
+            &[
+                PathBuf::from(format!("{}.settings.json", profile_id)),
+                PathBuf::from(format!("{}{}", profile_id, profile_bundle::BUNDLE_SUFFIX))
+                    .join(profile_bundle::BUNDLE_SETTINGS_FILE),
+            ],
+        )
+    }
+
+    fn require_git_store(&self) -> AppResult<GitStore> {
+        if !self.git_store_enabled {
+            return Err(AppError::ConfigError("Git profile store is not enabled".to_string()));
+        }
+        GitStore::open_or_init(&self.claude_dir)
+    }
+
+    /// Set where snapshots and local overrides of subscribed profiles are kept
+    pub fn set_subscriptions_dir(&mut self, dir: PathBuf) {
+        self.subscriptions_dir = Some(dir);
+    }
+
+    /// Replace the subscriptions. New ones are synced right away; local state of
+    /// removed ones (snapshots and overrides) is deleted.
+    pub fn set_subscriptions(&mut self, subscriptions: Vec<ProfileSubscription>) -> AppResult<()> {
+        let store = self.subscription_store()?;
+        for old in &self.subscriptions {
+            if !subscriptions.iter().any(|s| s.name == old.name) {
+                store.remove(&old.name)?;
+            }
+        }
+        for subscription in &subscriptions {
+            if subscription.enabled && !store.is_synced(&subscription.name) {
+                // 共享目录暂时不可用时保留订阅，稍后可手动同步
+                if let Err(e) = store.sync(subscription, None, &self.trusted_keys) {
+                    log::warn!("Failed to sync subscription '{}': {}", subscription.name, e);
+                }
+            }
+        }
+        self.subscriptions = subscriptions;
+        self.clear_cache();
+        self.scan_profiles()?;
+        Ok(())
+    }
+
+    fn subscription_store(&self) -> AppResult<SubscriptionStore> {
+        self.subscriptions_dir
+            .clone()
+            .map(SubscriptionStore::new)
+            .ok_or_else(|| AppError::ConfigError("Subscriptions are not configured".to_string()))
+    }
+
+    /// Update state of every subscription against its shared directory
+    pub fn get_subscription_status(&self) -> AppResult<Vec<SubscriptionStatus>> {
+        let store = self.subscription_store()?;
+        Ok(self.subscriptions.iter().map(|s| store.status(s)).collect())
+    }
+
+    /// Take the upstream version of one profile, or of the whole subscription when
+    /// `profile` is `None`. Local overrides are applied again on top.
+    pub fn update_subscription(&mut self, name: &str, profile: Option<&str>) -> AppResult<Vec<String>> {
+        let subscription = self
+            .subscriptions
+            .iter()
+            .find(|s| s.name == name)
+            .cloned()
+            .ok_or_else(|| AppError::ConfigError(format!("Subscription '{}' not found", name)))?;
+        let changed = self
+            .subscription_store()?
+            .sync(&subscription, profile, &self.trusted_keys)?;
+        self.clear_cache();
+        self.scan_profiles()?;
+        Ok(changed)
+    }
+
+    /// Local override layer of a subscribed profile (a JSON merge patch), if any
+    pub fn get_profile_override(&self, profile_id: &str) -> AppResult<Option<String>> {
+        let (subscription, profile) = Self::split_subscribed(profile_id)?;
+        self.subscription_store()?.read_override(subscription, profile)
+    }
+
+    /// Replace the local override of a subscribed profile; `None` removes it
+    pub fn save_profile_override(&mut self, profile_id: &str, content: Option<&str>) -> AppResult<()> {
+        let (subscription, profile) = Self::split_subscribed(profile_id)?;
+        let store = self.subscription_store()?;
+        let previous = store.read_override(subscription, profile)?;
+        store.save_override(subscription, profile, content)?;
+        self.clear_cache();
+        self.scan_profiles()?;
+
+        // The override only counts together with the shared profile, so check the merged result
+        let merged = self.profiles.iter().find(|p| p.name == profile_id).map(|p| p.content.clone());
+        if let Some(Err(e)) = merged.map(|merged| self.enforce_policy_on_content(profile_id, &merged)) {
+            store.save_override(subscription, profile, previous.as_deref())?;
+            self.clear_cache();
+            self.scan_profiles()?;
+            return Err(e);
+        }
+        log::info!("Saved local override of '{}'", profile_id);
+        Ok(())
+    }
+
+    fn split_subscribed(profile_id: &str) -> AppResult<(&str, &str)> {
+        subscriptions::split_qualified_name(profile_id).ok_or_else(|| {
+            AppError::ConfigError(format!("Profile '{}' is not from a subscription", profile_id))
+        })
+    }
+
+    /// Subscribed profiles follow their shared directory and only take local overrides
+    fn ensure_writable(&self, profile_id: &str) -> AppResult<()> {
+        match self.profiles.iter().find(|p| p.name == profile_id).and_then(|p| p.source.as_ref()) {
+            Some(source) => Err(AppError::ConfigError(format!(
+                "Profile '{}' comes from subscription '{}' and is read-only; edit its local override instead",
+                profile_id, source
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the organization policy file checked on save, create, apply and switch
+    pub fn set_policy_path(&mut self, path: PathBuf) {
+        self.policy_path = Some(path);
+    }
+
+    fn policy(&self) -> AppResult<Option<OrgPolicy>> {
+        match &self.policy_path {
+            Some(path) => OrgPolicy::load(path),
+            None => Ok(None),
+        }
+    }
+
+    /// Policy violations of a profile, checked against its resolved content
+    pub fn get_policy_violations(&self, profile_id: &str) -> AppResult<Vec<PolicyViolation>> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|p| p.name == profile_id)
+            .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))?;
+        let Some(policy) = self.policy()? else {
+            return Ok(Vec::new());
+        };
+        let json_value = serde_json::from_str::<serde_json::Value>(&profile.content)?;
+        Ok(policy.evaluate(&self.resolve_for_comparison(json_value)))
+    }
+
+    /// Refuse content that violates the organization policy
+    fn enforce_policy(&self, profile_name: &str, json_value: &serde_json::Value) -> AppResult<()> {
+        let Some(policy) = self.policy()? else {
+            return Ok(());
+        };
+        let violations = policy.evaluate(json_value);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        log::warn!("Profile '{}' violates the organization policy: {:?}", profile_name, violations);
+        Err(policy.violation_error(profile_name, &violations))
+    }
+
+    /// Same check for unsaved content; placeholders are resolved where possible
+    fn enforce_policy_on_content(&self, profile_name: &str, content: &str) -> AppResult<()> {
+        let json_value = serde_json::from_str::<serde_json::Value>(content)?;
+        self.enforce_policy(profile_name, &self.resolve_for_comparison(json_value))
+    }
+
+    /// Mark a profile that breaks the organization policy, whatever its match status
+    pub fn with_policy_status(&self, profile_content: &str, status: ProfileStatus) -> ProfileStatus {
+        let policy = match self.policy() {
+            Ok(Some(policy)) => policy,
+            Ok(None) => return status,
+            Err(e) => return ProfileStatus::Error(e.to_string()),
+        };
+        let Ok(json_value) = serde_json::from_str::<serde_json::Value>(profile_content) else {
+            return status;
+        };
+        let violations = policy.evaluate(&self.resolve_for_comparison(json_value));
+        if violations.is_empty() {
+            status
+        } else {
+            ProfileStatus::NonCompliant(violations.iter().map(ToString::to_string).collect())
+        }
+    }
+
+    /// Set the trusted signing keys and re-verify every profile
+    pub fn set_trusted_keys(&mut self, keys: Vec<TrustedKey>) -> AppResult<()> {
+        self.trusted_keys = keys;
+        self.scan_profiles()?;
+        Ok(())
+    }
+
+    fn verify_signature(&self, profile: &Profile) -> SignatureState {
+        if self.trusted_keys.is_empty() {
+            return SignatureState::NotChecked;
+        }
+        match profile.bundle_dir.as_deref().map(ProfileBundle::load).transpose() {
+            Ok(bundle) => self.verify_loaded(profile, bundle.as_ref()),
+            Err(e) => SignatureState::Invalid(e.to_string()),
+        }
+    }
+
+    fn verify_loaded(&self, profile: &Profile, bundle: Option<&ProfileBundle>) -> SignatureState {
+        // 订阅配置由本地合成，签名状态来自同步时的上游快照
+        if let (Some(_), Some((subscription, name))) =
+            (&profile.source, subscriptions::split_qualified_name(&profile.name))
+        {
+            return match self.subscription_store() {
+                Ok(store) => store.verify_snapshot(subscription, name, &self.trusted_keys),
+                Err(e) => SignatureState::Invalid(e.to_string()),
+            };
+        }
+        profile_signing::verify(
+            &profile_signing::signed_payload(profile.content.as_bytes(), bundle),
+            &profile_signing::signature_path(&profile.path),
+            &self.trusted_keys,
+        )
+    }
+
+    /// Refuse unsigned or tampered profiles once trusted keys are configured
+    fn ensure_signed(&self, profile: &Profile, bundle: Option<&ProfileBundle>) -> AppResult<()> {
+        match self.verify_loaded(profile, bundle) {
+            SignatureState::NotChecked | SignatureState::Valid(_) => Ok(()),
+            SignatureState::Unsigned => Err(AppError::SignatureError(format!(
+                "Profile '{}' is not signed by a trusted key",
+                profile.name
+            ))),
+            SignatureState::Invalid(reason) => Err(AppError::SignatureError(format!(
+                "Profile '{}': {}",
+                profile.name, reason
+            ))),
+        }
+    }
+
+    /// Mark unsigned and tampered profiles; takes precedence over every other status
+    pub fn with_signature_status(&self, profile_name: &str, status: ProfileStatus) -> ProfileStatus {
+        match self.profiles.iter().find(|p| p.name == profile_name).map(|p| &p.signature) {
+            Some(SignatureState::Unsigned) => ProfileStatus::Unsigned,
+            Some(SignatureState::Invalid(reason)) => ProfileStatus::SignatureInvalid(reason.clone()),
+            _ => status,
+        }
+    }
+
+    /// Set the file holding profile metadata; without it metadata cannot be edited
+    pub fn set_metadata_path(&mut self, path: PathBuf) {
+        self.metadata_path = Some(path);
+    }
+
+    fn metadata_store(&self) -> AppResult<MetadataStore> {
+        match &self.metadata_path {
+            Some(path) => MetadataStore::load(path),
+            None => Ok(MetadataStore::default()),
+        }
+    }
+
+    fn save_metadata_store(&self, store: &MetadataStore) -> AppResult<()> {
+        let path = self
+            .metadata_path
+            .as_ref()
+            .ok_or_else(|| AppError::ConfigError("Profile metadata is not available".to_string()))?;
+        store.save(path)
+    }
+
+    /// Set the file recording the profiles recently switched to
+    pub fn set_recent_profiles_path(&mut self, path: PathBuf) {
+        self.recent_profiles_path = Some(path);
+    }
+
+    /// Update the recent profiles list; a failure only costs the tray its "Recent" entry
+    fn update_recent_profiles(&self, update: impl FnOnce(&mut RecentProfiles) -> bool) {
+        let Some(path) = &self.recent_profiles_path else {
+            return;
+        };
+        let result = RecentProfiles::load(path).and_then(|mut recent| {
+            if update(&mut recent) {
+                recent.save(path)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to update recent profiles: {}", e);
+        }
+    }
+
+    /// Replace the metadata of a profile and re-sort the profile list
+    pub fn save_profile_metadata(&mut self, profile_id: &str, metadata: ProfileMetadata) -> AppResult<()> {
+        if !self.profiles.iter().any(|p| p.name == profile_id) {
+            return Err(AppError::ConfigError(format!("Profile '{}' not found", profile_id)));
+        }
+        metadata.validate()?;
+        let mut store = self.metadata_store()?;
+        store.set(profile_id, metadata);
+        self.save_metadata_store(&store)?;
+        self.scan_profiles()?;
+        Ok(())
+    }
+
+    /// Set the manual order of profiles; profiles not listed keep their position
+    pub fn reorder_profiles(&mut self, profile_ids: &[String]) -> AppResult<()> {
+        if let Some(unknown) = profile_ids.iter().find(|id| !self.profiles.iter().any(|p| &p.name == *id)) {
+            return Err(AppError::ConfigError(format!("Profile '{}' not found", unknown)));
+        }
+        let mut store = self.metadata_store()?;
+        store.reorder(profile_ids);
+        self.save_metadata_store(&store)?;
+        self.scan_profiles()?;
+        Ok(())
+    }
+
+    /// Get the configured switch hooks
+    pub fn get_switch_hooks(&self) -> AppResult<SwitchHooksConfig> {
+        match &self.switch_hooks_path {
+            Some(path) => SwitchHooksConfig::load(path),
+            None => Ok(SwitchHooksConfig::default()),
+        }
+    }
+
+    /// Replace the switch hooks
+    pub fn save_switch_hooks(&self, hooks: &SwitchHooksConfig) -> AppResult<()> {
+        let path = self
+            .switch_hooks_path
+            .as_ref()
+            .ok_or_else(|| AppError::HookError("No hooks file configured".to_string()))?;
+        hooks.save(path)?;
+        log::info!("Saved switch hooks");
+        Ok(())
+    }
+
+    /// Hook runs of the most recent switch, with their captured output
+    pub fn get_last_hook_runs(&self) -> &[HookRun] {
+        &self.last_hook_runs
+    }
+
+    /// Get all user variables
+    pub fn get_user_variables(&self) -> AppResult<BTreeMap<String, String>> {
+        match &self.variables_path {
+            Some(path) => interpolation::load_user_variables(path),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Create or update a user variable
+    pub fn set_user_variable(&mut self, name: &str, value: &str) -> AppResult<()> {
+        interpolation::validate_variable_name(name)?;
+        let path = self.require_variables_path()?;
+
+        let mut variables = interpolation::load_user_variables(&path)?;
+        variables.insert(name.to_string(), value.to_string());
+        interpolation::save_user_variables(&path, &variables)?;
+
+        self.clear_cache();
+        log::info!("Saved user variable: {}", name);
+        Ok(())
+    }
+
+    /// Remove a user variable
+    pub fn remove_user_variable(&mut self, name: &str) -> AppResult<()> {
+        let path = self.require_variables_path()?;
+
+        let mut variables = interpolation::load_user_variables(&path)?;
+        if variables.remove(name).is_none() {
+            return Err(AppError::ConfigError(format!(
+                "Variable '{}' does not exist",
+                name
+            )));
+        }
+        interpolation::save_user_variables(&path, &variables)?;
+
+        self.clear_cache();
+        log::info!("Removed user variable: {}", name);
+        Ok(())
+    }
+
+    fn require_variables_path(&self) -> AppResult<PathBuf> {
+        self.variables_path
+            .clone()
+            .ok_or_else(|| AppError::ConfigError("No variables file configured".to_string()))
+    }
+
+    /// Warn when a file containing plaintext secrets is exposed to other users
+    pub fn get_permission_warning(&self, path: &Path, content: &str) -> Option<String> {
+        let json_value = serde_json::from_str::<serde_json::Value>(content).ok()?;
+        if !file_permissions::contains_plaintext_secrets(&json_value) {
+            return None;
+        }
+        file_permissions::exposure_warning(path)
+    }
+
+    /// Get the permission warning for a profile by ID ("current" is settings.json)
+    pub fn get_profile_permission_warning(&mut self, profile_id: &str) -> Option<String> {
+        let path = self.get_profile_path(profile_id).ok()?;
+        let content = self.read_profile_content(profile_id).ok()?;
+        self.get_permission_warning(&path, &content)
+    }
+
+    /// Restrict settings.json, its backups and all profiles to the owner (0600), along
+    /// with the local settings of `projects` and their backups, which hold resolved secrets
+    pub fn fix_permissions(&self, projects: &[PathBuf]) -> AppResult<PermissionFixResult> {
+        let mut paths = vec![self.default_settings_path.clone()];
+        paths.extend(self.profiles.iter().map(|p| p.path.clone()));
+
+        for project in projects {
+            let local_path = SettingsScope::ProjectLocal(project.clone()).settings_path(&self.claude_dir);
+            let backup_dir = self.backup_dir_for(&local_path);
+            paths.push(local_path);
+            if let Ok(entries) = fs::read_dir(&backup_dir) {
+                paths.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()));
+            }
+            paths.push(backup_dir);
+        }
+
+        let backup_prefix = format!(
+            "{}.backup.",
+            self.default_settings_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("settings.json")
+        );
+        if let Ok(entries) = fs::read_dir(&self.claude_dir) {
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| {
+                        entry
+                            .file_name()
+                            .to_string_lossy()
+                            .starts_with(&backup_prefix)
+                    })
+                    .map(|entry| entry.path()),
+            );
+        }
+
+        let mut result = PermissionFixResult {
+            fixed_files: Vec::new(),
+            failed_files: Vec::new(),
+        };
+        // 配置历史仓库保存了每个版本的配置内容
+        paths.push(self.claude_dir.join(".git"));
+        for path in paths.iter().filter(|p| p.exists()) {
+            let restricted = if path.is_dir() {
+                file_permissions::restrict_dir_to_owner(path)
+            } else {
+                file_permissions::restrict_to_owner(path)
+            };
+            match restricted {
+                Ok(()) => result.fixed_files.push(path.to_string_lossy().to_string()),
+                Err(e) => {
+                    log::warn!("Failed to restrict permissions of {:?}: {}", path, e);
+                    result
+                        .failed_files
+                        .push(format!("{}: {}", path.to_string_lossy(), e));
+                }
+            }
+        }
+
+        log::info!(
+            "Fixed permissions of {} files ({} failed)",
+            result.fixed_files.len(),
+            result.failed_files.len()
+        );
+        Ok(result)
+    }
+
+    fn get_profile_path(&self, profile_id: &str) -> AppResult<PathBuf> {
+        if profile_id == "current" {
+            return Ok(self.default_settings_path.clone());
         }
+        self.profiles
+            .iter()
+            .find(|p| p.name == profile_id)
+            .map(|p| p.path.clone())
+            .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))
     }
 
     /// Clear all caches when needed
@@ -140,6 +758,7 @@ impl ConfigService {
     pub fn scan_profiles(&mut self) -> AppResult<Vec<Profile>> {
         let mut profiles = Vec::new();
         let mut scan_errors = Vec::new();
+        let mut bundle_dirs = Vec::new();
 
         log::info!("Scanning for profiles in: {:?}", self.claude_dir);
 
@@ -196,6 +815,78 @@ impl ConfigService {
                             }
                         }
                     }
+                } else if path.is_dir() && filename.ends_with(profile_bundle::BUNDLE_SUFFIX) {
+                    bundle_dirs.push(path.clone());
+                }
+            }
+        }
+
+        // Directory bundles (<name>.profile/settings.json); a flat profile of the same name wins
+        for bundle_dir in bundle_dirs {
+            let Some(profile_name) = bundle_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(profile_bundle::BUNDLE_SUFFIX))
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            let settings_path = bundle_dir.join(profile_bundle::BUNDLE_SETTINGS_FILE);
+            if profile_name.is_empty() || !settings_path.is_file() {
+                continue;
+            }
+            if profiles.iter().any(|p| p.name == profile_name) {
+                scan_errors.push(format!(
+                    "Bundle {:?} ignored: profile '{}' already exists",
+                    bundle_dir, profile_name
+                ));
+                continue;
+            }
+
+            match self.load_profile_optimized(&profile_name, &settings_path) {
+                Ok(mut profile) => {
+                    log::info!("Found bundle profile: {} at {:?}", profile.name, bundle_dir);
+                    profile.bundle_dir = Some(bundle_dir);
+                    profiles.push(profile);
+                }
+                Err(e) => {
+                    let error_msg = format!("Failed to load profile {}: {}", profile_name, e);
+                    log::warn!("{}", error_msg);
+                    scan_errors.push(error_msg);
+                }
+            }
+        }
+
+        // Subscribed team profiles ("<subscription>:<profile>"), read-only
+        for subscription in self.subscriptions.clone() {
+            if !subscription.enabled {
+                continue;
+            }
+            let Ok(store) = self.subscription_store() else {
+                break;
+            };
+            let status = store.status(&subscription);
+            if let Some(error) = &status.error {
+                scan_errors.push(format!("Subscription '{}': {}", subscription.name, error));
+            }
+            let files = match store.local_profiles(&subscription.name) {
+                Ok(files) => files,
+                Err(e) => {
+                    scan_errors.push(format!("Subscription '{}' is not synced: {}", subscription.name, e));
+                    continue;
+                }
+            };
+            for (name, path) in files {
+                let profile_name = subscriptions::qualified_name(&subscription.name, &name);
+                match self.load_profile_optimized(&profile_name, &path) {
+                    Ok(mut profile) => {
+                        profile.source = Some(subscription.name.clone());
+                        profile.update_available = status.profiles.iter().any(|p| {
+                            p.profile == name && p.state != subscriptions::UpstreamState::UpToDate
+                        });
+                        profiles.push(profile);
+                    }
+                    Err(e) => scan_errors.push(format!("Failed to load profile {}: {}", profile_name, e)),
                 }
             }
         }
@@ -211,6 +902,23 @@ impl ConfigService {
             }
         }
 
+        // 元数据文件损坏时仍然列出配置，只是没有排序和标签
+        let metadata = self.metadata_store().unwrap_or_else(|e| {
+            log::warn!("Failed to load profile metadata: {}", e);
+            MetadataStore::default()
+        });
+        for profile in &mut profiles {
+            profile.metadata = metadata.get(&profile.name);
+        }
+        profiles.sort_by(profile_metadata::compare_profiles);
+
+        for profile in &mut profiles {
+            profile.signature = self.verify_signature(profile);
+            if let SignatureState::Invalid(reason) = &profile.signature {
+                log::warn!("Signature of profile '{}' is invalid: {}", profile.name, reason);
+            }
+        }
+
         // Update active status for all profiles
         match self.update_profile_status_optimized(&mut profiles) {
             Ok(()) => {
@@ -266,6 +974,11 @@ impl ConfigService {
             path: path.to_path_buf(),
             content,
             is_active: false, // Will be updated by update_profile_status_optimized
+            bundle_dir: None,
+            source: None,
+            update_available: false,
+            signature: SignatureState::NotChecked, // 由 scan_profiles 统一校验
+            metadata: ProfileMetadata::default(),
         })
     }
 
@@ -306,8 +1019,9 @@ impl ConfigService {
         };
 
         for profile in profiles.iter_mut() {
-            profile.is_active =
-                self.compare_configurations_optimized(&profile.content, &default_json);
+            profile.is_active = self
+                .compare_configurations_optimized(&profile.content, &default_json)
+                && self.bundle_installed(profile);
         }
 
         Ok(())
@@ -320,7 +1034,7 @@ impl ConfigService {
         default_json: &serde_json::Value,
     ) -> bool {
         match serde_json::from_str::<serde_json::Value>(profile_content) {
-            Ok(profile_json) => profile_json == *default_json,
+            Ok(profile_json) => self.resolve_for_comparison(profile_json) == *default_json,
             Err(e) => {
                 log::warn!("Failed to parse profile content as JSON: {}", e);
                 false
@@ -339,7 +1053,17 @@ impl ConfigService {
         profile_content: &str, 
         ignored_fields: Option<&[String]>
     ) -> ProfileStatus {
-        let default_content = match fs::read_to_string(&self.default_settings_path) {
+        self.detailed_status_against(&self.default_settings_path, profile_content, ignored_fields)
+    }
+
+    /// Compare profile content with the settings file of any scope
+    fn detailed_status_against(
+        &self,
+        settings_path: &Path,
+        profile_content: &str,
+        ignored_fields: Option<&[String]>,
+    ) -> ProfileStatus {
+        let default_content = match fs::read_to_string(settings_path) {
             Ok(content) => content,
             Err(e) => {
                 return ProfileStatus::Error(format!("Failed to read default settings: {}", e));
@@ -359,6 +1083,9 @@ impl ConfigService {
             }
         };
 
+        // settings.json holds resolved secrets, so compare against the resolved profile
+        let profile_json = self.resolve_for_comparison(profile_json);
+
         // Use provided ignored fields or get from settings
         let fields_to_ignore = ignored_fields
             .map(|f| f.to_vec())
@@ -419,6 +1146,49 @@ impl ConfigService {
         self.compare_json_ignoring_fields(json1, json2, &[ignore_field.to_string()])
     }
 
+    /// Build the context used to resolve ${env:..}, ${var:..}, ${home} and ${claude_dir}
+    fn interpolation_context(&self) -> InterpolationContext {
+        let variables = self.get_user_variables().unwrap_or_else(|e| {
+            log::warn!("Failed to load user variables: {}", e);
+            BTreeMap::new()
+        });
+        InterpolationContext::new(self.claude_dir.clone(), variables)
+    }
+
+    /// Resolve all placeholders for content written to settings.json
+    fn resolve_placeholders(&self, json_value: &serde_json::Value) -> AppResult<serde_json::Value> {
+        let placeholders = interpolation::find_placeholders(json_value);
+        if placeholders.is_empty() {
+            return Ok(json_value.clone());
+        }
+
+        let context = self.interpolation_context();
+        let needs_vault = placeholders
+            .iter()
+            .any(|p| matches!(p, Placeholder::Secret(_)));
+
+        match &self.secret_vault {
+            Some(vault) if needs_vault => {
+                let vault = vault.lock().map_err(|e| {
+                    AppError::SecretError(format!("Failed to lock secret vault: {}", e))
+                })?;
+                interpolation::resolve_placeholders(json_value, &context, Some(&vault))
+            }
+            _ => interpolation::resolve_placeholders(json_value, &context, None),
+        }
+    }
+
+    /// Resolve placeholders for status comparison, falling back to the raw profile
+    fn resolve_for_comparison(&self, profile_json: serde_json::Value) -> serde_json::Value {
+        match self.resolve_placeholders(&profile_json) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                log::debug!("Comparing unresolved profile content: {}", e);
+                profile_json
+            }
+        }
+    }
+
     /// Read the default settings.json content
     fn read_default_settings(&self) -> AppResult<String> {
         fs::read_to_string(&self.default_settings_path)
@@ -432,7 +1202,9 @@ impl ConfigService {
             serde_json::from_str::<serde_json::Value>(profile_content),
             serde_json::from_str::<serde_json::Value>(default_content),
         ) {
-            (Ok(profile_json), Ok(default_json)) => profile_json == default_json,
+            (Ok(profile_json), Ok(default_json)) => {
+                self.resolve_for_comparison(profile_json) == default_json
+            }
             _ => false,
         }
     }
@@ -443,7 +1215,9 @@ impl ConfigService {
 
         for profile in &self.profiles {
             let status = self.get_detailed_profile_status(&profile.content);
-            statuses.push(status);
+            let status = self.with_bundle_status(&profile.name, status);
+            let status = self.with_policy_status(&profile.content, status);
+            statuses.push(self.with_signature_status(&profile.name, status));
         }
 
         statuses
@@ -452,21 +1226,55 @@ impl ConfigService {
     /// Get the status of a specific profile with detailed comparison
     pub fn get_profile_status(&self, profile_name: &str) -> ProfileStatus {
         if let Some(profile) = self.profiles.iter().find(|p| p.name == profile_name) {
-            self.get_detailed_profile_status(&profile.content)
+            let status = self.get_detailed_profile_status(&profile.content);
+            let status = self.with_bundle_status(profile_name, status);
+            let status = self.with_policy_status(&profile.content, status);
+            self.with_signature_status(profile_name, status)
         } else {
             ProfileStatus::Error(format!("Profile '{}' not found", profile_name))
         }
     }
 
-    /// Calculate CRC32 checksum for content comparison
-    pub fn calculate_checksum(content: &str) -> u32 {
-        crc32fast::hash(content.as_bytes())
+    /// Downgrade a settings match to a partial match when the bundle files
+    /// (CLAUDE.md, agents, commands) installed in the Claude directory differ
+    pub fn with_bundle_status(&self, profile_name: &str, status: ProfileStatus) -> ProfileStatus {
+        match status {
+            ProfileStatus::FullMatch | ProfileStatus::PartialMatch => {
+                match self.profiles.iter().find(|p| p.name == profile_name) {
+                    Some(profile) if !self.bundle_installed(profile) => ProfileStatus::PartialMatch,
+                    _ => status,
+                }
+            }
+            other => other,
+        }
     }
 
-    /// Get file metadata for monitoring purposes
-    pub fn get_file_metadata(&self, path: &Path) -> AppResult<FileMetadata> {
-        let metadata = fs::metadata(path).map_err(|e| {
-            AppError::FileSystemError(format!("Failed to get file metadata: {}", e))
+    /// Whether the managed files in the Claude directory are exactly those of the profile's bundle
+    fn bundle_installed(&self, profile: &Profile) -> bool {
+        self.bundle_installed_in(profile, &self.claude_dir)
+    }
+
+    /// Same check against any directory bundle files are installed into
+    fn bundle_installed_in(&self, profile: &Profile, artifact_dir: &Path) -> bool {
+        match profile.bundle_dir.as_deref().map(ProfileBundle::load) {
+            Some(Ok(bundle)) => profile_bundle::is_installed(artifact_dir, Some(&bundle)),
+            Some(Err(e)) => {
+                log::warn!("Failed to load bundle of profile '{}': {}", profile.name, e);
+                false
+            }
+            None => profile_bundle::is_installed(artifact_dir, None),
+        }
+    }
+
+    /// Calculate CRC32 checksum for content comparison
+    pub fn calculate_checksum(content: &str) -> u32 {
+        crc32fast::hash(content.as_bytes())
+    }
+
+    /// Get file metadata for monitoring purposes
+    pub fn get_file_metadata(&self, path: &Path) -> AppResult<FileMetadata> {
+        let metadata = fs::metadata(path).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to get file metadata: {}", e))
         })?;
 
         let content = fs::read_to_string(path)
@@ -497,9 +1305,70 @@ impl ConfigService {
         &self.profiles
     }
 
-    /// Switch to a specific profile configuration with enhanced error handling
-    pub fn switch_profile(&mut self, profile_name: &str) -> AppResult<()> {
-        log::info!("Attempting to switch to profile: {}", profile_name);
+    /// The lease for a temporary switch to `profile_name`; a running lease keeps its
+    /// previous state
+    fn new_switch_lease(
+        &mut self,
+        profile_name: &str,
+        duration: std::time::Duration,
+    ) -> AppResult<SwitchLease> {
+        let previous = match SwitchLease::load(&self.claude_dir)? {
+            Some(lease) => lease.previous,
+            None => {
+                self.refresh_profile_status()?;
+                match self.profiles.iter().find(|p| p.is_active) {
+                    Some(active) => PreviousState::Profile(active.name.clone()),
+                    None => PreviousState::Settings(fs::read_to_string(&self.default_settings_path)?),
+                }
+            }
+        };
+        if previous == PreviousState::Profile(profile_name.to_string()) {
+            return Err(AppError::ConfigError(format!(
+                "Profile '{}' is already active",
+                profile_name
+            )));
+        }
+
+        SwitchLease::new(profile_name, previous, duration)
+    }
+
+    /// The running temporary switch, if any
+    pub fn get_switch_lease(&self) -> Option<SwitchLease> {
+        SwitchLease::load(&self.claude_dir)
+            .inspect_err(|e| log::warn!("Failed to read switch lease: {}", e))
+            .ok()
+            .flatten()
+    }
+
+    /// Clear the running lease; also returns whether its profile is still active, so
+    /// reverting would not override a switch the user made in the meantime
+    fn take_switch_lease(&mut self) -> AppResult<Option<(SwitchLease, bool)>> {
+        let Some(lease) = SwitchLease::load(&self.claude_dir)? else {
+            return Ok(None);
+        };
+        // 无论恢复是否成功都结束租约，避免每次检查都重复失败
+        SwitchLease::clear(&self.claude_dir)?;
+
+        self.refresh_profile_status()?;
+        let still_active = self
+            .profiles
+            .iter()
+            .any(|p| p.name == lease.profile && p.is_active);
+        Ok(Some((lease, still_active)))
+    }
+
+    /// Checks that run before the pre-switch hooks: the profile, its signature, placeholders,
+    /// policy and whether the settings file can be written. `None` if already applied.
+    fn prepare_switch(
+        &mut self,
+        profile_name: &str,
+        scope: &SettingsScope,
+    ) -> AppResult<Option<PendingSwitch>> {
+        log::info!(
+            "Attempting to switch to profile: {} (scope: {})",
+            profile_name,
+            scope.label()
+        );
 
         // Input validation
         if profile_name.is_empty() {
@@ -507,29 +1376,39 @@ impl ConfigService {
                 "Profile name cannot be empty".to_string(),
             ));
         }
+        scope.validate()?;
 
         // Find the profile
         let profile = self
             .profiles
             .iter()
             .find(|p| p.name == profile_name)
+            .cloned()
             .ok_or_else(|| {
                 AppError::ConfigError(format!("Profile '{}' not found", profile_name))
             })?;
 
+        let target_path = scope.settings_path(&self.claude_dir);
+        let artifact_dir = scope.artifact_dir(&self.claude_dir);
+
         // Check if profile is already active
-        if profile.is_active {
+        let already_active = match scope {
+            SettingsScope::User => profile.is_active,
+            _ => self.is_applied_at(&profile, &target_path, artifact_dir.as_deref()),
+        };
+        if already_active {
             log::info!(
                 "Profile '{}' is already active, no action needed",
                 profile_name
             );
-            return Ok(());
+            return Ok(None);
         }
 
         // Validate profile content before switching
-        match serde_json::from_str::<serde_json::Value>(&profile.content) {
-            Ok(_) => {
+        let profile_json = match serde_json::from_str::<serde_json::Value>(&profile.content) {
+            Ok(json) => {
                 log::debug!("Profile content validation passed for: {}", profile_name);
+                json
             }
             Err(e) => {
                 return Err(AppError::ConfigError(format!(
@@ -537,17 +1416,56 @@ impl ConfigService {
                     profile_name, e
                 )));
             }
+        };
+
+        // The shared project settings.json is committed with the project, so vault
+        // secrets must not be resolved into it
+        if matches!(scope, SettingsScope::ProjectShared(_))
+            && interpolation::find_placeholders(&profile_json)
+                .iter()
+                .any(|p| matches!(p, Placeholder::Secret(_)))
+        {
+            return Err(AppError::SecretError(format!(
+                "Profile '{}' references vault secrets and cannot be applied to the shared project settings; apply it to the project's local settings instead",
+                profile_name
+            )));
         }
 
+        // Load bundle files up front so a broken bundle fails before anything is written,
+        // and check the signature against exactly what is about to be installed
+        let bundle = profile.bundle_dir.as_deref().map(ProfileBundle::load).transpose()?;
+        self.ensure_signed(&profile, bundle.as_ref())?;
+        // The project local scope has no place for bundle files, so only settings are applied there
+        let bundle = bundle.filter(|_| artifact_dir.is_some());
+
+        // Make sure every placeholder resolves before anything is written
+        let resolved = self.resolve_placeholders(&profile_json)?;
+        self.enforce_policy(profile_name, &resolved)?;
+
         // Pre-flight checks
-        if !self.default_settings_path.exists() {
-            return Err(AppError::FileSystemError(
-                "Default settings file does not exist".to_string(),
-            ));
+        match scope {
+            SettingsScope::User => {
+                if !target_path.exists() {
+                    return Err(AppError::FileSystemError(
+                        "Default settings file does not exist".to_string(),
+                    ));
+                }
+            }
+            _ => {
+                // Project settings are optional; create <project>/.claude on first use
+                if let Some(parent) = target_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| {
+                        AppError::FileSystemError(format!(
+                            "Failed to create project settings directory: {}",
+                            e
+                        ))
+                    })?;
+                }
+            }
         }
 
         // Check if settings file is writable
-        let test_write_path = self.default_settings_path.with_extension("json.write_test");
+        let test_write_path = target_path.with_extension("json.write_test");
         if let Err(e) = fs::write(&test_write_path, "test") {
             return Err(AppError::FileSystemError(format!(
                 "Cannot write to settings directory: {}",
@@ -556,25 +1474,124 @@ impl ConfigService {
         }
         let _ = fs::remove_file(&test_write_path);
 
+        let hook_context = HookContext {
+            old_profile: self
+                .profiles
+                .iter()
+                .find(|p| match scope {
+                    SettingsScope::User => p.is_active,
+                    _ => self.is_applied_at(p, &target_path, artifact_dir.as_deref()),
+                })
+                .map(|p| p.name.clone()),
+            new_profile: profile_name.to_string(),
+            scope: scope.kind().to_string(),
+            project_dir: scope.project().map(Path::to_path_buf),
+            claude_dir: self.claude_dir.clone(),
+        };
+        Ok(Some(PendingSwitch {
+            profile,
+            scope: scope.clone(),
+            bundle,
+            hooks: self.get_switch_hooks()?,
+            context: hook_context,
+        }))
+    }
+
+    /// Write a prepared switch once its pre-switch hooks have run; a failed hook aborts it
+    fn finish_switch(&mut self, pending: &PendingSwitch, pre_runs: Vec<HookRun>) -> AppResult<()> {
+        let PendingSwitch {
+            profile,
+            scope,
+            bundle,
+            ..
+        } = pending;
+        let profile_name = profile.name.as_str();
+        let target_path = scope.settings_path(&self.claude_dir);
+        let artifact_dir = scope.artifact_dir(&self.claude_dir);
+
+        self.last_hook_runs = pre_runs;
+        if let Some(failed) = self.last_hook_runs.iter().find(|run| !run.succeeded()) {
+            return Err(AppError::HookError(format!(
+                "Pre-switch hook failed, switch to '{}' aborted: {}",
+                profile_name,
+                failed.failure_summary()
+            )));
+        }
+
         // Create backup of current settings with timestamp
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-        let backup_path = self
-            .default_settings_path
-            .with_extension(format!("json.backup.{}", timestamp));
+        let backup_path = if target_path.exists() {
+            let backup_dir = self.backup_dir_for(&target_path);
+            let file_name = target_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let backup_path = backup_dir.join(format!("{}.backup.{}", file_name, timestamp));
+            if let Err(e) = fs::create_dir_all(&backup_dir)
+                .map_err(AppError::from)
+                .and_then(|_| self.create_backup_of(&target_path, &backup_path))
+            {
+                return Err(AppError::FileSystemError(format!(
+                    "Failed to create backup before switching: {}",
+                    e
+                )));
+            }
+            Some(backup_path)
+        } else {
+            None
+        };
+        let discard_backup = || {
+            if let Some(backup_path) = &backup_path {
+                let _ = fs::remove_file(backup_path);
+            }
+        };
 
-        if let Err(e) = self.create_backup(&backup_path) {
-            return Err(AppError::FileSystemError(format!(
-                "Failed to create backup before switching: {}",
-                e
-            )));
-        }
+        // Journal every file before it changes so a crash mid-switch is undone at startup
+        let operation = format!("switch to {} ({})", profile_name, scope.label());
+        let mut journal = match self.begin_journal(&operation, &target_path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                discard_backup();
+                return Err(e);
+            }
+        };
+
+        // Install bundle files (or remove the previous bundle's) before touching settings.json
+        let bundle_transaction = match &artifact_dir {
+            Some(artifact_dir) => {
+                match BundleTransaction::begin(
+                    artifact_dir,
+                    profile_name,
+                    bundle.as_ref(),
+                    &mut journal,
+                ) {
+                    Ok(transaction) => Some(transaction),
+                    Err(e) => {
+                        let _ = journal.rollback();
+                        discard_backup();
+                        return Err(e);
+                    }
+                }
+            }
+            None => None,
+        };
 
         // Perform atomic switch operation with rollback on failure
-        match self.perform_switch_atomic(&profile.content) {
+        let switch_result = match self.perform_switch_atomic_to(&target_path, &profile.content) {
+            Ok(()) => bundle_transaction.map_or(Ok(()), BundleTransaction::commit),
+            Err(e) => {
+                if let Some(transaction) = bundle_transaction {
+                    transaction.rollback();
+                }
+                Err(e)
+            }
+        };
+        match switch_result {
             Ok(()) => {
+                journal.commit();
                 log::info!("Successfully switched to profile: {}", profile_name);
 
                 // Clear caches since files have changed
@@ -587,30 +1604,97 @@ impl ConfigService {
                 }
 
                 // Remove backup file on success (keep only a few recent backups)
-                self.cleanup_old_backups();
+                self.cleanup_old_backups_of(&target_path);
+
+                self.git_commit(None, &format!("Switch to '{}' ({})", profile_name, scope.label()));
+                if *scope == SettingsScope::User {
+                    self.update_recent_profiles(|recent| {
+                        recent.record(profile_name);
+                        true
+                    });
+                }
 
                 Ok(())
             }
             Err(e) => {
                 log::error!("Failed to switch profile, attempting rollback: {}", e);
 
-                // Attempt to restore from backup
-                if let Err(rollback_err) = self.restore_from_backup(&backup_path) {
+                // Attempt to restore from backup; a file that did not exist is removed again
+                let rollback_result = match &backup_path {
+                    Some(backup_path) => self.restore_backup_to(&target_path, backup_path),
+                    None if target_path.exists() => fs::remove_file(&target_path).map_err(|e| {
+                        AppError::FileSystemError(format!("Failed to remove settings file: {}", e))
+                    }),
+                    None => Ok(()),
+                };
+                if let Err(rollback_err) = rollback_result {
                     log::error!(
                         "CRITICAL: Failed to rollback after failed switch: {}",
                         rollback_err
                     );
+                    // The journal stays behind so the rollback is retried on next start
                     return Err(AppError::ConfigError(
                         format!("Profile switch failed and rollback failed. Original error: {}. Rollback error: {}", e, rollback_err)
                     ));
                 }
 
+                journal.discard();
                 log::info!("Successfully rolled back after failed switch");
                 Err(e)
             }
         }
     }
 
+    /// Whether the profile is applied exactly (settings and bundle files) at a scope's paths
+    fn is_applied_at(
+        &self,
+        profile: &Profile,
+        settings_path: &Path,
+        artifact_dir: Option<&Path>,
+    ) -> bool {
+        let Some(current) = fs::read_to_string(settings_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            return false;
+        };
+        if !self.compare_configurations_optimized(&profile.content, &current) {
+            return false;
+        }
+        match artifact_dir {
+            Some(dir) => self.bundle_installed_in(profile, dir),
+            None => true,
+        }
+    }
+
+    /// Status of a profile against one scope's settings file (and bundle files where the scope has them)
+    pub fn get_profile_scope_status(
+        &self,
+        profile_name: &str,
+        scope: &SettingsScope,
+        ignored_fields: Option<&[String]>,
+    ) -> ProfileStatus {
+        let Some(profile) = self.profiles.iter().find(|p| p.name == profile_name) else {
+            return ProfileStatus::Error(format!("Profile '{}' not found", profile_name));
+        };
+
+        let settings_path = scope.settings_path(&self.claude_dir);
+        if !settings_path.exists() && *scope != SettingsScope::User {
+            return ProfileStatus::NoMatch;
+        }
+
+        let status =
+            self.detailed_status_against(&settings_path, &profile.content, ignored_fields);
+        match (status, scope.artifact_dir(&self.claude_dir)) {
+            (ProfileStatus::FullMatch | ProfileStatus::PartialMatch, Some(dir))
+                if !self.bundle_installed_in(profile, &dir) =>
+            {
+                ProfileStatus::PartialMatch
+            }
+            (status, _) => status,
+        }
+    }
+
     /// Apply profile content directly to default settings without reading from file
     /// This is used when applying content from the editor that may not be saved yet
     pub fn apply_profile_content(&mut self, content: &str) -> AppResult<()> {
@@ -624,9 +1708,10 @@ impl ConfigService {
         }
 
         // Validate content before applying
-        match serde_json::from_str::<serde_json::Value>(content) {
-            Ok(_) => {
+        let content_json = match serde_json::from_str::<serde_json::Value>(content) {
+            Ok(json) => {
                 log::debug!("Profile content validation passed");
+                json
             }
             Err(e) => {
                 return Err(AppError::ConfigError(format!(
@@ -634,7 +1719,11 @@ impl ConfigService {
                     e
                 )));
             }
-        }
+        };
+
+        // Make sure every placeholder resolves before anything is written
+        let resolved = self.resolve_placeholders(&content_json)?;
+        self.enforce_policy("current", &resolved)?;
 
         // Pre-flight checks
         if !self.default_settings_path.exists() {
@@ -669,9 +1758,18 @@ impl ConfigService {
             )));
         }
 
+        let journal = match self.begin_journal("apply editor content", &self.default_settings_path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                let _ = fs::remove_file(&backup_path);
+                return Err(e);
+            }
+        };
+
         // Perform atomic switch operation with rollback on failure
         match self.perform_switch_atomic(content) {
             Ok(()) => {
+                journal.commit();
                 log::info!("Successfully applied profile content");
 
                 // Clear caches since files have changed
@@ -697,19 +1795,37 @@ impl ConfigService {
                         "CRITICAL: Failed to rollback after failed application: {}",
                         rollback_err
                     );
+                    // The journal stays behind so the rollback is retried on next start
                     return Err(AppError::ConfigError(
                         format!("Profile application failed and rollback failed. Original error: {}. Rollback error: {}", e, rollback_err)
                     ));
                 }
 
+                journal.discard();
+
                 log::info!("Successfully rolled back after failed application");
                 Err(e)
             }
         }
     }
 
+    /// Start a write-ahead journal that already protects the target settings file
+    fn begin_journal(&self, operation: &str, target_path: &Path) -> AppResult<SwitchJournal> {
+        let mut journal = SwitchJournal::begin(&self.claude_dir, operation)?;
+        if let Err(e) = journal.protect(target_path) {
+            let _ = journal.rollback();
+            return Err(e);
+        }
+        Ok(journal)
+    }
+
     /// Perform the actual configuration switch with enhanced atomic operation
     fn perform_switch_atomic(&self, new_content: &str) -> AppResult<()> {
+        self.perform_switch_atomic_to(&self.default_settings_path, new_content)
+    }
+
+    /// Atomically write resolved profile content to the settings file of any scope
+    fn perform_switch_atomic_to(&self, target_path: &Path, new_content: &str) -> AppResult<()> {
         // Validate the new content is valid JSON with proper structure
         let json_value = serde_json::from_str::<serde_json::Value>(new_content)
             .map_err(|e| AppError::ConfigError(format!("Invalid JSON content: {}", e)))?;
@@ -721,12 +1837,15 @@ impl ConfigService {
             ));
         }
 
+        // Resolve placeholders; only settings.json receives the resolved values
+        let json_value = self.resolve_placeholders(&json_value)?;
+
         // Normalize JSON formatting for consistency
         let normalized_content = serde_json::to_string_pretty(&json_value)
             .map_err(|e| AppError::ConfigError(format!("Failed to serialize JSON: {}", e)))?;
 
         // Write to temporary file first (in same directory for atomic rename)
-        let temp_path = self.create_temp_file_path(&self.default_settings_path)?;
+        let temp_path = self.create_temp_file_path(target_path)?;
 
         // Clean up any existing temp file
         if temp_path.exists() {
@@ -735,9 +1854,10 @@ impl ConfigService {
             })?;
         }
 
-        fs::write(&temp_path, &normalized_content).map_err(|e| {
-            AppError::FileSystemError(format!("Failed to write temporary file: {}", e))
-        })?;
+        file_permissions::write_private_file(&temp_path, normalized_content.as_bytes())
+            .map_err(|e| {
+                AppError::FileSystemError(format!("Failed to write temporary file: {}", e))
+            })?;
 
         // Verify temp file was written correctly
         let temp_verification = fs::read_to_string(&temp_path)
@@ -750,15 +1870,24 @@ impl ConfigService {
             ));
         }
 
+        // Keep the existing file mode across the rename
+        if let Err(e) = file_permissions::preserve_mode(&temp_path, target_path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(AppError::PermissionError(format!(
+                "Failed to preserve settings file permissions: {}",
+                e
+            )));
+        }
+
         // Atomic move (rename) operation
-        fs::rename(&temp_path, &self.default_settings_path).map_err(|e| {
+        fs::rename(&temp_path, target_path).map_err(|e| {
             // Clean up temp file on failure
             let _ = fs::remove_file(&temp_path);
             AppError::FileSystemError(format!("Failed to replace settings file: {}", e))
         })?;
 
         // Final verification
-        let final_verification = fs::read_to_string(&self.default_settings_path).map_err(|e| {
+        let final_verification = fs::read_to_string(target_path).map_err(|e| {
             AppError::FileSystemError(format!("Failed to verify final file: {}", e))
         })?;
 
@@ -774,13 +1903,20 @@ impl ConfigService {
 
     /// Clean up old backup files (keep only the 5 most recent)
     fn cleanup_old_backups(&self) {
-        let backup_pattern = format!("{}.backup.", self.default_settings_path.display());
+        self.cleanup_old_backups_of(&self.default_settings_path);
+    }
 
-        if let Ok(entries) = fs::read_dir(
-            self.default_settings_path
-                .parent()
-                .unwrap_or(&self.claude_dir),
-        ) {
+    /// Clean up old backups of a settings file of any scope
+    fn cleanup_old_backups_of(&self, target_path: &Path) {
+        let backup_pattern = format!(
+            "{}.backup.",
+            target_path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default()
+        );
+
+        if let Ok(entries) = fs::read_dir(self.backup_dir_for(target_path)) {
             let mut backup_files: Vec<_> = entries
                 .filter_map(|entry| entry.ok())
                 .filter(|entry| {
@@ -817,9 +1953,34 @@ impl ConfigService {
         }
     }
 
+    /// Directory holding the backups of a settings file: next to the user settings,
+    /// or a per-project directory under the Claude directory for project scopes
+    fn backup_dir_for(&self, target_path: &Path) -> PathBuf {
+        let settings_dir = target_path.parent().unwrap_or(&self.claude_dir);
+        if settings_dir == self.claude_dir {
+            return self.claude_dir.clone();
+        }
+        let project = settings_dir.parent().unwrap_or(settings_dir);
+        let name = project
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.claude_dir.join(PROJECT_BACKUP_DIR).join(format!(
+            "{}-{:08x}",
+            name,
+            crc32fast::hash(project.to_string_lossy().as_bytes())
+        ))
+    }
+
     /// Create a backup of the current settings
     fn create_backup(&self, backup_path: &Path) -> AppResult<()> {
-        fs::copy(&self.default_settings_path, backup_path)
+        self.create_backup_of(&self.default_settings_path, backup_path)
+    }
+
+    /// Create a backup of the settings file of any scope
+    fn create_backup_of(&self, target_path: &Path, backup_path: &Path) -> AppResult<()> {
+        fs::copy(target_path, backup_path)
+            .and_then(|_| file_permissions::restrict_to_owner(backup_path))
             .map_err(|e| AppError::FileSystemError(format!("Failed to create backup: {}", e)))?;
 
         log::debug!("Created backup at: {:?}", backup_path);
@@ -828,6 +1989,11 @@ impl ConfigService {
 
     /// Restore settings from backup with enhanced error handling
     fn restore_from_backup(&self, backup_path: &Path) -> AppResult<()> {
+        self.restore_backup_to(&self.default_settings_path, backup_path)
+    }
+
+    /// Restore the settings file of any scope from a backup
+    fn restore_backup_to(&self, target_path: &Path, backup_path: &Path) -> AppResult<()> {
         if !backup_path.exists() {
             return Err(AppError::FileSystemError(format!(
                 "Backup file does not exist: {:?}",
@@ -845,13 +2011,13 @@ impl ConfigService {
         })?;
 
         // Use atomic operation for restore too
-        let temp_path = self.create_temp_file_path(&self.default_settings_path)?;
+        let temp_path = self.create_temp_file_path(target_path)?;
 
         fs::copy(backup_path, &temp_path).map_err(|e| {
             AppError::FileSystemError(format!("Failed to copy backup to temp: {}", e))
         })?;
 
-        fs::rename(&temp_path, &self.default_settings_path).map_err(|e| {
+        fs::rename(&temp_path, target_path).map_err(|e| {
             let _ = fs::remove_file(&temp_path);
             AppError::FileSystemError(format!("Failed to restore from backup: {}", e))
         })?;
@@ -889,6 +2055,16 @@ impl ConfigService {
         &self.claude_dir
     }
 
+    /// Running Claude Code sessions that started before the settings of `scope` last
+    /// changed and keep the previous configuration until they are restarted
+    pub fn stale_sessions(&self, scope: &SettingsScope) -> Vec<ClaudeSession> {
+        process_inspector::stale_sessions(
+            &process_inspector::find_claude_sessions(),
+            scope,
+            &self.claude_dir,
+        )
+    }
+
     /// Get all profiles information including Current
     pub fn get_all_profiles_info(&mut self) -> AppResult<Vec<ProfileInfo>> {
         log::debug!("get_all_profiles_info: Starting to get all profiles information");
@@ -902,7 +2078,7 @@ impl ConfigService {
 
         // Add Current profile first
         log::debug!("get_all_profiles_info: Getting current settings content");
-        let _current_content = match self.get_default_settings_cached() {
+        let current_content = match self.get_default_settings_cached() {
             Ok(content) => {
                 log::debug!("get_all_profiles_info: Successfully got current settings content ({} bytes)", content.len());
                 content
@@ -926,6 +2102,11 @@ impl ConfigService {
                 .map(|m| m.modified_time)
                 .unwrap_or(SystemTime::UNIX_EPOCH),
             file_size: current_metadata.as_ref().map(|m| m.size).unwrap_or(0),
+            permission_warning: self
+                .get_permission_warning(&self.default_settings_path, &current_content),
+            source: None,
+            update_available: false,
+            metadata: ProfileMetadata::default(),
         });
 
         // Add all other profiles
@@ -943,6 +2124,10 @@ impl ConfigService {
                     .get_file_metadata(&profile.path)
                     .map(|m| m.size)
                     .unwrap_or(0),
+                permission_warning: self.get_permission_warning(&profile.path, &profile.content),
+                source: profile.source.clone(),
+                update_available: profile.update_available,
+                metadata: profile.metadata.clone(),
             });
         }
 
@@ -981,10 +2166,12 @@ impl ConfigService {
         if !validation_result.is_valid {
             return Err(AppError::ConfigError("JSON validation failed".to_string()));
         }
+        self.enforce_policy_on_content(profile_id, content)?;
 
         if profile_id == "current" {
             return self.save_current_settings(content);
         }
+        self.ensure_writable(profile_id)?;
 
         // Find the profile and clone the path to avoid borrowing conflicts
         let profile_path = self
@@ -994,15 +2181,213 @@ impl ConfigService {
             .map(|p| p.path.clone())
             .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))?;
 
+        // Keep the content from before the first recorded save so it can be restored
+        if let Ok(history) = self.history() {
+            let previous = match history.list(profile_id) {
+                Ok(versions) if versions.is_empty() => fs::read_to_string(&profile_path).ok(),
+                _ => None,
+            };
+            if let Some(previous) = previous {
+                if let Err(e) = history.record(profile_id, &previous) {
+                    log::warn!("Failed to record previous version of '{}': {}", profile_id, e);
+                }
+            }
+        }
+
         // Save to profile file
         self.save_to_file(&profile_path, content)?;
 
         // Update cache
         self.profile_cache.remove(&profile_path);
 
+        if let Ok(history) = self.history() {
+            if let Err(e) = history.record(profile_id, content) {
+                log::warn!("Failed to record version of '{}': {}", profile_id, e);
+            }
+        }
+        self.git_commit(Some(profile_id), &format!("Update profile '{}'", profile_id));
+
+        Ok(())
+    }
+
+    /// Saved versions of a profile, newest first
+    pub fn list_profile_versions(&self, profile_id: &str) -> AppResult<Vec<ProfileVersion>> {
+        self.history()?.list(profile_id)
+    }
+
+    /// Content of one saved version
+    pub fn get_profile_version(&self, profile_id: &str, version: u64) -> AppResult<String> {
+        self.history()?.read(profile_id, version)
+    }
+
+    /// Diff two saved versions, or a version against the profile file when `to` is `None`
+    pub fn diff_profile_versions(
+        &mut self,
+        profile_id: &str,
+        from: u64,
+        to: Option<u64>,
+    ) -> AppResult<ProfileDiff> {
+        let history = self.history()?;
+        let old = history.read(profile_id, from)?;
+        let new = match to {
+            Some(to) => history.read(profile_id, to)?,
+            None => self.read_profile_content(profile_id)?,
+        };
+
+        let lines = profile_history::diff_lines(&old, &new);
+        let count = |kind| lines.iter().filter(|line| line.kind == kind).count();
+        Ok(ProfileDiff {
+            from,
+            to,
+            added: count(profile_history::DiffKind::Added),
+            removed: count(profile_history::DiffKind::Removed),
+            lines,
+        })
+    }
+
+    /// Write a saved version back to the profile; the restore itself becomes a new version
+    pub fn restore_profile_version(&mut self, profile_id: &str, version: u64) -> AppResult<()> {
+        let content = self.history()?.read(profile_id, version)?;
+        self.save_profile_content(profile_id, &content)?;
+        log::info!("Restored version {} of profile '{}'", version, profile_id);
+        Ok(())
+    }
+
+    /// Get the permission rules of a profile together with detected issues
+    pub fn get_profile_permissions(&mut self, profile_id: &str) -> AppResult<ProfilePermissions> {
+        let json_value = self.read_profile_json(profile_id)?;
+        Ok(ProfilePermissions::from_settings(&json_value))
+    }
+
+    /// Get all hook commands of a profile
+    pub fn get_profile_hooks(&mut self, profile_id: &str) -> AppResult<Vec<HookEntry>> {
+        let json_value = self.read_profile_json(profile_id)?;
+        hooks_manager::list_hooks(&json_value)
+    }
+
+    /// Copy a hook into several profiles; profiles that already have it are left as-is.
+    /// Returns the read-only profiles that were skipped.
+    pub fn copy_hook_to_profiles(&mut self, hook: &HookEntry, profile_ids: &[String]) -> AppResult<Vec<String>> {
+        self.edit_profiles_json(profile_ids, |settings| {
+            if hooks_manager::contains_hook(settings, hook)? {
+                return Ok(());
+            }
+            hooks_manager::add_hook(settings, hook)
+        })
+    }
+
+    /// Read and parse a profile's JSON content
+    fn read_profile_json(&mut self, profile_id: &str) -> AppResult<serde_json::Value> {
+        let content = self.read_profile_content(profile_id)?;
+        serde_json::from_str::<serde_json::Value>(&content).map_err(|e| {
+            AppError::ConfigError(format!("Profile '{}' contains invalid JSON: {}", profile_id, e))
+        })
+    }
+
+    /// Apply a structured edit to one or more profiles. Every edit is checked in
+    /// memory first, then written through `save_profile_content` so validation applies;
+    /// if a write fails, the profiles already written get their old content back.
+    /// Read-only subscribed profiles are skipped and returned, unless nothing else is
+    /// left to edit.
+    pub fn edit_profiles_json<F>(&mut self, profile_ids: &[String], mut edit: F) -> AppResult<Vec<String>>
+    where
+        F: FnMut(&mut serde_json::Value) -> AppResult<()>,
+    {
+        let (skipped, writable): (Vec<&String>, Vec<&String>) = profile_ids
+            .iter()
+            .partition(|profile_id| self.ensure_writable(profile_id).is_err());
+        if writable.is_empty() {
+            if let Some(profile_id) = skipped.first() {
+                self.ensure_writable(profile_id)?;
+            }
+        }
+        for profile_id in &skipped {
+            log::warn!("Skipping read-only profile '{}' in edit", profile_id);
+        }
+
+        let mut updated = Vec::new();
+        for profile_id in writable {
+            let original = self.read_profile_content(profile_id)?;
+            let mut json_value = serde_json::from_str::<serde_json::Value>(&original).map_err(|e| {
+                AppError::ConfigError(format!("Profile '{}' contains invalid JSON: {}", profile_id, e))
+            })?;
+
+            edit(&mut json_value).map_err(|e| match e {
+                AppError::ConfigError(msg) => {
+                    AppError::ConfigError(format!("Profile '{}': {}", profile_id, msg))
+                }
+                other => other,
+            })?;
+            updated.push((profile_id, original, serde_json::to_string_pretty(&json_value)?));
+        }
+
+        for (i, (profile_id, _, content)) in updated.iter().enumerate() {
+            if let Err(e) = self.save_profile_content(profile_id, content) {
+                // 回滚已写入的配置
+                for (written_id, original, _) in updated[..i].iter().rev() {
+                    if let Err(restore_err) = self.restore_profile_content(written_id, original) {
+                        log::error!("Failed to roll back profile '{}': {}", written_id, restore_err);
+                    }
+                }
+                let _ = self.refresh_profile_status();
+                return Err(e);
+            }
+        }
+        self.refresh_profile_status()?;
+        Ok(skipped.into_iter().cloned().collect())
+    }
+
+    /// Put back content read before a failed bulk edit, bypassing validation
+    /// since the old content may not pass the current policy
+    fn restore_profile_content(&mut self, profile_id: &str, content: &str) -> AppResult<()> {
+        let path = if profile_id == "current" {
+            self.default_settings_path.clone()
+        } else {
+            self.find_profile(profile_id)?.path.clone()
+        };
+        self.save_to_file(&path, content)?;
+        self.profile_cache.remove(&path);
+        self.git_commit(Some(profile_id), &format!("Roll back profile '{}'", profile_id));
         Ok(())
     }
 
+    /// Create a profile from a template. Plaintext secret fields go to the vault and the
+    /// profile only references them; they are removed again if creation fails.
+    pub fn create_profile_from_template(
+        &mut self,
+        template: &ProfileTemplate,
+        profile_name: &str,
+        values: &HashMap<String, String>,
+    ) -> AppResult<String> {
+        let vault = self.secret_vault.clone();
+        let lock_vault = || -> AppResult<std::sync::MutexGuard<'_, SecretVault>> {
+            vault
+                .as_ref()
+                .ok_or_else(|| AppError::SecretError("Secret vault is not configured".to_string()))?
+                .lock()
+                .map_err(|e| AppError::SecretError(format!("Failed to lock secret vault: {}", e)))
+        };
+
+        let existing_secrets: Vec<String> = match lock_vault() {
+            Ok(vault) => vault.list_secrets().into_iter().map(|s| s.name).collect(),
+            Err(_) => Vec::new(),
+        };
+        let rendered = template.render(profile_name, values, &existing_secrets)?;
+        if !rendered.secrets.is_empty() {
+            // 存入后立即释放保险库锁，创建配置时的占位符解析还要再取这把锁
+            profile_templates::store_secrets(&mut *lock_vault()?, &rendered.secrets)?;
+        }
+
+        let result = self.create_profile(profile_name, &rendered.content);
+        if result.is_err() && !rendered.secrets.is_empty() {
+            match lock_vault() {
+                Ok(mut vault) => profile_templates::remove_secrets(&mut vault, &rendered.secrets),
+                Err(e) => log::warn!("Failed to remove template secrets: {}", e),
+            }
+        }
+        result
+    }
+
     /// Create a new profile with given name and content
     pub fn create_profile(&mut self, profile_name: &str, content: &str) -> AppResult<String> {
         // Validate profile name
@@ -1013,6 +2398,7 @@ impl ConfigService {
         if !validation_result.is_valid {
             return Err(AppError::ConfigError("JSON validation failed".to_string()));
         }
+        self.enforce_policy_on_content(profile_name, content)?;
 
         // Check if profile already exists
         if self.profiles.iter().any(|p| p.name == profile_name) {
@@ -1035,60 +2421,338 @@ impl ConfigService {
             path: profile_path.clone(),
             content: content.to_string(),
             is_active: false,
+            bundle_dir: None,
+            source: None,
+            update_available: false,
+            signature: SignatureState::NotChecked,
+            metadata: ProfileMetadata::default(),
         };
         self.profiles.push(new_profile);
 
         // Refresh profile status
         self.refresh_profile_status()?;
 
+        self.git_commit(Some(profile_name), &format!("Create profile '{}'", profile_name));
+
         Ok(profile_path.to_string_lossy().to_string())
     }
 
-    /// Delete a profile by profile ID
-    pub fn delete_profile(&mut self, profile_id: &str) -> AppResult<()> {
-        log::info!("Attempting to delete profile: {}", profile_id);
-
-        // Prevent deletion of 'current' profile
-        if profile_id == "current" {
-            return Err(AppError::ConfigError(
-                "Cannot delete the current profile".to_string(),
-            ));
-        }
-
-        // Find the profile to delete
-        let profile_index = self
-            .profiles
+    fn find_profile(&self, profile_id: &str) -> AppResult<&Profile> {
+        self.profiles
             .iter()
-            .position(|p| p.name == profile_id)
-            .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))?;
-
-        let profile = &self.profiles[profile_index];
-        let profile_path = profile.path.clone();
+            .find(|p| p.name == profile_id)
+            .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))
+    }
 
-        // Check if profile file exists
-        if !profile_path.exists() {
-            log::warn!("Profile file does not exist: {}", profile_path.display());
-            // Still remove from profiles list even if file doesn't exist
-        } else {
-            // Delete the file
-            if let Err(e) = fs::remove_file(&profile_path) {
-                return Err(AppError::FileSystemError(format!(
-                    "Failed to delete profile file: {}",
-                    e
-                )));
+    /// Copy a profile under a free "<name> copy" name; a subscribed profile becomes an
+    /// editable local profile. Bundle files and display metadata are copied too.
+    pub fn duplicate_profile(&mut self, profile_id: &str) -> AppResult<String> {
+        let profile = self.find_profile(profile_id)?.clone();
+        let base = subscriptions::split_qualified_name(&profile.name)
+            .map_or(profile.name.as_str(), |(_, name)| name);
+        let new_name = (1..)
+            .map(|n| match n {
+                1 => format!("{} copy", base),
+                n => format!("{} copy {}", base, n),
+            })
+            .find(|name| !self.profiles.iter().any(|p| &p.name == name))
+            .unwrap_or_default();
+
+        match &profile.bundle_dir {
+            Some(bundle_dir) => {
+                self.validate_profile_name(&new_name)?;
+                self.enforce_policy_on_content(&new_name, &profile.content)?;
+                let bundle = ProfileBundle::load(bundle_dir)?;
+                self.write_imported_bundle(&new_name, &bundle, &profile.content, false)?;
+            }
+            None => {
+                self.create_profile(&new_name, &profile.content)?;
             }
-            log::info!("Deleted profile file: {}", profile_path.display());
         }
 
-        // Remove from profiles list
-        self.profiles.remove(profile_index);
+        // 置顶和排序位置不复制，副本排在普通位置
+        let metadata = ProfileMetadata {
+            pinned: false,
+            position: None,
+            ..profile.metadata.clone()
+        };
+        if self.metadata_path.is_some() {
+            let mut store = self.metadata_store()?;
+            store.set(&new_name, metadata);
+            self.save_metadata_store(&store)?;
+        }
+        self.scan_profiles()?;
 
-        // Remove from cache if exists
+        log::info!("Duplicated profile '{}' as '{}'", profile_id, new_name);
+        Ok(new_name)
+    }
+
+    /// Settings keys that applying the profile would change, placeholders resolved where possible
+    pub fn diff_with_current(&mut self, profile_id: &str) -> AppResult<Vec<KeyChange>> {
+        let profile_json = serde_json::from_str::<serde_json::Value>(&self.find_profile(profile_id)?.content)?;
+        let profile_json = self.resolve_for_comparison(profile_json);
+        let current = serde_json::from_str::<serde_json::Value>(&self.get_default_settings_cached()?)?;
+        Ok(tray_actions::changed_keys(&current, &profile_json))
+    }
+
+    /// The profile's `env` block as shell commands, with every placeholder but secrets
+    /// resolved; returns the script and the number of variables
+    pub fn get_env_exports(&self, profile_id: &str) -> AppResult<(String, usize)> {
+        let profile_json = serde_json::from_str::<serde_json::Value>(&self.find_profile(profile_id)?.content)?;
+        // 密钥保持 ${secret:..} 引用，不把保险库中的明文复制到剪贴板
+        let resolved = interpolation::resolve_except_secrets(&profile_json, &self.interpolation_context())?;
+        let Some(env) = resolved.get("env").and_then(|env| env.as_object()).filter(|env| !env.is_empty()) else {
+            return Err(AppError::ConfigError(format!("Profile '{}' has no env variables", profile_id)));
+        };
+        let script = tray_actions::shell_exports(env, cfg!(windows));
+        let count = script.lines().count();
+        Ok((script, count))
+    }
+
+    /// File or bundle directory that holds a profile
+    pub fn get_profile_location(&self, profile_id: &str) -> AppResult<PathBuf> {
+        let profile = self.find_profile(profile_id)?;
+        Ok(profile.bundle_dir.clone().unwrap_or_else(|| profile.path.clone()))
+    }
+
+    /// Write profiles into one portable archive; plaintext credentials are stripped or
+    /// replaced with ${secret:..} placeholders
+    pub fn export_profiles(
+        &mut self,
+        profile_ids: &[String],
+        path: &Path,
+        secrets: SecretHandling,
+    ) -> AppResult<ArchiveManifest> {
+        let mut exports = Vec::new();
+        for profile_id in profile_ids {
+            let bundle_dir = self
+                .profiles
+                .iter()
+                .find(|p| &p.name == profile_id)
+                .map(|p| p.bundle_dir.clone())
+                .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))?;
+            let mut settings = self.read_profile_json(profile_id)?;
+            // 订阅的配置以不带订阅前缀的名称导出
+            let name = subscriptions::split_qualified_name(profile_id)
+                .map_or(profile_id.as_str(), |(_, name)| name)
+                .to_string();
+            let touched = profile_archive::sanitize_secrets(&mut settings, &name, secrets);
+            exports.push(ExportProfile {
+                bundle: bundle_dir.as_deref().map(ProfileBundle::load).transpose()?,
+                name,
+                settings,
+                secrets: touched,
+            });
+        }
+        profile_archive::write_archive(path, exports, secrets)
+    }
+
+    /// Read an archive and report name conflicts and validation problems without writing anything
+    pub fn preview_profile_import(&self, path: &Path) -> AppResult<ImportPreview> {
+        let (manifest, archived) = profile_archive::read_archive(path)?;
+        let profiles = archived
+            .iter()
+            .map(|profile| ImportCandidate {
+                name: profile.info.name.clone(),
+                bundle: profile.info.bundle,
+                conflict: self.profiles.iter().any(|p| p.name == profile.info.name),
+                errors: self.import_errors(&profile.info.name, profile),
+                secrets: profile.info.secrets.clone(),
+            })
+            .collect();
+        Ok(ImportPreview { manifest, profiles })
+    }
+
+    /// Import profiles from an archive. Profiles without a choice are imported under their
+    /// own name; a name that already exists needs a rename or an explicit overwrite. Every
+    /// profile is checked before the first one is written.
+    pub fn import_profiles(&mut self, path: &Path, choices: &[ImportChoice]) -> AppResult<Vec<String>> {
+        let (_, archived) = profile_archive::read_archive(path)?;
+
+        let mut plan: Vec<(String, bool, ArchivedProfile)> = Vec::new();
+        for profile in archived {
+            let action = choices
+                .iter()
+                .find(|c| c.profile == profile.info.name)
+                .map_or(ImportAction::Import, |c| c.action.clone());
+            let (target, overwrite) = match action {
+                ImportAction::Skip => continue,
+                ImportAction::Import => (profile.info.name.clone(), false),
+                ImportAction::Rename(name) => (name, false),
+                ImportAction::Overwrite => (profile.info.name.clone(), true),
+            };
+
+            let existing = self.profiles.iter().find(|p| p.name == target);
+            match existing {
+                Some(_) if !overwrite => {
+                    return Err(AppError::ConfigError(format!(
+                        "Profile '{}' already exists; rename or overwrite it",
+                        target
+                    )))
+                }
+                Some(existing) if existing.bundle_dir.is_some() != profile.info.bundle => {
+                    return Err(AppError::ConfigError(format!(
+                        "Profile '{}' cannot be overwritten by a profile of a different kind; rename it",
+                        target
+                    )))
+                }
+                Some(_) => self.ensure_writable(&target)?,
+                None => {}
+            }
+            if plan.iter().any(|(name, ..)| *name == target) {
+                return Err(AppError::ConfigError(format!(
+                    "Two imported profiles would be named '{}'",
+                    target
+                )));
+            }
+            let errors = self.import_errors(&target, &profile);
+            if !errors.is_empty() {
+                return Err(AppError::ConfigError(format!(
+                    "Cannot import '{}': {}",
+                    profile.info.name,
+                    errors.join("; ")
+                )));
+            }
+            plan.push((target, existing.is_some(), profile));
+        }
+
+        let mut imported = Vec::new();
+        for (target, exists, profile) in plan {
+            match (&profile.bundle, exists) {
+                (None, false) => {
+                    self.create_profile(&target, &profile.settings)?;
+                }
+                (None, true) => self.save_profile_content(&target, &profile.settings)?,
+                (Some(bundle), _) => {
+                    self.write_imported_bundle(&target, bundle, &profile.settings, exists)?
+                }
+            }
+            log::info!("Imported profile '{}' as '{}'", profile.info.name, target);
+            imported.push(target);
+        }
+        self.scan_profiles()?;
+        Ok(imported)
+    }
+
+    /// Validation problems that keep an archived profile from being imported as `name`
+    fn import_errors(&self, name: &str, profile: &ArchivedProfile) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Err(e) = self.validate_profile_name(name) {
+            errors.push(e.to_string());
+        }
+        match self.validate_json_content(&profile.settings) {
+            Ok(result) => errors.extend(result.errors.into_iter().map(|e| e.message)),
+            Err(e) => errors.push(e.to_string()),
+        }
+        if errors.is_empty() {
+            if let Err(e) = self.enforce_policy_on_content(name, &profile.settings) {
+                errors.push(e.to_string());
+            }
+        }
+        errors
+    }
+
+    /// Write an imported directory profile; an existing one loses files the archive does not have
+    fn write_imported_bundle(
+        &mut self,
+        name: &str,
+        bundle: &ProfileBundle,
+        settings: &str,
+        exists: bool,
+    ) -> AppResult<()> {
+        let bundle_dir = self
+            .claude_dir
+            .join(format!("{}{}", name, profile_bundle::BUNDLE_SUFFIX));
+        if exists {
+            for rel in ProfileBundle::load(&bundle_dir)?.files.keys() {
+                fs::remove_file(bundle_dir.join(rel))?;
+            }
+        }
+        fs::create_dir_all(&bundle_dir)?;
+        for (rel, content) in &bundle.files {
+            let path = bundle_dir.join(rel);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
+        }
+
+        if exists {
+            // 走正常保存流程，保留历史版本和 git 记录
+            return self.save_profile_content(name, settings);
+        }
+        self.save_to_file(&bundle_dir.join(profile_bundle::BUNDLE_SETTINGS_FILE), settings)?;
+        self.git_commit(Some(name), &format!("Create profile '{}'", name));
+        Ok(())
+    }
+
+    /// Delete a profile by profile ID
+    pub fn delete_profile(&mut self, profile_id: &str) -> AppResult<()> {
+        log::info!("Attempting to delete profile: {}", profile_id);
+
+        // Prevent deletion of 'current' profile
+        if profile_id == "current" {
+            return Err(AppError::ConfigError(
+                "Cannot delete the current profile".to_string(),
+            ));
+        }
+        self.ensure_writable(profile_id)?;
+
+        // Find the profile to delete
+        let profile_index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == profile_id)
+            .ok_or_else(|| AppError::ConfigError(format!("Profile '{}' not found", profile_id)))?;
+
+        let profile = &self.profiles[profile_index];
+        let profile_path = profile.path.clone();
+
+        // Check if profile file exists
+        if let Some(bundle_dir) = profile.bundle_dir.clone() {
+            // A bundle profile is removed together with its CLAUDE.md, agents and commands
+            if let Err(e) = fs::remove_dir_all(&bundle_dir) {
+                return Err(AppError::FileSystemError(format!(
+                    "Failed to delete profile bundle: {}",
+                    e
+                )));
+            }
+            log::info!("Deleted profile bundle: {}", bundle_dir.display());
+        } else if !profile_path.exists() {
+            log::warn!("Profile file does not exist: {}", profile_path.display());
+            // Still remove from profiles list even if file doesn't exist
+        } else {
+            // Delete the file
+            if let Err(e) = fs::remove_file(&profile_path) {
+                return Err(AppError::FileSystemError(format!(
+                    "Failed to delete profile file: {}",
+                    e
+                )));
+            }
+            log::info!("Deleted profile file: {}", profile_path.display());
+        }
+
+        // Remove from profiles list
+        self.profiles.remove(profile_index);
+
+        // Remove from cache if exists
         self.profile_cache.remove(&profile_path);
 
+        // A later profile with the same name starts without the old metadata
+        if let Ok(mut store) = self.metadata_store() {
+            if store.profiles.remove(profile_id).is_some() {
+                if let Err(e) = self.save_metadata_store(&store) {
+                    log::warn!("Failed to remove metadata of profile '{}': {}", profile_id, e);
+                }
+            }
+        }
+        self.update_recent_profiles(|recent| recent.remove(profile_id));
+
         // Refresh remaining profiles status
         self.refresh_profile_status()?;
 
+        self.git_commit(Some(profile_id), &format!("Delete profile '{}'", profile_id));
+
         log::info!("Successfully deleted profile: {}", profile_id);
         Ok(())
     }
@@ -1165,7 +2829,44 @@ impl ConfigService {
 
     /// Validate JSON content using extensible validation framework
     pub fn validate_json_content(&self, content: &str) -> AppResult<ValidationResult> {
-        self.validator.validate(content)
+        let mut result = self.validator.validate(content)?;
+
+        // Unresolved ${env:..} / ${var:..} placeholders would fail at switch time
+        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(content) {
+            let errors = interpolation::validate_placeholders(
+                content,
+                &json_value,
+                &self.interpolation_context(),
+            );
+            if !errors.is_empty() {
+                result.is_valid = false;
+                result.errors.extend(errors);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Validate profile content and warn when the file holding it exposes plaintext secrets
+    pub fn validate_profile_content(
+        &mut self,
+        profile_id: &str,
+        content: &str,
+    ) -> AppResult<ValidationResult> {
+        let mut result = self.validate_json_content(content)?;
+
+        if let Ok(path) = self.get_profile_path(profile_id) {
+            if let Some(warning) = self.get_permission_warning(&path, content) {
+                result.warnings.push(ValidationError {
+                    line: 1,
+                    column: 1,
+                    message: warning,
+                    error_type: "permission".to_string(),
+                });
+            }
+        }
+
+        Ok(result)
     }
 
     /// Get the names of all active validation rules
@@ -1249,9 +2950,19 @@ impl ConfigService {
             })?;
         }
 
-        fs::write(&temp_path, &normalized_content).map_err(|e| {
-            AppError::FileSystemError(format!("Failed to write temporary file: {}", e))
-        })?;
+        file_permissions::write_private_file(&temp_path, normalized_content.as_bytes())
+            .map_err(|e| {
+                AppError::FileSystemError(format!("Failed to write temporary file: {}", e))
+            })?;
+
+        // Keep the existing file mode across the rename
+        if let Err(e) = file_permissions::preserve_mode(&temp_path, path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(AppError::PermissionError(format!(
+                "Failed to preserve file permissions: {}",
+                e
+            )));
+        }
 
         // Atomic move
         fs::rename(&temp_path, path).map_err(|e| {
@@ -1263,6 +2974,140 @@ impl ConfigService {
     }
 }
 
+/// A switch whose checks passed and whose pre-switch hooks are about to run
+pub struct PendingSwitch {
+    profile: Profile,
+    scope: SettingsScope,
+    bundle: Option<ProfileBundle>,
+    hooks: SwitchHooksConfig,
+    context: HookContext,
+}
+
+impl PendingSwitch {
+    fn run_hooks(&self, stage: HookStage) -> Vec<HookRun> {
+        switch_hooks::run_hooks(
+            &self.hooks.hooks_for(&self.profile.name, stage),
+            stage,
+            &self.context,
+        )
+    }
+}
+
+/// How a switch reaches the config service. A switch runs in steps around its hooks:
+/// `&mut ConfigService` is held for all of them, while the shared
+/// `&Mutex<ConfigService>` is locked per step and stays free while hooks run.
+pub trait ConfigAccess: Sized {
+    fn with<R>(&mut self, f: impl FnOnce(&mut ConfigService) -> AppResult<R>) -> AppResult<R>;
+
+    /// Switch to a specific profile configuration with enhanced error handling
+    fn switch_profile(mut self, profile_name: &str) -> AppResult<()> {
+        switch_in_scope(&mut self, profile_name, &SettingsScope::User)
+    }
+
+    /// Apply a profile to the user settings or to a project's shared or local settings
+    fn switch_profile_in_scope(mut self, profile_name: &str, scope: &SettingsScope) -> AppResult<()> {
+        switch_in_scope(&mut self, profile_name, scope)
+    }
+
+    /// Switch to a profile for `duration`, then revert to whatever was active before.
+    /// Starting a new lease while one is running keeps the original previous state.
+    fn switch_profile_temporarily(
+        mut self,
+        profile_name: &str,
+        duration: std::time::Duration,
+    ) -> AppResult<SwitchLease> {
+        let lease = self.with(|config| config.new_switch_lease(profile_name, duration))?;
+        switch_in_scope(&mut self, profile_name, &SettingsScope::User)?;
+        self.with(|config| lease.save(&config.claude_dir))?;
+
+        log::info!(
+            "Switched to '{}' until {} (lease)",
+            profile_name,
+            lease.expires_at
+        );
+        Ok(lease)
+    }
+
+    /// End the temporary switch. With `revert`, the previous state is restored unless the
+    /// user has switched to something else in the meantime.
+    fn end_switch_lease(mut self, revert: bool) -> AppResult<Option<SwitchLease>> {
+        end_lease(&mut self, revert)
+    }
+
+    /// Revert the lease if it has expired; returns the lease that ended
+    fn expire_switch_lease(mut self) -> AppResult<Option<SwitchLease>> {
+        let expired = self.with(|config| {
+            Ok(config
+                .get_switch_lease()
+                .is_some_and(|lease| lease.is_expired(switch_lease::now_secs())))
+        })?;
+        if expired {
+            end_lease(&mut self, true)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ConfigAccess for &mut ConfigService {
+    fn with<R>(&mut self, f: impl FnOnce(&mut ConfigService) -> AppResult<R>) -> AppResult<R> {
+        f(self)
+    }
+}
+
+impl ConfigAccess for &Mutex<ConfigService> {
+    fn with<R>(&mut self, f: impl FnOnce(&mut ConfigService) -> AppResult<R>) -> AppResult<R> {
+        let mut config = self
+            .lock()
+            .map_err(|e| AppError::ConfigError(format!("Failed to lock config service: {}", e)))?;
+        f(&mut config)
+    }
+}
+
+fn switch_in_scope(
+    access: &mut impl ConfigAccess,
+    profile_name: &str,
+    scope: &SettingsScope,
+) -> AppResult<()> {
+    let Some(pending) = access.with(|config| config.prepare_switch(profile_name, scope))? else {
+        return Ok(());
+    };
+
+    // Pre-switch hooks run before anything is written, so a failing one simply aborts
+    let pre_runs = pending.run_hooks(HookStage::PreSwitch);
+    access.with(|config| config.finish_switch(&pending, pre_runs))?;
+
+    // Post-switch hook failures are reported but don't undo the switch
+    let post_runs = pending.run_hooks(HookStage::PostSwitch);
+    access.with(|config| {
+        config.last_hook_runs.extend(post_runs);
+        Ok(())
+    })
+}
+
+fn end_lease(access: &mut impl ConfigAccess, revert: bool) -> AppResult<Option<SwitchLease>> {
+    let Some((lease, still_active)) = access.with(|config| config.take_switch_lease())? else {
+        return Ok(None);
+    };
+    if !revert {
+        return Ok(Some(lease));
+    }
+    if !still_active {
+        log::info!(
+            "Lease for '{}' ended; another configuration is active, nothing to revert",
+            lease.profile
+        );
+        return Ok(Some(lease));
+    }
+
+    match &lease.previous {
+        PreviousState::Profile(name) => switch_in_scope(access, name, &SettingsScope::User)?,
+        PreviousState::Settings(content) => access.with(|config| config.apply_profile_content(content))?,
+    }
+    log::info!("Lease for '{}' ended, previous configuration restored", lease.profile);
+    Ok(Some(lease))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1413,4 +3258,946 @@ mod tests {
         assert_eq!(restored_content, r#"{"theme": "dark", "language": "en"}"#);
         assert!(!backup_path.exists()); // Backup should be removed
     }
+
+    fn attach_test_vault(service: &mut ConfigService, temp_dir: &TempDir) {
+        let mut vault = SecretVault::open(temp_dir.path().join("vault.json")).unwrap();
+        vault.initialize(Some("passphrase")).unwrap();
+        vault.add_secret("work-token", "sk-secret").unwrap();
+        service.set_secret_vault(Arc::new(Mutex::new(vault)));
+    }
+
+    #[test]
+    fn test_create_profile_from_template_with_vault() {
+        let (mut service, temp_dir) = create_test_config_service();
+        attach_test_vault(&mut service, &temp_dir);
+        let registry = profile_templates::TemplateRegistry::load(None);
+        let template = registry.get("litellm-proxy").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("ANTHROPIC_BASE_URL".to_string(), "https://proxy".to_string());
+        values.insert("ANTHROPIC_AUTH_TOKEN".to_string(), "sk-plain".to_string());
+        service.create_profile_from_template(template, "proxy", &values).unwrap();
+
+        let content = service.read_profile_content("proxy").unwrap();
+        assert!(content.contains("${secret:proxy.ANTHROPIC_AUTH_TOKEN}") && !content.contains("sk-plain"));
+        let vault = service.secret_vault.clone().unwrap();
+        assert_eq!(vault.lock().unwrap().get_secret("proxy.ANTHROPIC_AUTH_TOKEN").unwrap(), "sk-plain");
+
+        // A failed creation takes its secrets out of the vault again
+        assert!(service.create_profile_from_template(template, "proxy", &values).is_err());
+        let names: Vec<String> = vault.lock().unwrap().list_secrets().into_iter().map(|s| s.name).collect();
+        assert!(!names.contains(&"proxy.ANTHROPIC_AUTH_TOKEN-2".to_string()));
+        assert!(names.contains(&"proxy.ANTHROPIC_AUTH_TOKEN".to_string()));
+    }
+
+    #[test]
+    fn test_switch_profile_resolves_secrets() {
+        let (mut service, temp_dir) = create_test_config_service();
+        attach_test_vault(&mut service, &temp_dir);
+
+        let profile_content = r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "${secret:work-token}"}}"#;
+        let profile_path = temp_dir.path().join("work.settings.json");
+        fs::write(&profile_path, profile_content).unwrap();
+        service.scan_profiles().unwrap();
+
+        service.switch_profile("work").unwrap();
+
+        // settings.json receives the secret, the profile keeps the reference
+        let settings = fs::read_to_string(&service.default_settings_path).unwrap();
+        assert!(settings.contains("sk-secret"));
+        assert_eq!(fs::read_to_string(&profile_path).unwrap(), profile_content);
+
+        // Status compares against the resolved profile
+        assert_eq!(
+            service.get_detailed_profile_status(profile_content),
+            ProfileStatus::FullMatch
+        );
+        assert!(service.get_profiles()[0].is_active);
+    }
+
+    #[test]
+    fn test_switch_profile_missing_secret_fails_before_writing() {
+        let (mut service, temp_dir) = create_test_config_service();
+        attach_test_vault(&mut service, &temp_dir);
+
+        let profile_content = r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "${secret:absent}"}}"#;
+        fs::write(temp_dir.path().join("broken.settings.json"), profile_content).unwrap();
+        service.scan_profiles().unwrap();
+
+        let result = service.switch_profile("broken");
+        match result {
+            Err(AppError::SecretError(msg)) => assert!(msg.contains("absent")),
+            other => panic!("Expected SecretError, got {:?}", other),
+        }
+
+        let settings = fs::read_to_string(&service.default_settings_path).unwrap();
+        assert_eq!(settings, r#"{"theme": "dark", "language": "en"}"#);
+        let backups = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains(".backup."))
+            .count();
+        assert_eq!(backups, 0);
+    }
+
+    #[test]
+    fn test_switch_profile_resolves_variables() {
+        let (mut service, temp_dir) = create_test_config_service();
+        service.set_variables_path(temp_dir.path().join("variables.json"));
+        service.set_user_variable("api_host", "https://proxy.example").unwrap();
+
+        let profile_content =
+            r#"{"env": {"ANTHROPIC_BASE_URL": "${var:api_host}", "HOOKS": "${claude_dir}/hooks"}}"#;
+        fs::write(temp_dir.path().join("proxy.settings.json"), profile_content).unwrap();
+        service.scan_profiles().unwrap();
+
+        service.switch_profile("proxy").unwrap();
+
+        let settings: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&service.default_settings_path).unwrap())
+                .unwrap();
+        assert_eq!(settings["env"]["ANTHROPIC_BASE_URL"], "https://proxy.example");
+        assert_eq!(
+            settings["env"]["HOOKS"],
+            format!("{}/hooks", temp_dir.path().to_string_lossy())
+        );
+        assert_eq!(
+            service.get_detailed_profile_status(profile_content),
+            ProfileStatus::FullMatch
+        );
+    }
+
+    #[test]
+    fn test_unresolved_variables_are_validation_errors() {
+        let (mut service, temp_dir) = create_test_config_service();
+        service.set_variables_path(temp_dir.path().join("variables.json"));
+
+        let content = r#"{"env": {"URL": "${var:api_host}"}}"#;
+        let result = service.validate_json_content(content).unwrap();
+        assert!(!result.is_valid);
+        assert!(result.errors.iter().any(|e| e.message.contains("api_host")));
+
+        service.set_user_variable("api_host", "https://proxy.example").unwrap();
+        assert!(service.validate_json_content(content).unwrap().is_valid);
+
+        service.remove_user_variable("api_host").unwrap();
+        assert!(service.remove_user_variable("api_host").is_err());
+        assert!(matches!(
+            service.apply_profile_content(content),
+            Err(AppError::ConfigError(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permissions_preserved_and_fixed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (mut service, temp_dir) = create_test_config_service();
+        let mode_of = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // New profiles are private
+        let content = r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "sk-plain"}}"#;
+        service.create_profile("work", content).unwrap();
+        let profile_path = temp_dir.path().join("work.settings.json");
+        assert_eq!(mode_of(&profile_path), 0o600);
+
+        // Switching keeps the existing settings.json mode
+        fs::set_permissions(
+            &service.default_settings_path,
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        service.switch_profile("work").unwrap();
+        assert_eq!(mode_of(&service.default_settings_path), 0o644);
+
+        // settings.json now holds a plaintext token readable by others
+        assert!(service.get_profile_permission_warning("current").is_some());
+        assert!(service.get_profile_permission_warning("work").is_none());
+        let result = service.validate_profile_content("current", content).unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.warnings.len(), 1);
+
+        // The profile history repository keeps every version of the settings
+        fs::create_dir_all(temp_dir.path().join(".git")).unwrap();
+        fs::set_permissions(temp_dir.path().join(".git"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Project local settings receive resolved secrets, and so do their backups
+        let project = temp_dir.path().join("project");
+        let local_path = project.join(".claude/settings.local.json");
+        fs::create_dir_all(local_path.parent().unwrap()).unwrap();
+        fs::write(&local_path, "{}").unwrap();
+        fs::set_permissions(&local_path, fs::Permissions::from_mode(0o644)).unwrap();
+        service
+            .switch_profile_in_scope("work", &SettingsScope::ProjectLocal(project.clone()))
+            .unwrap();
+        let backup_dir = service.backup_dir_for(&local_path);
+        let backup = fs::read_dir(&backup_dir).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(mode_of(&backup), 0o600);
+        fs::set_permissions(&backup, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let fixed = service.fix_permissions(std::slice::from_ref(&project)).unwrap();
+        assert!(fixed.failed_files.is_empty());
+        assert_eq!(mode_of(&service.default_settings_path), 0o600);
+        assert_eq!(mode_of(&temp_dir.path().join(".git")), 0o700);
+        assert_eq!(mode_of(&local_path), 0o600);
+        assert_eq!(mode_of(&backup), 0o600);
+        assert_eq!(mode_of(&backup_dir), 0o700);
+        assert!(service.get_profile_permission_warning("current").is_none());
+    }
+
+    #[test]
+    fn test_edit_permissions_across_profiles() {
+        let (mut service, temp_dir) = create_test_config_service();
+        fs::write(temp_dir.path().join("a.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        fs::write(
+            temp_dir.path().join("b.settings.json"),
+            r#"{"permissions": {"deny": ["Bash(rm:*)"]}}"#,
+        )
+        .unwrap();
+        service.scan_profiles().unwrap();
+        let ids = vec!["a".to_string(), "b".to_string()];
+
+        service
+            .edit_profiles_json(&ids, |settings| {
+                crate::permissions_editor::add_rule(
+                    settings,
+                    crate::permissions_editor::PermissionList::Allow,
+                    "Bash(npm run:*)",
+                )
+            })
+            .unwrap();
+        assert_eq!(service.get_profile_permissions("a").unwrap().rules.len(), 1);
+        assert_eq!(service.get_profile_permissions("b").unwrap().rules.len(), 2);
+
+        // A contradiction in one profile leaves every profile untouched
+        let before_a = fs::read_to_string(temp_dir.path().join("a.settings.json")).unwrap();
+        let result = service.edit_profiles_json(&ids, |settings| {
+            crate::permissions_editor::add_rule(
+                settings,
+                crate::permissions_editor::PermissionList::Ask,
+                "Bash(rm:*)",
+            )
+        });
+        assert!(result.unwrap_err().to_string().contains("Profile 'b'"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.settings.json")).unwrap(),
+            before_a
+        );
+
+        // A write that fails after 'a' was saved puts 'a' back
+        let b_path = temp_dir.path().join("b.settings.json");
+        let result = service.edit_profiles_json(&ids, |settings| {
+            if settings.get("permissions").and_then(|p| p.get("deny")).is_some() {
+                // 'b' 变成目录，写入时失败
+                fs::remove_file(&b_path).unwrap();
+                fs::create_dir(&b_path).unwrap();
+            }
+            settings["model"] = serde_json::json!("haiku");
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.settings.json")).unwrap(),
+            before_a
+        );
+    }
+
+    #[test]
+    fn test_copy_hook_to_profiles() {
+        let (mut service, temp_dir) = create_test_config_service();
+        fs::write(temp_dir.path().join("a.settings.json"), r#"{}"#).unwrap();
+        fs::write(temp_dir.path().join("b.settings.json"), r#"{}"#).unwrap();
+        service.scan_profiles().unwrap();
+
+        let hook = HookEntry {
+            event: "Stop".to_string(),
+            matcher: None,
+            command: "echo done".to_string(),
+            timeout: Some(30),
+        };
+        let ids = vec!["a".to_string(), "b".to_string()];
+        service.copy_hook_to_profiles(&hook, &ids).unwrap();
+        // Copying again is a no-op rather than a duplicate
+        service.copy_hook_to_profiles(&hook, &ids).unwrap();
+
+        for id in &ids {
+            assert_eq!(service.get_profile_hooks(id).unwrap(), vec![hook.clone()]);
+        }
+
+        // Hooks pointing at programs missing from this PATH are only warned about
+        let content = r#"{"hooks": {"Stop": [{"hooks": [{"type": "command", "command": "cccs-missing-hook-binary"}]}]}}"#;
+        let result = service.validate_json_content(content).unwrap();
+        assert!(result.is_valid);
+        assert!(result.warnings[0].message.contains("cccs-missing-hook-binary"));
+        service.save_profile_content("a", content).unwrap();
+
+        // Unknown events still block the save
+        let content = r#"{"hooks": {"OnSave": [{"hooks": [{"type": "command", "command": "echo x"}]}]}}"#;
+        assert!(!service.validate_json_content(content).unwrap().is_valid);
+        assert!(service.save_profile_content("a", content).is_err());
+    }
+
+    #[test]
+    fn test_bundle_profiles_switch_and_status() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+
+        let bundle_dir = claude_dir.join("work.profile");
+        fs::create_dir_all(bundle_dir.join("agents")).unwrap();
+        fs::write(bundle_dir.join("settings.json"), r#"{"theme": "light"}"#).unwrap();
+        fs::write(bundle_dir.join("CLAUDE.md"), "# Work rules").unwrap();
+        fs::write(bundle_dir.join("agents/reviewer.md"), "review").unwrap();
+        fs::write(claude_dir.join("plain.settings.json"), r#"{"theme": "dark"}"#).unwrap();
+
+        let profiles = service.scan_profiles().unwrap();
+        let work = profiles.iter().find(|p| p.name == "work").unwrap();
+        assert_eq!(work.bundle_dir.as_deref(), Some(bundle_dir.as_path()));
+        assert_eq!(work.path, bundle_dir.join("settings.json"));
+
+        service.switch_profile("work").unwrap();
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "# Work rules"
+        );
+        assert!(claude_dir.join("agents/reviewer.md").exists());
+        assert_eq!(service.get_profile_status("work"), ProfileStatus::FullMatch);
+        assert!(!claude_dir.join(".cccs-journal.json").exists());
+
+        // Settings still match but a managed file drifted
+        fs::write(claude_dir.join("CLAUDE.md"), "edited").unwrap();
+        service.refresh_profile_status().unwrap();
+        assert_eq!(service.get_profile_status("work"), ProfileStatus::PartialMatch);
+        assert!(!service.get_profiles().iter().any(|p| p.is_active));
+
+        // Switching again reinstalls the bundle
+        service.switch_profile("work").unwrap();
+        assert_eq!(service.get_profile_status("work"), ProfileStatus::FullMatch);
+
+        // A plain profile removes the bundle files
+        service.switch_profile("plain").unwrap();
+        assert!(!claude_dir.join("CLAUDE.md").exists());
+        assert!(!claude_dir.join("agents/reviewer.md").exists());
+        assert_eq!(service.get_profile_status("plain"), ProfileStatus::FullMatch);
+
+        service.delete_profile("work").unwrap();
+        assert!(!bundle_dir.exists());
+    }
+
+    #[test]
+    fn test_bundle_not_installed_when_switch_fails() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+
+        let bundle_dir = claude_dir.join("broken.profile");
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::write(bundle_dir.join("settings.json"), r#"{"key": "${secret:missing}"}"#).unwrap();
+        fs::write(bundle_dir.join("CLAUDE.md"), "broken").unwrap();
+        fs::write(claude_dir.join("CLAUDE.md"), "mine").unwrap();
+        service.scan_profiles().unwrap();
+
+        assert!(service.switch_profile("broken").is_err());
+        assert_eq!(fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(), "mine");
+    }
+
+    #[test]
+    fn test_apply_profile_to_project_scopes() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(&project).unwrap();
+
+        let bundle_dir = temp_dir.path().join("work.profile");
+        fs::create_dir_all(bundle_dir.join("commands")).unwrap();
+        fs::write(bundle_dir.join("settings.json"), r#"{"theme": "light"}"#).unwrap();
+        fs::write(bundle_dir.join("commands/deploy.md"), "deploy").unwrap();
+        service.scan_profiles().unwrap();
+
+        let shared = SettingsScope::ProjectShared(project.clone());
+        let local = SettingsScope::ProjectLocal(project.clone());
+        assert_eq!(
+            service.get_profile_scope_status("work", &shared, None),
+            ProfileStatus::NoMatch
+        );
+
+        service.switch_profile_in_scope("work", &shared).unwrap();
+        assert!(project.join(".claude/settings.json").exists());
+        assert!(project.join(".claude/commands/deploy.md").exists());
+        assert_eq!(
+            service.get_profile_scope_status("work", &shared, None),
+            ProfileStatus::FullMatch
+        );
+        // The user scope is untouched
+        assert_eq!(
+            service.get_profile_scope_status("work", &SettingsScope::User, None),
+            ProfileStatus::NoMatch
+        );
+        assert!(!temp_dir.path().join("commands").exists());
+
+        // The local scope only receives settings
+        service.switch_profile_in_scope("work", &local).unwrap();
+        assert!(project.join(".claude/settings.local.json").exists());
+        assert_eq!(
+            service.get_profile_scope_status("work", &local, None),
+            ProfileStatus::FullMatch
+        );
+
+        // Existing project settings are backed up outside the project before being replaced
+        fs::write(project.join(".claude/settings.local.json"), r#"{"theme": "x"}"#).unwrap();
+        service.switch_profile_in_scope("work", &local).unwrap();
+        let count_backups = |dir: &Path| {
+            fs::read_dir(dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .filter(|e| e.file_name().to_string_lossy().contains(".json.backup."))
+                        .count()
+                })
+                .unwrap_or(0)
+        };
+        assert_eq!(count_backups(&project.join(".claude")), 0);
+        let backup_dir = service.backup_dir_for(&project.join(".claude/settings.local.json"));
+        assert!(backup_dir.starts_with(temp_dir.path().join(PROJECT_BACKUP_DIR)));
+        assert_eq!(count_backups(&backup_dir), 1);
+
+        // Vault secrets are never written into the shared settings
+        fs::write(
+            temp_dir.path().join("token.settings.json"),
+            r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "${secret:token}"}}"#,
+        )
+        .unwrap();
+        service.scan_profiles().unwrap();
+        let err = service.switch_profile_in_scope("token", &shared).unwrap_err();
+        assert!(err.to_string().contains("local settings"));
+        assert_eq!(
+            service.get_profile_scope_status("work", &shared, None),
+            ProfileStatus::FullMatch
+        );
+
+        let missing = SettingsScope::ProjectShared(temp_dir.path().join("missing"));
+        assert!(service.switch_profile_in_scope("work", &missing).is_err());
+    }
+
+    #[test]
+    fn test_profile_history() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+        fs::write(claude_dir.join("work.settings.json"), "{\n  \"model\": \"opus\"\n}").unwrap();
+        service.scan_profiles().unwrap();
+        service.set_history_dir(claude_dir.join("history"));
+
+        // The content before the first save is recorded too
+        service.save_profile_content("work", "{\n  \"model\": \"sonnet\"\n}").unwrap();
+        service.save_profile_content("work", "{\n  \"model\": \"sonnet\"\n}").unwrap();
+        service.save_profile_content("work", "{\n  \"model\": \"haiku\"\n}").unwrap();
+        let ids: Vec<u64> = service.list_profile_versions("work").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        let diff = service.diff_profile_versions("work", 1, Some(2)).unwrap();
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        let diff = service.diff_profile_versions("work", 3, None).unwrap();
+        assert_eq!((diff.added, diff.removed), (0, 0));
+
+        service.restore_profile_version("work", 1).unwrap();
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("work.settings.json")).unwrap(),
+            "{\n  \"model\": \"opus\"\n}"
+        );
+        let versions = service.list_profile_versions("work").unwrap();
+        assert_eq!(versions.len(), 4);
+        assert_eq!(versions[0].hash, versions[3].hash);
+    }
+
+    #[test]
+    fn test_git_store() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+        fs::write(claude_dir.join("opus.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        service.scan_profiles().unwrap();
+        assert!(service.get_git_log(None).is_err());
+
+        service.set_git_store_enabled(true).unwrap();
+        service.create_profile("sonnet", r#"{"model": "sonnet"}"#).unwrap();
+        service.save_profile_content("sonnet", r#"{"model": "sonnet", "env": {}}"#).unwrap();
+        service.switch_profile("sonnet").unwrap();
+        service.delete_profile("opus").unwrap();
+
+        let messages = |log: Vec<GitCommit>| log.into_iter().map(|c| c.message).collect::<Vec<_>>();
+        assert_eq!(
+            messages(service.get_git_log(Some("sonnet")).unwrap()),
+            vec!["Update profile 'sonnet'", "Create profile 'sonnet'"]
+        );
+        assert_eq!(
+            messages(service.get_git_log(Some("opus")).unwrap()),
+            vec!["Delete profile 'opus'", "Track existing profiles"]
+        );
+        assert_eq!(service.get_git_log(None).unwrap().len(), 5);
+
+        let created = &service.get_git_log(Some("sonnet")).unwrap()[1];
+        let content = service.get_profile_at_revision("sonnet", &created.id).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&content).unwrap(),
+            serde_json::json!({"model": "sonnet"})
+        );
+        assert!(service.get_profile_at_revision("opus", "HEAD").is_err());
+    }
+
+    #[test]
+    fn test_export_and_import_profiles() {
+        let (mut source, source_dir) = create_test_config_service();
+        fs::write(
+            source_dir.path().join("work.settings.json"),
+            r#"{"model": "opus", "env": {"ANTHROPIC_AUTH_TOKEN": "sk-123"}}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(source_dir.path().join("team.profile/agents")).unwrap();
+        fs::write(source_dir.path().join("team.profile/settings.json"), "{}").unwrap();
+        fs::write(source_dir.path().join("team.profile/agents/review.md"), "review").unwrap();
+        source.scan_profiles().unwrap();
+
+        let archive = source_dir.path().join("profiles.tar.gz");
+        let manifest = source
+            .export_profiles(
+                &["work".to_string(), "team".to_string()],
+                &archive,
+                SecretHandling::Placeholder,
+            )
+            .unwrap();
+        assert_eq!(manifest.profiles[0].secrets, vec!["env.ANTHROPIC_AUTH_TOKEN"]);
+
+        let (mut target, target_dir) = create_test_config_service();
+        fs::write(target_dir.path().join("work.settings.json"), r#"{"model": "haiku"}"#).unwrap();
+        target.scan_profiles().unwrap();
+
+        let preview = target.preview_profile_import(&archive).unwrap();
+        let conflicts: Vec<(&str, bool)> =
+            preview.profiles.iter().map(|p| (p.name.as_str(), p.conflict)).collect();
+        assert_eq!(conflicts, vec![("work", true), ("team", false)]);
+        assert!(preview.profiles.iter().all(|p| p.errors.is_empty()));
+
+        // A conflict needs an explicit choice, and nothing is written when one is missing
+        assert!(target.import_profiles(&archive, &[]).is_err());
+        assert!(!target_dir.path().join("team.profile").exists());
+
+        let imported = target
+            .import_profiles(
+                &archive,
+                &[ImportChoice {
+                    profile: "work".to_string(),
+                    action: ImportAction::Rename("work-shared".to_string()),
+                }],
+            )
+            .unwrap();
+        assert_eq!(imported, vec!["work-shared", "team"]);
+        let content = fs::read_to_string(target_dir.path().join("work-shared.settings.json")).unwrap();
+        assert!(content.contains("${secret:work.ANTHROPIC_AUTH_TOKEN}"));
+        assert!(!content.contains("sk-123"));
+        assert_eq!(
+            fs::read_to_string(target_dir.path().join("team.profile/agents/review.md")).unwrap(),
+            "review"
+        );
+
+        target
+            .import_profiles(
+                &archive,
+                &[
+                    ImportChoice { profile: "work".to_string(), action: ImportAction::Overwrite },
+                    ImportChoice { profile: "team".to_string(), action: ImportAction::Skip },
+                ],
+            )
+            .unwrap();
+        let content = fs::read_to_string(target_dir.path().join("work.settings.json")).unwrap();
+        assert!(content.contains("opus"));
+    }
+
+    #[test]
+    fn test_signed_profiles() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let key_file = temp_dir.path().join("platform.key");
+        let public_key = profile_signing::generate_signing_key(&key_file).unwrap();
+        let key = profile_signing::load_signing_key(&key_file).unwrap();
+
+        let signed = temp_dir.path().join("signed.settings.json");
+        fs::write(&signed, r#"{"model": "opus"}"#).unwrap();
+        profile_signing::sign_profile(&signed, None, &key).unwrap();
+        fs::write(temp_dir.path().join("plain.settings.json"), r#"{"model": "haiku"}"#).unwrap();
+        service.scan_profiles().unwrap();
+
+        // No trusted keys: signatures are not checked
+        assert_eq!(service.get_profile_status("plain"), ProfileStatus::NoMatch);
+        service
+            .set_trusted_keys(vec![TrustedKey {
+                name: "platform".to_string(),
+                public_key,
+            }])
+            .unwrap();
+
+        let signature = |service: &ConfigService, name: &str| {
+            service.get_profiles().iter().find(|p| p.name == name).unwrap().signature.clone()
+        };
+        assert_eq!(signature(&service, "signed"), SignatureState::Valid("platform".to_string()));
+        assert_eq!(service.get_profile_status("plain"), ProfileStatus::Unsigned);
+        assert!(matches!(service.switch_profile("plain"), Err(AppError::SignatureError(_))));
+        service.switch_profile("signed").unwrap();
+
+        fs::write(&signed, r#"{"model": "opus", "env": {"ANTHROPIC_BASE_URL": "https://evil.example"}}"#).unwrap();
+        service.scan_profiles().unwrap();
+        assert!(matches!(service.get_profile_status("signed"), ProfileStatus::SignatureInvalid(_)));
+        assert!(matches!(service.switch_profile("signed"), Err(AppError::SignatureError(_))));
+    }
+
+    #[test]
+    fn test_subscribed_profiles_keep_upstream_signature() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let key_file = temp_dir.path().join("platform.key");
+        let public_key = profile_signing::generate_signing_key(&key_file).unwrap();
+        let key = profile_signing::load_signing_key(&key_file).unwrap();
+        service
+            .set_trusted_keys(vec![TrustedKey {
+                name: "platform".to_string(),
+                public_key,
+            }])
+            .unwrap();
+
+        let shared = TempDir::new().unwrap();
+        let upstream = shared.path().join("work.settings.json");
+        fs::write(&upstream, r#"{"model": "opus"}"#).unwrap();
+        profile_signing::sign_profile(&upstream, None, &key).unwrap();
+        service.set_subscriptions_dir(temp_dir.path().join("subscriptions"));
+        service
+            .set_subscriptions(vec![ProfileSubscription {
+                name: "team".to_string(),
+                path: shared.path().to_path_buf(),
+                enabled: true,
+            }])
+            .unwrap();
+
+        // The merged profile inherits the state of the upstream snapshot, override included
+        let signature = |service: &ConfigService| {
+            service.get_profiles().iter().find(|p| p.name == "team:work").unwrap().signature.clone()
+        };
+        assert_eq!(signature(&service), SignatureState::Valid("platform".to_string()));
+        service
+            .save_profile_override("team:work", Some(r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "sk-me"}}"#))
+            .unwrap();
+        assert_eq!(signature(&service), SignatureState::Valid("platform".to_string()));
+        service.switch_profile("team:work").unwrap();
+
+        // An unsigned update is flagged once synced
+        fs::write(&upstream, r#"{"model": "sonnet"}"#).unwrap();
+        fs::remove_file(profile_signing::signature_path(&upstream)).unwrap();
+        service.update_subscription("team", None).unwrap();
+        assert_eq!(service.get_profile_status("team:work"), ProfileStatus::Unsigned);
+    }
+
+    #[test]
+    fn test_profile_metadata_and_order() {
+        let (mut service, temp_dir) = create_test_config_service();
+        for name in ["beta", "alpha", "gamma"] {
+            let content = format!(r#"{{"env": {{"PROFILE": "{}"}}}}"#, name);
+            fs::write(temp_dir.path().join(format!("{}.settings.json", name)), content).unwrap();
+        }
+        service.scan_profiles().unwrap();
+        let names = |service: &ConfigService| -> Vec<String> {
+            service.get_profiles().iter().map(|p| p.name.clone()).collect()
+        };
+        assert_eq!(names(&service), vec!["alpha", "beta", "gamma"]);
+
+        // Without a metadata file metadata cannot be edited
+        assert!(service.save_profile_metadata("beta", ProfileMetadata::default()).is_err());
+        let metadata_path = temp_dir.path().join("cccs").join("profile_metadata.json");
+        service.set_metadata_path(metadata_path.clone());
+
+        let metadata = ProfileMetadata {
+            description: Some("Client work".to_string()),
+            tags: vec!["client".to_string()],
+            icon: Some("💼".to_string()),
+            pinned: true,
+            ..Default::default()
+        };
+        service.save_profile_metadata("gamma", metadata.clone()).unwrap();
+        service
+            .reorder_profiles(&["beta".to_string(), "alpha".to_string()])
+            .unwrap();
+        assert_eq!(names(&service), vec!["gamma", "beta", "alpha"]);
+        assert_eq!(service.get_profiles()[0].metadata, metadata);
+
+        // The profile file itself is untouched
+        assert!(!fs::read_to_string(temp_dir.path().join("gamma.settings.json")).unwrap().contains("Client"));
+        let info = service.get_all_profiles_info().unwrap();
+        assert_eq!(info.iter().find(|p| p.id == "gamma").unwrap().metadata.tags, vec!["client"]);
+
+        let invalid = ProfileMetadata {
+            color: Some("red".to_string()),
+            ..Default::default()
+        };
+        assert!(service.save_profile_metadata("gamma", invalid).is_err());
+        assert!(service.reorder_profiles(&["missing".to_string()]).is_err());
+
+        // Switches are recorded newest first; deleting a profile drops it from the list
+        let recent_path = temp_dir.path().join("cccs").join("recent_profiles.json");
+        service.set_recent_profiles_path(recent_path.clone());
+        service.switch_profile("gamma").unwrap();
+        service.switch_profile("alpha").unwrap();
+        assert_eq!(RecentProfiles::load(&recent_path).unwrap().profiles, vec!["alpha", "gamma"]);
+
+        service.delete_profile("gamma").unwrap();
+        assert!(!MetadataStore::load(&metadata_path).unwrap().profiles.contains_key("gamma"));
+        assert_eq!(RecentProfiles::load(&recent_path).unwrap().profiles, vec!["alpha"]);
+    }
+
+    #[test]
+    fn test_tray_profile_actions() {
+        let (mut service, temp_dir) = create_test_config_service();
+        fs::write(
+            temp_dir.path().join("work.settings.json"),
+            r#"{"theme": "light", "language": "en", "env": {"ANTHROPIC_MODEL": "opus"}}"#,
+        )
+        .unwrap();
+        let bundle_dir = temp_dir.path().join("team.profile");
+        fs::create_dir_all(bundle_dir.join("agents")).unwrap();
+        fs::write(bundle_dir.join("settings.json"), "{}").unwrap();
+        fs::write(bundle_dir.join("agents/review.md"), "review").unwrap();
+        service.set_metadata_path(temp_dir.path().join("cccs").join("profile_metadata.json"));
+        service.scan_profiles().unwrap();
+        service
+            .save_profile_metadata("work", ProfileMetadata { icon: Some("💼".to_string()), pinned: true, ..Default::default() })
+            .unwrap();
+
+        let keys: Vec<String> = service.diff_with_current("work").unwrap().into_iter().map(|c| c.key).collect();
+        assert_eq!(keys, vec!["env.ANTHROPIC_MODEL", "theme"]);
+
+        let (script, count) = service.get_env_exports("work").unwrap();
+        assert_eq!(count, 1);
+        assert!(script.contains("ANTHROPIC_MODEL") && script.contains("opus"));
+        assert!(service.get_env_exports("team").is_err());
+        fs::write(
+            temp_dir.path().join("vaulted.settings.json"),
+            r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "${secret:work.token}"}}"#,
+        )
+        .unwrap();
+        service.scan_profiles().unwrap();
+        let (script, _) = service.get_env_exports("vaulted").unwrap();
+        assert!(script.contains("${secret:work.token}"));
+        service.delete_profile("vaulted").unwrap();
+
+        assert_eq!(service.duplicate_profile("work").unwrap(), "work copy");
+        assert_eq!(service.duplicate_profile("work").unwrap(), "work copy 2");
+        let copy = service.get_profiles().iter().find(|p| p.name == "work copy").unwrap();
+        assert_eq!(copy.metadata.icon.as_deref(), Some("💼"));
+        assert!(!copy.metadata.pinned);
+
+        assert_eq!(service.duplicate_profile("team").unwrap(), "team copy");
+        assert_eq!(
+            service.get_profile_location("team copy").unwrap(),
+            temp_dir.path().join("team copy.profile")
+        );
+        assert!(temp_dir.path().join("team copy.profile/agents/review.md").exists());
+    }
+
+    #[test]
+    fn test_org_policy_enforcement() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let policy_path = temp_dir.path().join("policy.json");
+        fs::write(
+            &policy_path,
+            r#"{"rules": [{"key": "permissions.defaultMode", "forbidden_values": ["bypassPermissions"]}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("yolo.settings.json"),
+            r#"{"permissions": {"defaultMode": "bypassPermissions"}}"#,
+        )
+        .unwrap();
+        service.scan_profiles().unwrap();
+
+        // Without a policy nothing is checked
+        assert!(service.get_policy_violations("yolo").unwrap().is_empty());
+        service.set_policy_path(policy_path.clone());
+
+        let violations = service.get_policy_violations("yolo").unwrap();
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(service.get_profile_status("yolo"), ProfileStatus::NonCompliant(ref v) if v.len() == 1));
+        assert!(matches!(service.switch_profile("yolo"), Err(AppError::PolicyError(_))));
+        assert!(matches!(
+            service.apply_profile_content(r#"{"permissions": {"defaultMode": "bypassPermissions"}}"#),
+            Err(AppError::PolicyError(_))
+        ));
+
+        let bad = r#"{"permissions": {"defaultMode": "bypassPermissions"}, "model": "opus"}"#;
+        assert!(service.create_profile("bad", bad).is_err());
+        assert!(!temp_dir.path().join("bad.settings.json").exists());
+        assert!(service.save_profile_content("yolo", bad).is_err());
+
+        service
+            .save_profile_content("yolo", r#"{"permissions": {"defaultMode": "acceptEdits"}}"#)
+            .unwrap();
+        service.scan_profiles().unwrap();
+        assert!(service.get_policy_violations("yolo").unwrap().is_empty());
+        service.switch_profile("yolo").unwrap();
+
+        // A broken policy file blocks instead of allowing everything
+        fs::write(&policy_path, "{ not json").unwrap();
+        assert!(matches!(service.get_profile_status("yolo"), ProfileStatus::Error(_)));
+        assert!(service.create_profile("other", "{}").is_err());
+    }
+
+    #[test]
+    fn test_subscribed_profiles() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let shared = TempDir::new().unwrap();
+        fs::write(shared.path().join("work.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        service.set_subscriptions_dir(temp_dir.path().join("subscriptions"));
+        service
+            .set_subscriptions(vec![ProfileSubscription {
+                name: "team".to_string(),
+                path: shared.path().to_path_buf(),
+                enabled: true,
+            }])
+            .unwrap();
+
+        let profile = service.get_profiles().iter().find(|p| p.name == "team:work").cloned().unwrap();
+        assert_eq!(profile.source.as_deref(), Some("team"));
+        assert!(!profile.update_available);
+
+        // Read-only apart from the local override
+        assert!(service.save_profile_content("team:work", "{}").is_err());
+        assert!(service.delete_profile("team:work").is_err());
+        fs::write(temp_dir.path().join("mine.settings.json"), "{}").unwrap();
+        service.scan_profiles().unwrap();
+        let ids = vec!["team:work".to_string(), "mine".to_string()];
+        let skipped = service
+            .edit_profiles_json(&ids, |settings| {
+                settings["model"] = serde_json::json!("haiku");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(skipped, vec!["team:work"]);
+        assert!(service.read_profile_content("mine").unwrap().contains("haiku"));
+        assert!(service.edit_profiles_json(&ids[..1], |_| Ok(())).is_err());
+        service.delete_profile("mine").unwrap();
+        service
+            .save_profile_override("team:work", Some(r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "sk-me"}}"#))
+            .unwrap();
+        let content = service.read_profile_content("team:work").unwrap();
+        assert!(content.contains("opus") && content.contains("sk-me"));
+
+        fs::write(shared.path().join("work.settings.json"), r#"{"model": "sonnet"}"#).unwrap();
+        service.scan_profiles().unwrap();
+        assert!(service.get_profiles().iter().any(|p| p.name == "team:work" && p.update_available));
+
+        assert_eq!(service.update_subscription("team", None).unwrap(), vec!["work"]);
+        let content = service.read_profile_content("team:work").unwrap();
+        assert!(content.contains("sonnet") && content.contains("sk-me"));
+        assert!(!service.get_profiles().iter().any(|p| p.update_available));
+
+        service.switch_profile("team:work").unwrap();
+        service.set_subscriptions(Vec::new()).unwrap();
+        assert!(service.get_profiles().is_empty());
+        assert!(!temp_dir.path().join("subscriptions/team").exists());
+    }
+
+    #[test]
+    fn test_temporary_switch_reverts() {
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+        let original = fs::read_to_string(claude_dir.join("settings.json")).unwrap();
+        fs::write(claude_dir.join("opus.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        fs::write(claude_dir.join("sonnet.settings.json"), r#"{"model": "sonnet"}"#).unwrap();
+        service.scan_profiles().unwrap();
+
+        // No profile was active, so the original settings are restored
+        let duration = std::time::Duration::from_secs(60 * 60);
+        let lease = service.switch_profile_temporarily("opus", duration).unwrap();
+        assert_eq!(lease.previous, PreviousState::Settings(original.clone()));
+        assert_eq!(service.get_switch_lease(), Some(lease.clone()));
+        assert_eq!(service.expire_switch_lease().unwrap(), None);
+
+        // A second lease keeps the first previous state
+        let lease = service.switch_profile_temporarily("sonnet", duration).unwrap();
+        assert_eq!(lease.previous, PreviousState::Settings(original.clone()));
+
+        let mut expired = lease.clone();
+        expired.expires_at = expired.started_at;
+        expired.save(claude_dir).unwrap();
+        assert_eq!(service.expire_switch_lease().unwrap(), Some(expired));
+        let restored = fs::read_to_string(claude_dir.join("settings.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&restored).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&original).unwrap()
+        );
+        assert_eq!(service.get_switch_lease(), None);
+
+        // Reverting to a previous profile goes through the normal switch
+        service.switch_profile("sonnet").unwrap();
+        let lease = service.switch_profile_temporarily("opus", duration).unwrap();
+        assert_eq!(lease.previous, PreviousState::Profile("sonnet".to_string()));
+        service.end_switch_lease(true).unwrap();
+        assert_eq!(service.get_profile_status("sonnet"), ProfileStatus::FullMatch);
+
+        // A manual change during the lease is not undone
+        service.switch_profile_temporarily("opus", duration).unwrap();
+        service.apply_profile_content(r#"{"model": "haiku"}"#).unwrap();
+        service.end_switch_lease(true).unwrap();
+        assert!(fs::read_to_string(claude_dir.join("settings.json"))
+            .unwrap()
+            .contains("haiku"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_switch_hooks() {
+        use crate::switch_hooks::{HookCommand, SwitchHooks};
+
+        let (mut service, temp_dir) = create_test_config_service();
+        let claude_dir = temp_dir.path();
+        fs::write(claude_dir.join("work.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        fs::write(claude_dir.join("home.settings.json"), r#"{"model": "sonnet"}"#).unwrap();
+        service.scan_profiles().unwrap();
+        service.set_switch_hooks_path(claude_dir.join("hooks/switch_hooks.json"));
+
+        let hook = |command: &str| HookCommand {
+            command: command.to_string(),
+            timeout_secs: None,
+        };
+        let mut hooks = SwitchHooksConfig::default();
+        hooks.global.post_switch.push(hook("echo \"$CCCS_OLD_PROFILE>$CCCS_NEW_PROFILE@$CCCS_SCOPE\""));
+        hooks.profiles.insert(
+            "work".to_string(),
+            SwitchHooks {
+                pre_switch: vec![hook("echo 'vpn down' >&2; exit 1")],
+                post_switch: Vec::new(),
+            },
+        );
+        service.save_switch_hooks(&hooks).unwrap();
+
+        // A failing pre-switch hook leaves the settings untouched
+        let original = fs::read_to_string(claude_dir.join("settings.json")).unwrap();
+        let error = service.switch_profile("work").unwrap_err();
+        assert!(matches!(error, AppError::HookError(ref msg) if msg.contains("vpn down")));
+        assert_eq!(fs::read_to_string(claude_dir.join("settings.json")).unwrap(), original);
+        assert_eq!(service.get_last_hook_runs().len(), 1);
+
+        service.switch_profile("home").unwrap();
+        let runs = service.get_last_hook_runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].stdout.trim(), ">home@user");
+
+        hooks.profiles.clear();
+        service.save_switch_hooks(&hooks).unwrap();
+        service.switch_profile("work").unwrap();
+        assert_eq!(service.get_last_hook_runs()[0].stdout.trim(), "home>work@user");
+
+        // Hooks run without the shared service locked
+        hooks.global.post_switch = vec![hook("sleep 1")];
+        service.save_switch_hooks(&hooks).unwrap();
+        let shared = std::sync::Arc::new(Mutex::new(service));
+        let switching = {
+            let shared = std::sync::Arc::clone(&shared);
+            std::thread::spawn(move || shared.as_ref().switch_profile("home"))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(shared.try_lock().is_ok());
+        switching.join().unwrap().unwrap();
+        assert_eq!(shared.lock().unwrap().get_last_hook_runs().len(), 1);
+    }
 }
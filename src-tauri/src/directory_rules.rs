@@ -0,0 +1,375 @@
+// Directory rules: glob patterns mapping project directories to the profile and scope they use
+use crate::config_service::{ConfigAccess, ConfigService};
+use crate::settings_scope::SettingsScope;
+use crate::{AppError, AppResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where a matching rule applies its profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleScope {
+    User,          // 切换用户级配置
+    ProjectShared, // 写入项目 .claude/settings.json
+    ProjectLocal,  // 写入项目 .claude/settings.local.json
+}
+
+impl RuleScope {
+    fn to_settings_scope(self, project: &Path) -> SettingsScope {
+        match self {
+            Self::User => SettingsScope::User,
+            Self::ProjectShared => SettingsScope::ProjectShared(project.to_path_buf()),
+            Self::ProjectLocal => SettingsScope::ProjectLocal(project.to_path_buf()),
+        }
+    }
+}
+
+/// A rule such as `~/work/clientA/**` -> profile `ClientA`; the first enabled match wins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryRule {
+    pub pattern: String, // 支持 ~、*、? 与 **
+    pub profile: String,
+    pub scope: RuleScope,
+    #[serde(default = "DirectoryRule::default_enabled")]
+    pub enabled: bool,
+}
+
+impl DirectoryRule {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if self.profile.trim().is_empty() {
+            return Err(AppError::SettingsError(format!(
+                "Rule '{}' has no profile",
+                self.pattern
+            )));
+        }
+        compile_pattern(&self.pattern).map(|_| ())
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        match compile_pattern(&self.pattern) {
+            Ok(regex) => regex.is_match(&normalize_path(path)),
+            Err(e) => {
+                log::warn!("Skipping invalid directory rule: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Result of testing a path against the rules without applying anything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleDryRun {
+    pub path: PathBuf,
+    pub matching_rules: Vec<usize>, // 所有匹配的规则序号，第一个生效
+    pub rule: Option<usize>,
+    pub profile: Option<String>,
+    pub scope: Option<SettingsScope>, // 规则生效时会写入的作用域
+    pub note: Option<String>,
+}
+
+/// One profile application derived from the rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedApplication {
+    pub rule: usize,
+    pub profile: String,
+    pub scope: SettingsScope,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleApplication {
+    pub rule: usize,
+    pub profile: String,
+    pub scope: SettingsScope,
+    pub error: Option<String>,
+}
+
+/// Index and rule of the first enabled rule matching `path`
+pub fn find_match<'a>(rules: &'a [DirectoryRule], path: &Path) -> Option<(usize, &'a DirectoryRule)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.enabled && rule.matches(path))
+}
+
+/// Show which rule would apply to `path` and what it would write
+pub fn dry_run(rules: &[DirectoryRule], projects: &[PathBuf], path: &Path) -> RuleDryRun {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let matching_rules: Vec<usize> = rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.enabled && rule.matches(&path))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut result = RuleDryRun {
+        path: path.clone(),
+        rule: matching_rules.first().copied(),
+        matching_rules,
+        profile: None,
+        scope: None,
+        note: None,
+    };
+    let Some(index) = result.rule else {
+        result.note = Some("No rule matches this path".to_string());
+        return result;
+    };
+
+    let rule = &rules[index];
+    result.profile = Some(rule.profile.clone());
+    match rule.scope {
+        RuleScope::User => result.scope = Some(SettingsScope::User),
+        scope => match enclosing_project(projects, &path) {
+            Some(project) => result.scope = Some(scope.to_settings_scope(project)),
+            None => {
+                result.note = Some(
+                    "Path is not inside a registered project; register it to apply this rule"
+                        .to_string(),
+                )
+            }
+        },
+    }
+    result
+}
+
+/// Applications for every registered project matched by a project-scoped rule, plus the
+/// user-scoped rule matching `working_dir` if one is given
+pub fn plan(
+    rules: &[DirectoryRule],
+    projects: &[PathBuf],
+    working_dir: Option<&Path>,
+) -> Vec<PlannedApplication> {
+    let mut planned = Vec::new();
+
+    for project in projects {
+        if let Some((index, rule)) = find_match(rules, project) {
+            if rule.scope != RuleScope::User {
+                planned.push(PlannedApplication {
+                    rule: index,
+                    profile: rule.profile.clone(),
+                    scope: rule.scope.to_settings_scope(project),
+                });
+            }
+        }
+    }
+
+    // 用户级只有一个，只能依据当前工作目录切换
+    if let Some(working_dir) = working_dir {
+        let working_dir = working_dir
+            .canonicalize()
+            .unwrap_or_else(|_| working_dir.to_path_buf());
+        if let Some((index, rule)) = find_match(rules, &working_dir) {
+            if rule.scope == RuleScope::User {
+                planned.push(PlannedApplication {
+                    rule: index,
+                    profile: rule.profile.clone(),
+                    scope: SettingsScope::User,
+                });
+            }
+        }
+    }
+
+    planned
+}
+
+/// Apply planned rules; failures are reported per application and don't stop the rest.
+/// The config service is locked per switch, so it stays free while switch hooks run.
+pub fn apply(config: &Mutex<ConfigService>, planned: &[PlannedApplication]) -> Vec<RuleApplication> {
+    planned
+        .iter()
+        .map(|item| {
+            let error = config
+                .switch_profile_in_scope(&item.profile, &item.scope)
+                .err()
+                .map(|e| {
+                    log::warn!(
+                        "Directory rule #{} failed to apply '{}' to {}: {}",
+                        item.rule,
+                        item.profile,
+                        item.scope.label(),
+                        e
+                    );
+                    e.to_string()
+                });
+            RuleApplication {
+                rule: item.rule,
+                profile: item.profile.clone(),
+                scope: item.scope.clone(),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// The innermost registered project containing `path`
+fn enclosing_project<'a>(projects: &'a [PathBuf], path: &Path) -> Option<&'a PathBuf> {
+    projects
+        .iter()
+        .filter(|project| path.starts_with(project))
+        .max_by_key(|project| project.components().count())
+}
+
+fn normalize_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Translate a glob into an anchored regex: `*` and `?` stay within one path segment,
+/// `**` crosses segments and a trailing `/**` also matches the directory itself
+fn compile_pattern(pattern: &str) -> AppResult<Regex> {
+    let invalid = |reason: &str| {
+        AppError::SettingsError(format!("Invalid rule pattern '{}': {}", pattern, reason))
+    };
+
+    let trimmed = pattern.trim();
+    let expanded = match trimmed.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => {
+            let home = dirs::home_dir().ok_or_else(|| invalid("home directory not found"))?;
+            format!("{}{}", home.to_string_lossy(), rest)
+        }
+        _ => trimmed.to_string(),
+    };
+    let mut glob = if cfg!(windows) {
+        expanded.replace('\\', "/")
+    } else {
+        expanded
+    };
+    if glob.is_empty() {
+        return Err(invalid("pattern is empty"));
+    }
+    if !glob.starts_with("**") && !Path::new(&glob).is_absolute() {
+        return Err(invalid("pattern must be absolute, start with ~ or start with **"));
+    }
+
+    while glob.len() > 1 && glob.ends_with('/') {
+        glob.pop();
+    }
+    let recursive_suffix = glob.len() > 3 && glob.ends_with("/**");
+    if recursive_suffix {
+        glob.truncate(glob.len() - 3);
+    }
+
+    let mut regex = String::from(if cfg!(any(windows, target_os = "macos")) {
+        "(?i)^"
+    } else {
+        "^"
+    });
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                if chars.get(i) == Some(&'/') {
+                    i += 1;
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    if recursive_suffix {
+        regex.push_str("(?:/.*)?");
+    }
+    regex.push('$');
+
+    Regex::new(&regex).map_err(|e| invalid(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn rule(pattern: &str, profile: &str, scope: RuleScope) -> DirectoryRule {
+        DirectoryRule {
+            pattern: pattern.to_string(),
+            profile: profile.to_string(),
+            scope,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        let recursive = rule("/work/clientA/**", "ClientA", RuleScope::ProjectLocal);
+        assert!(recursive.matches(Path::new("/work/clientA")));
+        assert!(recursive.matches(Path::new("/work/clientA/api/server")));
+        assert!(!recursive.matches(Path::new("/work/clientAB")));
+
+        let single = rule("/work/*/web", "Web", RuleScope::ProjectShared);
+        assert!(single.matches(Path::new("/work/clientA/web")));
+        assert!(!single.matches(Path::new("/work/a/b/web")));
+
+        let anywhere = rule("**/sandbox-?", "Sandbox", RuleScope::User);
+        assert!(anywhere.matches(Path::new("/home/u/sandbox-1")));
+        assert!(!anywhere.matches(Path::new("/home/u/sandbox-12")));
+
+        assert!(!compile_pattern("~/work/**").unwrap().as_str().contains('~'));
+
+        assert!(rule("relative/**", "X", RuleScope::User).validate().is_err());
+        assert!(rule("/work/**", " ", RuleScope::User).validate().is_err());
+    }
+
+    #[test]
+    fn test_plan_and_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let client_a = root.join("clientA/api");
+        let other = root.join("other");
+        std::fs::create_dir_all(&client_a).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+
+        let mut disabled = rule(&format!("{}/**", root.display()), "Off", RuleScope::User);
+        disabled.enabled = false;
+        let rules = vec![
+            disabled,
+            rule(&format!("{}/clientA/**", root.display()), "ClientA", RuleScope::ProjectLocal),
+            rule(&format!("{}/**", root.display()), "Personal", RuleScope::User),
+        ];
+        let projects = vec![client_a.clone(), other.clone()];
+
+        // 项目规则写入已登记项目，用户级规则只按工作目录生效
+        let planned = plan(&rules, &projects, None);
+        assert_eq!(
+            planned,
+            vec![PlannedApplication {
+                rule: 1,
+                profile: "ClientA".to_string(),
+                scope: SettingsScope::ProjectLocal(client_a.clone()),
+            }]
+        );
+        let planned = plan(&rules, &projects, Some(&other));
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[1].scope, SettingsScope::User);
+        assert_eq!(planned[1].profile, "Personal");
+
+        let result = dry_run(&rules, &projects, &client_a.join("src"));
+        assert_eq!(result.matching_rules, vec![1, 2]);
+        assert_eq!(result.profile.as_deref(), Some("ClientA"));
+        assert_eq!(result.scope, Some(SettingsScope::ProjectLocal(client_a)));
+
+        let result = dry_run(&rules, &[], &root.join("clientA/web"));
+        assert_eq!(result.rule, Some(1));
+        assert!(result.scope.is_none());
+        assert!(result.note.is_some());
+
+        let result = dry_run(&rules, &projects, Path::new("/nowhere"));
+        assert!(result.rule.is_none());
+    }
+}
@@ -0,0 +1,398 @@
+// Effective settings: the merge of every settings layer Claude Code reads
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings layers, highest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsLayer {
+    Enterprise,    // managed-settings.json，无法被覆盖
+    CliArgs,       // claude --settings <file|json>
+    ProjectLocal,  // <project>/.claude/settings.local.json
+    ProjectShared, // <project>/.claude/settings.json
+    User,          // ~/.claude/settings.json
+}
+
+impl SettingsLayer {
+    pub const PRECEDENCE: [SettingsLayer; 5] = [
+        Self::Enterprise,
+        Self::CliArgs,
+        Self::ProjectLocal,
+        Self::ProjectShared,
+        Self::User,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Enterprise => "enterprise",
+            Self::CliArgs => "cli",
+            Self::ProjectLocal => "project local",
+            Self::ProjectShared => "project shared",
+            Self::User => "user",
+        }
+    }
+}
+
+/// Where a layer came from and whether it could be used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSource {
+    pub layer: SettingsLayer,
+    pub path: Option<String>, // CLI 传入的内联 JSON 或未找到项目时为 None
+    pub found: bool,
+    pub error: Option<String>,
+}
+
+/// One leaf key of the effective settings, e.g. `env.ANTHROPIC_MODEL` or `permissions.allow`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveKey {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub layer: SettingsLayer,                // 最终生效的层
+    pub overridden: Vec<SettingsLayer>,      // 同样定义了该键、但被覆盖（或数组被合并）的层
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveSettings {
+    pub working_dir: String,
+    pub project_dir: Option<String>,
+    pub layers: Vec<LayerSource>,
+    pub settings: serde_json::Value,
+    pub keys: Vec<EffectiveKey>,
+}
+
+/// System-wide managed settings installed by an administrator
+pub fn default_managed_settings_path() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/ClaudeCode/managed-settings.json")
+    } else if cfg!(windows) {
+        PathBuf::from(r"C:\ProgramData\ClaudeCode\managed-settings.json")
+    } else {
+        PathBuf::from("/etc/claude-code/managed-settings.json")
+    }
+}
+
+pub struct SettingsResolver {
+    claude_dir: PathBuf,
+    managed_settings_path: PathBuf,
+}
+
+impl SettingsResolver {
+    pub fn new(claude_dir: PathBuf) -> Self {
+        Self {
+            claude_dir,
+            managed_settings_path: default_managed_settings_path(),
+        }
+    }
+
+    pub fn with_managed_settings_path(mut self, path: PathBuf) -> Self {
+        self.managed_settings_path = path;
+        self
+    }
+
+    /// Nearest ancestor of `working_dir` with a `.claude` directory, other than the user's own
+    pub fn find_project_dir(&self, working_dir: &Path) -> Option<PathBuf> {
+        working_dir
+            .ancestors()
+            .find(|dir| {
+                let dot_claude = dir.join(".claude");
+                dot_claude.is_dir() && dot_claude != self.claude_dir
+            })
+            .map(Path::to_path_buf)
+    }
+
+    /// Compute the settings Claude Code would run with in `working_dir`.
+    /// `cli_settings` is what would be passed to `claude --settings`: a file path or inline JSON.
+    pub fn resolve(
+        &self,
+        working_dir: &Path,
+        cli_settings: Option<&str>,
+    ) -> AppResult<EffectiveSettings> {
+        let project_dir = self.find_project_dir(working_dir);
+        let mut layers = Vec::new();
+        let mut values = Vec::new();
+
+        for layer in SettingsLayer::PRECEDENCE {
+            let (source, value) = match layer {
+                SettingsLayer::CliArgs => Self::load_cli_layer(cli_settings)?,
+                _ => {
+                    let path = match layer {
+                        SettingsLayer::Enterprise => Some(self.managed_settings_path.clone()),
+                        SettingsLayer::ProjectLocal => project_dir
+                            .as_ref()
+                            .map(|dir| dir.join(".claude").join("settings.local.json")),
+                        SettingsLayer::ProjectShared => project_dir
+                            .as_ref()
+                            .map(|dir| dir.join(".claude").join("settings.json")),
+                        _ => Some(self.claude_dir.join("settings.json")),
+                    };
+                    Self::load_file_layer(layer, path)
+                }
+            };
+            layers.push(source);
+            if let Some(value) = value {
+                values.push((layer, value));
+            }
+        }
+
+        // Lowest precedence first, so every layer overrides the ones merged before it
+        let mut settings = serde_json::Map::new();
+        let mut keys = BTreeMap::new();
+        for (layer, value) in values.iter().rev() {
+            if let serde_json::Value::Object(map) = value {
+                merge_into(&mut settings, map, "", *layer, &mut keys);
+            }
+        }
+
+        Ok(EffectiveSettings {
+            working_dir: working_dir.to_string_lossy().to_string(),
+            project_dir: project_dir.map(|dir| dir.to_string_lossy().to_string()),
+            layers,
+            settings: serde_json::Value::Object(settings),
+            keys: keys.into_values().collect(),
+        })
+    }
+
+    fn load_file_layer(
+        layer: SettingsLayer,
+        path: Option<PathBuf>,
+    ) -> (LayerSource, Option<serde_json::Value>) {
+        let mut source = LayerSource {
+            layer,
+            path: path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            found: false,
+            error: None,
+        };
+        let Some(path) = path.filter(|p| p.is_file()) else {
+            return (source, None);
+        };
+        source.found = true;
+
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| parse_object(&content));
+        match parsed {
+            Ok(value) => (source, Some(value)),
+            Err(e) => {
+                // A broken layer is skipped, like Claude Code does, but reported
+                log::warn!("Ignoring {} settings {:?}: {}", layer.label(), path, e);
+                source.error = Some(e);
+                (source, None)
+            }
+        }
+    }
+
+    fn load_cli_layer(
+        cli_settings: Option<&str>,
+    ) -> AppResult<(LayerSource, Option<serde_json::Value>)> {
+        let mut source = LayerSource {
+            layer: SettingsLayer::CliArgs,
+            path: None,
+            found: false,
+            error: None,
+        };
+        let Some(cli_settings) = cli_settings.map(str::trim).filter(|s| !s.is_empty()) else {
+            return Ok((source, None));
+        };
+
+        let content = if cli_settings.starts_with('{') {
+            cli_settings.to_string()
+        } else {
+            source.path = Some(cli_settings.to_string());
+            fs::read_to_string(cli_settings).map_err(|e| {
+                AppError::ConfigError(format!("Cannot read --settings file {}: {}", cli_settings, e))
+            })?
+        };
+        let value = parse_object(&content)
+            .map_err(|e| AppError::ConfigError(format!("Invalid --settings value: {}", e)))?;
+
+        source.found = true;
+        Ok((source, Some(value)))
+    }
+}
+
+fn parse_object(content: &str) -> Result<serde_json::Value, String> {
+    let value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if !value.is_object() {
+        return Err("settings must be a JSON object".to_string());
+    }
+    Ok(value)
+}
+
+/// Deep-merge `source` into `target`: objects merge key by key, arrays are concatenated
+/// without duplicates (as Claude Code does for permission rules) and anything else is replaced.
+fn merge_into(
+    target: &mut serde_json::Map<String, serde_json::Value>,
+    source: &serde_json::Map<String, serde_json::Value>,
+    prefix: &str,
+    layer: SettingsLayer,
+    keys: &mut BTreeMap<String, EffectiveKey>,
+) {
+    for (name, value) in source {
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        match (target.get_mut(name), value) {
+            (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(incoming)) => {
+                merge_into(existing, incoming, &path, layer, keys);
+            }
+            (Some(serde_json::Value::Array(existing)), serde_json::Value::Array(incoming)) => {
+                for item in incoming {
+                    if !existing.contains(item) {
+                        existing.push(item.clone());
+                    }
+                }
+                let merged = serde_json::Value::Array(existing.clone());
+                annotate(keys, &path, merged, layer);
+            }
+            (_, serde_json::Value::Object(incoming)) => {
+                remove_annotations(keys, &path);
+                let mut nested = serde_json::Map::new();
+                merge_into(&mut nested, incoming, &path, layer, keys);
+                target.insert(name.clone(), serde_json::Value::Object(nested));
+            }
+            (_, incoming) => {
+                remove_annotations(keys, &format!("{}.", path));
+                target.insert(name.clone(), incoming.clone());
+                annotate(keys, &path, incoming.clone(), layer);
+            }
+        }
+    }
+}
+
+fn annotate(
+    keys: &mut BTreeMap<String, EffectiveKey>,
+    path: &str,
+    value: serde_json::Value,
+    layer: SettingsLayer,
+) {
+    match keys.get_mut(path) {
+        Some(entry) => {
+            if entry.layer != layer {
+                entry.overridden.insert(0, entry.layer);
+            }
+            entry.layer = layer;
+            entry.value = value;
+        }
+        None => {
+            keys.insert(
+                path.to_string(),
+                EffectiveKey {
+                    key: path.to_string(),
+                    value,
+                    layer,
+                    overridden: Vec::new(),
+                },
+            );
+        }
+    }
+}
+
+/// Drop annotations replaced by a value of a different shape
+fn remove_annotations(keys: &mut BTreeMap<String, EffectiveKey>, prefix: &str) {
+    let nested = format!("{}.", prefix.trim_end_matches('.'));
+    keys.retain(|key, _| key != prefix && !key.starts_with(&nested));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_all_layers() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("home/.claude");
+        let project = temp_dir.path().join("work/app");
+        let managed = temp_dir.path().join("managed-settings.json");
+
+        write(
+            &claude_dir.join("settings.json"),
+            r#"{"model": "sonnet", "env": {"A": "user", "B": "user"}, "permissions": {"allow": ["Read"]}}"#,
+        );
+        write(
+            &project.join(".claude/settings.json"),
+            r#"{"env": {"A": "shared"}, "permissions": {"allow": ["Bash(npm test)"]}}"#,
+        );
+        write(&project.join(".claude/settings.local.json"), r#"{"model": "opus"}"#);
+        write(&managed, r#"{"permissions": {"deny": ["WebFetch"]}}"#);
+        fs::create_dir_all(project.join("src")).unwrap();
+
+        let resolver = SettingsResolver::new(claude_dir).with_managed_settings_path(managed);
+        let effective = resolver
+            .resolve(&project.join("src"), Some(r#"{"env": {"B": "cli"}}"#))
+            .unwrap();
+
+        assert_eq!(effective.project_dir, Some(project.to_string_lossy().to_string()));
+        assert_eq!(effective.settings["model"], "opus");
+        assert_eq!(effective.settings["env"]["A"], "shared");
+        assert_eq!(effective.settings["env"]["B"], "cli");
+        assert_eq!(
+            effective.settings["permissions"]["allow"],
+            serde_json::json!(["Read", "Bash(npm test)"])
+        );
+
+        let key = |name: &str| effective.keys.iter().find(|k| k.key == name).unwrap();
+        assert_eq!(key("model").layer, SettingsLayer::ProjectLocal);
+        assert_eq!(key("model").overridden, vec![SettingsLayer::User]);
+        assert_eq!(key("env.B").layer, SettingsLayer::CliArgs);
+        assert_eq!(key("permissions.allow").layer, SettingsLayer::ProjectShared);
+        assert_eq!(key("permissions.deny").layer, SettingsLayer::Enterprise);
+        assert!(effective.layers.iter().all(|l| l.found));
+    }
+
+    #[test]
+    fn test_missing_and_broken_layers() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join(".claude");
+        write(&claude_dir.join("settings.json"), r#"{"env": {"A": "1"}}"#);
+        let managed = temp_dir.path().join("managed-settings.json");
+        write(&managed, "{ not json");
+
+        let resolver =
+            SettingsResolver::new(claude_dir.clone()).with_managed_settings_path(managed);
+        // The user's own ~/.claude is not a project
+        let effective = resolver.resolve(temp_dir.path(), None).unwrap();
+        assert!(effective.project_dir.is_none());
+        assert_eq!(effective.settings, serde_json::json!({"env": {"A": "1"}}));
+
+        let enterprise = &effective.layers[0];
+        assert!(enterprise.found);
+        assert!(enterprise.error.is_some());
+
+        assert!(resolver.resolve(temp_dir.path(), Some("[1, 2]")).is_err());
+        assert!(resolver
+            .resolve(temp_dir.path(), Some("/does/not/exist.json"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_shape_change_replaces_annotations() {
+        let mut settings = serde_json::Map::new();
+        let mut keys = BTreeMap::new();
+        let user = serde_json::json!({"statusLine": {"type": "command", "command": "x"}});
+        let local = serde_json::json!({"statusLine": "off"});
+
+        merge_into(&mut settings, user.as_object().unwrap(), "", SettingsLayer::User, &mut keys);
+        merge_into(
+            &mut settings,
+            local.as_object().unwrap(),
+            "",
+            SettingsLayer::ProjectLocal,
+            &mut keys,
+        );
+
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys["statusLine"].layer, SettingsLayer::ProjectLocal);
+    }
+}
@@ -27,6 +27,18 @@ pub enum AppError {
     #[error("I18n error: {0}")]
     I18nError(String),
     
+    #[error("Secret vault error: {0}")]
+    SecretError(String),
+    
+    #[error("Switch hook error: {0}")]
+    HookError(String),
+    
+    #[error("Organization policy: {0}")]
+    PolicyError(String),
+    
+    #[error("Signature verification failed: {0}")]
+    SignatureError(String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
@@ -0,0 +1,170 @@
+// Restrictive file permissions for config files that may hold API tokens
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Mode used for newly created settings, profiles and vault files
+#[cfg(unix)]
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+
+/// Mode of directories whose files keep copies of settings, e.g. the profile history repository
+#[cfg(unix)]
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// Create (or truncate) a file readable and writable only by its owner
+pub fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(PRIVATE_FILE_MODE);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Give a temp file the mode of the file it is about to replace.
+/// New targets keep the private mode the temp file was created with.
+pub fn preserve_mode(temp_path: &Path, target_path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        if let Ok(metadata) = fs::metadata(target_path) {
+            fs::set_permissions(temp_path, metadata.permissions())?;
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (temp_path, target_path);
+    }
+    Ok(())
+}
+
+/// Restrict an existing file to its owner (0600)
+pub fn restrict_to_owner(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(PRIVATE_FILE_MODE))?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+    }
+    Ok(())
+}
+
+/// Restrict an existing directory to its owner (0700); the files inside become unreachable for others
+pub fn restrict_dir_to_owner(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(PRIVATE_DIR_MODE))?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+    }
+    Ok(())
+}
+
+/// Describe why a file is exposed to other users, if it is
+pub fn exposure_warning(path: &Path) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path).ok()?;
+        // SAFETY: geteuid has no preconditions and cannot fail
+        let uid = unsafe { libc::geteuid() };
+        if metadata.uid() != uid {
+            return Some(format!(
+                "File is owned by another user (uid {})",
+                metadata.uid()
+            ));
+        }
+
+        let mode = metadata.mode() & 0o777;
+        if mode & 0o044 != 0 {
+            return Some(format!(
+                "File is readable by other users (mode {:o}); run fix permissions to restrict it to 600",
+                mode
+            ));
+        }
+        None
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Whether a configuration contains plaintext credentials, i.e. a token/key/secret/password
+/// field with a literal value instead of a ${...} placeholder
+pub fn contains_plaintext_secrets(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => map.iter().any(|(key, item)| match item {
+            serde_json::Value::String(s) => {
+                is_secret_key(key) && !s.trim().is_empty() && !s.contains("${")
+            }
+            other => contains_plaintext_secrets(other),
+        }),
+        serde_json::Value::Array(items) => items.iter().any(contains_plaintext_secrets),
+        _ => false,
+    }
+}
+
+pub(crate) fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    ["TOKEN", "API_KEY", "APIKEY", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|marker| key.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_plaintext_secrets() {
+        let plain = serde_json::json!({"env": {"ANTHROPIC_AUTH_TOKEN": "sk-123"}});
+        assert!(contains_plaintext_secrets(&plain));
+
+        let referenced = serde_json::json!({"env": {"ANTHROPIC_AUTH_TOKEN": "${secret:work}"}});
+        assert!(!contains_plaintext_secrets(&referenced));
+
+        let harmless = serde_json::json!({"env": {"ANTHROPIC_BASE_URL": "https://x"}, "model": "opus"});
+        assert!(!contains_plaintext_secrets(&harmless));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_write_and_mode_preservation() {
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("settings.json");
+        write_private_file(&target, b"{}").unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert!(exposure_warning(&target).is_none());
+
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+        assert!(exposure_warning(&target).is_some());
+
+        let temp = temp_dir.path().join(".settings.json.tmp");
+        write_private_file(&temp, b"{\"a\": 1}").unwrap();
+        preserve_mode(&temp, &target).unwrap();
+        fs::rename(&temp, &target).unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o640);
+
+        restrict_to_owner(&target).unwrap();
+        assert!(exposure_warning(&target).is_none());
+    }
+}
@@ -0,0 +1,256 @@
+// Optional git repository in the Claude directory that records every profile change as a commit
+use crate::file_permissions;
+use crate::{AppError, AppResult};
+use git2::{DiffOptions, IndexAddOption, Repository, Signature, Sort};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 只跟踪配置文件和配置目录，~/.claude 下的其他内容（会话、凭据等）一律忽略
+const EXCLUDE_RULES: &str = "# Managed by CCCS: only profiles are tracked
+/*
+!/*.settings.json
+!/*.profile/
+";
+
+/// One commit as shown in the profile log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitCommit {
+    pub id: String,
+    pub short_id: String,
+    pub message: String,
+    pub author: String,
+    pub time: i64, // 提交时间（unix 秒）
+}
+
+/// Repository rooted at the Claude directory; only paths passed in are ever staged
+pub struct GitStore {
+    repo: Repository,
+    workdir: PathBuf,
+}
+
+impl GitStore {
+    /// Open the repository, creating it (with the exclude rules) on first use
+    pub fn open_or_init(dir: &Path) -> AppResult<Self> {
+        let repo = match Repository::open(dir) {
+            Ok(repo) => repo,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                log::info!("Initializing profile repository in {}", dir.display());
+                Repository::init(dir).map_err(git_error)?
+            }
+            Err(e) => return Err(git_error(e)),
+        };
+        // 历史中的配置可能含有明文令牌，仓库只允许所有者访问
+        file_permissions::restrict_dir_to_owner(repo.path())?;
+
+        let exclude_path = repo.path().join("info").join("exclude");
+        let existing = fs::read_to_string(&exclude_path).unwrap_or_default();
+        if !existing.contains(EXCLUDE_RULES) {
+            fs::create_dir_all(exclude_path.parent().unwrap_or(repo.path()))?;
+            fs::write(&exclude_path, format!("{}{}", existing, EXCLUDE_RULES))?;
+        }
+
+        Ok(Self {
+            repo,
+            workdir: dir.to_path_buf(),
+        })
+    }
+
+    /// Stage `paths` (relative to the Claude directory; missing ones are removed) and
+    /// commit them. Returns the new commit id, or `None` when nothing changed and
+    /// `allow_empty` is false.
+    pub fn commit(&self, paths: &[PathBuf], message: &str, allow_empty: bool) -> AppResult<Option<String>> {
+        let mut index = self.repo.index().map_err(git_error)?;
+        for path in paths {
+            let absolute = self.workdir.join(path);
+            if absolute.is_dir() {
+                // 先清掉目录旧条目，已删除的文件才会从提交中消失
+                index.remove_dir(path, 0).map_err(git_error)?;
+                index
+                    .add_all([path], IndexAddOption::FORCE, None)
+                    .map_err(git_error)?;
+            } else if absolute.is_file() {
+                index.add_path(path).map_err(git_error)?;
+            } else {
+                index.remove_path(path).map_err(git_error)?;
+                index.remove_dir(path, 0).map_err(git_error)?;
+            }
+        }
+        index.write().map_err(git_error)?;
+
+        let tree_id = index.write_tree().map_err(git_error)?;
+        let tree = self.repo.find_tree(tree_id).map_err(git_error)?;
+        let parent = self.repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        if !allow_empty && parent.as_ref().is_some_and(|p| p.tree_id() == tree_id) {
+            return Ok(None);
+        }
+
+        let signature = self.signature()?;
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let id = self
+            .repo
+            .commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .map_err(git_error)?;
+        log::info!("Committed profile change {}: {}", id, message);
+        Ok(Some(id.to_string()))
+    }
+
+    /// Commits touching any of `paths`, newest first; all commits when `paths` is empty
+    pub fn log(&self, paths: &[PathBuf], limit: usize) -> AppResult<Vec<GitCommit>> {
+        if self.repo.head().is_err() {
+            return Ok(Vec::new()); // 还没有任何提交
+        }
+
+        let mut walk = self.repo.revwalk().map_err(git_error)?;
+        // 同一秒内的提交按时间排序不稳定，按拓扑顺序保证新提交在前
+        walk.set_sorting(Sort::TOPOLOGICAL).map_err(git_error)?;
+        walk.push_head().map_err(git_error)?;
+
+        let mut commits = Vec::new();
+        for id in walk {
+            if commits.len() >= limit {
+                break;
+            }
+            let commit = self.repo.find_commit(id.map_err(git_error)?).map_err(git_error)?;
+            if !paths.is_empty() && !self.touches(&commit, paths)? {
+                continue;
+            }
+            let id = commit.id().to_string();
+            commits.push(GitCommit {
+                short_id: id[..7].to_string(),
+                id,
+                message: commit.message().unwrap_or("").trim_end().to_string(),
+                author: commit.author().name().unwrap_or("").to_string(),
+                time: commit.time().seconds(),
+            });
+        }
+        Ok(commits)
+    }
+
+    fn touches(&self, commit: &git2::Commit, paths: &[PathBuf]) -> AppResult<bool> {
+        let tree = commit.tree().map_err(git_error)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(git_error)?),
+            Err(_) => None,
+        };
+        let mut options = DiffOptions::new();
+        for path in paths {
+            options.pathspec(path);
+        }
+        let diff = self
+            .repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .map_err(git_error)?;
+        Ok(diff.deltas().len() > 0)
+    }
+
+    /// Content of the first of `paths` that exists at `revision` (any rev-parse syntax)
+    pub fn show(&self, revision: &str, paths: &[PathBuf]) -> AppResult<String> {
+        let tree = self
+            .repo
+            .revparse_single(revision)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(|e| AppError::ConfigError(format!("Unknown revision '{}': {}", revision, e.message())))?;
+
+        for path in paths {
+            let Ok(entry) = tree.get_path(path) else {
+                continue;
+            };
+            let blob = entry
+                .to_object(&self.repo)
+                .and_then(|object| object.peel_to_blob())
+                .map_err(git_error)?;
+            return String::from_utf8(blob.content().to_vec()).map_err(|_| {
+                AppError::ConfigError(format!("{} is not valid UTF-8", path.display()))
+            });
+        }
+        Err(AppError::ConfigError(format!(
+            "Profile does not exist at revision '{}'",
+            revision
+        )))
+    }
+
+    /// The user's git identity, or a local CCCS identity when none is configured
+    fn signature(&self) -> AppResult<Signature<'static>> {
+        match self.repo.signature() {
+            Ok(signature) => Ok(signature.to_owned()),
+            Err(_) => Signature::now("CCCS", "cccs@localhost").map_err(git_error),
+        }
+    }
+}
+
+fn git_error(e: git2::Error) -> AppError {
+    AppError::FileSystemError(format!("Git error: {}", e.message()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_commit_log_and_show() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let store = GitStore::open_or_init(dir).unwrap();
+        assert!(store.log(&[], 10).unwrap().is_empty());
+
+        let work = PathBuf::from("work.settings.json");
+        fs::write(dir.join(&work), r#"{"model": "opus"}"#).unwrap();
+        fs::write(dir.join("settings.json"), "{}").unwrap();
+        let first = store.commit(std::slice::from_ref(&work), "Create profile 'work'", false).unwrap().unwrap();
+
+        fs::write(dir.join(&work), r#"{"model": "sonnet"}"#).unwrap();
+        store.commit(std::slice::from_ref(&work), "Update profile 'work'", false).unwrap();
+        assert_eq!(store.commit(std::slice::from_ref(&work), "No change", false).unwrap(), None);
+        assert!(store.commit(&[], "Switch to 'work' (user)", true).unwrap().is_some());
+
+        let log = store.log(std::slice::from_ref(&work), 10).unwrap();
+        let messages: Vec<&str> = log.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages, vec!["Update profile 'work'", "Create profile 'work'"]);
+        assert_eq!(store.log(&[], 10).unwrap().len(), 3);
+
+        assert_eq!(store.show(&first, std::slice::from_ref(&work)).unwrap(), r#"{"model": "opus"}"#);
+        assert_eq!(store.show("HEAD", std::slice::from_ref(&work)).unwrap(), r#"{"model": "sonnet"}"#);
+        assert!(store.show(&first, &[PathBuf::from("settings.json")]).is_err());
+
+        fs::remove_file(dir.join(&work)).unwrap();
+        store.commit(std::slice::from_ref(&work), "Delete profile 'work'", false).unwrap();
+        assert!(store.show("HEAD", std::slice::from_ref(&work)).is_err());
+        assert_eq!(store.log(&[work], 10).unwrap().len(), 3);
+
+        // 重复打开不会重复写入忽略规则
+        GitStore::open_or_init(dir).unwrap();
+        let exclude = fs::read_to_string(dir.join(".git/info/exclude")).unwrap();
+        assert_eq!(exclude.matches(EXCLUDE_RULES).count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(".git")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+
+    #[test]
+    fn test_bundle_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let store = GitStore::open_or_init(dir).unwrap();
+
+        let bundle = PathBuf::from("team.profile");
+        fs::create_dir_all(dir.join("team.profile/agents")).unwrap();
+        fs::write(dir.join("team.profile/settings.json"), "{}").unwrap();
+        fs::write(dir.join("team.profile/agents/review.md"), "review").unwrap();
+        store.commit(std::slice::from_ref(&bundle), "Create profile 'team'", false).unwrap();
+
+        fs::remove_file(dir.join("team.profile/agents/review.md")).unwrap();
+        store.commit(std::slice::from_ref(&bundle), "Update profile 'team'", false).unwrap();
+        assert!(store.show("HEAD", &[PathBuf::from("team.profile/agents/review.md")]).is_err());
+        assert_eq!(
+            store.show("HEAD~1", &[PathBuf::from("team.profile/agents/review.md")]).unwrap(),
+            "review"
+        );
+        assert_eq!(store.log(&[bundle], 10).unwrap().len(), 2);
+    }
+}
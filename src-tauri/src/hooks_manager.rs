@@ -0,0 +1,429 @@
+// Typed model and editing helpers for the Claude Code `hooks` section
+use crate::validation::ValidationRule;
+use crate::{AppError, AppResult, ValidationError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Hook events understood by Claude Code
+pub const KNOWN_HOOK_EVENTS: &[&str] = &[
+    "PreToolUse",
+    "PostToolUse",
+    "Notification",
+    "UserPromptSubmit",
+    "Stop",
+    "SubagentStop",
+    "PreCompact",
+    "SessionStart",
+    "SessionEnd",
+];
+
+// Commands resolved by the shell itself rather than PATH
+const SHELL_BUILTINS: &[&str] = &[
+    "echo", "cd", "test", "[", "true", "false", "exit", "export", "printf", "read", "source",
+    ".", ":", "exec", "eval", "set", "if", "for", "while",
+];
+
+fn default_hook_type() -> String {
+    "command".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookCommand {
+    #[serde(rename = "type", default = "default_hook_type")]
+    pub hook_type: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookMatcher {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matcher: Option<String>,
+    #[serde(default)]
+    pub hooks: Vec<HookCommand>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Event name -> matcher groups
+pub type HooksConfig = BTreeMap<String, Vec<HookMatcher>>;
+
+/// One hook command flattened with its event and matcher, as used by the editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookEntry {
+    pub event: String,
+    pub matcher: Option<String>,
+    pub command: String,
+    pub timeout: Option<u64>,
+}
+
+fn same_matcher(a: Option<&str>, b: Option<&str>) -> bool {
+    a.unwrap_or("") == b.unwrap_or("")
+}
+
+/// Parse the hooks section of a settings object (empty when absent)
+pub fn parse_hooks(settings: &serde_json::Value) -> AppResult<HooksConfig> {
+    match settings.get("hooks") {
+        None | Some(serde_json::Value::Null) => Ok(HooksConfig::new()),
+        Some(hooks) => serde_json::from_value(hooks.clone())
+            .map_err(|e| AppError::ConfigError(format!("Invalid hooks section: {}", e))),
+    }
+}
+
+/// Write the hooks section back, removing it when no hooks are left
+pub fn write_hooks(settings: &mut serde_json::Value, hooks: &HooksConfig) -> AppResult<()> {
+    let root = settings
+        .as_object_mut()
+        .ok_or_else(|| AppError::ConfigError("Configuration must be a JSON object".to_string()))?;
+
+    if hooks.is_empty() {
+        root.remove("hooks");
+    } else {
+        root.insert("hooks".to_string(), serde_json::to_value(hooks)?);
+    }
+    Ok(())
+}
+
+pub fn list_hooks(settings: &serde_json::Value) -> AppResult<Vec<HookEntry>> {
+    let hooks = parse_hooks(settings)?;
+    Ok(hooks
+        .iter()
+        .flat_map(|(event, groups)| {
+            groups.iter().flat_map(move |group| {
+                group.hooks.iter().map(move |hook| HookEntry {
+                    event: event.clone(),
+                    matcher: group.matcher.clone(),
+                    command: hook.command.clone(),
+                    timeout: hook.timeout,
+                })
+            })
+        })
+        .collect())
+}
+
+pub fn contains_hook(settings: &serde_json::Value, entry: &HookEntry) -> AppResult<bool> {
+    Ok(list_hooks(settings)?.iter().any(|h| {
+        h.event == entry.event
+            && same_matcher(h.matcher.as_deref(), entry.matcher.as_deref())
+            && h.command == entry.command
+    }))
+}
+
+/// Add a hook command, creating the event and matcher group as needed
+pub fn add_hook(settings: &mut serde_json::Value, entry: &HookEntry) -> AppResult<()> {
+    if !KNOWN_HOOK_EVENTS.contains(&entry.event.as_str()) {
+        return Err(AppError::ConfigError(format!(
+            "Unknown hook event '{}'",
+            entry.event
+        )));
+    }
+    if entry.command.trim().is_empty() {
+        return Err(AppError::ConfigError("Hook command cannot be empty".to_string()));
+    }
+    if let Some(matcher) = &entry.matcher {
+        validate_matcher(matcher).map_err(AppError::ConfigError)?;
+    }
+    if contains_hook(settings, entry)? {
+        return Err(AppError::ConfigError(format!(
+            "Hook '{}' already exists for {}",
+            entry.command, entry.event
+        )));
+    }
+
+    let mut hooks = parse_hooks(settings)?;
+    let groups = hooks.entry(entry.event.clone()).or_default();
+    let command = HookCommand {
+        hook_type: default_hook_type(),
+        command: entry.command.clone(),
+        timeout: entry.timeout,
+        extra: serde_json::Map::new(),
+    };
+
+    match groups
+        .iter_mut()
+        .find(|g| same_matcher(g.matcher.as_deref(), entry.matcher.as_deref()))
+    {
+        Some(group) => group.hooks.push(command),
+        None => groups.push(HookMatcher {
+            matcher: entry.matcher.clone(),
+            hooks: vec![command],
+            extra: serde_json::Map::new(),
+        }),
+    }
+
+    write_hooks(settings, &hooks)
+}
+
+/// Remove a hook command; empty matcher groups and events are dropped
+pub fn remove_hook(settings: &mut serde_json::Value, entry: &HookEntry) -> AppResult<()> {
+    let mut hooks = parse_hooks(settings)?;
+    let mut removed = false;
+
+    if let Some(groups) = hooks.get_mut(&entry.event) {
+        for group in groups
+            .iter_mut()
+            .filter(|g| same_matcher(g.matcher.as_deref(), entry.matcher.as_deref()))
+        {
+            let before = group.hooks.len();
+            group.hooks.retain(|h| h.command != entry.command);
+            removed |= group.hooks.len() != before;
+        }
+        groups.retain(|g| !g.hooks.is_empty());
+        if groups.is_empty() {
+            hooks.remove(&entry.event);
+        }
+    }
+
+    if !removed {
+        return Err(AppError::ConfigError(format!(
+            "Hook '{}' not found for {}",
+            entry.command, entry.event
+        )));
+    }
+    write_hooks(settings, &hooks)
+}
+
+/// Replace an existing hook with a new definition
+pub fn update_hook(
+    settings: &mut serde_json::Value,
+    old_entry: &HookEntry,
+    new_entry: &HookEntry,
+) -> AppResult<()> {
+    remove_hook(settings, old_entry)?;
+    add_hook(settings, new_entry)
+}
+
+/// Matchers are regular expressions; empty and "*" match every tool
+fn validate_matcher(matcher: &str) -> Result<(), String> {
+    if matcher.is_empty() || matcher == "*" {
+        return Ok(());
+    }
+    regex::Regex::new(matcher)
+        .map(|_| ())
+        .map_err(|e| format!("Malformed hook matcher '{}': {}", matcher, e))
+}
+
+/// `NAME=value` prefixes set environment variables for the command
+fn is_env_assignment(token: &str) -> bool {
+    match token.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Extract the program a hook command runs, if it can be determined statically
+fn command_program(command: &str) -> Option<String> {
+    let token = command.split_whitespace().find(|t| !is_env_assignment(t))?;
+    let token = token.trim_matches(|c| c == '"' || c == '\'');
+
+    // Variables and substitutions are only known when the hook runs
+    if token.is_empty() || token.contains('$') || token.contains('`') {
+        return None;
+    }
+    if SHELL_BUILTINS.contains(&token) {
+        return None;
+    }
+    Some(token.to_string())
+}
+
+fn is_executable(path: &Path) -> bool {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                metadata.permissions().mode() & 0o111 != 0
+            }
+            #[cfg(not(unix))]
+            {
+                true
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Check that a hook program resolves on PATH (or as a path) and is executable.
+/// Relative paths are resolved by Claude Code against the project it runs in, so
+/// they can't be checked here.
+fn check_program(program: &str) -> Result<(), String> {
+    if program.contains('/') || program.contains('\\') {
+        let path = match program.strip_prefix("~/") {
+            Some(rest) => match dirs::home_dir() {
+                Some(home) => home.join(rest),
+                None => return Ok(()),
+            },
+            None => PathBuf::from(program),
+        };
+        if !path.is_absolute() {
+            return Ok(());
+        }
+        if !path.exists() {
+            return Err(format!("Hook command '{}' does not exist", program));
+        }
+        if !is_executable(&path) {
+            return Err(format!("Hook command '{}' is not executable", program));
+        }
+        return Ok(());
+    }
+
+    let found = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths).any(|dir| {
+                is_executable(&dir.join(program))
+                    || (cfg!(windows) && is_executable(&dir.join(format!("{}.exe", program))))
+            })
+        })
+        .unwrap_or(false);
+
+    if found {
+        Ok(())
+    } else {
+        Err(format!(
+            "Hook command '{}' was not found on PATH or is not executable",
+            program
+        ))
+    }
+}
+
+/// Validation rule for the hooks section: structure, event names, matchers and commands
+pub struct HooksRule;
+
+impl ValidationRule for HooksRule {
+    fn validate(&self, json_value: &serde_json::Value) -> Vec<ValidationError> {
+        let error = |message: String| ValidationError {
+            line: 1,
+            column: 1,
+            message,
+            error_type: "hooks".to_string(),
+        };
+        // 找不到程序只是警告：GUI 进程的 PATH 可能与终端不同，配置也可能来自其他机器
+        let warning = |message: String| ValidationError {
+            error_type: "hook_program".to_string(),
+            ..error(message)
+        };
+
+        let hooks = match parse_hooks(json_value) {
+            Ok(hooks) => hooks,
+            Err(e) => return vec![error(e.to_string())],
+        };
+
+        let mut errors = Vec::new();
+        for (event, groups) in &hooks {
+            if !KNOWN_HOOK_EVENTS.contains(&event.as_str()) {
+                errors.push(error(format!("Unknown hook event '{}'", event)));
+            }
+            for group in groups {
+                if let Some(matcher) = &group.matcher {
+                    if let Err(message) = validate_matcher(matcher) {
+                        errors.push(error(format!("{}: {}", event, message)));
+                    }
+                }
+                for hook in &group.hooks {
+                    if hook.hook_type != "command" {
+                        continue;
+                    }
+                    if let Some(program) = command_program(&hook.command) {
+                        if let Err(message) = check_program(&program) {
+                            errors.push(warning(format!("{}: {}", event, message)));
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    fn get_rule_name(&self) -> &'static str {
+        "hooks"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(event: &str, matcher: Option<&str>, command: &str) -> HookEntry {
+        HookEntry {
+            event: event.to_string(),
+            matcher: matcher.map(str::to_string),
+            command: command.to_string(),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_add_list_update_remove() {
+        let mut settings = serde_json::json!({"model": "opus"});
+
+        add_hook(&mut settings, &entry("PreToolUse", Some("Bash"), "echo pre")).unwrap();
+        add_hook(&mut settings, &entry("PreToolUse", Some("Bash"), "echo again")).unwrap();
+        add_hook(&mut settings, &entry("Stop", None, "echo done")).unwrap();
+        assert_eq!(settings["hooks"]["PreToolUse"][0]["hooks"].as_array().unwrap().len(), 2);
+        assert_eq!(settings["hooks"]["PreToolUse"][0]["hooks"][0]["type"], "command");
+
+        assert!(add_hook(&mut settings, &entry("PreToolUse", Some("Bash"), "echo pre")).is_err());
+        assert!(add_hook(&mut settings, &entry("OnSave", None, "echo x")).is_err());
+        assert!(add_hook(&mut settings, &entry("Stop", Some("(unclosed"), "echo x")).is_err());
+
+        update_hook(
+            &mut settings,
+            &entry("Stop", None, "echo done"),
+            &entry("Stop", Some(""), "echo finished"),
+        )
+        .unwrap();
+        assert_eq!(list_hooks(&settings).unwrap().len(), 3);
+
+        remove_hook(&mut settings, &entry("PreToolUse", Some("Bash"), "echo pre")).unwrap();
+        remove_hook(&mut settings, &entry("PreToolUse", Some("Bash"), "echo again")).unwrap();
+        remove_hook(&mut settings, &entry("Stop", None, "echo finished")).unwrap();
+        assert!(settings.get("hooks").is_none());
+        assert_eq!(settings["model"], "opus");
+    }
+
+    #[test]
+    fn test_unknown_fields_are_preserved() {
+        let mut settings = serde_json::json!({
+            "hooks": {"Stop": [{"hooks": [{"type": "command", "command": "echo a", "custom": 1}]}]}
+        });
+        add_hook(&mut settings, &entry("Stop", None, "echo b")).unwrap();
+        assert_eq!(settings["hooks"]["Stop"][0]["hooks"][0]["custom"], 1);
+    }
+
+    #[test]
+    fn test_hooks_rule() {
+        let rule = HooksRule;
+        let settings = serde_json::json!({
+            "hooks": {
+                "PreToolUse": [{"matcher": "Edit|Write", "hooks": [{"type": "command", "command": "echo ok"}]}],
+                "PostToolUse": [{"matcher": "[bad", "hooks": []}],
+                "OnSave": [],
+                "Stop": [{"hooks": [
+                    {"type": "command", "command": "cccs-definitely-missing-binary --flag"},
+                    {"type": "command", "command": "\"$CLAUDE_PROJECT_DIR\"/hook.sh"},
+                    {"type": "command", "command": ".claude/hooks/lint.sh"},
+                    {"type": "command", "command": "./scripts/x.sh --fix"}
+                ]}]
+            }
+        });
+
+        let errors = rule.validate(&settings);
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|e| e.message.contains("Unknown hook event 'OnSave'")));
+        assert!(errors.iter().any(|e| e.message.contains("Malformed hook matcher")));
+        assert!(errors.iter().any(|e| e.message.contains("cccs-definitely-missing-binary")));
+        // A missing program is only a warning; structural problems block
+        assert_eq!(errors.iter().filter(|e| e.error_type == "hook_program").count(), 1);
+        assert_eq!(errors.iter().filter(|e| e.error_type == "hooks").count(), 2);
+
+        assert!(rule.validate(&serde_json::json!({"model": "opus"})).is_empty());
+    }
+}
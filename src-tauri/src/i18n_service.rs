@@ -111,6 +111,14 @@ impl I18nService {
         en_resources.insert("switching_profile".to_string(), "Switching profile...".to_string());
         en_resources.insert("profile_switched".to_string(), "Profile switched successfully".to_string());
         en_resources.insert("switch_failed".to_string(), "Failed to switch profile".to_string());
+        en_resources.insert("scheduled_switch".to_string(), "Schedule '{}' switched to {}".to_string());
+        en_resources.insert("lease_ended".to_string(), "Temporary switch to {} ended".to_string());
+        en_resources.insert("profile_action_done".to_string(), "Profile {}".to_string());
+        en_resources.insert("profile_action_failed".to_string(), "Profile action failed".to_string());
+        en_resources.insert("profile_duplicated".to_string(), "Duplicated as '{}'".to_string());
+        en_resources.insert("env_copied".to_string(), "Copied {} environment variables; secrets stay as ${secret:..} references".to_string());
+        en_resources.insert("stale_session".to_string(), "1 Claude Code session still uses the previous profile until it is restarted".to_string());
+        en_resources.insert("stale_sessions".to_string(), "{} Claude Code sessions still use the previous profile until they are restarted".to_string());
         en_resources.insert("claude_not_found".to_string(), "Claude Code installation not found".to_string());
         en_resources.insert("settings_not_found".to_string(), "settings.json not found. Please run Claude Code at least once.".to_string());
         en_resources.insert("monitor_interval".to_string(), "Monitor interval: {} minutes".to_string());
@@ -140,6 +148,14 @@ impl I18nService {
         zh_resources.insert("switching_profile".to_string(), "正在切换配置...".to_string());
         zh_resources.insert("profile_switched".to_string(), "配置切换成功".to_string());
         zh_resources.insert("switch_failed".to_string(), "配置切换失败".to_string());
+        zh_resources.insert("scheduled_switch".to_string(), "定时计划 '{}' 切换到 {}".to_string());
+        zh_resources.insert("lease_ended".to_string(), "临时切换到 {} 已结束".to_string());
+        zh_resources.insert("profile_action_done".to_string(), "配置 {}".to_string());
+        zh_resources.insert("profile_action_failed".to_string(), "配置操作失败".to_string());
+        zh_resources.insert("profile_duplicated".to_string(), "已复制为 '{}'".to_string());
+        zh_resources.insert("env_copied".to_string(), "已复制 {} 个环境变量，密钥保留为 ${secret:..} 引用".to_string());
+        zh_resources.insert("stale_session".to_string(), "1 个 Claude Code 会话仍在使用之前的配置，重启后生效".to_string());
+        zh_resources.insert("stale_sessions".to_string(), "{} 个 Claude Code 会话仍在使用之前的配置，重启后生效".to_string());
         zh_resources.insert("claude_not_found".to_string(), "未找到 Claude Code 安装".to_string());
         zh_resources.insert("settings_not_found".to_string(), "未找到 settings.json 文件。请至少运行一次 Claude Code。".to_string());
         zh_resources.insert("monitor_interval".to_string(), "监控间隔: {} 分钟".to_string());
@@ -0,0 +1,491 @@
+// Placeholder interpolation for profile string values
+use crate::file_permissions;
+use crate::secret_vault::{self, SecretVault};
+use crate::{AppError, AppResult, ValidationError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A recognised ${...} placeholder. Anything else inside ${} is left untouched,
+/// so shell-style ${VAR} in hook commands keeps working.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Placeholder {
+    Home,
+    ClaudeDir,
+    Env(String),
+    Var(String),
+    Secret(String),
+}
+
+impl Placeholder {
+    fn parse(inner: &str) -> Option<Self> {
+        match inner {
+            "home" => return Some(Self::Home),
+            "claude_dir" => return Some(Self::ClaudeDir),
+            _ => {}
+        }
+
+        let (kind, name) = inner.split_once(':')?;
+        if name.is_empty() {
+            return None;
+        }
+        match kind {
+            "env" => Some(Self::Env(name.to_string())),
+            "var" => Some(Self::Var(name.to_string())),
+            "secret" => Some(Self::Secret(name.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Render the placeholder the way it is written in a profile
+    pub fn to_template(&self) -> String {
+        match self {
+            Self::Home => "${home}".to_string(),
+            Self::ClaudeDir => "${claude_dir}".to_string(),
+            Self::Env(name) => format!("${{env:{}}}", name),
+            Self::Var(name) => format!("${{var:{}}}", name),
+            Self::Secret(name) => format!("${{secret:{}}}", name),
+        }
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder),
+}
+
+/// Split a string into literal text and recognised placeholders
+fn split_template(s: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+
+        match Placeholder::parse(&after[..end]) {
+            Some(placeholder) => {
+                if start > 0 {
+                    segments.push(Segment::Text(&rest[..start]));
+                }
+                segments.push(Segment::Placeholder(placeholder));
+            }
+            None => segments.push(Segment::Text(&rest[..start + 2 + end + 1])),
+        }
+        rest = &after[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    segments
+}
+
+/// Values available to non-secret placeholders
+#[derive(Debug, Clone, Default)]
+pub struct InterpolationContext {
+    pub home_dir: Option<PathBuf>,
+    pub claude_dir: PathBuf,
+    pub variables: BTreeMap<String, String>,
+}
+
+impl InterpolationContext {
+    pub fn new(claude_dir: PathBuf, variables: BTreeMap<String, String>) -> Self {
+        Self {
+            home_dir: dirs::home_dir(),
+            claude_dir,
+            variables,
+        }
+    }
+
+    /// Look up a non-secret placeholder
+    fn lookup(&self, placeholder: &Placeholder) -> Option<String> {
+        match placeholder {
+            Placeholder::Home => self
+                .home_dir
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+            Placeholder::ClaudeDir => Some(self.claude_dir.to_string_lossy().to_string()),
+            Placeholder::Env(name) => std::env::var(name).ok(),
+            Placeholder::Var(name) => self.variables.get(name).cloned(),
+            Placeholder::Secret(_) => None,
+        }
+    }
+}
+
+/// Collect all placeholders used in string values (sorted, without duplicates)
+pub fn find_placeholders(value: &serde_json::Value) -> Vec<Placeholder> {
+    let mut placeholders = Vec::new();
+    collect_placeholders(value, &mut placeholders);
+    placeholders.sort();
+    placeholders.dedup();
+    placeholders
+}
+
+fn collect_placeholders(value: &serde_json::Value, placeholders: &mut Vec<Placeholder>) {
+    match value {
+        serde_json::Value::String(s) => {
+            for segment in split_template(s) {
+                if let Segment::Placeholder(placeholder) = segment {
+                    placeholders.push(placeholder);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_placeholders(item, placeholders);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for item in map.values() {
+                collect_placeholders(item, placeholders);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Find non-secret placeholders that cannot be resolved in the given context
+pub fn find_unresolved(
+    value: &serde_json::Value,
+    context: &InterpolationContext,
+) -> Vec<Placeholder> {
+    find_placeholders(value)
+        .into_iter()
+        .filter(|p| !matches!(p, Placeholder::Secret(_)) && context.lookup(p).is_none())
+        .collect()
+}
+
+/// Resolve every placeholder in string values. Missing secrets fail with a
+/// `SecretError`, other unresolved placeholders with a `ConfigError`.
+pub fn resolve_placeholders(
+    value: &serde_json::Value,
+    context: &InterpolationContext,
+    vault: Option<&SecretVault>,
+) -> AppResult<serde_json::Value> {
+    let placeholders = find_placeholders(value);
+    if placeholders.is_empty() {
+        return Ok(value.clone());
+    }
+
+    let secret_names: Vec<String> = placeholders
+        .iter()
+        .filter_map(|p| match p {
+            Placeholder::Secret(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let secrets = if secret_names.is_empty() {
+        BTreeMap::new()
+    } else {
+        secret_vault::resolve_secrets(&secret_names, vault)?
+    };
+
+    let unresolved = find_unresolved(value, context);
+    if !unresolved.is_empty() {
+        let names: Vec<String> = unresolved.iter().map(|p| p.to_template()).collect();
+        return Err(AppError::ConfigError(format!(
+            "Unresolved variables in profile: {}",
+            names.join(", ")
+        )));
+    }
+
+    Ok(replace_placeholders(value, context, &secrets))
+}
+
+/// Resolve every placeholder except secrets, which stay as `${secret:..}` references
+/// so the result can leave CCCS (clipboard, shell) without the vault's plaintext
+pub fn resolve_except_secrets(
+    value: &serde_json::Value,
+    context: &InterpolationContext,
+) -> AppResult<serde_json::Value> {
+    let unresolved = find_unresolved(value, context);
+    if !unresolved.is_empty() {
+        let names: Vec<String> = unresolved.iter().map(|p| p.to_template()).collect();
+        return Err(AppError::ConfigError(format!(
+            "Unresolved variables in profile: {}",
+            names.join(", ")
+        )));
+    }
+
+    let references: BTreeMap<String, String> = find_placeholders(value)
+        .into_iter()
+        .filter_map(|p| match &p {
+            Placeholder::Secret(name) => Some((name.clone(), p.to_template())),
+            _ => None,
+        })
+        .collect();
+    Ok(replace_placeholders(value, context, &references))
+}
+
+fn replace_placeholders(
+    value: &serde_json::Value,
+    context: &InterpolationContext,
+    secrets: &BTreeMap<String, String>,
+) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => {
+            let mut result = String::with_capacity(s.len());
+            for segment in split_template(s) {
+                match segment {
+                    Segment::Text(text) => result.push_str(text),
+                    Segment::Placeholder(Placeholder::Secret(name)) => {
+                        result.push_str(secrets.get(&name).map(String::as_str).unwrap_or_default())
+                    }
+                    Segment::Placeholder(placeholder) => {
+                        result.push_str(&context.lookup(&placeholder).unwrap_or_default())
+                    }
+                }
+            }
+            serde_json::Value::String(result)
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(
+            items
+                .iter()
+                .map(|item| replace_placeholders(item, context, secrets))
+                .collect(),
+        ),
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), replace_placeholders(v, context, secrets)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Report unresolved non-secret placeholders as validation errors, pointing
+/// at their first occurrence in the raw content
+pub fn validate_placeholders(
+    content: &str,
+    value: &serde_json::Value,
+    context: &InterpolationContext,
+) -> Vec<ValidationError> {
+    find_unresolved(value, context)
+        .into_iter()
+        .map(|placeholder| {
+            let template = placeholder.to_template();
+            let (line, column) = content
+                .find(&template)
+                .map(|offset| line_and_column(content, offset))
+                .unwrap_or((1, 1));
+            let message = match &placeholder {
+                Placeholder::Env(name) => {
+                    format!("Environment variable '{}' is not set ({})", name, template)
+                }
+                Placeholder::Var(name) => {
+                    format!("Variable '{}' is not defined ({})", name, template)
+                }
+                _ => format!("Cannot resolve {}", template),
+            };
+            ValidationError {
+                line,
+                column,
+                message,
+                error_type: "variable".to_string(),
+            }
+        })
+        .collect()
+}
+
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|l| l.chars().count())
+        .unwrap_or(0)
+        + 1;
+    (line, column)
+}
+
+/// Get the default user variables file path
+pub fn default_variables_path() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("variables.json"))
+}
+
+/// Load user variables (a flat JSON object of strings); a missing file means no variables
+pub fn load_user_variables(path: &Path) -> AppResult<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        AppError::ConfigError(format!("Failed to read variables file {:?}: {}", path, e))
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        AppError::ConfigError(format!("Failed to parse variables file {:?}: {}", path, e))
+    })
+}
+
+/// Save user variables atomically
+pub fn save_user_variables(path: &Path, variables: &BTreeMap<String, String>) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            AppError::ConfigError(format!("Failed to create variables directory: {}", e))
+        })?;
+    }
+
+    let content = serde_json::to_string_pretty(variables)?;
+    let temp_path = path.with_extension("json.tmp");
+    file_permissions::write_private_file(&temp_path, content.as_bytes()).map_err(|e| {
+        AppError::FileSystemError(format!("Failed to write variables file: {}", e))
+    })?;
+    fs::rename(&temp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::FileSystemError(format!("Failed to save variables file: {}", e))
+    })?;
+
+    Ok(())
+}
+
+/// Validate a variable name (letters, digits, '-', '_' and '.')
+pub fn validate_variable_name(name: &str) -> AppResult<()> {
+    if name.is_empty() {
+        return Err(AppError::ConfigError("Variable name cannot be empty".to_string()));
+    }
+    if name.len() > 100 {
+        return Err(AppError::ConfigError(
+            "Variable name too long (max 100 characters)".to_string(),
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(AppError::ConfigError(format!(
+            "Variable name '{}' contains invalid characters. Use letters, digits, '-', '_' or '.'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_context() -> InterpolationContext {
+        let mut variables = BTreeMap::new();
+        variables.insert("project_root".to_string(), "/work/project".to_string());
+        InterpolationContext {
+            home_dir: Some(PathBuf::from("/home/tester")),
+            claude_dir: PathBuf::from("/home/tester/.claude"),
+            variables,
+        }
+    }
+
+    #[test]
+    fn test_find_placeholders() {
+        let value = serde_json::json!({
+            "env": {"A": "${env:CCCS_TEST_A}", "B": "${home}/x ${var:root}"},
+            "hooks": ["echo ${HOME}", "${secret:token}"]
+        });
+
+        let placeholders = find_placeholders(&value);
+        assert_eq!(
+            placeholders,
+            vec![
+                Placeholder::Home,
+                Placeholder::Env("CCCS_TEST_A".to_string()),
+                Placeholder::Var("root".to_string()),
+                Placeholder::Secret("token".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_placeholders() {
+        std::env::set_var("CCCS_TEST_USER", "alice");
+        let context = create_test_context();
+        let value = serde_json::json!({
+            "env": {
+                "USER_NAME": "${env:CCCS_TEST_USER}",
+                "PROJECT": "${var:project_root}/src",
+                "CONFIG": "${claude_dir}/agents",
+                "CACHE": "${home}/.cache"
+            },
+            "command": "echo ${HOME} ${unknown}",
+            "count": 3
+        });
+
+        let resolved = resolve_placeholders(&value, &context, None).unwrap();
+        assert_eq!(resolved["env"]["USER_NAME"], "alice");
+        assert_eq!(resolved["env"]["PROJECT"], "/work/project/src");
+        assert_eq!(resolved["env"]["CONFIG"], "/home/tester/.claude/agents");
+        assert_eq!(resolved["env"]["CACHE"], "/home/tester/.cache");
+        assert_eq!(resolved["command"], "echo ${HOME} ${unknown}");
+        assert_eq!(resolved["count"], 3);
+    }
+
+    #[test]
+    fn test_resolve_except_secrets_keeps_references() {
+        let context = create_test_context();
+        let value = serde_json::json!({"env": {"TOKEN": "Bearer ${secret:token}", "ROOT": "${var:project_root}"}});
+
+        let resolved = resolve_except_secrets(&value, &context).unwrap();
+        assert_eq!(resolved["env"]["TOKEN"], "Bearer ${secret:token}");
+        assert_eq!(resolved["env"]["ROOT"], "/work/project");
+        assert!(resolve_except_secrets(&serde_json::json!({"a": "${var:missing}"}), &context).is_err());
+    }
+
+    #[test]
+    fn test_resolve_unresolved_variables_fails() {
+        let context = create_test_context();
+        let value = serde_json::json!({"a": "${var:missing}", "b": "${env:CCCS_TEST_NOT_SET}"});
+
+        match resolve_placeholders(&value, &context, None) {
+            Err(AppError::ConfigError(msg)) => {
+                assert!(msg.contains("${var:missing}"));
+                assert!(msg.contains("${env:CCCS_TEST_NOT_SET}"));
+            }
+            other => panic!("Expected ConfigError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_secret_without_vault_fails() {
+        let context = create_test_context();
+        let value = serde_json::json!({"token": "${secret:work-token}"});
+
+        assert!(matches!(
+            resolve_placeholders(&value, &context, None),
+            Err(AppError::SecretError(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_placeholders_reports_location() {
+        let context = create_test_context();
+        let content = "{\n  \"env\": {\n    \"X\": \"${var:nope}\"\n  }\n}";
+        let value: serde_json::Value = serde_json::from_str(content).unwrap();
+
+        let errors = validate_placeholders(content, &value, &context);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(errors[0].column, 11);
+        assert_eq!(errors[0].error_type, "variable");
+
+        // Secrets are resolved at switch time and are not validation errors
+        let value = serde_json::json!({"token": "${secret:anything}"});
+        assert!(validate_placeholders("", &value, &context).is_empty());
+    }
+
+    #[test]
+    fn test_user_variables_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("variables.json");
+
+        assert!(load_user_variables(&path).unwrap().is_empty());
+
+        let mut variables = BTreeMap::new();
+        variables.insert("root".to_string(), "/srv".to_string());
+        save_user_variables(&path, &variables).unwrap();
+        assert_eq!(load_user_variables(&path).unwrap(), variables);
+    }
+}
@@ -2,11 +2,36 @@
 // Core modules
 mod app;
 mod claude_detector;
+mod cli;
 mod config_service;
+mod directory_rules;
+mod effective_settings;
 mod error;
+mod file_permissions;
+mod git_store;
+mod hooks_manager;
 mod i18n_service;
+mod interpolation;
+mod mcp_service;
 mod monitor_service;
+mod org_policy;
+mod permissions_editor;
+mod process_inspector;
+mod profile_archive;
+mod profile_bundle;
+mod profile_history;
+mod profile_metadata;
+mod profile_signing;
+mod profile_templates;
+mod schedule_service;
+mod secret_vault;
+mod settings_scope;
 mod settings_service;
+mod subscriptions;
+mod switch_hooks;
+mod switch_journal;
+mod switch_lease;
+mod tray_actions;
 mod tray_service;
 mod types;
 mod validation;
@@ -16,13 +41,32 @@ mod validation;
 pub mod performance_tests;
 
 // Re-exports for public API
+pub use cli::run_cli;
 pub use error::AppError;
 pub use types::*;
 
 pub type AppResult<T> = Result<T, AppError>;
 
 use app::App;
+use config_service::ConfigAccess;
+use directory_rules::RuleApplication;
+use effective_settings::{EffectiveSettings, SettingsResolver};
+use git_store::GitCommit;
+use hooks_manager::HookEntry;
+use mcp_service::McpSetInfo;
+use org_policy::PolicyViolation;
+use permissions_editor::{PermissionList, ProfilePermissions};
+use process_inspector::ClaudeSession;
+use profile_metadata::ProfileMetadata;
+use profile_archive::{ArchiveManifest, ImportChoice, ImportPreview, SecretHandling};
+use profile_signing::TrustedKey;
+use profile_history::{ProfileDiff, ProfileVersion};
+use settings_scope::{ScopeStatus, SettingsScope};
+use subscriptions::{ProfileSubscription, SubscriptionStatus};
+use switch_hooks::{HookRun, SwitchHooksConfig};
+use switch_lease::LeaseInfo;
 use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Manager, Emitter};
 
@@ -148,12 +192,17 @@ async fn get_profile_status(
     match config.read_profile_content(&profile_id) {
         Ok(content) => {
             let status = config.get_detailed_profile_status_with_ignored_fields(&content, Some(&ignored_fields));
-            let icon = match status {
-                crate::ProfileStatus::FullMatch => "✅",
-                crate::ProfileStatus::PartialMatch => "🔄",
-                crate::ProfileStatus::Error(_) => "❌",
-                crate::ProfileStatus::NoMatch => "",
-            };
+            let status = config.with_bundle_status(&profile_id, status);
+            let status = config.with_policy_status(&content, status);
+            let status = config.with_signature_status(&profile_id, status);
+            let icon = status_icon(&status);
+
+            // Plaintext secrets readable by other users
+            if let Some(warning) = config.get_profile_permission_warning(&profile_id) {
+                log::warn!("Profile '{}' permission warning: {}", profile_id, warning);
+                return Ok(format!("{}⚠️", icon));
+            }
+
             Ok(icon.to_string())
         }
         Err(e) => {
@@ -163,6 +212,109 @@ async fn get_profile_status(
     }
 }
 
+/// Organization policy violations of a profile; empty when it complies or there is no policy
+#[tauri::command]
+async fn get_policy_violations(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<PolicyViolation>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config
+        .get_policy_violations(&profile_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Replace the description, tags, color, icon, pin and position of a profile
+#[tauri::command]
+async fn update_profile_metadata(
+    profile_id: String,
+    metadata: ProfileMetadata,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("update_profile_metadata called for profile: {}", profile_id);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    if let Err(e) = config.save_profile_metadata(&profile_id, metadata) {
+        log::error!("Failed to save profile metadata: {}", e);
+        return Err(e.to_string());
+    }
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(())
+}
+
+/// Set the manual order of profiles in the list and the tray menu
+#[tauri::command]
+async fn reorder_profiles(
+    profile_ids: Vec<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("reorder_profiles called with {} profiles", profile_ids.len());
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    if let Err(e) = config.reorder_profiles(&profile_ids) {
+        log::error!("Failed to reorder profiles: {}", e);
+        return Err(e.to_string());
+    }
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 async fn load_profile_content(
     profile_id: String,
@@ -233,13 +385,65 @@ async fn save_profile(
 }
 
 #[tauri::command]
-async fn create_new_profile(
-    profile_name: String,
-    content: String,
+async fn list_profile_versions(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<ProfileVersion>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config.list_profile_versions(&profile_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_profile_version(
+    profile_id: String,
+    version: u64,
     app_state: tauri::State<'_, Arc<Mutex<App>>>,
 ) -> Result<String, String> {
-    log::info!("create_new_profile called for profile: {}", profile_name);
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
 
+    config
+        .get_profile_version(&profile_id, version)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn diff_profile_versions(
+    profile_id: String,
+    from: u64,
+    to: Option<u64>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<ProfileDiff, String> {
     let app = match app_state.try_lock() {
         Ok(guard) => guard,
         Err(e) => {
@@ -257,34 +461,55 @@ async fn create_new_profile(
         }
     };
 
-    let result = config.create_profile(&profile_name, &content);
-    drop(config); // 释放锁，避免死锁
-    
-    match result {
-        Ok(path) => {
-            log::info!("Successfully created profile: {} at {}", profile_name, path);
-            
-            // Update tray menu to show the new profile
-            if let Err(e) = app.update_tray_menu() {
-                log::warn!("Failed to update tray menu after creating profile: {}", e);
-                // Don't fail the operation since profile creation was successful
-            }
-            
-            Ok(path)
+    config
+        .diff_profile_versions(&profile_id, from, to)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_profile_version(
+    profile_id: String,
+    version: u64,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("restore_profile_version called for profile: {} (version {})", profile_id, version);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
         }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
         Err(e) => {
-            log::error!("Failed to create profile: {}", e);
-            Err(e.to_string())
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
         }
+    };
+
+    if let Err(e) = config.restore_profile_version(&profile_id, version) {
+        log::error!("Failed to restore profile version: {}", e);
+        return Err(e.to_string());
+    }
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
     }
+    Ok(())
 }
 
 #[tauri::command]
-async fn delete_profile(
-    profile_id: String,
+async fn set_git_store_enabled(
+    enabled: bool,
     app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
 ) -> Result<(), String> {
-    log::info!("delete_profile called for profile: {}", profile_id);
+    log::info!("set_git_store_enabled called: {}", enabled);
 
     let app = match app_state.try_lock() {
         Ok(guard) => guard,
@@ -303,25 +528,52 @@ async fn delete_profile(
         }
     };
 
-    match config.delete_profile(&profile_id) {
-        Ok(()) => {
-            log::info!("Successfully deleted profile: {}", profile_id);
-            Ok(())
+    // 仓库初始化成功后才保存设置
+    config.set_git_store_enabled(enabled).map_err(|e| {
+        log::error!("Failed to set up git profile store: {}", e);
+        e.to_string()
+    })?;
+
+    let mut settings = settings_state
+        .lock()
+        .map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    settings.set_git_store_enabled(enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_git_log(
+    profile_id: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<GitCommit>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
         }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
         Err(e) => {
-            log::error!("Failed to delete profile: {}", e);
-            Err(e.to_string())
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
         }
-    }
+    };
+
+    config
+        .get_git_log(profile_id.as_deref())
+        .map_err(|e| e.to_string())
 }
 
+/// Profile content at an older revision; the editor opens it and saving makes it current
 #[tauri::command]
-async fn validate_json_content(
-    content: String,
+async fn get_profile_at_revision(
+    profile_id: String,
+    revision: String,
     app_state: tauri::State<'_, Arc<Mutex<App>>>,
-) -> Result<ValidationResult, String> {
-    log::debug!("validate_json_content called");
-
+) -> Result<String, String> {
     let app = match app_state.try_lock() {
         Ok(guard) => guard,
         Err(e) => {
@@ -339,25 +591,77 @@ async fn validate_json_content(
         }
     };
 
-    match config.validate_json_content(&content) {
-        Ok(result) => {
-            log::debug!("JSON validation result: valid={}", result.is_valid);
-            Ok(result)
+    config
+        .get_profile_at_revision(&profile_id, &revision)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_subscriptions(
+    subscriptions: Vec<ProfileSubscription>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
+) -> Result<(), String> {
+    log::info!("update_subscriptions called: {} subscriptions", subscriptions.len());
+
+    // 先校验并保存设置，再同步新增的订阅
+    match settings_state.lock() {
+        Ok(mut settings) => settings
+            .update_subscriptions(subscriptions.clone())
+            .map_err(|e| e.to_string())?,
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return Err("Failed to access settings".to_string());
         }
+    }
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
         Err(e) => {
-            log::error!("Failed to validate JSON content: {}", e);
-            Err(e.to_string())
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
         }
+    };
+
+    if let Err(e) = config.set_subscriptions(subscriptions) {
+        log::error!("Failed to apply subscriptions: {}", e);
+        return Err(e.to_string());
+    }
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
     }
+    Ok(())
 }
 
+/// Replace the trusted signing keys; every profile is verified again
 #[tauri::command]
-async fn apply_profile(
-    profile_id: String,
-    content: String,
+async fn update_trusted_keys(
+    keys: Vec<TrustedKey>,
     app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
 ) -> Result<(), String> {
-    log::info!("apply_profile called for profile: {} with content length: {}", profile_id, content.len());
+    log::info!("update_trusted_keys called: {} keys", keys.len());
+
+    match settings_state.lock() {
+        Ok(mut settings) => settings
+            .update_trusted_keys(keys.clone())
+            .map_err(|e| e.to_string())?,
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return Err("Failed to access settings".to_string());
+        }
+    }
 
     let app = match app_state.try_lock() {
         Ok(guard) => guard,
@@ -376,25 +680,1961 @@ async fn apply_profile(
         }
     };
 
-    // Apply the profile content directly (this will copy the provided content to default settings.json)
-    let result = config.apply_profile_content(&content);
+    if let Err(e) = config.set_trusted_keys(keys) {
+        log::error!("Failed to apply trusted keys: {}", e);
+        return Err(e.to_string());
+    }
+
     drop(config); // 释放锁，避免死锁
-    
-    match result {
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_subscription_status(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<SubscriptionStatus>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config.get_subscription_status().map_err(|e| e.to_string())
+}
+
+/// Take upstream changes of a whole subscription, or of one of its profiles
+#[tauri::command]
+async fn update_subscription(
+    name: String,
+    profile: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("update_subscription called: {} ({:?})", name, profile);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let changed = match config.update_subscription(&name, profile.as_deref()) {
+        Ok(changed) => changed,
+        Err(e) => {
+            log::error!("Failed to update subscription: {}", e);
+            return Err(e.to_string());
+        }
+    };
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(changed)
+}
+
+#[tauri::command]
+async fn get_profile_override(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Option<String>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config
+        .get_profile_override(&profile_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_profile_override(
+    profile_id: String,
+    content: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("save_profile_override called for profile: {}", profile_id);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    if let Err(e) = config.save_profile_override(&profile_id, content.as_deref()) {
+        log::error!("Failed to save profile override: {}", e);
+        return Err(e.to_string());
+    }
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn create_new_profile(
+    profile_name: String,
+    content: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<String, String> {
+    log::info!("create_new_profile called for profile: {}", profile_name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.create_profile(&profile_name, &content);
+    drop(config); // 释放锁，避免死锁
+    
+    match result {
+        Ok(path) => {
+            log::info!("Successfully created profile: {} at {}", profile_name, path);
+            
+            // Update tray menu to show the new profile
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after creating profile: {}", e);
+                // Don't fail the operation since profile creation was successful
+            }
+            
+            Ok(path)
+        }
+        Err(e) => {
+            log::error!("Failed to create profile: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Export profiles into a portable archive at `path`
+#[tauri::command]
+async fn export_profiles(
+    profile_ids: Vec<String>,
+    path: String,
+    secrets: Option<SecretHandling>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<ArchiveManifest, String> {
+    log::info!("export_profiles called: {} profiles to {}", profile_ids.len(), path);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config
+        .export_profiles(&profile_ids, std::path::Path::new(&path), secrets.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Conflicts and validation problems of an archive, shown before importing it
+#[tauri::command]
+async fn preview_profile_import(
+    path: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<ImportPreview, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config
+        .preview_profile_import(std::path::Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_profiles(
+    path: String,
+    choices: Vec<ImportChoice>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("import_profiles called: {}", path);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let imported = match config.import_profiles(std::path::Path::new(&path), &choices) {
+        Ok(imported) => imported,
+        Err(e) => {
+            log::error!("Failed to import profiles: {}", e);
+            return Err(e.to_string());
+        }
+    };
+
+    drop(config); // 释放锁，避免死锁
+    if let Err(e) = app.update_tray_menu() {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+    Ok(imported)
+}
+
+#[tauri::command]
+async fn list_profile_templates() -> Result<Vec<profile_templates::ProfileTemplate>, String> {
+    log::debug!("list_profile_templates called");
+
+    let registry = profile_templates::TemplateRegistry::load_default();
+    Ok(registry.list().to_vec())
+}
+
+#[tauri::command]
+async fn create_profile_from_template(
+    template_id: String,
+    profile_name: String,
+    values: HashMap<String, String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<String, String> {
+    log::info!(
+        "create_profile_from_template called for profile: {} (template: {})",
+        profile_name,
+        template_id
+    );
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let registry = profile_templates::TemplateRegistry::load_default();
+    let template = match registry.get(&template_id) {
+        Ok(template) => template,
+        Err(e) => {
+            log::error!("Failed to load template {}: {}", template_id, e);
+            return Err(e.to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    // 明文的密钥字段存入保险库，配置中只写引用
+    let result = config.create_profile_from_template(template, &profile_name, &values);
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(path) => {
+            log::info!("Successfully created profile: {} at {}", profile_name, path);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after creating profile: {}", e);
+            }
+
+            Ok(path)
+        }
+        Err(e) => {
+            log::error!("Failed to create profile from template: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn delete_profile(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("delete_profile called for profile: {}", profile_id);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    match config.delete_profile(&profile_id) {
+        Ok(()) => {
+            log::info!("Successfully deleted profile: {}", profile_id);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to delete profile: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn validate_json_content(
+    content: String,
+    profile_id: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<ValidationResult, String> {
+    log::debug!("validate_json_content called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = match &profile_id {
+        Some(profile_id) => config.validate_profile_content(profile_id, &content),
+        None => config.validate_json_content(&content),
+    };
+
+    match result {
+        Ok(result) => {
+            log::debug!("JSON validation result: valid={}", result.is_valid);
+            Ok(result)
+        }
+        Err(e) => {
+            log::error!("Failed to validate JSON content: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn apply_profile(
+    profile_id: String,
+    content: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("apply_profile called for profile: {} with content length: {}", profile_id, content.len());
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    // Apply the profile content directly (this will copy the provided content to default settings.json)
+    let result = config.apply_profile_content(&content);
+    drop(config); // 释放锁，避免死锁
+    
+    match result {
+        Ok(()) => {
+            log::info!("Successfully applied profile content from: {}", profile_id);
+            
+            // Update tray menu to reflect the new active profile status
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after applying profile: {}", e);
+                // Don't fail the operation since profile application was successful
+            }
+            
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to apply profile content from '{}': {}", profile_id, e);
+            Err(format!("Failed to apply profile: {}", e))
+        }
+    }
+}
+
+/// Update the tray menu after an operation that ran without holding the app lock
+fn update_tray_menu_after(app_state: &Mutex<App>, action: &str) {
+    match app_state.try_lock() {
+        Ok(app) => {
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after {}: {}", action, e);
+            }
+        }
+        Err(e) => log::warn!("Failed to lock app state to update tray menu after {}: {}", action, e),
+    }
+}
+
+#[tauri::command]
+async fn switch_profile_temporarily(
+    profile_id: String,
+    minutes: u64,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<LeaseInfo, String> {
+    log::info!("switch_profile_temporarily called for profile: {} ({} minutes)", profile_id, minutes);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    // 切换钩子可能运行较久，运行期间不持有应用和配置服务锁
+    let config_service = app.get_config_service();
+    drop(app);
+
+    let result = config_service.as_ref().switch_profile_temporarily(
+        &profile_id,
+        std::time::Duration::from_secs(minutes.saturating_mul(60)),
+    );
+
+    match result {
+        Ok(lease) => {
+            update_tray_menu_after(&app_state, "temporary switch");
+            Ok(lease.info())
+        }
+        Err(e) => {
+            log::error!("Failed to switch temporarily to '{}': {}", profile_id, e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_switch_lease(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Option<LeaseInfo>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    Ok(config.get_switch_lease().map(|lease| lease.info()))
+}
+
+#[tauri::command]
+async fn end_switch_lease(
+    revert: bool,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("end_switch_lease called (revert: {})", revert);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    // 切换钩子可能运行较久，运行期间不持有应用和配置服务锁
+    let config_service = app.get_config_service();
+    drop(app);
+
+    let result = config_service.as_ref().end_switch_lease(revert);
+
+    match result {
+        Ok(_) => {
+            update_tray_menu_after(&app_state, "ending temporary switch");
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to end temporary switch: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Claude Code sessions still running with the settings from before the last switch
+#[tauri::command]
+async fn get_stale_sessions(
+    scope: Option<SettingsScope>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<ClaudeSession>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    Ok(config.stale_sessions(&scope.unwrap_or(SettingsScope::User)))
+}
+
+#[tauri::command]
+async fn get_switch_hooks(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<SwitchHooksConfig, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config.get_switch_hooks().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_switch_hooks(
+    hooks: SwitchHooksConfig,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("save_switch_hooks called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    config.save_switch_hooks(&hooks).map_err(|e| {
+        log::error!("Failed to save switch hooks: {}", e);
+        e.to_string()
+    })
+}
+
+#[tauri::command]
+async fn get_last_hook_runs(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<HookRun>, String> {
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    Ok(config.get_last_hook_runs().to_vec())
+}
+
+#[tauri::command]
+async fn apply_profile_to_scope(
+    profile_id: String,
+    scope: SettingsScope,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
+) -> Result<(), String> {
+    log::info!("apply_profile_to_scope called for profile: {} ({})", profile_id, scope.label());
+
+    // Only registered projects can be written to
+    if let Some(project) = scope.project() {
+        let registered = match settings_state.lock() {
+            Ok(settings) => settings.get_projects().iter().any(|p| p == project),
+            Err(e) => {
+                log::error!("Failed to lock settings service: {}", e);
+                return Err("Failed to access settings".to_string());
+            }
+        };
+        if !registered {
+            return Err(format!("Project is not registered: {}", project.display()));
+        }
+    }
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    // 切换钩子可能运行较久，运行期间不持有应用和配置服务锁
+    let config_service = app.get_config_service();
+    drop(app);
+
+    let result = config_service.as_ref().switch_profile_in_scope(&profile_id, &scope);
+
+    match result {
+        Ok(()) => {
+            log::info!("Applied profile '{}' to {}", profile_id, scope.label());
+            update_tray_menu_after(&app_state, "applying profile");
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to apply profile '{}' to {}: {}", profile_id, scope.label(), e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn apply_directory_rules(
+    working_dir: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
+) -> Result<Vec<RuleApplication>, String> {
+    log::info!("apply_directory_rules called (working dir: {:?})", working_dir);
+
+    let planned = match settings_state.lock() {
+        Ok(settings) => directory_rules::plan(
+            settings.get_directory_rules(),
+            settings.get_projects(),
+            working_dir.as_deref().map(std::path::Path::new),
+        ),
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return Err("Failed to access settings".to_string());
+        }
+    };
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    // 切换钩子可能运行较久，运行期间不持有应用和配置服务锁
+    let config_service = app.get_config_service();
+    drop(app);
+
+    let results = directory_rules::apply(&config_service, &planned);
+    update_tray_menu_after(&app_state, "applying directory rules");
+
+    Ok(results)
+}
+
+#[tauri::command]
+async fn get_profile_scope_statuses(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
+) -> Result<Vec<ScopeStatus>, String> {
+    let (projects, ignored_fields) = match settings_state.lock() {
+        Ok(settings) => (
+            settings.get_projects().to_vec(),
+            settings.get_ignored_fields().to_vec(),
+        ),
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return Err("Failed to access settings".to_string());
+        }
+    };
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let statuses = SettingsScope::all(&projects)
+        .into_iter()
+        .map(|scope| {
+            let status =
+                config.get_profile_scope_status(&profile_id, &scope, Some(&ignored_fields));
+            ScopeStatus {
+                settings_path: scope
+                    .settings_path(config.get_claude_dir())
+                    .to_string_lossy()
+                    .to_string(),
+                status: status_icon(&status).to_string(),
+                scope,
+            }
+        })
+        .collect();
+
+    Ok(statuses)
+}
+
+#[tauri::command]
+async fn get_effective_settings(
+    working_dir: String,
+    cli_settings: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<EffectiveSettings, String> {
+    log::info!("get_effective_settings called for: {}", working_dir);
+
+    let claude_dir = {
+        let app = match app_state.try_lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                log::error!("Failed to lock app state: {}", e);
+                return Err("Failed to access application state".to_string());
+            }
+        };
+
+        let config_service = app.get_config_service();
+        let config = match config_service.try_lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                log::error!("Failed to lock config service: {}", e);
+                return Err("Failed to access configuration service".to_string());
+            }
+        };
+        config.get_claude_dir().to_path_buf()
+    };
+
+    match SettingsResolver::new(claude_dir)
+        .resolve(std::path::Path::new(&working_dir), cli_settings.as_deref())
+    {
+        Ok(effective) => {
+            log::debug!("Resolved {} effective keys", effective.keys.len());
+            Ok(effective)
+        }
+        Err(e) => {
+            log::error!("Failed to resolve effective settings: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Icon shown for a profile status in the settings window
+fn status_icon(status: &ProfileStatus) -> &'static str {
+    match status {
+        ProfileStatus::FullMatch => "✅",
+        ProfileStatus::PartialMatch => "🔄",
+        ProfileStatus::Error(_) => "❌",
+        ProfileStatus::NonCompliant(_) => "🚫",
+        ProfileStatus::Unsigned => "🔓",
+        ProfileStatus::SignatureInvalid(_) => "⛔",
+        ProfileStatus::NoMatch => "",
+    }
+}
+
+#[tauri::command]
+async fn fix_permissions(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+    settings_state: tauri::State<'_, std::sync::Mutex<settings_service::SettingsService>>,
+) -> Result<PermissionFixResult, String> {
+    log::info!("fix_permissions called");
+
+    let projects = match settings_state.lock() {
+        Ok(settings) => settings.get_projects().to_vec(),
+        Err(e) => {
+            log::error!("Failed to lock settings service: {}", e);
+            return Err("Failed to access settings".to_string());
+        }
+    };
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    match config.fix_permissions(&projects) {
+        Ok(result) => {
+            log::info!(
+                "Restricted permissions of {} files",
+                result.fixed_files.len()
+            );
+            Ok(result)
+        }
+        Err(e) => {
+            log::error!("Failed to fix permissions: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_profile_permissions(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<ProfilePermissions, String> {
+    log::debug!("get_profile_permissions called for profile: {}", profile_id);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    match config.get_profile_permissions(&profile_id) {
+        Ok(permissions) => {
+            log::debug!(
+                "Profile {} has {} permission rules and {} issues",
+                profile_id,
+                permissions.rules.len(),
+                permissions.issues.len()
+            );
+            Ok(permissions)
+        }
+        Err(e) => {
+            log::error!("Failed to get profile permissions: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn add_permission_rule(
+    profile_ids: Vec<String>,
+    list: PermissionList,
+    rule: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("add_permission_rule called for profiles: {:?}", profile_ids);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(&profile_ids, |settings| {
+        permissions_editor::add_rule(settings, list, &rule)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(skipped) => {
+            log::info!("Successfully added permission rule: {}", rule);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing permissions: {}", e);
+            }
+
+            Ok(skipped)
+        }
+        Err(e) => {
+            log::error!("Failed to add permission rule: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn remove_permission_rule(
+    profile_ids: Vec<String>,
+    list: PermissionList,
+    rule: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("remove_permission_rule called for profiles: {:?}", profile_ids);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(&profile_ids, |settings| {
+        permissions_editor::remove_rule(settings, list, &rule)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(skipped) => {
+            log::info!("Successfully removed permission rule: {}", rule);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing permissions: {}", e);
+            }
+
+            Ok(skipped)
+        }
+        Err(e) => {
+            log::error!("Failed to remove permission rule: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn move_permission_rule(
+    profile_ids: Vec<String>,
+    from: PermissionList,
+    to: PermissionList,
+    rule: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("move_permission_rule called for profiles: {:?}", profile_ids);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(&profile_ids, |settings| {
+        permissions_editor::move_rule(settings, from, to, &rule)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(skipped) => {
+            log::info!("Successfully moved permission rule: {}", rule);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing permissions: {}", e);
+            }
+
+            Ok(skipped)
+        }
+        Err(e) => {
+            log::error!("Failed to move permission rule: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_profile_hooks(
+    profile_id: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<HookEntry>, String> {
+    log::debug!("get_profile_hooks called for profile: {}", profile_id);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    match config.get_profile_hooks(&profile_id) {
+        Ok(hooks) => {
+            log::debug!("Profile {} has {} hooks", profile_id, hooks.len());
+            Ok(hooks)
+        }
+        Err(e) => {
+            log::error!("Failed to get profile hooks: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn add_hook(
+    profile_id: String,
+    hook: HookEntry,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("add_hook called for profile: {} ({})", profile_id, hook.event);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(std::slice::from_ref(&profile_id), |settings| {
+        hooks_manager::add_hook(settings, &hook)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(_) => {
+            log::info!("Successfully added {} hook to profile: {}", hook.event, profile_id);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing hooks: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to add hook: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn update_hook(
+    profile_id: String,
+    old_hook: HookEntry,
+    new_hook: HookEntry,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("update_hook called for profile: {} ({})", profile_id, old_hook.event);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(std::slice::from_ref(&profile_id), |settings| {
+        hooks_manager::update_hook(settings, &old_hook, &new_hook)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(_) => {
+            log::info!("Successfully updated hook in profile: {}", profile_id);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing hooks: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to update hook: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn remove_hook(
+    profile_id: String,
+    hook: HookEntry,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("remove_hook called for profile: {} ({})", profile_id, hook.event);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.edit_profiles_json(std::slice::from_ref(&profile_id), |settings| {
+        hooks_manager::remove_hook(settings, &hook)
+    });
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(_) => {
+            log::info!("Successfully removed {} hook from profile: {}", hook.event, profile_id);
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing hooks: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to remove hook: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn copy_hook_to_profiles(
+    hook: HookEntry,
+    profile_ids: Vec<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<String>, String> {
+    log::info!("copy_hook_to_profiles called for profiles: {:?}", profile_ids);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.copy_hook_to_profiles(&hook, &profile_ids);
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(skipped) => {
+            log::info!("Successfully copied {} hook to {} profiles", hook.event, profile_ids.len());
+
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after editing hooks: {}", e);
+            }
+
+            Ok(skipped)
+        }
+        Err(e) => {
+            log::error!("Failed to copy hook to profiles: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn list_mcp_sets(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<McpSetInfo>, String> {
+    log::debug!("list_mcp_sets called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.list_sets() {
+        Ok(sets) => {
+            log::debug!("Found {} MCP sets", sets.len());
+            Ok(sets)
+        }
+        Err(e) => {
+            log::error!("Failed to list MCP sets: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn load_mcp_set_content(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<String, String> {
+    log::debug!("load_mcp_set_content called for set: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.read_set_content(&name) {
+        Ok(content) => {
+            log::debug!("Loaded MCP set: {}", name);
+            Ok(content)
+        }
+        Err(e) => {
+            log::error!("Failed to load MCP set: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn save_mcp_set(
+    name: String,
+    content: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("save_mcp_set called for set: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.save_set(&name, &content) {
+        Ok(()) => {
+            log::info!("Successfully saved MCP set: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to save MCP set: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn delete_mcp_set(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("delete_mcp_set called for set: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.delete_set(&name) {
+        Ok(()) => {
+            log::info!("Successfully deleted MCP set: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to delete MCP set: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn enable_mcp_set(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("enable_mcp_set called for set: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.enable_set(&name) {
+        Ok(()) => {
+            log::info!("Successfully enabled MCP set: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to enable MCP set: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn disable_mcp_set(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("disable_mcp_set called for set: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let mcp_service = app.get_mcp_service();
+    let mcp = match mcp_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock MCP service: {}", e);
+            return Err("Failed to access MCP service".to_string());
+        }
+    };
+
+    match mcp.disable_set(&name) {
+        Ok(()) => {
+            log::info!("Successfully disabled MCP set: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to disable MCP set: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_vault_status(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<VaultStatus, String> {
+    log::debug!("get_vault_status called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    Ok(vault.get_status())
+}
+
+#[tauri::command]
+async fn initialize_vault(
+    passphrase: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!(
+        "initialize_vault called (key source: {})",
+        if passphrase.is_some() { "passphrase" } else { "keyring" }
+    );
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    match vault.initialize(passphrase.as_deref()) {
         Ok(()) => {
-            log::info!("Successfully applied profile content from: {}", profile_id);
-            
-            // Update tray menu to reflect the new active profile status
+            log::info!("Successfully initialized secret vault");
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to initialize secret vault: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn unlock_vault(
+    passphrase: Option<String>,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("unlock_vault called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    let result = vault.unlock(passphrase.as_deref());
+    drop(vault); // 释放锁，避免死锁
+
+    match result {
+        Ok(()) => {
+            log::info!("Successfully unlocked secret vault");
+
+            // Profiles referencing secrets can now be compared against settings.json
             if let Err(e) = app.update_tray_menu() {
-                log::warn!("Failed to update tray menu after applying profile: {}", e);
-                // Don't fail the operation since profile application was successful
+                log::warn!("Failed to update tray menu after unlocking vault: {}", e);
             }
-            
+
             Ok(())
         }
         Err(e) => {
-            log::error!("Failed to apply profile content from '{}': {}", profile_id, e);
-            Err(format!("Failed to apply profile: {}", e))
+            log::error!("Failed to unlock secret vault: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn lock_vault(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("lock_vault called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    vault.lock();
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_secrets(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<Vec<SecretInfo>, String> {
+    log::info!("list_secrets called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    Ok(vault.list_secrets())
+}
+
+#[tauri::command]
+async fn add_secret(
+    name: String,
+    value: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("add_secret called for secret: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    match vault.add_secret(&name, &value) {
+        Ok(()) => {
+            log::info!("Successfully added secret: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to add secret: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn remove_secret(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("remove_secret called for secret: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    match vault.remove_secret(&name) {
+        Ok(()) => {
+            log::info!("Successfully removed secret: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to remove secret: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn rotate_secret(
+    name: String,
+    value: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("rotate_secret called for secret: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let secret_vault = app.get_secret_vault();
+    let mut vault = match secret_vault.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock secret vault: {}", e);
+            return Err("Failed to access secret vault".to_string());
+        }
+    };
+
+    match vault.rotate_secret(&name, &value) {
+        Ok(()) => {
+            log::info!("Successfully rotated secret: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to rotate secret: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_user_variables(
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<BTreeMap<String, String>, String> {
+    log::debug!("get_user_variables called");
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    match config.get_user_variables() {
+        Ok(variables) => {
+            log::debug!("Loaded {} user variables", variables.len());
+            Ok(variables)
+        }
+        Err(e) => {
+            log::error!("Failed to load user variables: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn set_user_variable(
+    name: String,
+    value: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("set_user_variable called for variable: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.set_user_variable(&name, &value);
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(()) => {
+            log::info!("Successfully saved variable: {}", name);
+
+            // Profiles using this variable may change their match status
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after variable change: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to save variable: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+async fn remove_user_variable(
+    name: String,
+    app_state: tauri::State<'_, Arc<Mutex<App>>>,
+) -> Result<(), String> {
+    log::info!("remove_user_variable called for variable: {}", name);
+
+    let app = match app_state.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock app state: {}", e);
+            return Err("Failed to access application state".to_string());
+        }
+    };
+
+    let config_service = app.get_config_service();
+    let mut config = match config_service.try_lock() {
+        Ok(guard) => guard,
+        Err(e) => {
+            log::error!("Failed to lock config service: {}", e);
+            return Err("Failed to access configuration service".to_string());
+        }
+    };
+
+    let result = config.remove_user_variable(&name);
+    drop(config); // 释放锁，避免死锁
+
+    match result {
+        Ok(()) => {
+            log::info!("Successfully removed variable: {}", name);
+
+            // Profiles using this variable may change their match status
+            if let Err(e) = app.update_tray_menu() {
+                log::warn!("Failed to update tray menu after variable change: {}", e);
+            }
+
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to remove variable: {}", e);
+            Err(e.to_string())
         }
     }
 }
@@ -435,6 +2675,7 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         .setup(setup_app)
         .invoke_handler(tauri::generate_handler![
             settings_service::get_settings,
@@ -447,6 +2688,19 @@ pub fn run() {
             settings_service::update_ignored_fields,
             settings_service::get_default_ignored_fields,
             settings_service::reset_ignored_fields_to_default,
+            settings_service::get_projects,
+            settings_service::add_project,
+            settings_service::remove_project,
+            settings_service::get_directory_rules,
+            settings_service::update_directory_rules,
+            settings_service::dry_run_directory_rules,
+            settings_service::get_profile_schedules,
+            settings_service::update_profile_schedules,
+            settings_service::set_schedule_suspended,
+            settings_service::get_subscriptions,
+            settings_service::get_trusted_keys,
+            settings_service::export_settings,
+            settings_service::import_settings,
             i18n_service::get_current_locale,
             i18n_service::set_locale,
             i18n_service::get_text,
@@ -454,12 +2708,71 @@ pub fn run() {
             get_profiles_info,
             get_profiles_list,
             get_profile_status,
+            get_policy_violations,
+            update_profile_metadata,
+            reorder_profiles,
             load_profile_content,
             save_profile,
             apply_profile,
+            apply_profile_to_scope,
+            switch_profile_temporarily,
+            get_switch_lease,
+            end_switch_lease,
+            get_switch_hooks,
+            save_switch_hooks,
+            get_last_hook_runs,
+            get_stale_sessions,
+            list_profile_versions,
+            get_profile_version,
+            diff_profile_versions,
+            restore_profile_version,
+            set_git_store_enabled,
+            get_git_log,
+            get_profile_at_revision,
+            update_subscriptions,
+            update_trusted_keys,
+            export_profiles,
+            preview_profile_import,
+            import_profiles,
+            get_subscription_status,
+            update_subscription,
+            get_profile_override,
+            save_profile_override,
+            apply_directory_rules,
+            get_profile_scope_statuses,
+            get_effective_settings,
             create_new_profile,
+            list_profile_templates,
+            create_profile_from_template,
             delete_profile,
             validate_json_content,
+            fix_permissions,
+            get_profile_permissions,
+            add_permission_rule,
+            remove_permission_rule,
+            move_permission_rule,
+            get_profile_hooks,
+            add_hook,
+            update_hook,
+            remove_hook,
+            copy_hook_to_profiles,
+            list_mcp_sets,
+            load_mcp_set_content,
+            save_mcp_set,
+            delete_mcp_set,
+            enable_mcp_set,
+            disable_mcp_set,
+            get_vault_status,
+            initialize_vault,
+            unlock_vault,
+            lock_vault,
+            list_secrets,
+            add_secret,
+            remove_secret,
+            rotate_secret,
+            get_user_variables,
+            set_user_variable,
+            remove_user_variable,
             close_settings_window,
             exit_application,
         ])
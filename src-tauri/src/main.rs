@@ -2,5 +2,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+  // `cccs <subcommand>` runs a command line tool instead of the tray app
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  if let Some(code) = app_lib::run_cli(&args) {
+    std::process::exit(code);
+  }
+
   app_lib::run();
 }
@@ -0,0 +1,582 @@
+// Named MCP server sets merged into ~/.claude.json
+use crate::file_permissions;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MCP_SET_SUFFIX: &str = ".mcp.json";
+
+/// Servers of one scope (user level or a single project)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpServers {
+    #[serde(rename = "mcpServers", default)]
+    pub mcp_servers: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Content of a `<name>.mcp.json` set file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpServerSet {
+    #[serde(rename = "mcpServers", default)]
+    pub mcp_servers: serde_json::Map<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, McpServers>, // 项目路径 -> 该项目的服务器
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpSetState {
+    Enabled,  // 所有服务器都已写入且一致
+    Partial,  // 只有部分服务器已写入
+    Disabled, // 没有服务器写入
+    Conflict, // 同名服务器存在但配置不同
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpSetInfo {
+    pub name: String,
+    pub file_path: String,
+    pub server_names: Vec<String>,
+    pub project_paths: Vec<String>,
+    pub state: McpSetState,
+}
+
+/// Each server of a set together with the ~/.claude.json scope it belongs to
+fn scoped_servers(set: &McpServerSet) -> Vec<(Option<&str>, &String, &serde_json::Value)> {
+    let mut servers: Vec<_> = set
+        .mcp_servers
+        .iter()
+        .map(|(name, config)| (None, name, config))
+        .collect();
+    for (project, scope) in &set.projects {
+        servers.extend(
+            scope
+                .mcp_servers
+                .iter()
+                .map(|(name, config)| (Some(project.as_str()), name, config)),
+        );
+    }
+    servers
+}
+
+fn scope_label(project: Option<&str>, name: &str) -> String {
+    match project {
+        Some(project) => format!("{} ({})", name, project),
+        None => name.to_string(),
+    }
+}
+
+fn existing_server<'a>(
+    claude_json: &'a serde_json::Value,
+    project: Option<&str>,
+    name: &str,
+) -> Option<&'a serde_json::Value> {
+    let scope = match project {
+        Some(project) => claude_json.get("projects")?.get(project)?,
+        None => claude_json,
+    };
+    scope.get("mcpServers")?.get(name)
+}
+
+/// Get (creating if needed) the mcpServers object of a scope
+fn servers_mut<'a>(
+    claude_json: &'a mut serde_json::Value,
+    project: Option<&str>,
+) -> AppResult<&'a mut serde_json::Map<String, serde_json::Value>> {
+    let invalid = |what: &str| AppError::ConfigError(format!("Invalid ~/.claude.json: {}", what));
+
+    let mut scope = claude_json
+        .as_object_mut()
+        .ok_or_else(|| invalid("not a JSON object"))?;
+    if let Some(project) = project {
+        scope = scope
+            .entry("projects")
+            .or_insert_with(|| serde_json::json!({}))
+            .as_object_mut()
+            .ok_or_else(|| invalid("'projects' is not an object"))?
+            .entry(project)
+            .or_insert_with(|| serde_json::json!({}))
+            .as_object_mut()
+            .ok_or_else(|| invalid("project entry is not an object"))?;
+    }
+    scope
+        .entry("mcpServers")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| invalid("'mcpServers' is not an object"))
+}
+
+/// Determine whether a set is currently merged into ~/.claude.json
+pub fn set_state(claude_json: &serde_json::Value, set: &McpServerSet) -> McpSetState {
+    let servers = scoped_servers(set);
+    let mut matching = 0;
+    let mut conflicting = 0;
+    for (project, name, config) in &servers {
+        match existing_server(claude_json, *project, name) {
+            Some(existing) if existing == *config => matching += 1,
+            Some(_) => conflicting += 1,
+            None => {}
+        }
+    }
+
+    if conflicting > 0 {
+        McpSetState::Conflict
+    } else if matching == servers.len() {
+        McpSetState::Enabled
+    } else if matching == 0 {
+        McpSetState::Disabled
+    } else {
+        McpSetState::Partial
+    }
+}
+
+/// Merge a set's servers into ~/.claude.json content. Servers with the same name
+/// but a different configuration are never overwritten.
+pub fn merge_set(claude_json: &mut serde_json::Value, set: &McpServerSet) -> AppResult<()> {
+    let conflicts: Vec<String> = scoped_servers(set)
+        .into_iter()
+        .filter(|(project, name, config)| {
+            existing_server(claude_json, *project, name).is_some_and(|existing| existing != *config)
+        })
+        .map(|(project, name, _)| scope_label(project, name))
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(AppError::ConfigError(format!(
+            "MCP servers already configured differently: {}",
+            conflicts.join(", ")
+        )));
+    }
+
+    for (project, name, config) in scoped_servers(set) {
+        servers_mut(claude_json, project)?.insert(name.clone(), config.clone());
+    }
+    Ok(())
+}
+
+/// Remove a set's servers from ~/.claude.json content. Servers edited since
+/// they were enabled are kept and returned.
+pub fn unmerge_set(claude_json: &mut serde_json::Value, set: &McpServerSet) -> AppResult<Vec<String>> {
+    let mut kept = Vec::new();
+    for (project, name, config) in scoped_servers(set) {
+        match existing_server(claude_json, project, name) {
+            Some(existing) if existing == config => {
+                servers_mut(claude_json, project)?.remove(name);
+            }
+            Some(_) => kept.push(scope_label(project, name)),
+            None => {}
+        }
+    }
+    Ok(kept)
+}
+
+pub struct McpService {
+    claude_dir: PathBuf,
+    claude_json_path: PathBuf,
+}
+
+impl McpService {
+    pub fn new(claude_dir: PathBuf, claude_json_path: PathBuf) -> Self {
+        Self {
+            claude_dir,
+            claude_json_path,
+        }
+    }
+
+    fn set_path(&self, name: &str) -> PathBuf {
+        self.claude_dir.join(format!("{}{}", name, MCP_SET_SUFFIX))
+    }
+
+    fn validate_set_name(name: &str) -> AppResult<()> {
+        if name.is_empty() || name.len() > 100 {
+            return Err(AppError::ConfigError(
+                "MCP set name must be 1-100 characters".to_string(),
+            ));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '))
+            || name.starts_with(' ')
+            || name.ends_with(' ')
+        {
+            return Err(AppError::ConfigError(format!(
+                "MCP set name '{}' may only contain letters, digits, spaces, '-' and '_'",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Find all `<name>.mcp.json` set files
+    fn set_names(&self) -> AppResult<Vec<String>> {
+        let entries = fs::read_dir(&self.claude_dir).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to read Claude directory: {}", e))
+        })?;
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|n| n.strip_suffix(MCP_SET_SUFFIX))
+                    .filter(|n| !n.is_empty() && !n.starts_with('.'))
+                    .map(str::to_string)
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn read_set_content(&self, name: &str) -> AppResult<String> {
+        Self::validate_set_name(name)?;
+        fs::read_to_string(self.set_path(name)).map_err(|e| {
+            AppError::ConfigError(format!("Failed to read MCP set '{}': {}", name, e))
+        })
+    }
+
+    pub fn load_set(&self, name: &str) -> AppResult<McpServerSet> {
+        let content = self.read_set_content(name)?;
+        serde_json::from_str(&content).map_err(|e| {
+            AppError::ConfigError(format!("MCP set '{}' is invalid: {}", name, e))
+        })
+    }
+
+    /// Create or replace a set file
+    pub fn save_set(&self, name: &str, content: &str) -> AppResult<()> {
+        Self::validate_set_name(name)?;
+        let set: McpServerSet = serde_json::from_str(content)
+            .map_err(|e| AppError::ConfigError(format!("Invalid MCP set content: {}", e)))?;
+        if scoped_servers(&set).is_empty() {
+            return Err(AppError::ConfigError(
+                "MCP set must define at least one server".to_string(),
+            ));
+        }
+
+        let normalized = serde_json::to_string_pretty(&set)?;
+        self.write_atomic(&self.set_path(name), &normalized)?;
+        log::info!("Saved MCP set: {}", name);
+        Ok(())
+    }
+
+    /// Delete a set file; an enabled set must be disabled first
+    pub fn delete_set(&self, name: &str) -> AppResult<()> {
+        let set = self.load_set(name)?;
+        if set_state(&self.read_claude_json()?.1, &set) != McpSetState::Disabled {
+            return Err(AppError::ConfigError(format!(
+                "MCP set '{}' is enabled; disable it before deleting",
+                name
+            )));
+        }
+
+        fs::remove_file(self.set_path(name)).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to delete MCP set '{}': {}", name, e))
+        })?;
+        log::info!("Deleted MCP set: {}", name);
+        Ok(())
+    }
+
+    pub fn list_sets(&self) -> AppResult<Vec<McpSetInfo>> {
+        let (_, claude_json) = self.read_claude_json()?;
+        let mut infos = Vec::new();
+
+        for name in self.set_names()? {
+            match self.load_set(&name) {
+                Ok(set) => infos.push(McpSetInfo {
+                    file_path: self.set_path(&name).to_string_lossy().to_string(),
+                    server_names: set.mcp_servers.keys().cloned().collect(),
+                    project_paths: set.projects.keys().cloned().collect(),
+                    state: set_state(&claude_json, &set),
+                    name,
+                }),
+                Err(e) => log::warn!("Skipping MCP set {}: {}", name, e),
+            }
+        }
+        Ok(infos)
+    }
+
+    /// Merge a set's servers into ~/.claude.json
+    pub fn enable_set(&self, name: &str) -> AppResult<()> {
+        let set = self.load_set(name)?;
+        let (original, mut claude_json) = self.read_claude_json()?;
+
+        merge_set(&mut claude_json, &set)?;
+        self.write_claude_json(&original, &claude_json)?;
+        log::info!("Enabled MCP set: {}", name);
+        Ok(())
+    }
+
+    /// Remove a set's servers from ~/.claude.json
+    pub fn disable_set(&self, name: &str) -> AppResult<()> {
+        let set = self.load_set(name)?;
+        let (original, mut claude_json) = self.read_claude_json()?;
+
+        let kept = unmerge_set(&mut claude_json, &set)?;
+        if !kept.is_empty() {
+            log::warn!(
+                "MCP servers modified since enabling set '{}' were kept: {}",
+                name,
+                kept.join(", ")
+            );
+        }
+        self.write_claude_json(&original, &claude_json)?;
+        log::info!("Disabled MCP set: {}", name);
+        Ok(())
+    }
+
+    /// Read ~/.claude.json, returning the raw content and parsed value
+    fn read_claude_json(&self) -> AppResult<(Option<String>, serde_json::Value)> {
+        if !self.claude_json_path.exists() {
+            return Ok((None, serde_json::json!({})));
+        }
+
+        let content = fs::read_to_string(&self.claude_json_path).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to read {:?}: {}", self.claude_json_path, e))
+        })?;
+        let value = serde_json::from_str(&content).map_err(|e| {
+            AppError::ConfigError(format!(
+                "{:?} contains invalid JSON: {}",
+                self.claude_json_path, e
+            ))
+        })?;
+        Ok((Some(content), value))
+    }
+
+    /// Write ~/.claude.json with backup, atomic rename and rollback
+    fn write_claude_json(
+        &self,
+        original: &Option<String>,
+        claude_json: &serde_json::Value,
+    ) -> AppResult<()> {
+        let content = serde_json::to_string_pretty(claude_json)?;
+
+        // Claude Code rewrites this file itself; refuse to clobber concurrent changes
+        let current = fs::read_to_string(&self.claude_json_path).ok();
+        if &current != original {
+            return Err(AppError::ConfigError(format!(
+                "{:?} changed while it was being updated; please retry",
+                self.claude_json_path
+            )));
+        }
+
+        let backup_path = match original {
+            Some(_) => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let backup_path = self
+                    .claude_json_path
+                    .with_extension(format!("json.backup.{}", timestamp));
+                fs::copy(&self.claude_json_path, &backup_path).map_err(|e| {
+                    AppError::FileSystemError(format!("Failed to create backup: {}", e))
+                })?;
+                Some(backup_path)
+            }
+            None => None,
+        };
+
+        match self.write_atomic(&self.claude_json_path, &content) {
+            Ok(()) => {
+                self.cleanup_old_backups();
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("Failed to write {:?}, rolling back: {}", self.claude_json_path, e);
+                if let Some(backup_path) = &backup_path {
+                    if let Err(restore_err) = self.write_atomic(
+                        &self.claude_json_path,
+                        &fs::read_to_string(backup_path).unwrap_or_default(),
+                    ) {
+                        log::error!("Failed to restore from backup: {}", restore_err);
+                        return Err(AppError::FileSystemError(format!(
+                            "Update failed and rollback failed. Backup kept at {:?}: {}",
+                            backup_path, e
+                        )));
+                    }
+                    let _ = fs::remove_file(backup_path);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Keep only the 5 most recent ~/.claude.json backups
+    fn cleanup_old_backups(&self) {
+        let (parent, prefix) = match (self.claude_json_path.parent(), self.claude_json_path.file_name()) {
+            (Some(parent), Some(name)) => (parent, format!("{}.backup.", name.to_string_lossy())),
+            _ => return,
+        };
+
+        if let Ok(entries) = fs::read_dir(parent) {
+            let mut backups: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+                .map(|entry| entry.path())
+                .collect();
+
+            // Backup names end with a timestamp; newest first
+            backups.sort_by_key(|path| {
+                std::cmp::Reverse(
+                    path.extension()
+                        .and_then(|e| e.to_str())
+                        .and_then(|e| e.parse::<u64>().ok())
+                        .unwrap_or(0),
+                )
+            });
+            for old_backup in backups.iter().skip(5) {
+                if let Err(e) = fs::remove_file(old_backup) {
+                    log::warn!("Failed to remove old backup {:?}: {}", old_backup, e);
+                }
+            }
+        }
+    }
+
+    /// Write through a private temp file in the same directory, then rename and verify
+    fn write_atomic(&self, path: &Path, content: &str) -> AppResult<()> {
+        let parent = path.parent().ok_or_else(|| {
+            AppError::FileSystemError("Cannot determine parent directory".to_string())
+        })?;
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("temp");
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let temp_path = parent.join(format!(
+            ".{}.{}.{}.tmp",
+            file_name,
+            timestamp,
+            std::process::id()
+        ));
+
+        file_permissions::write_private_file(&temp_path, content.as_bytes()).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to write temporary file: {}", e))
+        })?;
+
+        let verified = fs::read_to_string(&temp_path)
+            .map(|written| written == content)
+            .unwrap_or(false);
+        if !verified {
+            let _ = fs::remove_file(&temp_path);
+            return Err(AppError::FileSystemError(
+                "Temp file verification failed - data corruption detected".to_string(),
+            ));
+        }
+
+        if let Err(e) = file_permissions::preserve_mode(&temp_path, path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(AppError::PermissionError(format!(
+                "Failed to preserve file permissions: {}",
+                e
+            )));
+        }
+
+        fs::rename(&temp_path, path).map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            AppError::FileSystemError(format!("Failed to replace {:?}: {}", path, e))
+        })?;
+
+        let final_content = fs::read_to_string(path)
+            .map_err(|e| AppError::FileSystemError(format!("Failed to verify final file: {}", e)))?;
+        if final_content != content {
+            return Err(AppError::FileSystemError(
+                "Final file verification failed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_service() -> (McpService, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join(".claude");
+        fs::create_dir(&claude_dir).unwrap();
+        let service = McpService::new(claude_dir, temp_dir.path().join(".claude.json"));
+        (service, temp_dir)
+    }
+
+    const GITHUB_SET: &str = r#"{
+        "mcpServers": {"github": {"command": "npx", "args": ["-y", "server-github"]}},
+        "projects": {"/work/app": {"mcpServers": {"db": {"type": "sse", "url": "http://localhost:9000"}}}}
+    }"#;
+
+    #[test]
+    fn test_enable_and_disable_preserves_rest_of_file() {
+        let (service, temp_dir) = create_test_service();
+        let claude_json_path = temp_dir.path().join(".claude.json");
+        let original = r#"{
+  "numStartups": 12,
+  "mcpServers": {
+    "local": {
+      "command": "mine"
+    }
+  },
+  "projects": {
+    "/work/app": {
+      "allowedTools": []
+    }
+  }
+}"#;
+        fs::write(&claude_json_path, original).unwrap();
+        service.save_set("github", GITHUB_SET).unwrap();
+
+        assert_eq!(service.list_sets().unwrap()[0].state, McpSetState::Disabled);
+        service.enable_set("github").unwrap();
+
+        let merged: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&claude_json_path).unwrap()).unwrap();
+        assert_eq!(merged["numStartups"], 12);
+        assert_eq!(merged["mcpServers"]["local"]["command"], "mine");
+        assert_eq!(merged["mcpServers"]["github"]["command"], "npx");
+        assert_eq!(merged["projects"]["/work/app"]["mcpServers"]["db"]["type"], "sse");
+        assert_eq!(service.list_sets().unwrap()[0].state, McpSetState::Enabled);
+
+        // Set is enabled, so it cannot be deleted yet
+        assert!(service.delete_set("github").is_err());
+
+        service.disable_set("github").unwrap();
+        let restored = fs::read_to_string(&claude_json_path).unwrap();
+        assert!(restored.contains("\"local\""));
+        assert!(!restored.contains("github"));
+        assert!(restored.find("numStartups").unwrap() < restored.find("mcpServers").unwrap());
+        service.delete_set("github").unwrap();
+
+        // The previous content is kept as a backup
+        let backups = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(".claude.json.backup."))
+            .count();
+        assert!(backups >= 1);
+    }
+
+    #[test]
+    fn test_conflicting_server_is_not_overwritten() {
+        let (service, temp_dir) = create_test_service();
+        let claude_json_path = temp_dir.path().join(".claude.json");
+        let original = r#"{"mcpServers": {"github": {"command": "other"}}}"#;
+        fs::write(&claude_json_path, original).unwrap();
+        service.save_set("github", GITHUB_SET).unwrap();
+
+        assert_eq!(service.list_sets().unwrap()[0].state, McpSetState::Conflict);
+        assert!(service.enable_set("github").is_err());
+        assert_eq!(fs::read_to_string(&claude_json_path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_missing_claude_json_is_created() {
+        let (service, temp_dir) = create_test_service();
+        service.save_set("github", GITHUB_SET).unwrap();
+        service.enable_set("github").unwrap();
+        assert!(temp_dir.path().join(".claude.json").exists());
+
+        assert!(service.save_set("empty", r#"{"mcpServers": {}}"#).is_err());
+        assert!(service.save_set("../evil", GITHUB_SET).is_err());
+    }
+}
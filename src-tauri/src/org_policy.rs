@@ -0,0 +1,313 @@
+// Organization policy: declarative constraints every profile must satisfy before it is saved or applied
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that points CCCS at a different policy file
+pub const POLICY_PATH_ENV: &str = "CCCS_POLICY_FILE";
+
+/// Policy file installed by an administrator; a missing file means no policy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrgPolicy {
+    #[serde(default)]
+    pub name: Option<String>, // 显示在错误信息中，例如 "ACME security policy"
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Constraints on one settings key, addressed by a dotted path such as
+/// `permissions.defaultMode` or `env.ANTHROPIC_BASE_URL`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub key: String,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub forbidden_values: Vec<Value>, // 数组值逐项检查
+    #[serde(default)]
+    pub allowed_values: Vec<Value>,
+    #[serde(default)]
+    pub forbidden_hosts: Vec<String>, // "example.com" 或 "*.example.com"
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default)]
+    pub must_contain: Vec<Value>, // 值必须是包含这些项的数组
+    #[serde(default)]
+    pub message: Option<String>, // 替换自动生成的说明
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    pub key: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// `CCCS_POLICY_FILE` when set, otherwise the system-wide location
+pub fn default_policy_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(POLICY_PATH_ENV).filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/CCCS/policy.json")
+    } else if cfg!(windows) {
+        PathBuf::from(r"C:\ProgramData\CCCS\policy.json")
+    } else {
+        PathBuf::from("/etc/cccs/policy.json")
+    }
+}
+
+impl OrgPolicy {
+    /// `None` when there is no policy file. A file that cannot be read or parsed is
+    /// an error so that a broken policy never silently allows everything.
+    pub fn load(path: &Path) -> AppResult<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path).map_err(|e| {
+            AppError::PolicyError(format!("Failed to read policy file {:?}: {}", path, e))
+        })?;
+        let policy: Self = serde_json::from_str(&content).map_err(|e| {
+            AppError::PolicyError(format!("Invalid policy file {:?}: {}", path, e))
+        })?;
+        policy.validate()?;
+        Ok(Some(policy))
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        for rule in &self.rules {
+            if rule.key.is_empty() || rule.key.split('.').any(str::is_empty) {
+                return Err(AppError::PolicyError(format!(
+                    "Invalid policy key '{}'",
+                    rule.key
+                )));
+            }
+            for pattern in rule.forbidden_hosts.iter().chain(&rule.allowed_hosts) {
+                let domain = pattern.strip_prefix("*.").unwrap_or(pattern);
+                if domain.is_empty() || domain.contains(['*', '/', ':']) {
+                    return Err(AppError::PolicyError(format!(
+                        "Invalid host pattern '{}' for '{}'",
+                        pattern, rule.key
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// All violations of the policy by a settings document
+    pub fn evaluate(&self, settings: &Value) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            let problems = rule.check(lookup(settings, &rule.key));
+            if problems.is_empty() {
+                continue;
+            }
+            match &rule.message {
+                Some(message) => violations.push(PolicyViolation {
+                    key: rule.key.clone(),
+                    message: message.clone(),
+                }),
+                None => violations.extend(problems.into_iter().map(|message| PolicyViolation {
+                    key: rule.key.clone(),
+                    message,
+                })),
+            }
+        }
+        violations
+    }
+
+    /// Error describing why a profile cannot be used
+    pub fn violation_error(&self, profile: &str, violations: &[PolicyViolation]) -> AppError {
+        let details: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        AppError::PolicyError(format!(
+            "Profile '{}' violates {}: {}",
+            profile,
+            self.name.as_deref().unwrap_or("the organization policy"),
+            details.join("; ")
+        ))
+    }
+}
+
+impl PolicyRule {
+    fn check(&self, value: Option<&Value>) -> Vec<String> {
+        let Some(value) = value.filter(|v| !v.is_null()) else {
+            if self.required || !self.must_contain.is_empty() {
+                return vec!["must be set".to_string()];
+            }
+            return Vec::new();
+        };
+
+        let mut problems = Vec::new();
+        // 数组按元素检查，例如禁止 permissions.allow 中出现某条规则
+        let items: Vec<&Value> = match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+
+        for item in &items {
+            if self.forbidden_values.contains(item) {
+                problems.push(format!("{} is not allowed", item));
+            } else if !self.allowed_values.is_empty() && !self.allowed_values.contains(item) {
+                problems.push(format!("{} is not one of the allowed values", item));
+            }
+        }
+
+        if !self.forbidden_hosts.is_empty() || !self.allowed_hosts.is_empty() {
+            for item in &items {
+                let Some(url) = item.as_str() else {
+                    problems.push(format!("{} is not a URL", item));
+                    continue;
+                };
+                match url_host(url) {
+                    Some(host) if self.forbidden_hosts.iter().any(|p| host_matches(p, &host)) => {
+                        problems.push(format!("host '{}' is not allowed", host));
+                    }
+                    Some(host)
+                        if !self.allowed_hosts.is_empty()
+                            && !self.allowed_hosts.iter().any(|p| host_matches(p, &host)) =>
+                    {
+                        problems.push(format!("host '{}' is not an allowed host", host));
+                    }
+                    Some(_) => {}
+                    None => problems.push(format!("'{}' has no host", url)),
+                }
+            }
+        }
+
+        if !self.must_contain.is_empty() {
+            match value {
+                Value::Array(items) => {
+                    for required in &self.must_contain {
+                        if !items.contains(required) {
+                            problems.push(format!("must contain {}", required));
+                        }
+                    }
+                }
+                _ => problems.push("must be a list".to_string()),
+            }
+        }
+
+        problems
+    }
+}
+
+fn lookup<'a>(settings: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(settings, |value, part| value.get(part))
+}
+
+/// Host of a URL (or of a bare `host[:port]`), lowercased and without port
+fn url_host(url: &str) -> Option<String> {
+    let rest = url.trim().split_once("://").map_or(url.trim(), |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host_port.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host_port.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.trim_end_matches('.').to_ascii_lowercase())
+}
+
+/// `example.com` matches only that host, `*.example.com` matches its subdomains
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn policy() -> OrgPolicy {
+        serde_json::from_value(json!({
+            "name": "ACME policy",
+            "rules": [
+                {"key": "permissions.defaultMode", "forbidden_values": ["bypassPermissions"]},
+                {"key": "env.ANTHROPIC_BASE_URL", "forbidden_hosts": ["*.proxy.example"]},
+                {"key": "permissions.deny", "must_contain": ["Read(./.env)"],
+                 "message": "secrets in .env must be denied"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let policy = policy();
+        let compliant = json!({
+            "permissions": {"defaultMode": "acceptEdits", "deny": ["Read(./.env)", "WebFetch"]},
+            "env": {"ANTHROPIC_BASE_URL": "https://api.anthropic.com"}
+        });
+        assert!(policy.evaluate(&compliant).is_empty());
+
+        let violations = policy.evaluate(&json!({
+            "permissions": {"defaultMode": "bypassPermissions"},
+            "env": {"ANTHROPIC_BASE_URL": "https://user@EU.Proxy.example:8443/v1"}
+        }));
+        let keys: Vec<&str> = violations.iter().map(|v| v.key.as_str()).collect();
+        assert_eq!(keys, vec!["permissions.defaultMode", "env.ANTHROPIC_BASE_URL", "permissions.deny"]);
+        assert_eq!(violations[1].message, "host 'eu.proxy.example' is not allowed");
+        assert_eq!(violations[2].message, "secrets in .env must be denied");
+
+        let error = policy.violation_error("work", &violations[..1]).to_string();
+        assert!(error.contains("Profile 'work' violates ACME policy"));
+        assert!(error.contains("permissions.defaultMode: \"bypassPermissions\" is not allowed"));
+    }
+
+    #[test]
+    fn test_allowed_values_and_hosts() {
+        let rule = PolicyRule {
+            key: "env.ANTHROPIC_BASE_URL".to_string(),
+            allowed_hosts: vec!["api.anthropic.com".to_string()],
+            ..Default::default()
+        };
+        assert!(rule.check(None).is_empty());
+        assert!(rule.check(Some(&json!("https://api.anthropic.com/"))).is_empty());
+        assert_eq!(rule.check(Some(&json!("http://localhost:8080"))).len(), 1);
+
+        let rule = PolicyRule {
+            key: "permissions.allow".to_string(),
+            allowed_values: vec![json!("Read"), json!("Edit")],
+            required: true,
+            ..Default::default()
+        };
+        assert_eq!(rule.check(None), vec!["must be set"]);
+        assert!(rule.check(Some(&json!(["Read", "Edit"]))).is_empty());
+        assert_eq!(rule.check(Some(&json!(["Read", "Bash(*)"]))).len(), 1);
+
+        assert!(host_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+        assert_eq!(url_host("http://[::1]:8080/x").as_deref(), Some("::1"));
+    }
+
+    #[test]
+    fn test_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("policy.json");
+        assert!(OrgPolicy::load(&path).unwrap().is_none());
+
+        fs::write(&path, serde_json::to_string(&policy()).unwrap()).unwrap();
+        assert_eq!(OrgPolicy::load(&path).unwrap().unwrap().rules.len(), 3);
+
+        // 损坏的策略文件不能被当成"没有策略"
+        fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(OrgPolicy::load(&path), Err(AppError::PolicyError(_))));
+        fs::write(&path, r#"{"rules": [{"key": "env..X"}]}"#).unwrap();
+        assert!(OrgPolicy::load(&path).is_err());
+    }
+}
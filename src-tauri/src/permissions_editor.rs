@@ -0,0 +1,357 @@
+// Structured editing of permissions.allow / deny / ask rules
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionList {
+    Allow,
+    Deny,
+    Ask,
+}
+
+impl PermissionList {
+    pub const ALL: [PermissionList; 3] = [Self::Allow, Self::Deny, Self::Ask];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+            Self::Ask => "ask",
+        }
+    }
+}
+
+/// A parsed rule such as `Bash(npm run:*)`, `Read(~/.ssh/**)` or `WebSearch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRule {
+    pub tool: String,
+    pub specifier: Option<String>,
+}
+
+impl PermissionRule {
+    pub fn parse(rule: &str) -> AppResult<Self> {
+        let rule = rule.trim();
+        let invalid = |reason: &str| {
+            AppError::ConfigError(format!("Invalid permission rule '{}': {}", rule, reason))
+        };
+
+        let (tool, specifier) = match rule.find('(') {
+            Some(open) => {
+                if !rule.ends_with(')') {
+                    return Err(invalid("missing closing ')'"));
+                }
+                let specifier = rule[open + 1..rule.len() - 1].trim();
+                if specifier.is_empty() {
+                    return Err(invalid("empty specifier, omit the parentheses instead"));
+                }
+                (&rule[..open], Some(specifier.to_string()))
+            }
+            None => {
+                if rule.contains(')') {
+                    return Err(invalid("unexpected ')'"));
+                }
+                (rule, None)
+            }
+        };
+
+        if tool.is_empty() {
+            return Err(invalid("missing tool name"));
+        }
+        if !tool
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(invalid("tool name may only contain letters, digits, '_' and '-'"));
+        }
+
+        Ok(Self {
+            tool: tool.to_string(),
+            specifier,
+        })
+    }
+}
+
+impl fmt::Display for PermissionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.specifier {
+            Some(specifier) => write!(f, "{}({})", self.tool, specifier),
+            None => write!(f, "{}", self.tool),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRuleEntry {
+    pub list: PermissionList,
+    pub rule: String,
+    pub tool: Option<String>,      // 无法解析时为 None
+    pub specifier: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionIssue {
+    pub kind: String, // "duplicate" | "contradiction" | "invalid"
+    pub rule: String,
+    pub lists: Vec<PermissionList>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilePermissions {
+    pub rules: Vec<PermissionRuleEntry>,
+    pub issues: Vec<PermissionIssue>,
+}
+
+impl ProfilePermissions {
+    pub fn from_settings(settings: &serde_json::Value) -> Self {
+        Self {
+            rules: list_rules(settings),
+            issues: find_issues(settings),
+        }
+    }
+}
+
+fn rules_in(settings: &serde_json::Value, list: PermissionList) -> Vec<String> {
+    settings
+        .get("permissions")
+        .and_then(|p| p.get(list.key()))
+        .and_then(|l| l.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Normalized form used to compare rules (`Bash( ls )` equals `Bash(ls)`)
+fn normalize(rule: &str) -> String {
+    PermissionRule::parse(rule)
+        .map(|r| r.to_string())
+        .unwrap_or_else(|_| rule.trim().to_string())
+}
+
+fn rules_mut(
+    settings: &mut serde_json::Value,
+    list: PermissionList,
+) -> AppResult<&mut Vec<serde_json::Value>> {
+    let root = settings
+        .as_object_mut()
+        .ok_or_else(|| AppError::ConfigError("Configuration must be a JSON object".to_string()))?;
+    let permissions = root
+        .entry("permissions")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| AppError::ConfigError("'permissions' must be an object".to_string()))?;
+    permissions
+        .entry(list.key())
+        .or_insert_with(|| serde_json::json!([]))
+        .as_array_mut()
+        .ok_or_else(|| {
+            AppError::ConfigError(format!("'permissions.{}' must be an array", list.key()))
+        })
+}
+
+/// List all rules of all three lists
+pub fn list_rules(settings: &serde_json::Value) -> Vec<PermissionRuleEntry> {
+    PermissionList::ALL
+        .iter()
+        .flat_map(|&list| {
+            rules_in(settings, list).into_iter().map(move |rule| {
+                let parsed = PermissionRule::parse(&rule).ok();
+                PermissionRuleEntry {
+                    list,
+                    tool: parsed.as_ref().map(|r| r.tool.clone()),
+                    specifier: parsed.and_then(|r| r.specifier),
+                    rule,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Add a rule; refuses duplicates and rules already present in another list
+pub fn add_rule(settings: &mut serde_json::Value, list: PermissionList, rule: &str) -> AppResult<()> {
+    let rule = PermissionRule::parse(rule)?.to_string();
+
+    for other in PermissionList::ALL {
+        if rules_in(settings, other).iter().any(|r| normalize(r) == rule) {
+            return Err(AppError::ConfigError(if other == list {
+                format!("Rule '{}' already exists in permissions.{}", rule, list.key())
+            } else {
+                format!(
+                    "Rule '{}' contradicts permissions.{}; move it instead",
+                    rule,
+                    other.key()
+                )
+            }));
+        }
+    }
+
+    rules_mut(settings, list)?.push(serde_json::Value::String(rule));
+    Ok(())
+}
+
+/// Remove a rule from a list
+pub fn remove_rule(
+    settings: &mut serde_json::Value,
+    list: PermissionList,
+    rule: &str,
+) -> AppResult<()> {
+    let target = normalize(rule);
+    let rules = rules_mut(settings, list)?;
+    let before = rules.len();
+    rules.retain(|r| r.as_str().map(normalize).as_deref() != Some(target.as_str()));
+
+    if rules.len() == before {
+        return Err(AppError::ConfigError(format!(
+            "Rule '{}' not found in permissions.{}",
+            rule,
+            list.key()
+        )));
+    }
+    Ok(())
+}
+
+/// Move a rule from one list to another
+pub fn move_rule(
+    settings: &mut serde_json::Value,
+    from: PermissionList,
+    to: PermissionList,
+    rule: &str,
+) -> AppResult<()> {
+    if from == to {
+        return Ok(());
+    }
+    remove_rule(settings, from, rule)?;
+    add_rule(settings, to, rule)
+}
+
+/// Detect invalid syntax, duplicates within a list and rules present in several lists
+pub fn find_issues(settings: &serde_json::Value) -> Vec<PermissionIssue> {
+    let mut issues = Vec::new();
+    let mut seen: Vec<(String, Vec<PermissionList>)> = Vec::new();
+
+    for list in PermissionList::ALL {
+        let mut in_list: Vec<String> = Vec::new();
+        for rule in rules_in(settings, list) {
+            if let Err(e) = PermissionRule::parse(&rule) {
+                issues.push(PermissionIssue {
+                    kind: "invalid".to_string(),
+                    rule: rule.clone(),
+                    lists: vec![list],
+                    message: e.to_string(),
+                });
+            }
+
+            let normalized = normalize(&rule);
+            if in_list.contains(&normalized) {
+                issues.push(PermissionIssue {
+                    kind: "duplicate".to_string(),
+                    rule: normalized.clone(),
+                    lists: vec![list],
+                    message: format!(
+                        "Rule '{}' appears more than once in permissions.{}",
+                        normalized,
+                        list.key()
+                    ),
+                });
+                continue;
+            }
+            in_list.push(normalized.clone());
+
+            match seen.iter_mut().find(|(r, _)| *r == normalized) {
+                Some((_, lists)) => lists.push(list),
+                None => seen.push((normalized, vec![list])),
+            }
+        }
+    }
+
+    for (rule, lists) in seen.into_iter().filter(|(_, lists)| lists.len() > 1) {
+        let names: Vec<&str> = lists.iter().map(|l| l.key()).collect();
+        issues.push(PermissionIssue {
+            kind: "contradiction".to_string(),
+            message: format!("Rule '{}' is listed in {}", rule, names.join(" and ")),
+            rule,
+            lists,
+        });
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let rule = PermissionRule::parse("Bash(npm run:*)").unwrap();
+        assert_eq!(rule.tool, "Bash");
+        assert_eq!(rule.specifier.as_deref(), Some("npm run:*"));
+
+        let rule = PermissionRule::parse("Read(~/.ssh/**)").unwrap();
+        assert_eq!(rule.to_string(), "Read(~/.ssh/**)");
+
+        assert!(PermissionRule::parse("WebSearch").unwrap().specifier.is_none());
+        assert!(PermissionRule::parse("mcp__github__list_issues").is_ok());
+
+        assert!(PermissionRule::parse("Bash(npm").is_err());
+        assert!(PermissionRule::parse("Bash()").is_err());
+        assert!(PermissionRule::parse("(ls)").is_err());
+        assert!(PermissionRule::parse("Bad Tool").is_err());
+    }
+
+    #[test]
+    fn test_add_remove_move() {
+        let mut settings = serde_json::json!({"theme": "dark"});
+
+        add_rule(&mut settings, PermissionList::Allow, "Bash(npm run:*)").unwrap();
+        add_rule(&mut settings, PermissionList::Deny, "Read(~/.ssh/**)").unwrap();
+        assert!(add_rule(&mut settings, PermissionList::Allow, "Bash( npm run:* )").is_err());
+        assert!(add_rule(&mut settings, PermissionList::Ask, "Read(~/.ssh/**)").is_err());
+
+        move_rule(
+            &mut settings,
+            PermissionList::Allow,
+            PermissionList::Ask,
+            "Bash(npm run:*)",
+        )
+        .unwrap();
+        assert_eq!(settings["permissions"]["allow"], serde_json::json!([]));
+        assert_eq!(
+            settings["permissions"]["ask"],
+            serde_json::json!(["Bash(npm run:*)"])
+        );
+
+        remove_rule(&mut settings, PermissionList::Deny, "Read(~/.ssh/**)").unwrap();
+        assert!(remove_rule(&mut settings, PermissionList::Deny, "Read(~/.ssh/**)").is_err());
+        assert_eq!(list_rules(&settings).len(), 1);
+        assert_eq!(settings["theme"], "dark");
+    }
+
+    #[test]
+    fn test_find_issues() {
+        let settings = serde_json::json!({
+            "permissions": {
+                "allow": ["Bash(ls)", "Bash(ls)", "Edit"],
+                "deny": ["Edit", "Bash(oops"],
+                "ask": []
+            }
+        });
+
+        let issues = find_issues(&settings);
+        let kinds: Vec<&str> = issues.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds.iter().filter(|k| **k == "duplicate").count(), 1);
+        assert_eq!(kinds.iter().filter(|k| **k == "invalid").count(), 1);
+        let contradiction = issues.iter().find(|i| i.kind == "contradiction").unwrap();
+        assert_eq!(contradiction.rule, "Edit");
+        assert_eq!(
+            contradiction.lists,
+            vec![PermissionList::Allow, PermissionList::Deny]
+        );
+    }
+}
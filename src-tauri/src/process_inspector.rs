@@ -0,0 +1,468 @@
+// Running Claude Code sessions, found through procfs on Linux, ps/lsof on macOS and CIM on Windows
+use crate::settings_scope::SettingsScope;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// A running `claude` process owned by the current user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaudeSession {
+    pub pid: u32,
+    pub cwd: Option<PathBuf>,     // 部分平台无法读取工作目录
+    pub config_dir: PathBuf,      // CLAUDE_CONFIG_DIR，未设置时为 ~/.claude
+    pub started_at: Option<u64>,  // 启动时间（unix 秒）
+}
+
+/// Find every Claude Code CLI session of the current user. Inspection errors are logged
+/// and yield an empty list; this is only used for warnings.
+pub fn find_claude_sessions() -> Vec<ClaudeSession> {
+    let own_pid = std::process::id();
+    let mut sessions = platform::list_sessions();
+    sessions.retain(|session| session.pid != own_pid);
+    sessions
+}
+
+/// Sessions that started before the settings file of `scope` last changed, so they
+/// still run with the previous configuration until restarted
+pub fn stale_sessions(
+    sessions: &[ClaudeSession],
+    scope: &SettingsScope,
+    claude_dir: &Path,
+) -> Vec<ClaudeSession> {
+    let Some(changed_at) = modified_secs(&scope.settings_path(claude_dir)) else {
+        return Vec::new();
+    };
+
+    sessions
+        .iter()
+        .filter(|session| session.started_at.is_some_and(|started| started < changed_at))
+        .filter(|session| match scope.project() {
+            None => same_dir(&session.config_dir, claude_dir),
+            // 项目配置按工作目录生效，与 CLAUDE_CONFIG_DIR 无关
+            Some(project) => session
+                .cwd
+                .as_ref()
+                .is_some_and(|cwd| cwd.starts_with(project)),
+        })
+        .cloned()
+        .collect()
+}
+
+/// Whether a command line starts Claude Code: the `claude` binary itself, or node/bun
+/// running the npm package. The desktop app is also called Claude and is skipped.
+pub fn is_claude_command(args: &[String]) -> bool {
+    let Some(program) = args.first() else {
+        return false;
+    };
+    if program.contains(".app/Contents/MacOS/") || program.contains("AnthropicClaude") {
+        return false;
+    }
+
+    match program_name(program).as_str() {
+        "claude" => true,
+        "node" | "bun" => args[1..]
+            .iter()
+            .find(|arg| !arg.starts_with('-'))
+            .is_some_and(|script| {
+                program_name(script) == "claude" || script.contains("@anthropic-ai/claude-code")
+            }),
+        _ => false,
+    }
+}
+
+/// File name without directory and `.exe`; Windows names are case-insensitive
+fn program_name(path: &str) -> String {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if cfg!(windows) {
+        let name = name.to_lowercase();
+        name.strip_suffix(".exe").unwrap_or(&name).to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// CLAUDE_CONFIG_DIR of the session, resolved against its working directory
+fn config_dir_for(env_value: Option<&str>, cwd: Option<&Path>) -> Option<PathBuf> {
+    match env_value.filter(|value| !value.is_empty()) {
+        Some(value) => {
+            let path = PathBuf::from(value);
+            match cwd {
+                Some(cwd) if path.is_relative() => Some(cwd.join(path)),
+                _ => Some(path),
+            }
+        }
+        None => dirs::home_dir().map(|home| home.join(".claude")),
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{config_dir_for, is_claude_command, ClaudeSession};
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub fn list_sessions() -> Vec<ClaudeSession> {
+        let entries = match fs::read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to read /proc: {}", e);
+                return Vec::new();
+            }
+        };
+        // SAFETY: getuid 没有失败情况
+        let uid = unsafe { libc::getuid() };
+        let boot_time = boot_time();
+
+        let mut sessions = Vec::new();
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+                continue;
+            };
+            let dir = entry.path();
+            // 只关心当前用户的会话，其他用户的进程也读不到 cwd 和环境变量
+            if !fs::metadata(&dir).is_ok_and(|meta| meta.uid() == uid) {
+                continue;
+            }
+            let Ok(cmdline) = fs::read(dir.join("cmdline")) else {
+                continue;
+            };
+            let args: Vec<String> = cmdline
+                .split(|&b| b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            if !is_claude_command(&args) {
+                continue;
+            }
+
+            let cwd = fs::read_link(dir.join("cwd")).ok();
+            let env_config_dir = environ_value(&dir, "CLAUDE_CONFIG_DIR");
+            let Some(config_dir) = config_dir_for(env_config_dir.as_deref(), cwd.as_deref())
+            else {
+                continue;
+            };
+            sessions.push(ClaudeSession {
+                pid,
+                cwd,
+                config_dir,
+                started_at: boot_time.and_then(|boot| start_time(&dir, boot)),
+            });
+        }
+        sessions
+    }
+
+    fn environ_value(dir: &Path, name: &str) -> Option<String> {
+        let environ = fs::read(dir.join("environ")).ok()?;
+        let prefix = format!("{}=", name);
+        environ
+            .split(|&b| b == 0)
+            .map(String::from_utf8_lossy)
+            .find_map(|var| var.strip_prefix(&prefix).map(str::to_string))
+    }
+
+    /// System boot time from the btime line of /proc/stat
+    fn boot_time() -> Option<u64> {
+        let stat = fs::read_to_string("/proc/stat").ok()?;
+        stat.lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|value| value.trim().parse().ok())
+    }
+
+    /// Field 22 of /proc/<pid>/stat is the start time in clock ticks after boot
+    fn start_time(dir: &Path, boot_time: u64) -> Option<u64> {
+        let stat = fs::read_to_string(dir.join("stat")).ok()?;
+        // 进程名可能含空格和括号，从最后一个 ')' 之后开始数（第 3 个字段起）
+        let fields = &stat[stat.rfind(')')? + 1..];
+        let ticks: u64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+        // SAFETY: sysconf 只读取系统配置
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if ticks_per_sec <= 0 {
+            return None;
+        }
+        Some(boot_time + ticks / ticks_per_sec as u64)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{config_dir_for, is_claude_command, parse_elapsed, ClaudeSession};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    pub fn list_sessions() -> Vec<ClaudeSession> {
+        let output = match Command::new("ps")
+            .args(["-xww", "-o", "pid=,etime=,command="])
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                log::warn!("ps failed: {}", String::from_utf8_lossy(&output.stderr));
+                return Vec::new();
+            }
+            Err(e) => {
+                log::warn!("Failed to run ps: {}", e);
+                return Vec::new();
+            }
+        };
+        let now = crate::switch_lease::now_secs();
+
+        // ps 只给出空格拼接的命令行，按空白切分足以识别 claude
+        let mut found = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(pid), Some(elapsed)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let Ok(pid) = pid.parse::<u32>() else {
+                continue;
+            };
+            let args: Vec<String> = parts.map(str::to_string).collect();
+            if is_claude_command(&args) {
+                found.push((pid, parse_elapsed(elapsed).map(|secs| now.saturating_sub(secs))));
+            }
+        }
+        if found.is_empty() {
+            return Vec::new();
+        }
+
+        let cwds = working_dirs(&found.iter().map(|(pid, _)| *pid).collect::<Vec<_>>());
+        found
+            .into_iter()
+            .filter_map(|(pid, started_at)| {
+                let cwd = cwds.get(&pid).cloned();
+                let env_config_dir = environ_value(pid, "CLAUDE_CONFIG_DIR");
+                Some(ClaudeSession {
+                    pid,
+                    config_dir: config_dir_for(env_config_dir.as_deref(), cwd.as_deref())?,
+                    cwd,
+                    started_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Working directories via `lsof -Fn`, which prints p<pid> and n<path> lines
+    fn working_dirs(pids: &[u32]) -> HashMap<u32, PathBuf> {
+        let pid_list: Vec<String> = pids.iter().map(u32::to_string).collect();
+        let Ok(output) = Command::new("lsof")
+            .args(["-a", "-d", "cwd", "-Fn", "-p", &pid_list.join(",")])
+            .output()
+        else {
+            return HashMap::new();
+        };
+
+        let mut dirs = HashMap::new();
+        let mut current = None;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(pid) = line.strip_prefix('p') {
+                current = pid.parse::<u32>().ok();
+            } else if let (Some(path), Some(pid)) = (line.strip_prefix('n'), current) {
+                dirs.insert(pid, PathBuf::from(path));
+            }
+        }
+        dirs
+    }
+
+    /// `ps -E` appends the environment to the command of the user's own processes
+    fn environ_value(pid: u32, name: &str) -> Option<String> {
+        let output = Command::new("ps")
+            .args(["-Eww", "-o", "command=", "-p", &pid.to_string()])
+            .output()
+            .ok()?;
+        let prefix = format!("{}=", name);
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .find_map(|var| var.strip_prefix(&prefix).map(str::to_string))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{config_dir_for, is_claude_command, ClaudeSession};
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // 每行输出：进程号、启动时间（unix 秒）、命令行，以制表符分隔
+    const SCRIPT: &str = "Get-CimInstance Win32_Process -Filter \"Name='claude.exe' OR Name='node.exe' OR Name='bun.exe'\" | \
+        ForEach-Object { \"$($_.ProcessId)`t$(([DateTimeOffset]$_.CreationDate).ToUnixTimeSeconds())`t$($_.CommandLine)\" }";
+
+    /// Windows does not expose another process's working directory or environment
+    /// without reading its memory, so sessions are assumed to use the default config dir
+    pub fn list_sessions() -> Vec<ClaudeSession> {
+        let output = match Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) => {
+                log::warn!("Failed to query processes: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let Some(config_dir) = config_dir_for(None, None) else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.trim_end().splitn(3, '\t');
+                let pid = parts.next()?.parse::<u32>().ok()?;
+                let started_at = parts.next()?.parse::<u64>().ok();
+                let args: Vec<String> = parts
+                    .next()?
+                    .split_whitespace()
+                    .map(|arg| arg.trim_matches('"').to_string())
+                    .collect();
+                is_claude_command(&args).then(|| ClaudeSession {
+                    pid,
+                    cwd: None,
+                    config_dir: config_dir.clone(),
+                    started_at,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::ClaudeSession;
+
+    pub fn list_sessions() -> Vec<ClaudeSession> {
+        Vec::new()
+    }
+}
+
+/// Elapsed time as printed by `ps -o etime`: [[dd-]hh:]mm:ss
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_elapsed(value: &str) -> Option<u64> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, value),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(days * 24 * 60 * 60 + secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_is_claude_command() {
+        assert!(is_claude_command(&args("claude")));
+        assert!(is_claude_command(&args("/home/u/.local/bin/claude --resume")));
+        assert!(is_claude_command(&args(
+            "node --no-warnings /usr/lib/node_modules/@anthropic-ai/claude-code/cli.js"
+        )));
+        assert!(is_claude_command(&args("/usr/bin/node /usr/local/bin/claude")));
+
+        assert!(!is_claude_command(&[]));
+        assert!(!is_claude_command(&args("node server.js claude")));
+        assert!(!is_claude_command(&args("vim claude")));
+        assert!(!is_claude_command(&args("/Applications/Claude.app/Contents/MacOS/Claude")));
+    }
+
+    #[test]
+    fn test_parse_elapsed() {
+        assert_eq!(parse_elapsed("05:07"), Some(5 * 60 + 7));
+        assert_eq!(parse_elapsed("02:05:07"), Some(2 * 3600 + 5 * 60 + 7));
+        assert_eq!(parse_elapsed("3-02:05:07"), Some(3 * 86400 + 2 * 3600 + 5 * 60 + 7));
+        assert_eq!(parse_elapsed("bogus"), None);
+    }
+
+    #[test]
+    fn test_stale_sessions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join(".claude");
+        let project = temp_dir.path().join("app");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::create_dir_all(project.join(".claude")).unwrap();
+        std::fs::write(claude_dir.join("settings.json"), "{}").unwrap();
+        let changed_at = modified_secs(&claude_dir.join("settings.json")).unwrap();
+
+        let session = |pid, started_at, cwd: &Path| ClaudeSession {
+            pid,
+            cwd: Some(cwd.to_path_buf()),
+            config_dir: claude_dir.clone(),
+            started_at: Some(started_at),
+        };
+        let sessions = vec![
+            session(1, changed_at - 60, &project),
+            session(2, changed_at + 60, &project),
+            session(3, changed_at - 60, temp_dir.path()),
+            ClaudeSession {
+                config_dir: temp_dir.path().join("other"),
+                ..session(4, changed_at - 60, &project)
+            },
+        ];
+
+        let pids = |stale: Vec<ClaudeSession>| stale.iter().map(|s| s.pid).collect::<Vec<_>>();
+        assert_eq!(
+            pids(stale_sessions(&sessions, &SettingsScope::User, &claude_dir)),
+            vec![1, 3]
+        );
+
+        // 项目配置文件不存在时没有旧会话
+        let scope = SettingsScope::ProjectLocal(project.clone());
+        assert!(stale_sessions(&sessions, &scope, &claude_dir).is_empty());
+        std::fs::write(scope.settings_path(&claude_dir), "{}").unwrap();
+        assert_eq!(pids(stale_sessions(&sessions, &scope, &claude_dir)), vec![1, 4]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_claude_sessions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut child = std::process::Command::new("bash")
+            .args(["-c", "exec -a claude sleep 30"])
+            .current_dir(temp_dir.path())
+            .env("CLAUDE_CONFIG_DIR", "config")
+            .spawn()
+            .unwrap();
+
+        // 等待 exec 完成
+        let mut found = None;
+        for _ in 0..50 {
+            found = find_claude_sessions()
+                .into_iter()
+                .find(|session| session.pid == child.id());
+            if found.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let session = found.expect("claude session not found");
+        let cwd = temp_dir.path().canonicalize().unwrap();
+        assert_eq!(session.cwd.as_deref(), Some(cwd.as_path()));
+        assert_eq!(session.config_dir, cwd.join("config"));
+        assert!(session.started_at.is_some());
+    }
+}
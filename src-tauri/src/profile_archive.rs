@@ -0,0 +1,440 @@
+// Portable profile archives: a gzipped tar holding a manifest and one or more profiles
+use crate::file_permissions;
+use crate::interpolation::Placeholder;
+use crate::profile_bundle::{self, ProfileBundle, BUNDLE_SETTINGS_FILE};
+use crate::{AppError, AppResult};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const PROFILES_DIR: &str = "profiles";
+// 解压后的总大小上限，防止异常归档占满内存
+const MAX_ARCHIVE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// What happens to plaintext credentials when a profile is exported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretHandling {
+    #[default]
+    Placeholder, // 替换为 ${secret:<配置>.<键名>}，导入方在密钥库中补上
+    Strip,       // 直接删除该键
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub created_at: u64,
+    pub app_version: String,
+    pub secrets: SecretHandling,
+    pub profiles: Vec<ArchivedProfileInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedProfileInfo {
+    pub name: String,
+    pub bundle: bool, // 目录形式的配置（<name>.profile/）
+    pub files: BTreeMap<String, String>, // 配置内相对路径 -> sha256
+    #[serde(default)]
+    pub secrets: Vec<String>, // 导出时被删除或替换的键（点分路径）
+}
+
+/// A profile read from an archive, with every checksum verified
+#[derive(Debug, Clone)]
+pub struct ArchivedProfile {
+    pub info: ArchivedProfileInfo,
+    pub settings: String,
+    pub bundle: Option<ProfileBundle>,
+}
+
+/// Profile handed to `write_archive`
+pub struct ExportProfile {
+    pub name: String,
+    pub settings: Value,
+    pub bundle: Option<ProfileBundle>,
+    pub secrets: Vec<String>, // sanitize_secrets 的结果，记入清单
+}
+
+/// How one archived profile is brought in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "name", rename_all = "snake_case")]
+pub enum ImportAction {
+    Import,         // 名称不冲突时直接导入
+    Rename(String), // 以新名称导入
+    Overwrite,      // 替换同名的现有配置
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportChoice {
+    pub profile: String,
+    #[serde(flatten)]
+    pub action: ImportAction,
+}
+
+/// What importing an archive would do, shown before anything is written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub manifest: ArchiveManifest,
+    pub profiles: Vec<ImportCandidate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportCandidate {
+    pub name: String,
+    pub bundle: bool,
+    pub conflict: bool,       // 已存在同名配置
+    pub errors: Vec<String>,  // 校验失败的原因，存在时无法导入
+    pub secrets: Vec<String>, // 导出时删除或替换的键，导入后需补充
+}
+
+/// Remove or replace plaintext credentials; returns the dotted keys that were touched.
+/// Values that already are ${...} references are kept.
+pub fn sanitize_secrets(value: &mut Value, profile: &str, handling: SecretHandling) -> Vec<String> {
+    let mut touched = Vec::new();
+    sanitize_at(value, "", profile, handling, &mut touched);
+    touched
+}
+
+fn sanitize_at(value: &mut Value, prefix: &str, profile: &str, handling: SecretHandling, touched: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<String> = map.keys().cloned().collect();
+            for key in keys {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                let is_plaintext = matches!(
+                    map.get(&key),
+                    Some(Value::String(s)) if file_permissions::is_secret_key(&key)
+                        && !s.trim().is_empty()
+                        && !s.contains("${")
+                );
+                if !is_plaintext {
+                    if let Some(item) = map.get_mut(&key) {
+                        sanitize_at(item, &path, profile, handling, touched);
+                    }
+                    continue;
+                }
+                match handling {
+                    SecretHandling::Strip => {
+                        map.remove(&key);
+                    }
+                    SecretHandling::Placeholder => {
+                        let name = format!("{}.{}", profile, key)
+                            .chars()
+                            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+                            .collect();
+                        map.insert(key, Value::String(Placeholder::Secret(name).to_template()));
+                    }
+                }
+                touched.push(path);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                sanitize_at(item, &format!("{}[{}]", prefix, i), profile, handling, touched);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Write the archive atomically; secrets must already be sanitized
+pub fn write_archive(path: &Path, profiles: Vec<ExportProfile>, handling: SecretHandling) -> AppResult<ArchiveManifest> {
+    if profiles.is_empty() {
+        return Err(AppError::ConfigError("Select at least one profile to export".to_string()));
+    }
+
+    let created_at = crate::switch_lease::now_secs();
+    let mut manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        created_at,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        secrets: handling,
+        profiles: Vec::new(),
+    };
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+
+    for profile in profiles {
+        if manifest.profiles.iter().any(|p| p.name == profile.name) {
+            return Err(AppError::ConfigError(format!(
+                "Profile '{}' is listed twice",
+                profile.name
+            )));
+        }
+        let mut files = vec![(
+            BUNDLE_SETTINGS_FILE.to_string(),
+            serde_json::to_string_pretty(&profile.settings)?.into_bytes(),
+        )];
+        if let Some(bundle) = &profile.bundle {
+            files.extend(bundle.files.iter().map(|(rel, content)| (rel.clone(), content.clone())));
+        }
+
+        let mut info = ArchivedProfileInfo {
+            name: profile.name.clone(),
+            bundle: profile.bundle.is_some(),
+            files: BTreeMap::new(),
+            secrets: profile.secrets,
+        };
+        for (rel, content) in files {
+            info.files.insert(rel.clone(), sha256_hex(&content));
+            entries.push((format!("{}/{}/{}", PROFILES_DIR, profile.name, rel), content));
+        }
+        manifest.profiles.push(info);
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let result = (|| -> AppResult<()> {
+        let file = fs::File::create(&temp_path)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+        for (name, content) in std::iter::once((MANIFEST_FILE.to_string(), manifest_bytes)).chain(entries) {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o600);
+            header.set_mtime(created_at);
+            header.set_cksum();
+            builder.append_data(&mut header, &name, content.as_slice())?;
+        }
+        builder.into_inner()?.finish()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    fs::rename(&temp_path, path)?;
+
+    log::info!("Exported {} profiles to {:?}", manifest.profiles.len(), path);
+    Ok(manifest)
+}
+
+/// Read an archive and check it against its manifest: every file must be listed with
+/// a matching checksum, and nothing may point outside the profile it belongs to
+pub fn read_archive(path: &Path) -> AppResult<(ArchiveManifest, Vec<ArchivedProfile>)> {
+    let file = fs::File::open(path).map_err(|e| {
+        AppError::FileSystemError(format!("Failed to open archive {:?}: {}", path, e))
+    })?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut contents: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    let mut total = 0u64;
+
+    for entry in archive.entries().map_err(invalid_archive)? {
+        let mut entry = entry.map_err(invalid_archive)?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            continue;
+        }
+        let entry_path = entry.path().map_err(invalid_archive)?.into_owned();
+        if !entry_type.is_file() {
+            return Err(invalid(format!("unsupported entry {}", entry_path.display())));
+        }
+        let mut parts = Vec::new();
+        for component in entry_path.components() {
+            match component {
+                Component::Normal(part) => parts.push(
+                    part.to_str()
+                        .ok_or_else(|| invalid(format!("non UTF-8 path {}", entry_path.display())))?,
+                ),
+                Component::CurDir => {}
+                _ => return Err(invalid(format!("unsafe path {}", entry_path.display()))),
+            }
+        }
+
+        let mut content = Vec::new();
+        (&mut entry)
+            .take(MAX_ARCHIVE_BYTES - total + 1)
+            .read_to_end(&mut content)
+            .map_err(invalid_archive)?;
+        total += content.len() as u64;
+        if total > MAX_ARCHIVE_BYTES {
+            return Err(invalid("archive is too large".to_string()));
+        }
+        if contents.insert(parts.join("/"), content).is_some() {
+            return Err(invalid(format!("duplicate entry {}", entry_path.display())));
+        }
+    }
+
+    let manifest: ArchiveManifest = contents
+        .remove(MANIFEST_FILE)
+        .ok_or_else(|| invalid("manifest.json is missing".to_string()))
+        .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| invalid(format!("bad manifest: {}", e))))?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(invalid(format!(
+            "format version {} is newer than this CCCS supports",
+            manifest.format_version
+        )));
+    }
+
+    let mut profiles = Vec::new();
+    for info in &manifest.profiles {
+        if info.name.is_empty() || info.name.contains(['/', '\\']) || info.name.starts_with('.') {
+            return Err(invalid(format!("bad profile name '{}'", info.name)));
+        }
+        let mut settings = None;
+        let mut bundle = ProfileBundle::default();
+        for (rel, checksum) in &info.files {
+            let is_settings = rel == BUNDLE_SETTINGS_FILE;
+            let allowed = is_settings || (info.bundle && profile_bundle::is_managed_path(rel));
+            if !allowed {
+                return Err(invalid(format!("'{}' is not allowed in profile '{}'", rel, info.name)));
+            }
+            let key = format!("{}/{}/{}", PROFILES_DIR, info.name, rel);
+            let content = contents
+                .remove(&key)
+                .ok_or_else(|| invalid(format!("{} is missing", key)))?;
+            if sha256_hex(&content) != *checksum {
+                return Err(invalid(format!("checksum mismatch for {}", key)));
+            }
+            if is_settings {
+                settings = Some(
+                    String::from_utf8(content).map_err(|_| invalid(format!("{} is not UTF-8", key)))?,
+                );
+            } else {
+                bundle.files.insert(rel.clone(), content);
+            }
+        }
+        profiles.push(ArchivedProfile {
+            info: info.clone(),
+            settings: settings
+                .ok_or_else(|| invalid(format!("profile '{}' has no settings.json", info.name)))?,
+            bundle: info.bundle.then_some(bundle),
+        });
+    }
+
+    if let Some(extra) = contents.keys().next() {
+        return Err(invalid(format!("{} is not listed in the manifest", extra)));
+    }
+    Ok((manifest, profiles))
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn invalid(reason: String) -> AppError {
+    AppError::ConfigError(format!("Invalid profile archive: {}", reason))
+}
+
+fn invalid_archive(e: std::io::Error) -> AppError {
+    invalid(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sanitize_secrets() {
+        let original = json!({
+            "env": {
+                "ANTHROPIC_AUTH_TOKEN": "sk-123",
+                "OTHER_API_KEY": "${secret:mine}",
+                "ANTHROPIC_BASE_URL": "https://api.example"
+            }
+        });
+
+        let mut value = original.clone();
+        let touched = sanitize_secrets(&mut value, "work", SecretHandling::Placeholder);
+        assert_eq!(touched, vec!["env.ANTHROPIC_AUTH_TOKEN"]);
+        assert_eq!(value["env"]["ANTHROPIC_AUTH_TOKEN"], "${secret:work.ANTHROPIC_AUTH_TOKEN}");
+        assert_eq!(value["env"]["OTHER_API_KEY"], "${secret:mine}");
+
+        let mut value = original;
+        sanitize_secrets(&mut value, "work", SecretHandling::Strip);
+        assert!(value["env"].get("ANTHROPIC_AUTH_TOKEN").is_none());
+        assert_eq!(value["env"]["ANTHROPIC_BASE_URL"], "https://api.example");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("profiles.tar.gz");
+        let mut bundle = ProfileBundle::default();
+        bundle.files.insert("agents/review.md".to_string(), b"review".to_vec());
+
+        write_archive(
+            &path,
+            vec![
+                ExportProfile {
+                    name: "work".to_string(),
+                    settings: json!({"model": "opus"}),
+                    bundle: None,
+                    secrets: vec!["env.ANTHROPIC_AUTH_TOKEN".to_string()],
+                },
+                ExportProfile {
+                    name: "team".to_string(),
+                    settings: json!({}),
+                    bundle: Some(bundle),
+                    secrets: Vec::new(),
+                },
+            ],
+            SecretHandling::Strip,
+        )
+        .unwrap();
+
+        let (manifest, profiles) = read_archive(&path).unwrap();
+        assert_eq!(manifest.format_version, ARCHIVE_FORMAT_VERSION);
+        assert_eq!(manifest.secrets, SecretHandling::Strip);
+        assert_eq!(profiles.len(), 2);
+        assert!(profiles[0].bundle.is_none());
+        assert_eq!(profiles[0].info.secrets, vec!["env.ANTHROPIC_AUTH_TOKEN"]);
+        assert_eq!(serde_json::from_str::<Value>(&profiles[0].settings).unwrap(), json!({"model": "opus"}));
+        assert_eq!(profiles[1].bundle.as_ref().unwrap().files["agents/review.md"], b"review");
+    }
+
+    fn write_raw(path: &Path, entries: &[(&str, &[u8])]) {
+        let file = fs::File::create(path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o600);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_tampered_archives() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad.tar.gz");
+        let manifest = |checksum: &str| {
+            format!(
+                r#"{{"format_version": 1, "created_at": 0, "app_version": "x", "secrets": "strip",
+                    "profiles": [{{"name": "work", "bundle": false, "files": {{"settings.json": "{}"}}}}]}}"#,
+                checksum
+            )
+        };
+
+        let good = sha256_hex(b"{}");
+        write_raw(&path, &[("manifest.json", manifest(&good).as_bytes()), ("profiles/work/settings.json", b"{}")]);
+        assert!(read_archive(&path).is_ok());
+
+        write_raw(&path, &[("manifest.json", manifest(&good).as_bytes()), ("profiles/work/settings.json", b"{ }")]);
+        assert!(read_archive(&path).unwrap_err().to_string().contains("checksum mismatch"));
+
+        write_raw(
+            &path,
+            &[
+                ("manifest.json", manifest(&good).as_bytes()),
+                ("profiles/work/settings.json", b"{}"),
+                ("profiles/work/hooks.sh", b"rm -rf ~"),
+            ],
+        );
+        assert!(read_archive(&path).unwrap_err().to_string().contains("not listed"));
+    }
+}
@@ -0,0 +1,613 @@
+// Directory bundle profiles: settings.json plus CLAUDE.md, agents/*.md and commands/*.md
+use crate::switch_journal::SwitchJournal;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BUNDLE_SUFFIX: &str = ".profile";
+pub const BUNDLE_SETTINGS_FILE: &str = "settings.json";
+const MANIFEST_FILE: &str = ".cccs-bundle.json";
+pub const STAGING_DIR: &str = ".cccs-bundle-txn";
+const BACKUP_DIR: &str = ".cccs-bundle-backup";
+pub const ARTIFACT_DIRS: [&str; 2] = ["agents", "commands"];
+// 不再被引用的备份目录最多保留的数量
+const KEPT_BACKUP_DIRS: usize = 5;
+
+/// A bundle loaded from `<claude_dir>/<name>.profile/`
+#[derive(Debug, Clone, Default)]
+pub struct ProfileBundle {
+    pub files: BTreeMap<String, Vec<u8>>, // 相对路径（如 agents/reviewer.md）-> 内容
+}
+
+impl ProfileBundle {
+    /// Load every artifact of a bundle directory except its settings.json
+    pub fn load(bundle_dir: &Path) -> AppResult<Self> {
+        let mut files = BTreeMap::new();
+
+        let claude_md = bundle_dir.join("CLAUDE.md");
+        if claude_md.is_file() {
+            files.insert("CLAUDE.md".to_string(), read_file(&claude_md)?);
+        }
+
+        for dir_name in ARTIFACT_DIRS {
+            let dir = bundle_dir.join(dir_name);
+            if !dir.is_dir() {
+                continue;
+            }
+            let entries = fs::read_dir(&dir).map_err(|e| {
+                AppError::FileSystemError(format!(
+                    "Failed to read bundle directory {:?}: {}",
+                    dir, e
+                ))
+            })?;
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if path.is_file() && file_name.ends_with(".md") {
+                    files.insert(format!("{}/{}", dir_name, file_name), read_file(&path)?);
+                }
+            }
+        }
+
+        Ok(Self { files })
+    }
+}
+
+/// Record of the bundle artifacts cccs installed into the Claude directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub files: BTreeMap<String, String>, // 相对路径 -> 安装时的内容校验值
+    /// User files a bundle replaced, as `<timestamp>/<relative path>` inside the
+    /// backup directory; they are put back when no bundle ships the file any more
+    #[serde(default)]
+    pub originals: BTreeMap<String, String>,
+}
+
+impl BundleManifest {
+    pub fn load(claude_dir: &Path) -> AppResult<Self> {
+        let path = claude_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to read bundle manifest: {}", e))
+        })?;
+        let manifest: Self = serde_json::from_str(&content)
+            .map_err(|e| AppError::ConfigError(format!("Invalid bundle manifest: {}", e)))?;
+
+        // Never touch files outside the managed locations, even if the manifest says so
+        Ok(Self {
+            profile: manifest.profile,
+            files: manifest
+                .files
+                .into_iter()
+                .filter(|(rel_path, _)| is_managed_path(rel_path))
+                .collect(),
+            originals: manifest
+                .originals
+                .into_iter()
+                .filter(|(rel_path, backup)| {
+                    is_managed_path(rel_path)
+                        && backup.split_once('/').is_some_and(|(stamp, backup_path)| {
+                            !stamp.is_empty()
+                                && stamp.chars().all(|c| c.is_ascii_digit())
+                                && backup_path == rel_path
+                        })
+                })
+                .collect(),
+        })
+    }
+
+    fn save(&self, claude_dir: &Path) -> AppResult<()> {
+        let path = claude_dir.join(MANIFEST_FILE);
+        if self.files.is_empty() && self.originals.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| {
+                    AppError::FileSystemError(format!("Failed to remove bundle manifest: {}", e))
+                })?;
+            }
+            return Ok(());
+        }
+        write_atomic(&path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// Whether `rel_path` is one of the locations a bundle may manage
+pub fn is_managed_path(rel_path: &str) -> bool {
+    if rel_path == "CLAUDE.md" {
+        return true;
+    }
+    match rel_path.split_once('/') {
+        Some((dir, file_name)) => {
+            ARTIFACT_DIRS.contains(&dir)
+                && file_name.ends_with(".md")
+                && !file_name.contains(['/', '\\'])
+                && !file_name.starts_with('.')
+        }
+        None => false,
+    }
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:08x}", crc32fast::hash(content))
+}
+
+/// Whether the installed artifacts are exactly those of `bundle` (`None` = no artifacts).
+/// Files cccs installed earlier that are still on disk count as a mismatch.
+pub fn is_installed(claude_dir: &Path, bundle: Option<&ProfileBundle>) -> bool {
+    let empty = BTreeMap::new();
+    let expected = bundle.map(|b| &b.files).unwrap_or(&empty);
+
+    let all_present = expected.iter().all(|(rel_path, content)| {
+        fs::read(claude_dir.join(rel_path)).ok().as_ref() == Some(content)
+    });
+    if !all_present {
+        return false;
+    }
+
+    match BundleManifest::load(claude_dir) {
+        Ok(manifest) => manifest
+            .files
+            .keys()
+            .filter(|rel_path| !expected.contains_key(*rel_path))
+            .all(|rel_path| !claude_dir.join(rel_path).exists()),
+        Err(e) => {
+            log::warn!("Failed to load bundle manifest: {}", e);
+            expected.is_empty()
+        }
+    }
+}
+
+struct AppliedChange {
+    rel_path: String,
+    backup: Option<PathBuf>, // None 表示文件原本不存在
+    preserve: bool,          // 被覆盖的是用户自己的文件，提交后保留备份
+    original: bool,          // 安装配置包之前就存在的文件，离开配置包时恢复
+}
+
+/// Installs one bundle's artifacts and removes the previous bundle's, reversibly.
+/// Call `commit` once settings.json has been switched, or `rollback` if it failed.
+/// Every touched file is also recorded in the switch journal so a crash can be undone at startup.
+pub struct BundleTransaction {
+    claude_dir: PathBuf,
+    staging_dir: PathBuf,
+    manifest: BundleManifest,
+    applied: Vec<AppliedChange>,
+}
+
+impl BundleTransaction {
+    pub fn begin(
+        claude_dir: &Path,
+        profile_name: &str,
+        bundle: Option<&ProfileBundle>,
+        journal: &mut SwitchJournal,
+    ) -> AppResult<Self> {
+        let previous = BundleManifest::load(claude_dir)?;
+        journal.protect(&claude_dir.join(MANIFEST_FILE))?;
+        let staging_dir = claude_dir.join(STAGING_DIR);
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir).map_err(|e| {
+                AppError::FileSystemError(format!(
+                    "Failed to clean bundle staging directory: {}",
+                    e
+                ))
+            })?;
+        }
+
+        let mut transaction = Self {
+            claude_dir: claude_dir.to_path_buf(),
+            staging_dir,
+            manifest: BundleManifest {
+                profile: bundle.map(|_| profile_name.to_string()),
+                files: BTreeMap::new(),
+                originals: BTreeMap::new(),
+            },
+            applied: Vec::new(),
+        };
+
+        let empty = BTreeMap::new();
+        let target = bundle.map(|b| &b.files).unwrap_or(&empty);
+        if let Err(e) = transaction.apply(&previous, target, journal) {
+            transaction.rollback();
+            return Err(e);
+        }
+        Ok(transaction)
+    }
+
+    fn apply(
+        &mut self,
+        previous: &BundleManifest,
+        target: &BTreeMap<String, Vec<u8>>,
+        journal: &mut SwitchJournal,
+    ) -> AppResult<()> {
+        // Remove artifacts of the previous bundle that the new one does not ship,
+        // putting back the user's own file where a bundle replaced one
+        for (rel_path, installed_hash) in &previous.files {
+            if target.contains_key(rel_path) {
+                continue;
+            }
+            let path = self.claude_dir.join(rel_path);
+            let current = fs::read(&path).ok();
+            if current
+                .as_ref()
+                .is_some_and(|current| content_hash(current) != *installed_hash)
+            {
+                log::warn!(
+                    "Keeping {} because it was modified after being installed",
+                    rel_path
+                );
+                if let Some(original) = previous.originals.get(rel_path) {
+                    log::info!(
+                        "The file it replaced is still in {}",
+                        self.claude_dir.join(BACKUP_DIR).join(original).display()
+                    );
+                }
+                continue;
+            }
+
+            let original = previous.originals.get(rel_path).and_then(|original| {
+                fs::read(self.claude_dir.join(BACKUP_DIR).join(original))
+                    .map_err(|e| log::warn!("Failed to read original {}: {}", rel_path, e))
+                    .ok()
+            });
+            if current.is_none() && original.is_none() {
+                continue;
+            }
+
+            journal.protect(&path)?;
+            let backup = match current {
+                Some(_) => Some(self.backup(rel_path)?),
+                None => None,
+            };
+            self.applied.push(AppliedChange {
+                rel_path: rel_path.clone(),
+                backup,
+                preserve: false,
+                original: false,
+            });
+            match original {
+                Some(content) => {
+                    write_atomic(&path, &content)?;
+                    log::info!("Restored original {}", rel_path);
+                }
+                None => fs::remove_file(&path).map_err(|e| {
+                    AppError::FileSystemError(format!("Failed to remove {}: {}", rel_path, e))
+                })?,
+            }
+        }
+
+        // Install the new bundle's artifacts
+        for (rel_path, content) in target {
+            let path = self.claude_dir.join(rel_path);
+            self.manifest
+                .files
+                .insert(rel_path.clone(), content_hash(content));
+            if let Some(original) = previous.originals.get(rel_path) {
+                self.manifest
+                    .originals
+                    .insert(rel_path.clone(), original.clone());
+            }
+
+            let current = fs::read(&path).ok();
+            if current.as_ref() == Some(content) {
+                continue;
+            }
+
+            journal.protect(&path)?;
+            let change = match &current {
+                Some(existing) => AppliedChange {
+                    rel_path: rel_path.clone(),
+                    backup: Some(self.backup(rel_path)?),
+                    preserve: previous.files.get(rel_path) != Some(&content_hash(existing)),
+                    original: !previous.files.contains_key(rel_path),
+                },
+                None => AppliedChange {
+                    rel_path: rel_path.clone(),
+                    backup: None,
+                    preserve: false,
+                    original: false,
+                },
+            };
+            self.applied.push(change);
+            write_atomic(&path, content)?;
+        }
+
+        Ok(())
+    }
+
+    fn backup(&self, rel_path: &str) -> AppResult<PathBuf> {
+        let backup = self.staging_dir.join(rel_path);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                AppError::FileSystemError(format!(
+                    "Failed to create bundle staging directory: {}",
+                    e
+                ))
+            })?;
+        }
+        fs::copy(self.claude_dir.join(rel_path), &backup).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to back up {}: {}", rel_path, e))
+        })?;
+        Ok(backup)
+    }
+
+    /// Record the installed artifacts; user files that were overwritten stay in the backup directory
+    /// until they are restored. If the manifest cannot be written every change is rolled back.
+    pub fn commit(mut self) -> AppResult<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for change in self.applied.iter().filter(|c| c.preserve && c.original) {
+            self.manifest
+                .originals
+                .entry(change.rel_path.clone())
+                .or_insert_with(|| format!("{}/{}", timestamp, change.rel_path));
+        }
+
+        if let Err(e) = self.manifest.save(&self.claude_dir) {
+            self.rollback();
+            return Err(e);
+        }
+
+        let backup_root = self.claude_dir.join(BACKUP_DIR).join(timestamp.to_string());
+        for change in self.applied.iter().filter(|c| c.preserve) {
+            let (Some(backup), destination) = (&change.backup, backup_root.join(&change.rel_path))
+            else {
+                continue;
+            };
+            let moved = destination
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::rename(backup, &destination));
+            match moved {
+                Ok(()) => log::info!(
+                    "Previous {} was saved to {}",
+                    change.rel_path,
+                    destination.display()
+                ),
+                Err(e) => log::warn!("Failed to keep backup of {}: {}", change.rel_path, e),
+            }
+        }
+
+        let _ = fs::remove_dir_all(&self.staging_dir);
+        prune_backups(&self.claude_dir.join(BACKUP_DIR), &self.manifest);
+        Ok(())
+    }
+
+    /// Undo every change made so far, newest first
+    pub fn rollback(self) {
+        for change in self.applied.iter().rev() {
+            let path = self.claude_dir.join(&change.rel_path);
+            let result = match &change.backup {
+                Some(backup) => fs::read(backup)
+                    .map_err(AppError::from)
+                    .and_then(|content| write_atomic(&path, &content)),
+                None => fs::remove_file(&path).map_err(AppError::from),
+            };
+            if let Err(e) = result {
+                log::error!("Failed to roll back {}: {}", change.rel_path, e);
+            }
+        }
+        let _ = fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+/// Remove backup directories beyond the newest `KEPT_BACKUP_DIRS`, except those
+/// holding originals that are still to be restored
+fn prune_backups(backup_dir: &Path, manifest: &BundleManifest) {
+    let Ok(entries) = fs::read_dir(backup_dir) else {
+        return;
+    };
+    let mut stamps: Vec<(u64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.file_name().to_str()?.parse().ok()?, entry.path())))
+        .collect();
+    stamps.sort_by_key(|(stamp, _)| std::cmp::Reverse(*stamp));
+
+    let referenced: BTreeSet<&str> = manifest
+        .originals
+        .values()
+        .filter_map(|original| original.split('/').next())
+        .collect();
+    for (_, dir) in stamps.into_iter().skip(KEPT_BACKUP_DIRS) {
+        let in_use = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|name| referenced.contains(name));
+        if in_use {
+            continue;
+        }
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            log::warn!("Failed to remove old bundle backup {:?}: {}", dir, e);
+        }
+    }
+}
+
+fn read_file(path: &Path) -> AppResult<Vec<u8>> {
+    fs::read(path)
+        .map_err(|e| AppError::FileSystemError(format!("Failed to read {:?}: {}", path, e)))
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            AppError::FileSystemError(format!("Failed to create directory {:?}: {}", parent, e))
+        })?;
+    }
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("bundle");
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+    fs::write(&temp_path, content)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            AppError::FileSystemError(format!("Failed to write {:?}: {}", path, e))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn bundle(files: &[(&str, &str)]) -> ProfileBundle {
+        ProfileBundle {
+            files: files
+                .iter()
+                .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    fn install(claude_dir: &Path, name: &str, bundle: Option<&ProfileBundle>) {
+        let mut journal = SwitchJournal::begin(claude_dir, name).unwrap();
+        BundleTransaction::begin(claude_dir, name, bundle, &mut journal)
+            .unwrap()
+            .commit()
+            .unwrap();
+        journal.commit();
+    }
+
+    #[test]
+    fn test_load_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("settings.json"), "{}").unwrap();
+        fs::write(dir.join("CLAUDE.md"), "# Work").unwrap();
+        fs::create_dir_all(dir.join("agents")).unwrap();
+        fs::write(dir.join("agents/reviewer.md"), "review").unwrap();
+        fs::write(dir.join("agents/notes.txt"), "ignored").unwrap();
+        fs::create_dir_all(dir.join("commands")).unwrap();
+        fs::write(dir.join("commands/deploy.md"), "deploy").unwrap();
+
+        let loaded = ProfileBundle::load(dir).unwrap();
+        let paths: Vec<&str> = loaded.files.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            vec!["CLAUDE.md", "agents/reviewer.md", "commands/deploy.md"]
+        );
+    }
+
+    #[test]
+    fn test_managed_paths() {
+        assert!(is_managed_path("CLAUDE.md"));
+        assert!(is_managed_path("agents/reviewer.md"));
+        assert!(!is_managed_path("settings.json"));
+        assert!(!is_managed_path("agents/../settings.md"));
+        assert!(!is_managed_path("projects/x.md"));
+    }
+
+    #[test]
+    fn test_switch_between_bundles() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        let work = bundle(&[("CLAUDE.md", "work"), ("agents/reviewer.md", "review")]);
+        let home = bundle(&[("CLAUDE.md", "home")]);
+
+        install(claude_dir, "work", Some(&work));
+        assert!(is_installed(claude_dir, Some(&work)));
+        assert!(!is_installed(claude_dir, Some(&home)));
+
+        install(claude_dir, "home", Some(&home));
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "home"
+        );
+        assert!(!claude_dir.join("agents/reviewer.md").exists());
+        assert!(is_installed(claude_dir, Some(&home)));
+
+        // A plain profile removes everything that was installed
+        install(claude_dir, "plain", None);
+        assert!(!claude_dir.join("CLAUDE.md").exists());
+        assert!(is_installed(claude_dir, None));
+        assert!(!claude_dir.join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_rollback_restores_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        fs::write(claude_dir.join("CLAUDE.md"), "mine").unwrap();
+
+        let work = bundle(&[("CLAUDE.md", "work"), ("commands/deploy.md", "deploy")]);
+        let mut journal = SwitchJournal::begin(claude_dir, "switch to work").unwrap();
+        let transaction =
+            BundleTransaction::begin(claude_dir, "work", Some(&work), &mut journal).unwrap();
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "work"
+        );
+        transaction.rollback();
+        journal.rollback().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "mine"
+        );
+        assert!(!claude_dir.join("commands/deploy.md").exists());
+        assert!(!claude_dir.join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn test_user_files_are_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        fs::write(claude_dir.join("CLAUDE.md"), "mine").unwrap();
+
+        let work = bundle(&[("CLAUDE.md", "work"), ("agents/reviewer.md", "review")]);
+        install(claude_dir, "work", Some(&work));
+        let backups: Vec<_> = fs::read_dir(claude_dir.join(BACKUP_DIR)).unwrap().collect();
+        assert_eq!(backups.len(), 1);
+
+        // Switching to another bundle keeps the original for later
+        let home = bundle(&[("CLAUDE.md", "home"), ("agents/reviewer.md", "review")]);
+        install(claude_dir, "home", Some(&home));
+
+        // Leaving bundles restores the user's file; installed files the user
+        // edited afterwards are not removed
+        fs::write(claude_dir.join("agents/reviewer.md"), "edited").unwrap();
+        install(claude_dir, "plain", None);
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("CLAUDE.md")).unwrap(),
+            "mine"
+        );
+        assert_eq!(
+            fs::read_to_string(claude_dir.join("agents/reviewer.md")).unwrap(),
+            "edited"
+        );
+        assert_eq!(BundleManifest::load(claude_dir).unwrap().originals.len(), 0);
+    }
+
+    #[test]
+    fn test_old_backups_are_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        let backup_dir = claude_dir.join(BACKUP_DIR);
+        for stamp in 1..=8 {
+            fs::create_dir_all(backup_dir.join(stamp.to_string())).unwrap();
+        }
+        let manifest = BundleManifest {
+            profile: Some("work".to_string()),
+            files: BTreeMap::from([("CLAUDE.md".to_string(), "0".to_string())]),
+            originals: BTreeMap::from([("CLAUDE.md".to_string(), "1/CLAUDE.md".to_string())]),
+        };
+
+        prune_backups(&backup_dir, &manifest);
+        let mut left: Vec<u64> = fs::read_dir(&backup_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_str().unwrap().parse().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec![1, 4, 5, 6, 7, 8]);
+    }
+}
@@ -0,0 +1,296 @@
+// Version history of profile files, kept in a content-addressed store next to the CCCS settings
+use crate::file_permissions;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "versions.json";
+const OBJECTS_DIR: &str = "objects";
+// 每个配置最多保留的版本数，超出后删除最旧的
+const MAX_VERSIONS: usize = 100;
+
+/// One saved state of a profile; `hash` names the snapshot in the object store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileVersion {
+    pub id: u64,
+    pub hash: String,
+    pub saved_at: u64,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// Line diff between two versions; `to` is `None` for the profile file as it is now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileDiff {
+    pub from: u64,
+    pub to: Option<u64>,
+    pub added: usize,
+    pub removed: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Per-profile history: `<dir>/<profile>/versions.json` lists the versions, and
+/// `<dir>/<profile>/objects/<sha256>.json` holds each distinct content once
+pub struct ProfileHistory {
+    dir: PathBuf,
+}
+
+impl ProfileHistory {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn profile_dir(&self, profile: &str) -> AppResult<PathBuf> {
+        if profile.is_empty()
+            || profile == "."
+            || profile == ".."
+            || profile.contains(['/', '\\'])
+        {
+            return Err(AppError::ConfigError(format!(
+                "Invalid profile name for history: '{}'",
+                profile
+            )));
+        }
+        Ok(self.dir.join(profile))
+    }
+
+    /// Versions of a profile, newest first
+    pub fn list(&self, profile: &str) -> AppResult<Vec<ProfileVersion>> {
+        let mut versions = self.load_index(&self.profile_dir(profile)?)?;
+        versions.reverse();
+        Ok(versions)
+    }
+
+    /// Record `content` as the newest version. Returns `None` when it matches the
+    /// newest version already; content seen before reuses the stored snapshot.
+    pub fn record(&self, profile: &str, content: &str) -> AppResult<Option<ProfileVersion>> {
+        let profile_dir = self.profile_dir(profile)?;
+        let mut versions = self.load_index(&profile_dir)?;
+        let hash = content_hash(content);
+
+        if versions.last().is_some_and(|latest| latest.hash == hash) {
+            return Ok(None);
+        }
+
+        let objects_dir = profile_dir.join(OBJECTS_DIR);
+        fs::create_dir_all(&objects_dir)?;
+        let object_path = objects_dir.join(format!("{}.json", hash));
+        if !object_path.exists() {
+            // 快照可能包含令牌，与配置文件一样仅所有者可读
+            file_permissions::write_private_file(&object_path, content.as_bytes())?;
+        }
+
+        let version = ProfileVersion {
+            id: versions.last().map_or(1, |latest| latest.id + 1),
+            hash,
+            saved_at: crate::switch_lease::now_secs(),
+            size: content.len(),
+        };
+        versions.push(version.clone());
+
+        if versions.len() > MAX_VERSIONS {
+            let dropped: Vec<ProfileVersion> = versions.drain(..versions.len() - MAX_VERSIONS).collect();
+            for old in dropped {
+                if !versions.iter().any(|v| v.hash == old.hash) {
+                    let _ = fs::remove_file(objects_dir.join(format!("{}.json", old.hash)));
+                }
+            }
+        }
+
+        self.save_index(&profile_dir, &versions)?;
+        log::info!("Recorded version {} of profile '{}'", version.id, profile);
+        Ok(Some(version))
+    }
+
+    /// Content of one version
+    pub fn read(&self, profile: &str, id: u64) -> AppResult<String> {
+        let profile_dir = self.profile_dir(profile)?;
+        let version = self
+            .load_index(&profile_dir)?
+            .into_iter()
+            .find(|v| v.id == id)
+            .ok_or_else(|| {
+                AppError::ConfigError(format!("Profile '{}' has no version {}", profile, id))
+            })?;
+        let path = profile_dir
+            .join(OBJECTS_DIR)
+            .join(format!("{}.json", version.hash));
+        fs::read_to_string(&path).map_err(|e| {
+            AppError::FileSystemError(format!(
+                "Failed to read version {} of profile '{}': {}",
+                id, profile, e
+            ))
+        })
+    }
+
+    fn load_index(&self, profile_dir: &Path) -> AppResult<Vec<ProfileVersion>> {
+        let path = profile_dir.join(INDEX_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            AppError::ConfigError(format!("Invalid history index {}: {}", path.display(), e))
+        })
+    }
+
+    fn save_index(&self, profile_dir: &Path, versions: &[ProfileVersion]) -> AppResult<()> {
+        let path = profile_dir.join(INDEX_FILE);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(versions)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+}
+
+pub fn default_history_dir() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("history"))
+}
+
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Line diff based on the longest common subsequence; profiles are small enough
+/// for the quadratic table once the common head and tail are stripped
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]：old_mid[i..] 与 new_mid[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |kind, text: &str| DiffLine {
+        kind,
+        text: text.to_string(),
+    };
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|text| line(DiffKind::Context, text))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            lines.push(line(DiffKind::Context, old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len() && (j == new_mid.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(line(DiffKind::Removed, old_mid[i]));
+            i += 1;
+        } else {
+            lines.push(line(DiffKind::Added, new_mid[j]));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|text| line(DiffKind::Context, text)),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_dedup_and_read() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = ProfileHistory::new(temp_dir.path().to_path_buf());
+        assert!(history.list("work").unwrap().is_empty());
+
+        let v1 = history.record("work", "{\"model\": \"opus\"}").unwrap().unwrap();
+        assert!(history.record("work", "{\"model\": \"opus\"}").unwrap().is_none());
+        let v2 = history.record("work", "{\"model\": \"sonnet\"}").unwrap().unwrap();
+        let v3 = history.record("work", "{\"model\": \"opus\"}").unwrap().unwrap();
+
+        assert_eq!((v1.id, v2.id, v3.id), (1, 2, 3));
+        assert_eq!(v1.hash, v3.hash);
+        let ids: Vec<u64> = history.list("work").unwrap().iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        // 相同内容只存一份
+        let objects = fs::read_dir(temp_dir.path().join("work").join(OBJECTS_DIR)).unwrap();
+        assert_eq!(objects.count(), 2);
+
+        assert_eq!(history.read("work", 2).unwrap(), "{\"model\": \"sonnet\"}");
+        assert!(history.read("work", 9).is_err());
+        assert!(history.record("../escape", "{}").is_err());
+    }
+
+    #[test]
+    fn test_prune_old_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = ProfileHistory::new(temp_dir.path().to_path_buf());
+        for i in 0..MAX_VERSIONS + 5 {
+            history.record("work", &format!("{{\"n\": {}}}", i)).unwrap();
+        }
+
+        let versions = history.list("work").unwrap();
+        assert_eq!(versions.len(), MAX_VERSIONS);
+        assert_eq!(versions.last().unwrap().id, 6);
+        let objects = fs::read_dir(temp_dir.path().join("work").join(OBJECTS_DIR)).unwrap();
+        assert_eq!(objects.count(), MAX_VERSIONS);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "{\n  \"model\": \"opus\",\n  \"env\": {}\n}";
+        let new = "{\n  \"model\": \"sonnet\",\n  \"env\": {}\n}";
+        let diff = diff_lines(old, new);
+        let kinds: Vec<DiffKind> = diff.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiffKind::Context,
+                DiffKind::Removed,
+                DiffKind::Added,
+                DiffKind::Context,
+                DiffKind::Context,
+            ]
+        );
+        assert_eq!(diff[2].text, "  \"model\": \"sonnet\",");
+
+        assert!(diff_lines(old, old).iter().all(|l| l.kind == DiffKind::Context));
+        assert_eq!(diff_lines("", "a\nb").len(), 2);
+    }
+}
@@ -0,0 +1,337 @@
+// Display metadata of profiles (description, tags, color, icon, ordering), kept outside the profile JSON
+use crate::{AppError, AppResult, Profile};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_DESCRIPTION_LEN: usize = 500;
+const MAX_TAG_LEN: usize = 50;
+// 记录的最近切换数量上限；托盘显示其中的前 N 个
+pub const MAX_RECENT_PROFILES: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>, // "#RGB" 或 "#RRGGBB"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>, // 显示在托盘名称前的 emoji
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<u32>, // 手动排序位置，未设置的排在后面
+}
+
+impl ProfileMetadata {
+    pub fn validate(&self) -> AppResult<()> {
+        if self
+            .description
+            .as_ref()
+            .is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LEN)
+        {
+            return Err(AppError::ConfigError(format!(
+                "Description too long (max {} characters)",
+                MAX_DESCRIPTION_LEN
+            )));
+        }
+        for (i, tag) in self.tags.iter().enumerate() {
+            if tag.trim().is_empty() || tag.trim() != tag || tag.chars().count() > MAX_TAG_LEN {
+                return Err(AppError::ConfigError(format!("Invalid tag '{}'", tag)));
+            }
+            if self.tags[..i].contains(tag) {
+                return Err(AppError::ConfigError(format!("Duplicate tag '{}'", tag)));
+            }
+        }
+        if let Some(color) = &self.color {
+            let hex = color.strip_prefix('#').unwrap_or("");
+            if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(AppError::ConfigError(format!(
+                    "Invalid color '{}', use #RGB or #RRGGBB",
+                    color
+                )));
+            }
+        }
+        if let Some(icon) = &self.icon {
+            // 一个 emoji 可能由多个码点组成（肤色、ZWJ 序列）
+            if icon.trim().is_empty() || icon.chars().count() > 8 || icon.chars().any(char::is_control) {
+                return Err(AppError::ConfigError(format!("Invalid icon '{}'", icon)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Prefix shown before the name in the tray: the icon, or a swatch close to the color
+    pub fn badge(&self) -> Option<String> {
+        if let Some(icon) = &self.icon {
+            return Some(icon.clone());
+        }
+        self.color.as_deref().and_then(color_swatch).map(str::to_string)
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Nearest colored circle emoji for "#RGB" / "#RRGGBB"; menus cannot render arbitrary colors
+fn color_swatch(color: &str) -> Option<&'static str> {
+    let hex = color.strip_prefix('#')?;
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(|v| v as f32 / 255.0);
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    if max - min < 0.15 {
+        return Some(if lightness < 0.5 { "⚫" } else { "⚪" });
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / (max - min)).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / (max - min) + 2.0)
+    } else {
+        60.0 * ((r - g) / (max - min) + 4.0)
+    };
+    Some(match hue {
+        h if !(15.0..330.0).contains(&h) => "🔴",
+        h if h < 45.0 && lightness < 0.35 => "🟤",
+        h if h < 45.0 => "🟠",
+        h if h < 70.0 => "🟡",
+        h if h < 170.0 => "🟢",
+        h if h < 260.0 => "🔵",
+        _ => "🟣",
+    })
+}
+
+/// All profile metadata in one file: profile name -> metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataStore {
+    #[serde(flatten)]
+    pub profiles: BTreeMap<String, ProfileMetadata>,
+}
+
+/// Profiles most recently switched to, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecentProfiles {
+    #[serde(default)]
+    pub profiles: Vec<String>,
+}
+
+pub fn default_metadata_path() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("profile_metadata.json"))
+}
+
+pub fn default_recent_profiles_path() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("recent_profiles.json"))
+}
+
+/// A missing file reads as the default value
+fn load_json<T: Default + serde::de::DeserializeOwned>(path: &Path) -> AppResult<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| {
+        AppError::ConfigError(format!("Failed to read profile metadata {:?}: {}", path, e))
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        AppError::ConfigError(format!("Invalid profile metadata {:?}: {}", path, e))
+    })
+}
+
+fn save_json<T: Serialize>(value: &T, path: &Path) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+impl RecentProfiles {
+    pub fn load(path: &Path) -> AppResult<Self> {
+        load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        save_json(self, path)
+    }
+
+    /// Move a profile to the front, keeping at most `MAX_RECENT_PROFILES`
+    pub fn record(&mut self, profile: &str) {
+        self.profiles.retain(|p| p != profile);
+        self.profiles.insert(0, profile.to_string());
+        self.profiles.truncate(MAX_RECENT_PROFILES);
+    }
+
+    pub fn remove(&mut self, profile: &str) -> bool {
+        let len = self.profiles.len();
+        self.profiles.retain(|p| p != profile);
+        self.profiles.len() != len
+    }
+}
+
+impl MetadataStore {
+    /// A missing file means no metadata
+    pub fn load(path: &Path) -> AppResult<Self> {
+        load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        save_json(self, path)
+    }
+
+    pub fn get(&self, profile: &str) -> ProfileMetadata {
+        self.profiles.get(profile).cloned().unwrap_or_default()
+    }
+
+    /// Store metadata for a profile; empty metadata removes the entry
+    pub fn set(&mut self, profile: &str, metadata: ProfileMetadata) {
+        if metadata.is_empty() {
+            self.profiles.remove(profile);
+        } else {
+            self.profiles.insert(profile.to_string(), metadata);
+        }
+    }
+
+    /// Give the listed profiles positions in that order; others keep theirs
+    pub fn reorder(&mut self, order: &[String]) {
+        for (position, profile) in order.iter().enumerate() {
+            let mut metadata = self.get(profile);
+            metadata.position = Some(position as u32);
+            self.set(profile, metadata);
+        }
+    }
+}
+
+/// Pinned profiles first, then by position (unpositioned last), then by name
+pub fn compare_profiles(a: &Profile, b: &Profile) -> Ordering {
+    b.metadata
+        .pinned
+        .cmp(&a.metadata.pinned)
+        .then_with(|| match (a.metadata.position, b.metadata.position) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        })
+        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn profile(name: &str, pinned: bool, position: Option<u32>) -> Profile {
+        Profile {
+            name: name.to_string(),
+            path: PathBuf::from(format!("{}.settings.json", name)),
+            content: "{}".to_string(),
+            is_active: false,
+            bundle_dir: None,
+            source: None,
+            update_available: false,
+            signature: Default::default(),
+            metadata: ProfileMetadata {
+                pinned,
+                position,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut profiles = [
+            profile("zeta", false, None),
+            profile("Alpha", false, None),
+            profile("work", false, Some(1)),
+            profile("home", false, Some(0)),
+            profile("prod", true, None),
+        ];
+        profiles.sort_by(compare_profiles);
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["prod", "home", "work", "Alpha", "zeta"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = ProfileMetadata {
+            description: Some("Client A".to_string()),
+            tags: vec!["client".to_string(), "prod".to_string()],
+            color: Some("#1e90ff".to_string()),
+            icon: Some("🚀".to_string()),
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let invalid = [
+            ProfileMetadata { color: Some("blue".to_string()), ..Default::default() },
+            ProfileMetadata { tags: vec!["a".to_string(), "a".to_string()], ..Default::default() },
+            ProfileMetadata { tags: vec![" padded".to_string()], ..Default::default() },
+            ProfileMetadata { icon: Some(String::new()), ..Default::default() },
+        ];
+        assert!(invalid.iter().all(|m| m.validate().is_err()));
+    }
+
+    #[test]
+    fn test_badge() {
+        let color = |c: &str| ProfileMetadata {
+            color: Some(c.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(color("#e53935").badge().as_deref(), Some("🔴"));
+        assert_eq!(color("#1e90ff").badge().as_deref(), Some("🔵"));
+        assert_eq!(color("#0f0").badge().as_deref(), Some("🟢"));
+        assert_eq!(color("#8b4513").badge().as_deref(), Some("🟤"));
+        assert_eq!(color("#fafafa").badge().as_deref(), Some("⚪"));
+
+        let mut metadata = color("#0f0");
+        metadata.icon = Some("🚀".to_string());
+        assert_eq!(metadata.badge().as_deref(), Some("🚀"));
+        assert_eq!(ProfileMetadata::default().badge(), None);
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("profile_metadata.json");
+        let mut store = MetadataStore::load(&path).unwrap();
+        store.set("work", ProfileMetadata { pinned: true, ..Default::default() });
+        store.reorder(&["home".to_string(), "work".to_string()]);
+        store.save(&path).unwrap();
+
+        let store = MetadataStore::load(&path).unwrap();
+        assert_eq!(store.get("home").position, Some(0));
+        assert!(store.get("work").pinned && store.get("work").position == Some(1));
+        assert_eq!(store.get("other"), ProfileMetadata::default());
+
+        let mut store = store;
+        store.set("home", ProfileMetadata::default());
+        assert!(!store.profiles.contains_key("home"));
+    }
+
+    #[test]
+    fn test_recent_profiles() {
+        let mut recent = RecentProfiles::default();
+        for i in 0..12 {
+            recent.record(&format!("p{}", i));
+        }
+        recent.record("p5");
+        assert_eq!(recent.profiles.len(), MAX_RECENT_PROFILES);
+        assert_eq!(&recent.profiles[..3], ["p5", "p11", "p10"]);
+        assert!(recent.remove("p11"));
+        assert!(!recent.remove("p11"));
+    }
+}
@@ -0,0 +1,283 @@
+// Detached ed25519 signatures for profiles distributed by a platform team
+use crate::file_permissions;
+use crate::profile_bundle::ProfileBundle;
+use crate::{AppError, AppResult};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SIGNATURE_EXTENSION: &str = "sig";
+// 目录配置的签名内容：settings.json 之后依次列出每个附带文件的路径和 sha256
+const BUNDLE_PAYLOAD_HEADER: &[u8] = b"\0cccs-bundle-files-v1\n";
+
+/// Public key whose signatures are accepted, as configured in the CCCS settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub name: String,
+    pub public_key: String, // base64 编码的 32 字节 ed25519 公钥
+}
+
+impl TrustedKey {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::SettingsError(
+                "Trusted key name cannot be empty".to_string(),
+            ));
+        }
+        parse_public_key(&self.public_key).map(|_| ())
+    }
+}
+
+/// Content of `<profile file>.sig`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Outcome of checking a profile against the trusted keys
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum SignatureState {
+    #[default]
+    NotChecked, // 未配置受信任公钥，不做校验
+    Valid(String), // 签名公钥的名称
+    Unsigned,
+    Invalid(String), // 内容被修改、签名损坏或公钥不受信任
+}
+
+/// `work.settings.json` -> `work.settings.json.sig`
+pub fn signature_path(profile_path: &Path) -> PathBuf {
+    let mut path = profile_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Bytes covered by the signature: the settings file, plus every bundle file for directory profiles
+pub fn signed_payload(settings: &[u8], bundle: Option<&ProfileBundle>) -> Vec<u8> {
+    let mut payload = settings.to_vec();
+    if let Some(bundle) = bundle.filter(|b| !b.files.is_empty()) {
+        payload.extend_from_slice(BUNDLE_PAYLOAD_HEADER);
+        for (path, content) in &bundle.files {
+            let hash: String = Sha256::digest(content)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            payload.extend_from_slice(format!("{} {}\n", hash, path).as_bytes());
+        }
+    }
+    payload
+}
+
+/// Check `payload` against the signature file next to the profile
+pub fn verify(payload: &[u8], signature_file: &Path, trusted_keys: &[TrustedKey]) -> SignatureState {
+    if trusted_keys.is_empty() {
+        return SignatureState::NotChecked;
+    }
+    if !signature_file.exists() {
+        return SignatureState::Unsigned;
+    }
+
+    let detached = match fs::read_to_string(signature_file)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            serde_json::from_str::<DetachedSignature>(&content).map_err(|e| e.to_string())
+        }) {
+        Ok(detached) => detached,
+        Err(e) => return SignatureState::Invalid(format!("unreadable signature file: {}", e)),
+    };
+
+    let Some(trusted) = trusted_keys
+        .iter()
+        .find(|key| key.public_key.trim() == detached.public_key.trim())
+    else {
+        return SignatureState::Invalid("signed with an untrusted key".to_string());
+    };
+
+    let signature = match STANDARD
+        .decode(detached.signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    {
+        Some(signature) => signature,
+        None => return SignatureState::Invalid("malformed signature".to_string()),
+    };
+
+    match parse_public_key(&trusted.public_key) {
+        Ok(key) if key.verify_strict(payload, &signature).is_ok() => {
+            SignatureState::Valid(trusted.name.clone())
+        }
+        Ok(_) => SignatureState::Invalid("content does not match its signature".to_string()),
+        Err(e) => SignatureState::Invalid(e.to_string()),
+    }
+}
+
+/// Sign a profile file (and its bundle directory, if any) and write the detached signature
+pub fn sign_profile(profile_path: &Path, bundle_dir: Option<&Path>, key: &SigningKey) -> AppResult<PathBuf> {
+    let settings = fs::read(profile_path).map_err(|e| {
+        AppError::FileSystemError(format!("Failed to read profile {:?}: {}", profile_path, e))
+    })?;
+    let bundle = bundle_dir.map(ProfileBundle::load).transpose()?;
+    let signature = key.sign(&signed_payload(&settings, bundle.as_ref()));
+
+    let detached = DetachedSignature {
+        public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+        signature: STANDARD.encode(signature.to_bytes()),
+    };
+    let path = signature_path(profile_path);
+    fs::write(&path, serde_json::to_string_pretty(&detached)?)?;
+    log::info!("Signed profile {:?}", profile_path);
+    Ok(path)
+}
+
+/// Verify a profile on disk the same way a switch does
+pub fn verify_profile(profile_path: &Path, bundle_dir: Option<&Path>, trusted_keys: &[TrustedKey]) -> AppResult<SignatureState> {
+    let settings = fs::read(profile_path)?;
+    let bundle = bundle_dir.map(ProfileBundle::load).transpose()?;
+    Ok(verify(
+        &signed_payload(&settings, bundle.as_ref()),
+        &signature_path(profile_path),
+        trusted_keys,
+    ))
+}
+
+/// Create a new private key file (base64 seed, owner-only) and return its public key
+pub fn generate_signing_key(path: &Path) -> AppResult<String> {
+    if path.exists() {
+        return Err(AppError::FileSystemError(format!(
+            "{:?} already exists",
+            path
+        )));
+    }
+    let mut seed = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut seed);
+    let key = SigningKey::from_bytes(&seed);
+    file_permissions::write_private_file(path, STANDARD.encode(key.to_bytes()).as_bytes())?;
+    Ok(STANDARD.encode(key.verifying_key().as_bytes()))
+}
+
+pub fn load_signing_key(path: &Path) -> AppResult<SigningKey> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        AppError::FileSystemError(format!("Failed to read signing key {:?}: {}", path, e))
+    })?;
+    let seed: [u8; 32] = STANDARD
+        .decode(content.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AppError::ConfigError(format!("{:?} is not a signing key", path)))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn parse_public_key(public_key: &str) -> AppResult<VerifyingKey> {
+    let bytes: [u8; 32] = STANDARD
+        .decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            AppError::SettingsError(format!("Invalid ed25519 public key '{}'", public_key))
+        })?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| {
+        AppError::SettingsError(format!("Invalid ed25519 public key '{}'", public_key))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn trusted(name: &str, key_file: &Path) -> TrustedKey {
+        TrustedKey {
+            name: name.to_string(),
+            public_key: generate_signing_key(key_file).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("platform.key");
+        let platform = trusted("platform", &key_file);
+        let key = load_signing_key(&key_file).unwrap();
+
+        let profile = temp_dir.path().join("work.settings.json");
+        fs::write(&profile, r#"{"model": "opus"}"#).unwrap();
+        assert_eq!(verify_profile(&profile, None, &[]).unwrap(), SignatureState::NotChecked);
+        let trusted_keys = [platform];
+        assert_eq!(
+            verify_profile(&profile, None, &trusted_keys).unwrap(),
+            SignatureState::Unsigned
+        );
+
+        let sig = sign_profile(&profile, None, &key).unwrap();
+        assert_eq!(sig, temp_dir.path().join("work.settings.json.sig"));
+        assert_eq!(
+            verify_profile(&profile, None, &trusted_keys).unwrap(),
+            SignatureState::Valid("platform".to_string())
+        );
+
+        fs::write(&profile, r#"{"model": "sonnet"}"#).unwrap();
+        assert!(matches!(
+            verify_profile(&profile, None, &trusted_keys).unwrap(),
+            SignatureState::Invalid(_)
+        ));
+
+        // 其他密钥的签名不被接受
+        let other = load_signing_key(&{
+            let path = temp_dir.path().join("other.key");
+            generate_signing_key(&path).unwrap();
+            path
+        })
+        .unwrap();
+        sign_profile(&profile, None, &other).unwrap();
+        assert_eq!(
+            verify_profile(&profile, None, &trusted_keys).unwrap(),
+            SignatureState::Invalid("signed with an untrusted key".to_string())
+        );
+        assert!(generate_signing_key(&key_file).is_err());
+    }
+
+    #[test]
+    fn test_bundle_files_are_covered() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("platform.key");
+        let trusted_keys = [trusted("platform", &key_file)];
+        let key = load_signing_key(&key_file).unwrap();
+
+        let bundle_dir = temp_dir.path().join("team.profile");
+        fs::create_dir_all(bundle_dir.join("agents")).unwrap();
+        let settings = bundle_dir.join("settings.json");
+        fs::write(&settings, "{}").unwrap();
+        fs::write(bundle_dir.join("agents/review.md"), "review").unwrap();
+        sign_profile(&settings, Some(&bundle_dir), &key).unwrap();
+        assert!(matches!(
+            verify_profile(&settings, Some(&bundle_dir), &trusted_keys).unwrap(),
+            SignatureState::Valid(_)
+        ));
+
+        fs::write(bundle_dir.join("agents/review.md"), "approve everything").unwrap();
+        assert!(matches!(
+            verify_profile(&settings, Some(&bundle_dir), &trusted_keys).unwrap(),
+            SignatureState::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn test_trusted_key_validation() {
+        let key = TrustedKey {
+            name: "platform".to_string(),
+            public_key: "not a key".to_string(),
+        };
+        assert!(key.validate().is_err());
+        let key = TrustedKey {
+            name: " ".to_string(),
+            public_key: STANDARD.encode([0u8; 32]),
+        };
+        assert!(key.validate().is_err());
+    }
+}
@@ -0,0 +1,504 @@
+// Provider presets used for guided profile creation
+use crate::interpolation::Placeholder;
+use crate::secret_vault::SecretVault;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateFieldType {
+    String,
+    Url,
+    Number,
+    Boolean,
+    Secret,
+}
+
+/// One env variable a template asks the user for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub key: String,
+    pub label: String,
+    #[serde(default)]
+    pub description: String,
+    pub field_type: TemplateFieldType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub choices: Vec<String>, // 非空时只允许这些取值
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileTemplate {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub fixed_env: BTreeMap<String, String>, // 固定写入的环境变量
+    #[serde(default)]
+    pub fields: Vec<TemplateField>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// Settings JSON rendered from a template
+pub struct RenderedProfile {
+    pub content: String,
+    /// Vault entries (name, value) for the secret fields that were entered as plain
+    /// text; `content` only holds `${secret:name}` references to them
+    pub secrets: Vec<(String, String)>,
+}
+
+impl ProfileTemplate {
+    /// Validate user input and render the profile settings JSON. Plaintext values of
+    /// secret fields are replaced by references to new vault entries whose names do
+    /// not clash with `existing_secrets`.
+    pub fn render(
+        &self,
+        profile_name: &str,
+        values: &HashMap<String, String>,
+        existing_secrets: &[String],
+    ) -> AppResult<RenderedProfile> {
+        let mut errors = Vec::new();
+        let mut secrets: Vec<(String, String)> = Vec::new();
+
+        for key in values.keys() {
+            if !self.fields.iter().any(|f| &f.key == key) {
+                errors.push(format!("Unknown field '{}'", key));
+            }
+        }
+
+        let mut env: BTreeMap<String, String> = self.fixed_env.clone();
+        for field in &self.fields {
+            let value = values
+                .get(&field.key)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .or_else(|| field.default.clone());
+
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    if field.required {
+                        errors.push(format!("{} ({}) is required", field.label, field.key));
+                    }
+                    continue;
+                }
+            };
+
+            match field.normalize_value(&value) {
+                Ok(value) if field.field_type == TemplateFieldType::Secret && !value.contains("${") => {
+                    let name = unique_secret_name(profile_name, &field.key, |name| {
+                        existing_secrets.iter().any(|s| s == name) || secrets.iter().any(|(s, _)| s == name)
+                    });
+                    env.insert(field.key.clone(), Placeholder::Secret(name.clone()).to_template());
+                    secrets.push((name, value));
+                }
+                Ok(value) => {
+                    env.insert(field.key.clone(), value);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::ConfigError(format!(
+                "Invalid values for template '{}': {}",
+                self.id,
+                errors.join("; ")
+            )));
+        }
+
+        let content = serde_json::json!({ "env": env });
+        Ok(RenderedProfile {
+            content: serde_json::to_string_pretty(&content)?,
+            secrets,
+        })
+    }
+}
+
+/// `<profile>.<KEY>` limited to the characters the vault accepts, with a numeric
+/// suffix when the name is already taken
+fn unique_secret_name(profile_name: &str, key: &str, taken: impl Fn(&str) -> bool) -> String {
+    let base: String = format!("{}.{}", profile_name, key)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .take(90)
+        .collect();
+    let mut name = base.clone();
+    let mut counter = 2;
+    while taken(&name) {
+        name = format!("{}-{}", base, counter);
+        counter += 1;
+    }
+    name
+}
+
+/// Add the secrets of a rendered template to the vault; if one fails, the ones
+/// already added are removed again
+pub fn store_secrets(vault: &mut SecretVault, secrets: &[(String, String)]) -> AppResult<()> {
+    for (i, (name, value)) in secrets.iter().enumerate() {
+        if let Err(e) = vault.add_secret(name, value) {
+            remove_secrets(vault, &secrets[..i]);
+            return Err(AppError::SecretError(format!(
+                "Failed to store secret field in the vault ({}). Unlock the vault or enter a ${{secret:name}} reference instead",
+                e
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Undo `store_secrets`, e.g. when the profile could not be created
+pub fn remove_secrets(vault: &mut SecretVault, secrets: &[(String, String)]) {
+    for (name, _) in secrets {
+        if let Err(e) = vault.remove_secret(name) {
+            log::warn!("Failed to remove secret {}: {}", name, e);
+        }
+    }
+}
+
+impl TemplateField {
+    fn normalize_value(&self, value: &str) -> Result<String, String> {
+        // Placeholders are resolved at switch time
+        if value.contains("${") {
+            return Ok(value.to_string());
+        }
+
+        if !self.choices.is_empty() && !self.choices.iter().any(|c| c == value) {
+            return Err(format!(
+                "{} must be one of: {}",
+                self.key,
+                self.choices.join(", ")
+            ));
+        }
+
+        match self.field_type {
+            TemplateFieldType::String | TemplateFieldType::Secret => Ok(value.to_string()),
+            TemplateFieldType::Url => {
+                if value.starts_with("http://") || value.starts_with("https://") {
+                    Ok(value.trim_end_matches('/').to_string())
+                } else {
+                    Err(format!("{} must be an http(s) URL", self.key))
+                }
+            }
+            TemplateFieldType::Number => value
+                .parse::<f64>()
+                .map(|_| value.to_string())
+                .map_err(|_| format!("{} must be a number", self.key)),
+            TemplateFieldType::Boolean => match value.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" => Ok("1".to_string()),
+                "false" | "0" | "no" => Ok("0".to_string()),
+                _ => Err(format!("{} must be true or false", self.key)),
+            },
+        }
+    }
+}
+
+/// Built-in provider presets plus user templates from `<cccs dir>/templates/*.json`
+pub struct TemplateRegistry {
+    templates: Vec<ProfileTemplate>,
+}
+
+impl TemplateRegistry {
+    /// Load built-in templates and, if given, user templates (which override by id)
+    pub fn load(user_dir: Option<&Path>) -> Self {
+        let mut templates = builtin_templates();
+
+        if let Some(dir) = user_dir {
+            for template in load_user_templates(dir) {
+                match templates.iter_mut().find(|t| t.id == template.id) {
+                    Some(existing) => *existing = template,
+                    None => templates.push(template),
+                }
+            }
+        }
+
+        Self { templates }
+    }
+
+    /// Load templates using the default user template directory
+    pub fn load_default() -> Self {
+        match default_templates_dir() {
+            Ok(dir) => Self::load(Some(&dir)),
+            Err(e) => {
+                log::warn!("Failed to locate user templates directory: {}", e);
+                Self::load(None)
+            }
+        }
+    }
+
+    pub fn list(&self) -> &[ProfileTemplate] {
+        &self.templates
+    }
+
+    pub fn get(&self, template_id: &str) -> AppResult<&ProfileTemplate> {
+        self.templates
+            .iter()
+            .find(|t| t.id == template_id)
+            .ok_or_else(|| AppError::ConfigError(format!("Template '{}' not found", template_id)))
+    }
+}
+
+/// Get the user templates directory
+pub fn default_templates_dir() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("templates"))
+}
+
+fn load_user_templates(dir: &Path) -> Vec<ProfileTemplate> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    paths.sort();
+
+    let mut templates = Vec::new();
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                serde_json::from_str::<ProfileTemplate>(&content).map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(mut template) => {
+                template.builtin = false;
+                templates.push(template);
+            }
+            Err(e) => log::warn!("Skipping invalid template {:?}: {}", path, e),
+        }
+    }
+    templates
+}
+
+fn field(
+    key: &str,
+    label: &str,
+    field_type: TemplateFieldType,
+    required: bool,
+    default: Option<&str>,
+) -> TemplateField {
+    TemplateField {
+        key: key.to_string(),
+        label: label.to_string(),
+        description: String::new(),
+        field_type,
+        required,
+        default: default.map(str::to_string),
+        choices: Vec::new(),
+    }
+}
+
+fn model_fields() -> Vec<TemplateField> {
+    vec![
+        field("ANTHROPIC_MODEL", "Model", TemplateFieldType::String, false, None),
+        field(
+            "ANTHROPIC_SMALL_FAST_MODEL",
+            "Small/fast model",
+            TemplateFieldType::String,
+            false,
+            None,
+        ),
+    ]
+}
+
+fn builtin_templates() -> Vec<ProfileTemplate> {
+    let fixed = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    let mut anthropic_fields = vec![field(
+        "ANTHROPIC_API_KEY",
+        "API key",
+        TemplateFieldType::Secret,
+        true,
+        None,
+    )];
+    anthropic_fields.extend(model_fields());
+
+    let mut bedrock_fields = vec![
+        field("AWS_REGION", "AWS region", TemplateFieldType::String, true, Some("us-east-1")),
+        field("AWS_PROFILE", "AWS profile", TemplateFieldType::String, false, None),
+    ];
+    bedrock_fields.extend(model_fields());
+
+    let mut vertex_fields = vec![
+        field("CLOUD_ML_REGION", "Region", TemplateFieldType::String, true, Some("us-east5")),
+        field(
+            "ANTHROPIC_VERTEX_PROJECT_ID",
+            "GCP project ID",
+            TemplateFieldType::String,
+            true,
+            None,
+        ),
+    ];
+    vertex_fields.extend(model_fields());
+
+    let mut proxy_fields = vec![
+        field("ANTHROPIC_BASE_URL", "Proxy URL", TemplateFieldType::Url, true, None),
+        field("ANTHROPIC_AUTH_TOKEN", "Auth token", TemplateFieldType::Secret, true, None),
+    ];
+    proxy_fields.extend(model_fields());
+
+    vec![
+        ProfileTemplate {
+            id: "anthropic-api".to_string(),
+            name: "Anthropic API".to_string(),
+            description: "Direct access with an Anthropic API key".to_string(),
+            fixed_env: BTreeMap::new(),
+            fields: anthropic_fields,
+            builtin: true,
+        },
+        ProfileTemplate {
+            id: "bedrock".to_string(),
+            name: "Amazon Bedrock".to_string(),
+            description: "Claude via Amazon Bedrock using AWS credentials".to_string(),
+            fixed_env: fixed(&[("CLAUDE_CODE_USE_BEDROCK", "1")]),
+            fields: bedrock_fields,
+            builtin: true,
+        },
+        ProfileTemplate {
+            id: "vertex".to_string(),
+            name: "Google Vertex AI".to_string(),
+            description: "Claude via Google Vertex AI using gcloud credentials".to_string(),
+            fixed_env: fixed(&[("CLAUDE_CODE_USE_VERTEX", "1")]),
+            fields: vertex_fields,
+            builtin: true,
+        },
+        ProfileTemplate {
+            id: "litellm-proxy".to_string(),
+            name: "LiteLLM proxy".to_string(),
+            description: "An Anthropic-compatible gateway such as LiteLLM".to_string(),
+            fixed_env: BTreeMap::new(),
+            fields: proxy_fields,
+            builtin: true,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_bedrock_template() {
+        let registry = TemplateRegistry::load(None);
+        let template = registry.get("bedrock").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("ANTHROPIC_MODEL".to_string(), "claude-sonnet".to_string());
+        let rendered = template.render("aws", &values, &[]).unwrap();
+        assert!(rendered.secrets.is_empty());
+        let json: serde_json::Value = serde_json::from_str(&rendered.content).unwrap();
+
+        assert_eq!(json["env"]["CLAUDE_CODE_USE_BEDROCK"], "1");
+        assert_eq!(json["env"]["AWS_REGION"], "us-east-1"); // default
+        assert_eq!(json["env"]["ANTHROPIC_MODEL"], "claude-sonnet");
+        assert!(json["env"].get("AWS_PROFILE").is_none());
+    }
+
+    #[test]
+    fn test_render_rejects_invalid_input() {
+        let registry = TemplateRegistry::load(None);
+        let template = registry.get("litellm-proxy").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("ANTHROPIC_BASE_URL".to_string(), "not-a-url".to_string());
+        values.insert("UNKNOWN".to_string(), "x".to_string());
+        let err = template.render("proxy", &values, &[]).err().unwrap().to_string();
+        assert!(err.contains("http(s) URL"));
+        assert!(err.contains("ANTHROPIC_AUTH_TOKEN"));
+        assert!(err.contains("Unknown field 'UNKNOWN'"));
+
+        // Secret references are accepted as-is
+        let mut values = HashMap::new();
+        values.insert("ANTHROPIC_BASE_URL".to_string(), "https://proxy/".to_string());
+        values.insert("ANTHROPIC_AUTH_TOKEN".to_string(), "${secret:proxy}".to_string());
+        let rendered = template.render("proxy", &values, &[]).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&rendered.content).unwrap();
+        assert_eq!(json["env"]["ANTHROPIC_BASE_URL"], "https://proxy");
+        assert_eq!(json["env"]["ANTHROPIC_AUTH_TOKEN"], "${secret:proxy}");
+        assert!(rendered.secrets.is_empty());
+    }
+
+    #[test]
+    fn test_render_moves_secrets_to_vault() {
+        let registry = TemplateRegistry::load(None);
+        let template = registry.get("litellm-proxy").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("ANTHROPIC_BASE_URL".to_string(), "https://proxy".to_string());
+        values.insert("ANTHROPIC_AUTH_TOKEN".to_string(), "sk-plain".to_string());
+        let existing = vec!["my_proxy.ANTHROPIC_AUTH_TOKEN".to_string()];
+        let rendered = template.render("my proxy", &values, &existing).unwrap();
+
+        assert!(!rendered.content.contains("sk-plain"));
+        let json: serde_json::Value = serde_json::from_str(&rendered.content).unwrap();
+        assert_eq!(
+            json["env"]["ANTHROPIC_AUTH_TOKEN"],
+            "${secret:my_proxy.ANTHROPIC_AUTH_TOKEN-2}"
+        );
+        assert_eq!(
+            rendered.secrets,
+            vec![("my_proxy.ANTHROPIC_AUTH_TOKEN-2".to_string(), "sk-plain".to_string())]
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut vault = SecretVault::open(temp_dir.path().join("vault.json")).unwrap();
+        // 保险库未初始化时拒绝存储
+        assert!(store_secrets(&mut vault, &rendered.secrets).is_err());
+        vault.initialize(Some("passphrase")).unwrap();
+        store_secrets(&mut vault, &rendered.secrets).unwrap();
+        assert_eq!(vault.get_secret("my_proxy.ANTHROPIC_AUTH_TOKEN-2").unwrap(), "sk-plain");
+        remove_secrets(&mut vault, &rendered.secrets);
+        assert!(vault.list_secrets().is_empty());
+    }
+
+    #[test]
+    fn test_user_templates_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("corp.json"),
+            r#"{
+                "id": "corp-gateway",
+                "name": "Corp gateway",
+                "fixed_env": {"ANTHROPIC_BASE_URL": "https://llm.corp"},
+                "fields": [{"key": "TEAM", "label": "Team", "field_type": "string",
+                            "required": true, "choices": ["a", "b"]}],
+                "builtin": true
+            }"#,
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("broken.json"), "{ nope").unwrap();
+
+        let registry = TemplateRegistry::load(Some(temp_dir.path()));
+        let template = registry.get("corp-gateway").unwrap();
+        assert!(!template.builtin);
+        assert_eq!(registry.list().len(), 5);
+
+        let mut values = HashMap::new();
+        values.insert("TEAM".to_string(), "c".to_string());
+        assert!(template.render("corp", &values, &[]).is_err());
+        values.insert("TEAM".to_string(), "a".to_string());
+        assert!(template
+            .render("corp", &values, &[])
+            .unwrap()
+            .content
+            .contains("https://llm.corp"));
+    }
+}
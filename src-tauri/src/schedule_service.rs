@@ -0,0 +1,372 @@
+// Time-based profile switching with cron-like schedules
+use crate::{AppError, AppResult};
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::interval;
+
+const CHECK_INTERVAL_SECS: u64 = 30;
+// 睡眠/休眠后最多回溯 7 天补执行错过的切换
+const MAX_CATCH_UP_MINUTES: i64 = 7 * 24 * 60;
+
+/// A schedule such as `0 9 * * mon-fri` -> profile `Company Proxy`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSchedule {
+    pub name: String,
+    pub cron: String, // 分 时 日 月 周，例如 "30 18 * * 1-5"
+    pub profile: String,
+    #[serde(default)]
+    pub suspended: bool, // 可从托盘暂停
+}
+
+impl ProfileSchedule {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::SettingsError("Schedule name cannot be empty".to_string()));
+        }
+        if self.profile.trim().is_empty() {
+            return Err(AppError::SettingsError(format!(
+                "Schedule '{}' has no profile",
+                self.name
+            )));
+        }
+        CronExpression::parse(&self.cron).map(|_| ())
+    }
+}
+
+/// Five-field cron expression (minute hour day-of-month month day-of-week) with
+/// `*`, lists, ranges, steps and three-letter weekday names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> AppResult<Self> {
+        let invalid = |reason: String| {
+            AppError::SettingsError(format!("Invalid schedule '{}': {}", expression, reason))
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(format!("expected 5 fields, found {}", fields.len())));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7).map_err(&invalid)?;
+        // 0 和 7 都表示周日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59).map_err(&invalid)?,
+            hours: parse_field(fields[1], 0, 23).map_err(&invalid)?,
+            days: parse_field(fields[2], 1, 31).map_err(&invalid)?,
+            months: parse_field(fields[3], 1, 12).map_err(&invalid)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        let has = |mask: u64, value: u32| mask & (1 << value) != 0;
+
+        if !has(self.minutes, time.minute())
+            || !has(self.hours, time.hour())
+            || !has(self.months, time.month())
+        {
+            return false;
+        }
+
+        // 与 cron 相同：日和周都受限时满足其一即可
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step '{}'", step))?;
+                if step == 0 {
+                    return Err("step cannot be 0".to_string());
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // "5/15" 表示从 5 开始每 15 个单位
+            (value, if step > 1 { max } else { value })
+        };
+        if start > end {
+            return Err(format!("invalid range '{}'", range));
+        }
+
+        let mut value = start;
+        while value <= end {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+    let parsed = match value.parse::<u32>() {
+        Ok(number) => number,
+        Err(_) if max == 7 => WEEKDAYS
+            .iter()
+            .position(|day| day.eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("invalid weekday '{}'", value))? as u32,
+        Err(_) => return Err(format!("invalid value '{}'", value)),
+    };
+    if !(min..=max).contains(&parsed) {
+        return Err(format!("{} is out of range {}-{}", parsed, min, max));
+    }
+    Ok(parsed)
+}
+
+/// The schedule whose most recent occurrence in `(since, now]` is the latest, so a
+/// machine waking up after several missed switches lands on the profile it would have now
+pub fn latest_due(
+    schedules: &[ProfileSchedule],
+    since: NaiveDateTime,
+    now: NaiveDateTime,
+) -> Option<usize> {
+    let expressions: Vec<Option<CronExpression>> = schedules
+        .iter()
+        .map(|schedule| {
+            if schedule.suspended {
+                return None;
+            }
+            CronExpression::parse(&schedule.cron)
+                .inspect_err(|e| log::warn!("Skipping schedule '{}': {}", schedule.name, e))
+                .ok()
+        })
+        .collect();
+    if expressions.iter().all(Option::is_none) {
+        return None;
+    }
+
+    let earliest = since.max(now - ChronoDuration::minutes(MAX_CATCH_UP_MINUTES));
+    let mut minute = now.with_second(0)?.with_nanosecond(0)?;
+    while minute > earliest {
+        let due = expressions
+            .iter()
+            .position(|expression| matches!(expression, Some(e) if e.matches(&minute)));
+        if due.is_some() {
+            return due;
+        }
+        minute -= ChronoDuration::minutes(1);
+    }
+    None
+}
+
+pub struct ScheduleService {
+    is_running: Arc<Mutex<bool>>,
+    lease_timer_running: Arc<Mutex<bool>>,
+}
+
+impl ScheduleService {
+    pub fn new() -> Self {
+        Self {
+            is_running: Arc::new(Mutex::new(false)),
+            lease_timer_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Evaluate schedules on the current tokio runtime. `schedules` is read on every check so
+    /// edits and suspensions apply without a restart; `on_due` performs the switch.
+    pub fn start<P, F>(&mut self, schedules: P, on_due: F) -> AppResult<()>
+    where
+        P: Fn() -> Vec<ProfileSchedule> + Send + 'static,
+        F: Fn(&ProfileSchedule) + Send + 'static,
+    {
+        if *self.is_running.lock().unwrap() {
+            log::warn!("Schedule service is already running");
+            return Ok(());
+        }
+        *self.is_running.lock().unwrap() = true;
+
+        let is_running = Arc::clone(&self.is_running);
+        let mut timer = interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+
+        tokio::spawn(async move {
+            // 使用墙上时钟，系统睡眠后醒来时能发现错过的时间段
+            let mut last_check = Local::now().naive_local();
+
+            loop {
+                timer.tick().await;
+
+                if !*is_running.lock().unwrap() {
+                    log::info!("Schedule service stopped");
+                    break;
+                }
+
+                let now = Local::now().naive_local();
+                if now < last_check {
+                    log::info!("System clock moved backwards, resetting schedule check");
+                    last_check = now;
+                    continue;
+                }
+                if now - last_check > ChronoDuration::seconds(CHECK_INTERVAL_SECS as i64 * 4) {
+                    log::info!(
+                        "Catching up on schedules missed since {}",
+                        last_check.format("%Y-%m-%d %H:%M")
+                    );
+                }
+
+                let schedules = schedules();
+                if let Some(index) = latest_due(&schedules, last_check, now) {
+                    log::info!(
+                        "Schedule '{}' is due, switching to '{}'",
+                        schedules[index].name,
+                        schedules[index].profile
+                    );
+                    on_due(&schedules[index]);
+                }
+                last_check = now;
+            }
+
+            log::info!("Schedule service task terminated");
+        });
+
+        log::info!("Schedule service started");
+        Ok(())
+    }
+
+    /// Call `on_tick` on the same check interval to expire temporary switches and keep the
+    /// remaining time in the tray current
+    pub fn start_lease_timer<F>(&mut self, on_tick: F) -> AppResult<()>
+    where
+        F: Fn() + Send + 'static,
+    {
+        if *self.lease_timer_running.lock().unwrap() {
+            log::warn!("Lease timer is already running");
+            return Ok(());
+        }
+        *self.lease_timer_running.lock().unwrap() = true;
+
+        let is_running = Arc::clone(&self.lease_timer_running);
+        let mut timer = interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+
+        tokio::spawn(async move {
+            loop {
+                // 首次 tick 立即触发，重启前已到期的租约会马上恢复
+                timer.tick().await;
+
+                if !*is_running.lock().unwrap() {
+                    break;
+                }
+                on_tick();
+            }
+
+            log::info!("Lease timer task terminated");
+        });
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        *self.is_running.lock().unwrap() = false;
+        *self.lease_timer_running.lock().unwrap() = false;
+    }
+}
+
+impl Default for ScheduleService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 是周一
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedule(name: &str, cron: &str, profile: &str) -> ProfileSchedule {
+        ProfileSchedule {
+            name: name.to_string(),
+            cron: cron.to_string(),
+            profile: profile.to_string(),
+            suspended: false,
+        }
+    }
+
+    #[test]
+    fn test_cron_parsing() {
+        let workdays = CronExpression::parse("0 9 * * mon-fri").unwrap();
+        assert!(workdays.matches(&at(1, 9, 0)));
+        assert!(!workdays.matches(&at(6, 9, 0))); // 周六
+        assert!(!workdays.matches(&at(1, 9, 1)));
+
+        let sunday = CronExpression::parse("*/15 18-20 * * 7").unwrap();
+        assert!(sunday.matches(&at(7, 18, 45)));
+        assert!(!sunday.matches(&at(7, 21, 0)));
+
+        // 日和周都受限时任一满足即可
+        let either = CronExpression::parse("0 0 15 * 1").unwrap();
+        assert!(either.matches(&at(8, 0, 0)));
+        assert!(either.matches(&at(15, 0, 0)));
+        assert!(!either.matches(&at(16, 0, 0)));
+
+        assert!(CronExpression::parse("0 9 * *").is_err());
+        assert!(CronExpression::parse("60 9 * * *").is_err());
+        assert!(CronExpression::parse("0 9 * * funday").is_err());
+        assert!(CronExpression::parse("*/0 9 * * *").is_err());
+    }
+
+    #[test]
+    fn test_latest_due_catches_up() {
+        let schedules = vec![
+            schedule("work", "0 9 * * 1-5", "Company Proxy"),
+            schedule("evening", "30 18 * * *", "Personal"),
+        ];
+
+        assert_eq!(latest_due(&schedules, at(1, 8, 59), at(1, 9, 0)), Some(0));
+        assert_eq!(latest_due(&schedules, at(1, 9, 0), at(1, 9, 30)), None);
+
+        // 笔记本从周一中午睡到周二 10 点：最近错过的是周二 9 点
+        assert_eq!(latest_due(&schedules, at(1, 12, 0), at(2, 10, 0)), Some(0));
+        assert_eq!(latest_due(&schedules, at(1, 12, 0), at(1, 22, 0)), Some(1));
+
+        let mut suspended = schedules.clone();
+        suspended[1].suspended = true;
+        assert_eq!(latest_due(&suspended, at(1, 12, 0), at(1, 22, 0)), None);
+    }
+}
@@ -0,0 +1,605 @@
+// Encrypted secret vault referenced from profiles as ${secret:name}
+use crate::file_permissions;
+use crate::{AppError, AppResult, SecretInfo, VaultStatus};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const VAULT_FORMAT_VERSION: u32 = 1;
+const VERIFIER_PLAINTEXT: &[u8] = b"cccs-secret-vault";
+const KEYRING_SERVICE: &str = "cccs";
+const KEYRING_USER: &str = "secret-vault-key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultKeySource {
+    Passphrase,
+    Keyring,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedValue {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredSecret {
+    #[serde(flatten)]
+    value: EncryptedValue,
+    created_at: SystemTime,
+    updated_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    key_source: VaultKeySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    verifier: EncryptedValue,
+    #[serde(default)]
+    secrets: BTreeMap<String, StoredSecret>,
+}
+
+pub struct SecretVault {
+    vault_path: PathBuf,
+    data: Option<VaultFile>,
+    key: Option<[u8; 32]>,
+    unavailable: Option<String>, // 金库文件无法读取或解析时的错误；此时不能初始化或解锁，避免覆盖原文件
+}
+
+impl SecretVault {
+    /// Open the vault at the given path (the file is created on initialization)
+    pub fn open(vault_path: PathBuf) -> AppResult<Self> {
+        let data = if vault_path.exists() {
+            let content = fs::read_to_string(&vault_path).map_err(|e| {
+                AppError::SecretError(format!("Failed to read vault file: {}", e))
+            })?;
+            let data: VaultFile = serde_json::from_str(&content).map_err(|e| {
+                AppError::SecretError(format!("Failed to parse vault file: {}", e))
+            })?;
+            if data.version > VAULT_FORMAT_VERSION {
+                return Err(AppError::SecretError(format!(
+                    "Unsupported vault format version: {}",
+                    data.version
+                )));
+            }
+            Some(data)
+        } else {
+            None
+        };
+
+        let mut vault = Self {
+            vault_path,
+            data,
+            key: None,
+            unavailable: None,
+        };
+
+        // Keyring-protected vaults can be unlocked without user interaction
+        if vault.key_source() == Some(VaultKeySource::Keyring) {
+            if let Err(e) = vault.unlock(None) {
+                log::warn!("Failed to unlock secret vault from keyring: {}", e);
+            }
+        }
+
+        Ok(vault)
+    }
+
+    /// Open the vault at its default location in the CCCS config directory
+    pub fn open_default() -> AppResult<Self> {
+        Self::open(Self::default_vault_path()?)
+    }
+
+    /// Vault that could not be opened; it stays locked and the file is left untouched
+    pub fn unavailable(vault_path: PathBuf, error: &AppError) -> Self {
+        Self {
+            vault_path,
+            data: None,
+            key: None,
+            unavailable: Some(error.to_string()),
+        }
+    }
+
+    fn ensure_available(&self) -> AppResult<()> {
+        match &self.unavailable {
+            Some(error) => Err(AppError::SecretError(format!(
+                "Secret vault is unavailable: {}",
+                error
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the default vault file path
+    pub fn default_vault_path() -> AppResult<PathBuf> {
+        Ok(crate::settings_service::SettingsService::get_settings_directory()?
+            .join("secrets.vault.json"))
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.data.is_some()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    pub fn key_source(&self) -> Option<VaultKeySource> {
+        self.data.as_ref().map(|d| d.key_source)
+    }
+
+    /// Get a summary of the vault state
+    pub fn get_status(&self) -> VaultStatus {
+        VaultStatus {
+            initialized: self.is_initialized(),
+            unlocked: self.is_unlocked(),
+            key_source: self.key_source().map(|s| match s {
+                VaultKeySource::Passphrase => "passphrase".to_string(),
+                VaultKeySource::Keyring => "keyring".to_string(),
+            }),
+            secret_count: self.data.as_ref().map(|d| d.secrets.len()).unwrap_or(0),
+            error: self.unavailable.clone(),
+        }
+    }
+
+    /// Create a new empty vault protected by a passphrase, or by a random key
+    /// stored in the OS keyring when no passphrase is given
+    pub fn initialize(&mut self, passphrase: Option<&str>) -> AppResult<()> {
+        self.ensure_available()?;
+        if self.is_initialized() {
+            return Err(AppError::SecretError(
+                "Secret vault is already initialized".to_string(),
+            ));
+        }
+
+        let (key_source, salt, key) = match passphrase {
+            Some(passphrase) => {
+                if passphrase.is_empty() {
+                    return Err(AppError::SecretError(
+                        "Vault passphrase cannot be empty".to_string(),
+                    ));
+                }
+                let mut salt = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut salt);
+                let key = Self::derive_key(passphrase, &salt)?;
+                (VaultKeySource::Passphrase, Some(BASE64.encode(salt)), key)
+            }
+            None => {
+                let mut key = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                Self::keyring_entry()?
+                    .set_password(&BASE64.encode(key))
+                    .map_err(|e| {
+                        AppError::SecretError(format!("Failed to store vault key in keyring: {}", e))
+                    })?;
+                (VaultKeySource::Keyring, None, key)
+            }
+        };
+
+        let verifier = Self::encrypt(&key, VERIFIER_PLAINTEXT)?;
+        self.data = Some(VaultFile {
+            version: VAULT_FORMAT_VERSION,
+            key_source,
+            salt,
+            verifier,
+            secrets: BTreeMap::new(),
+        });
+        self.key = Some(key);
+        self.save()?;
+
+        log::info!("Initialized secret vault at {:?}", self.vault_path);
+        Ok(())
+    }
+
+    /// Unlock the vault with a passphrase, or from the OS keyring
+    pub fn unlock(&mut self, passphrase: Option<&str>) -> AppResult<()> {
+        self.ensure_available()?;
+        let data = self.data.as_ref().ok_or_else(|| {
+            AppError::SecretError("Secret vault is not initialized".to_string())
+        })?;
+
+        let key = match (data.key_source, passphrase) {
+            (VaultKeySource::Passphrase, Some(passphrase)) => {
+                let salt = data
+                    .salt
+                    .as_deref()
+                    .ok_or_else(|| AppError::SecretError("Vault salt is missing".to_string()))?;
+                let salt = BASE64
+                    .decode(salt)
+                    .map_err(|e| AppError::SecretError(format!("Invalid vault salt: {}", e)))?;
+                Self::derive_key(passphrase, &salt)?
+            }
+            (VaultKeySource::Passphrase, None) => {
+                return Err(AppError::SecretError(
+                    "A passphrase is required to unlock the secret vault".to_string(),
+                ));
+            }
+            (VaultKeySource::Keyring, _) => {
+                let encoded = Self::keyring_entry()?.get_password().map_err(|e| {
+                    AppError::SecretError(format!("Failed to read vault key from keyring: {}", e))
+                })?;
+                let bytes = BASE64.decode(encoded).map_err(|e| {
+                    AppError::SecretError(format!("Invalid vault key in keyring: {}", e))
+                })?;
+                bytes.try_into().map_err(|_| {
+                    AppError::SecretError("Invalid vault key length in keyring".to_string())
+                })?
+            }
+        };
+
+        match Self::decrypt(&key, &data.verifier) {
+            Ok(plaintext) if plaintext == VERIFIER_PLAINTEXT => {
+                self.key = Some(key);
+                log::info!("Secret vault unlocked");
+                Ok(())
+            }
+            _ => Err(AppError::SecretError(
+                "Incorrect passphrase for secret vault".to_string(),
+            )),
+        }
+    }
+
+    /// Forget the in-memory key
+    pub fn lock(&mut self) {
+        self.key = None;
+        log::info!("Secret vault locked");
+    }
+
+    /// Add a new secret
+    pub fn add_secret(&mut self, name: &str, value: &str) -> AppResult<()> {
+        Self::validate_secret_name(name)?;
+        if self.secrets()?.contains_key(name) {
+            return Err(AppError::SecretError(format!(
+                "Secret '{}' already exists",
+                name
+            )));
+        }
+
+        let encrypted = Self::encrypt(self.unlocked_key()?, value.as_bytes())?;
+        let now = SystemTime::now();
+        self.secrets_mut()?.insert(
+            name.to_string(),
+            StoredSecret {
+                value: encrypted,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        self.save()?;
+
+        log::info!("Added secret: {}", name);
+        Ok(())
+    }
+
+    /// Replace the value of an existing secret
+    pub fn rotate_secret(&mut self, name: &str, new_value: &str) -> AppResult<()> {
+        let encrypted = Self::encrypt(self.unlocked_key()?, new_value.as_bytes())?;
+        let secret = self
+            .secrets_mut()?
+            .get_mut(name)
+            .ok_or_else(|| AppError::SecretError(format!("Secret '{}' not found", name)))?;
+        secret.value = encrypted;
+        secret.updated_at = SystemTime::now();
+        self.save()?;
+
+        log::info!("Rotated secret: {}", name);
+        Ok(())
+    }
+
+    /// Remove a secret
+    pub fn remove_secret(&mut self, name: &str) -> AppResult<()> {
+        if self.secrets_mut()?.remove(name).is_none() {
+            return Err(AppError::SecretError(format!("Secret '{}' not found", name)));
+        }
+        self.save()?;
+
+        log::info!("Removed secret: {}", name);
+        Ok(())
+    }
+
+    /// List secret names and timestamps (values are never returned)
+    pub fn list_secrets(&self) -> Vec<SecretInfo> {
+        self.data
+            .as_ref()
+            .map(|d| {
+                d.secrets
+                    .iter()
+                    .map(|(name, secret)| SecretInfo {
+                        name: name.clone(),
+                        created_at: secret.created_at,
+                        updated_at: secret.updated_at,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Decrypt a secret value
+    pub fn get_secret(&self, name: &str) -> AppResult<String> {
+        let key = self.unlocked_key()?;
+        let secret = self
+            .secrets()?
+            .get(name)
+            .ok_or_else(|| AppError::SecretError(format!("Secret '{}' not found", name)))?;
+        let plaintext = Self::decrypt(key, &secret.value)?;
+        String::from_utf8(plaintext)
+            .map_err(|e| AppError::SecretError(format!("Secret '{}' is not valid UTF-8: {}", name, e)))
+    }
+
+    /// Validate a secret name (letters, digits, '-', '_' and '.')
+    pub fn validate_secret_name(name: &str) -> AppResult<()> {
+        if name.is_empty() {
+            return Err(AppError::SecretError("Secret name cannot be empty".to_string()));
+        }
+        if name.len() > 100 {
+            return Err(AppError::SecretError(
+                "Secret name too long (max 100 characters)".to_string(),
+            ));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(AppError::SecretError(format!(
+                "Secret name '{}' contains invalid characters. Use letters, digits, '-', '_' or '.'",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    fn secrets(&self) -> AppResult<&BTreeMap<String, StoredSecret>> {
+        self.data
+            .as_ref()
+            .map(|d| &d.secrets)
+            .ok_or_else(|| AppError::SecretError("Secret vault is not initialized".to_string()))
+    }
+
+    fn secrets_mut(&mut self) -> AppResult<&mut BTreeMap<String, StoredSecret>> {
+        self.data
+            .as_mut()
+            .map(|d| &mut d.secrets)
+            .ok_or_else(|| AppError::SecretError("Secret vault is not initialized".to_string()))
+    }
+
+    fn unlocked_key(&self) -> AppResult<&[u8; 32]> {
+        if !self.is_initialized() {
+            return Err(AppError::SecretError(
+                "Secret vault is not initialized".to_string(),
+            ));
+        }
+        self.key
+            .as_ref()
+            .ok_or_else(|| AppError::SecretError("Secret vault is locked".to_string()))
+    }
+
+    fn keyring_entry() -> AppResult<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .map_err(|e| AppError::SecretError(format!("Failed to access OS keyring: {}", e)))
+    }
+
+    fn derive_key(passphrase: &str, salt: &[u8]) -> AppResult<[u8; 32]> {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| AppError::SecretError(format!("Failed to derive vault key: {}", e)))?;
+        Ok(key)
+    }
+
+    fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> AppResult<EncryptedValue> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|e| AppError::SecretError(format!("Failed to encrypt secret: {}", e)))?;
+        Ok(EncryptedValue {
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    fn decrypt(key: &[u8; 32], value: &EncryptedValue) -> AppResult<Vec<u8>> {
+        let nonce = BASE64
+            .decode(&value.nonce)
+            .map_err(|e| AppError::SecretError(format!("Invalid nonce: {}", e)))?;
+        if nonce.len() != 12 {
+            return Err(AppError::SecretError("Invalid nonce length".to_string()));
+        }
+        let ciphertext = BASE64
+            .decode(&value.ciphertext)
+            .map_err(|e| AppError::SecretError(format!("Invalid ciphertext: {}", e)))?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| AppError::SecretError("Failed to decrypt secret".to_string()))
+    }
+
+    /// Persist the vault file atomically
+    fn save(&self) -> AppResult<()> {
+        let data = match &self.data {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+
+        if let Some(parent) = self.vault_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                AppError::SecretError(format!("Failed to create vault directory: {}", e))
+            })?;
+        }
+
+        let content = serde_json::to_string_pretty(data)
+            .map_err(|e| AppError::SecretError(format!("Failed to serialize vault: {}", e)))?;
+
+        let temp_path = self.vault_path.with_extension("json.tmp");
+        file_permissions::write_private_file(&temp_path, content.as_bytes())
+            .map_err(|e| AppError::SecretError(format!("Failed to write vault file: {}", e)))?;
+        fs::rename(&temp_path, &self.vault_path).map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            AppError::SecretError(format!("Failed to save vault file: {}", e))
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Decrypt the named secrets for ${secret:name} placeholders.
+/// Fails listing all missing secrets if any name cannot be resolved.
+pub fn resolve_secrets(
+    names: &[String],
+    vault: Option<&SecretVault>,
+) -> AppResult<BTreeMap<String, String>> {
+    let vault = vault.ok_or_else(|| {
+        AppError::SecretError(format!(
+            "Profile references secrets ({}) but no secret vault is configured",
+            names.join(", ")
+        ))
+    })?;
+    if !vault.is_initialized() {
+        return Err(AppError::SecretError(format!(
+            "Profile references secrets ({}) but the secret vault is not initialized",
+            names.join(", ")
+        )));
+    }
+    if !vault.is_unlocked() {
+        return Err(AppError::SecretError(format!(
+            "Profile references secrets ({}) but the secret vault is locked",
+            names.join(", ")
+        )));
+    }
+
+    let mut resolved = BTreeMap::new();
+    let mut missing = Vec::new();
+    for name in names {
+        match vault.get_secret(name) {
+            Ok(secret) => {
+                resolved.insert(name.clone(), secret);
+            }
+            Err(_) => missing.push(name.clone()),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(AppError::SecretError(format!(
+            "Missing secrets referenced by profile: {}",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_vault() -> (SecretVault, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let mut vault = SecretVault::open(temp_dir.path().join("vault.json")).unwrap();
+        vault.initialize(Some("correct horse")).unwrap();
+        (vault, temp_dir)
+    }
+
+    #[test]
+    fn test_add_and_get_secret() {
+        let (mut vault, _temp_dir) = create_test_vault();
+
+        vault.add_secret("work-token", "sk-123").unwrap();
+        assert_eq!(vault.get_secret("work-token").unwrap(), "sk-123");
+        assert!(vault.add_secret("work-token", "other").is_err());
+
+        // Values are not stored in plaintext
+        let file_content = fs::read_to_string(&vault.vault_path).unwrap();
+        assert!(!file_content.contains("sk-123"));
+    }
+
+    #[test]
+    fn test_corrupt_vault_is_unavailable() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vault.json");
+        fs::write(&path, "{ corrupt").unwrap();
+
+        let error = SecretVault::open(path.clone()).err().unwrap();
+        let mut vault = SecretVault::unavailable(path.clone(), &error);
+        assert!(!vault.is_unlocked());
+        assert!(vault.get_status().error.is_some());
+        assert!(vault.initialize(Some("passphrase")).is_err());
+        assert!(vault.unlock(Some("passphrase")).is_err());
+        // 原文件保持不变，便于用户手动恢复
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ corrupt");
+    }
+
+    #[test]
+    fn test_unlock_with_passphrase() {
+        let (mut vault, temp_dir) = create_test_vault();
+        vault.add_secret("token", "value").unwrap();
+
+        let mut reopened = SecretVault::open(temp_dir.path().join("vault.json")).unwrap();
+        assert!(reopened.is_initialized());
+        assert!(!reopened.is_unlocked());
+        assert!(reopened.get_secret("token").is_err());
+
+        assert!(reopened.unlock(Some("wrong")).is_err());
+        reopened.unlock(Some("correct horse")).unwrap();
+        assert_eq!(reopened.get_secret("token").unwrap(), "value");
+    }
+
+    #[test]
+    fn test_rotate_and_remove_secret() {
+        let (mut vault, _temp_dir) = create_test_vault();
+        vault.add_secret("token", "old").unwrap();
+
+        vault.rotate_secret("token", "new").unwrap();
+        assert_eq!(vault.get_secret("token").unwrap(), "new");
+        assert_eq!(vault.list_secrets().len(), 1);
+
+        vault.remove_secret("token").unwrap();
+        assert!(vault.list_secrets().is_empty());
+        assert!(vault.remove_secret("token").is_err());
+        assert!(vault.rotate_secret("token", "x").is_err());
+    }
+
+    #[test]
+    fn test_validate_secret_name() {
+        assert!(SecretVault::validate_secret_name("work-token_1.a").is_ok());
+        assert!(SecretVault::validate_secret_name("").is_err());
+        assert!(SecretVault::validate_secret_name("has space").is_err());
+        assert!(SecretVault::validate_secret_name("brace}").is_err());
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        let (mut vault, _temp_dir) = create_test_vault();
+        vault.add_secret("work-token", "sk-123").unwrap();
+
+        let resolved = resolve_secrets(&["work-token".to_string()], Some(&vault)).unwrap();
+        assert_eq!(resolved.get("work-token").map(String::as_str), Some("sk-123"));
+    }
+
+    #[test]
+    fn test_resolve_missing_secret_fails() {
+        let (vault, _temp_dir) = create_test_vault();
+        let names = vec!["absent".to_string()];
+
+        match resolve_secrets(&names, Some(&vault)) {
+            Err(AppError::SecretError(msg)) => assert!(msg.contains("absent")),
+            _ => panic!("Expected SecretError"),
+        }
+
+        // Without a vault, references cannot be resolved either
+        assert!(resolve_secrets(&names, None).is_err());
+
+        // A locked vault refuses to resolve
+        let mut locked = vault;
+        locked.lock();
+        assert!(resolve_secrets(&names, Some(&locked)).is_err());
+    }
+}
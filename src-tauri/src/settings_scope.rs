@@ -0,0 +1,136 @@
+// Settings scopes a profile can be applied to: user level or a registered project
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "project", rename_all = "snake_case")]
+pub enum SettingsScope {
+    User,                   // ~/.claude/settings.json
+    ProjectShared(PathBuf), // <project>/.claude/settings.json，随仓库共享
+    ProjectLocal(PathBuf),  // <project>/.claude/settings.local.json，仅本机
+}
+
+impl SettingsScope {
+    pub fn project(&self) -> Option<&Path> {
+        match self {
+            Self::User => None,
+            Self::ProjectShared(project) | Self::ProjectLocal(project) => Some(project),
+        }
+    }
+
+    /// The settings file Claude Code reads for this scope
+    pub fn settings_path(&self, claude_dir: &Path) -> PathBuf {
+        match self {
+            Self::User => claude_dir.join("settings.json"),
+            Self::ProjectShared(project) => project.join(".claude").join("settings.json"),
+            Self::ProjectLocal(project) => project.join(".claude").join("settings.local.json"),
+        }
+    }
+
+    /// Where bundle artifacts (CLAUDE.md, agents, commands) go; the local scope has none
+    pub fn artifact_dir(&self, claude_dir: &Path) -> Option<PathBuf> {
+        match self {
+            Self::User => Some(claude_dir.to_path_buf()),
+            Self::ProjectShared(project) => Some(project.join(".claude")),
+            Self::ProjectLocal(_) => None,
+        }
+    }
+
+    /// Serialized kind, also passed to switch hooks as CCCS_SCOPE
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::ProjectShared(_) => "project_shared",
+            Self::ProjectLocal(_) => "project_local",
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::User => "user".to_string(),
+            Self::ProjectShared(project) => format!("project {} (shared)", project.display()),
+            Self::ProjectLocal(project) => format!("project {} (local)", project.display()),
+        }
+    }
+
+    /// Project scopes need an existing absolute project directory
+    pub fn validate(&self) -> AppResult<()> {
+        let Some(project) = self.project() else {
+            return Ok(());
+        };
+        if !project.is_absolute() {
+            return Err(AppError::ConfigError(format!(
+                "Project path must be absolute: {}",
+                project.display()
+            )));
+        }
+        if !project.is_dir() {
+            return Err(AppError::ConfigError(format!(
+                "Project directory does not exist: {}",
+                project.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// User scope plus the shared and local scope of every project
+    pub fn all(projects: &[PathBuf]) -> Vec<Self> {
+        let mut scopes = vec![Self::User];
+        for project in projects {
+            scopes.push(Self::ProjectShared(project.clone()));
+            scopes.push(Self::ProjectLocal(project.clone()));
+        }
+        scopes
+    }
+}
+
+/// Status of one profile in one scope, as shown next to the scope selector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeStatus {
+    pub scope: SettingsScope,
+    pub settings_path: String,
+    pub status: String, // 与 get_profile_status 相同的状态图标
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_paths() {
+        let claude_dir = Path::new("/home/u/.claude");
+        let project = PathBuf::from("/work/app");
+
+        assert_eq!(
+            SettingsScope::User.settings_path(claude_dir),
+            claude_dir.join("settings.json")
+        );
+        assert_eq!(
+            SettingsScope::ProjectLocal(project.clone()).settings_path(claude_dir),
+            project.join(".claude/settings.local.json")
+        );
+        assert_eq!(
+            SettingsScope::ProjectShared(project.clone()).artifact_dir(claude_dir),
+            Some(project.join(".claude"))
+        );
+        assert!(SettingsScope::ProjectLocal(project.clone())
+            .artifact_dir(claude_dir)
+            .is_none());
+        assert_eq!(SettingsScope::all(&[project]).len(), 3);
+    }
+
+    #[test]
+    fn test_scope_serialization() {
+        let scope: SettingsScope =
+            serde_json::from_str(r#"{"kind": "project_local", "project": "/work/app"}"#).unwrap();
+        assert_eq!(scope, SettingsScope::ProjectLocal(PathBuf::from("/work/app")));
+        assert_eq!(
+            serde_json::to_value(SettingsScope::User).unwrap(),
+            serde_json::json!({"kind": "user"})
+        );
+        assert!(SettingsScope::ProjectShared(PathBuf::from("relative"))
+            .validate()
+            .is_err());
+    }
+}
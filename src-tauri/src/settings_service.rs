@@ -1,7 +1,15 @@
 // Settings service for user configuration management
+use crate::directory_rules::{self, DirectoryRule, RuleDryRun};
+use crate::profile_signing::TrustedKey;
+use crate::schedule_service::ProfileSchedule;
+use crate::subscriptions::ProfileSubscription;
 use crate::{AppError, AppResult, UserSettings};
 use std::path::{Path, PathBuf};
 use std::fs;
+use tauri::Emitter;
+
+// 目录规则或项目列表变化后发出，应用会重新执行目录规则
+pub const DIRECTORY_RULES_CHANGED_EVENT: &str = "directory_rules_changed";
 
 pub struct SettingsService {
     settings_file_path: PathBuf,
@@ -45,7 +53,7 @@ impl SettingsService {
     }
     
     /// Get the platform-specific settings directory
-    fn get_settings_directory() -> AppResult<PathBuf> {
+    pub fn get_settings_directory() -> AppResult<PathBuf> {
         dirs::config_dir()
             .map(|dir| dir.join("cccs"))
             .ok_or_else(|| AppError::SettingsError("Failed to get config directory".to_string()))
@@ -120,6 +128,21 @@ impl SettingsService {
         crate::UserSettings::validate_ignored_fields(&settings.ignored_fields)
             .map_err(|e| AppError::SettingsError(format!("Invalid ignored fields in settings: {}", e)))?;
         
+        for rule in &settings.directory_rules {
+            rule.validate()?;
+        }
+        Self::validate_profile_schedules(&settings.profile_schedules)?;
+        Self::validate_subscriptions(&settings.subscriptions)?;
+        Self::validate_trusted_keys(&settings.trusted_keys)?;
+        
+        if settings.recent_profiles_count > crate::profile_metadata::MAX_RECENT_PROFILES {
+            return Err(AppError::SettingsError(format!(
+                "Invalid recent profiles count: {}. Must be at most {}.",
+                settings.recent_profiles_count,
+                crate::profile_metadata::MAX_RECENT_PROFILES
+            )));
+        }
+        
         Ok(())
     }
     
@@ -242,6 +265,185 @@ impl SettingsService {
         Ok(())
     }
     
+    /// Get registered project directories
+    pub fn get_projects(&self) -> &[PathBuf] {
+        &self.current_settings.projects
+    }
+    
+    /// Register a project directory so profiles can be applied to its scopes
+    pub fn add_project(&mut self, project: &Path) -> AppResult<PathBuf> {
+        let project = project.canonicalize().map_err(|e| {
+            AppError::SettingsError(format!("Invalid project directory {}: {}", project.display(), e))
+        })?;
+        if !project.is_dir() {
+            return Err(AppError::SettingsError(format!(
+                "Project path is not a directory: {}",
+                project.display()
+            )));
+        }
+        
+        if !self.current_settings.projects.contains(&project) {
+            log::info!("Registering project: {}", project.display());
+            self.current_settings.projects.push(project.clone());
+            self.save_settings(&self.current_settings)?;
+        }
+        Ok(project)
+    }
+    
+    /// Unregister a project directory; its settings files are left untouched
+    pub fn remove_project(&mut self, project: &Path) -> AppResult<()> {
+        let before = self.current_settings.projects.len();
+        self.current_settings.projects.retain(|p| p != project);
+        if self.current_settings.projects.len() == before {
+            return Err(AppError::SettingsError(format!(
+                "Project not registered: {}",
+                project.display()
+            )));
+        }
+        
+        log::info!("Unregistered project: {}", project.display());
+        self.save_settings(&self.current_settings)
+    }
+    
+    /// Get directory rules in match order
+    pub fn get_directory_rules(&self) -> &[DirectoryRule] {
+        &self.current_settings.directory_rules
+    }
+    
+    /// Replace the directory rules; every pattern must compile
+    pub fn update_directory_rules(&mut self, rules: Vec<DirectoryRule>) -> AppResult<()> {
+        for rule in &rules {
+            rule.validate()?;
+        }
+        
+        self.current_settings.directory_rules = rules;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Directory rules updated: {} rules", self.current_settings.directory_rules.len());
+        Ok(())
+    }
+    
+    /// Show which directory rule matches `path` without applying it
+    pub fn dry_run_directory_rules(&self, path: &Path) -> RuleDryRun {
+        directory_rules::dry_run(
+            &self.current_settings.directory_rules,
+            &self.current_settings.projects,
+            path,
+        )
+    }
+    
+    /// Get profile schedules
+    pub fn get_profile_schedules(&self) -> &[ProfileSchedule] {
+        &self.current_settings.profile_schedules
+    }
+    
+    /// Replace the profile schedules
+    pub fn update_profile_schedules(&mut self, schedules: Vec<ProfileSchedule>) -> AppResult<()> {
+        Self::validate_profile_schedules(&schedules)?;
+        
+        self.current_settings.profile_schedules = schedules;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Profile schedules updated: {} schedules", self.current_settings.profile_schedules.len());
+        Ok(())
+    }
+    
+    /// Suspend or resume a schedule by name
+    pub fn set_schedule_suspended(&mut self, name: &str, suspended: bool) -> AppResult<()> {
+        let schedule = self.current_settings.profile_schedules
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or_else(|| AppError::SettingsError(format!("Schedule not found: {}", name)))?;
+        schedule.suspended = suspended;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Schedule '{}' {}", name, if suspended { "suspended" } else { "resumed" });
+        Ok(())
+    }
+    
+    /// Remember whether the git-backed profile store is enabled
+    pub fn set_git_store_enabled(&mut self, enabled: bool) -> AppResult<()> {
+        self.current_settings.git_store_enabled = enabled;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Git profile store setting: {}", enabled);
+        Ok(())
+    }
+    
+    /// Get team profile subscriptions
+    pub fn get_subscriptions(&self) -> &[ProfileSubscription] {
+        &self.current_settings.subscriptions
+    }
+    
+    /// Replace the team profile subscriptions
+    pub fn update_subscriptions(&mut self, subscriptions: Vec<ProfileSubscription>) -> AppResult<()> {
+        Self::validate_subscriptions(&subscriptions)?;
+        
+        self.current_settings.subscriptions = subscriptions;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Subscriptions updated: {} subscriptions", self.current_settings.subscriptions.len());
+        Ok(())
+    }
+    
+    /// Subscription names prefix profile names and must be unique
+    fn validate_subscriptions(subscriptions: &[ProfileSubscription]) -> AppResult<()> {
+        for (i, subscription) in subscriptions.iter().enumerate() {
+            subscription.validate()?;
+            if subscriptions[..i].iter().any(|s| s.name == subscription.name) {
+                return Err(AppError::SettingsError(format!(
+                    "Duplicate subscription name: {}",
+                    subscription.name
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Get the public keys whose profile signatures are trusted
+    pub fn get_trusted_keys(&self) -> &[TrustedKey] {
+        &self.current_settings.trusted_keys
+    }
+    
+    /// Replace the trusted signing keys
+    pub fn update_trusted_keys(&mut self, keys: Vec<TrustedKey>) -> AppResult<()> {
+        Self::validate_trusted_keys(&keys)?;
+        
+        self.current_settings.trusted_keys = keys;
+        self.save_settings(&self.current_settings)?;
+        
+        log::info!("Trusted keys updated: {} keys", self.current_settings.trusted_keys.len());
+        Ok(())
+    }
+    
+    /// Key names are shown as the signer of a profile and must be unique
+    fn validate_trusted_keys(keys: &[TrustedKey]) -> AppResult<()> {
+        for (i, key) in keys.iter().enumerate() {
+            key.validate()?;
+            if keys[..i].iter().any(|k| k.name == key.name) {
+                return Err(AppError::SettingsError(format!(
+                    "Duplicate trusted key name: {}",
+                    key.name
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Validate schedules; names identify schedules in the tray and must be unique
+    fn validate_profile_schedules(schedules: &[ProfileSchedule]) -> AppResult<()> {
+        for (i, schedule) in schedules.iter().enumerate() {
+            schedule.validate()?;
+            if schedules[..i].iter().any(|s| s.name == schedule.name) {
+                return Err(AppError::SettingsError(format!(
+                    "Duplicate schedule name: {}",
+                    schedule.name
+                )));
+            }
+        }
+        Ok(())
+    }
+    
     /// Get settings file path
     pub fn get_settings_file_path(&self) -> &Path {
         &self.settings_file_path
@@ -399,6 +601,135 @@ pub async fn reset_ignored_fields_to_default(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_projects(
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<Vec<PathBuf>, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.get_projects().to_vec())
+}
+
+#[tauri::command]
+pub async fn add_project(
+    project: PathBuf,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<PathBuf, String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    let added = service.add_project(&project)
+        .map_err(|e| e.to_string())?;
+    drop(service);
+    let _ = app_handle.emit(DIRECTORY_RULES_CHANGED_EVENT, ());
+    Ok(added)
+}
+
+#[tauri::command]
+pub async fn remove_project(
+    project: PathBuf,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<(), String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.remove_project(&project)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_directory_rules(
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<Vec<DirectoryRule>, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.get_directory_rules().to_vec())
+}
+
+#[tauri::command]
+pub async fn update_directory_rules(
+    rules: Vec<DirectoryRule>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<(), String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.update_directory_rules(rules)
+        .map_err(|e| e.to_string())?;
+    drop(service);
+    let _ = app_handle.emit(DIRECTORY_RULES_CHANGED_EVENT, ());
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn dry_run_directory_rules(
+    path: PathBuf,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<RuleDryRun, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.dry_run_directory_rules(&path))
+}
+
+#[tauri::command]
+pub async fn get_profile_schedules(
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<Vec<ProfileSchedule>, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.get_profile_schedules().to_vec())
+}
+
+#[tauri::command]
+pub async fn update_profile_schedules(
+    schedules: Vec<ProfileSchedule>,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<(), String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.update_profile_schedules(schedules)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_schedule_suspended(
+    name: String,
+    suspended: bool,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<(), String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.set_schedule_suspended(&name, suspended)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_subscriptions(
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<Vec<ProfileSubscription>, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.get_subscriptions().to_vec())
+}
+
+#[tauri::command]
+pub async fn export_settings(
+    path: String,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<(), String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.export_settings(Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_settings(
+    path: String,
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<UserSettings, String> {
+    let mut service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    service.import_settings(Path::new(&path))
+        .map_err(|e| e.to_string())?;
+    Ok(service.get_current_settings().clone())
+}
+
+#[tauri::command]
+pub async fn get_trusted_keys(
+    state: tauri::State<'_, std::sync::Mutex<SettingsService>>,
+) -> Result<Vec<TrustedKey>, String> {
+    let service = state.lock().map_err(|e| format!("Failed to lock settings service: {}", e))?;
+    Ok(service.get_trusted_keys().to_vec())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -519,4 +850,67 @@ mod tests {
         assert!(backup_path.exists());
         assert!(backup_path.file_name().unwrap().to_str().unwrap().starts_with("cccs_settings_backup_"));
     }
+    
+    #[test]
+    fn test_register_projects() {
+        let (mut service, temp_dir) = create_test_settings_service();
+        let project = temp_dir.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        
+        let registered = service.add_project(&project).unwrap();
+        assert_eq!(registered, project.canonicalize().unwrap());
+        service.add_project(&project).unwrap();
+        assert_eq!(service.get_projects().len(), 1);
+        
+        assert!(service.add_project(&temp_dir.path().join("missing")).is_err());
+        
+        service.remove_project(&registered).unwrap();
+        assert!(service.get_projects().is_empty());
+        assert!(service.remove_project(&registered).is_err());
+    }
+    
+    #[test]
+    fn test_directory_rules() {
+        let (mut service, temp_dir) = create_test_settings_service();
+        let project = service.add_project(temp_dir.path()).unwrap();
+        
+        let rule = DirectoryRule {
+            pattern: format!("{}/**", project.display()),
+            profile: "ClientA".to_string(),
+            scope: directory_rules::RuleScope::ProjectShared,
+            enabled: true,
+        };
+        service.update_directory_rules(vec![rule.clone()]).unwrap();
+        
+        let loaded = service.load_settings().unwrap();
+        assert_eq!(loaded.directory_rules, vec![rule.clone()]);
+        
+        let result = service.dry_run_directory_rules(&project);
+        assert_eq!(result.rule, Some(0));
+        assert_eq!(result.profile.as_deref(), Some("ClientA"));
+        
+        let invalid = DirectoryRule { pattern: "relative/*".to_string(), ..rule };
+        assert!(service.update_directory_rules(vec![invalid]).is_err());
+        assert_eq!(service.get_directory_rules().len(), 1);
+    }
+    
+    #[test]
+    fn test_profile_schedules() {
+        let (mut service, _temp_dir) = create_test_settings_service();
+        
+        let schedule = ProfileSchedule {
+            name: "work".to_string(),
+            cron: "0 9 * * mon-fri".to_string(),
+            profile: "Company Proxy".to_string(),
+            suspended: false,
+        };
+        service.update_profile_schedules(vec![schedule.clone()]).unwrap();
+        assert!(service.update_profile_schedules(vec![schedule.clone(), schedule.clone()]).is_err());
+        
+        service.set_schedule_suspended("work", true).unwrap();
+        assert!(service.set_schedule_suspended("missing", true).is_err());
+        
+        let loaded = service.load_settings().unwrap();
+        assert!(loaded.profile_schedules[0].suspended);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,540 @@
+// Team profile subscriptions: read-only profiles from a shared directory with local override layers
+use crate::file_permissions;
+use crate::profile_signing::{self, SignatureState, TrustedKey};
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const UPSTREAM_DIR: &str = "upstream"; // 上次同步时的上游快照
+const OVERRIDES_DIR: &str = "overrides"; // 本地覆盖层（JSON merge patch）
+const PROFILES_DIR: &str = "profiles"; // 快照叠加覆盖层后的实际配置
+const PROFILE_SUFFIX: &str = ".settings.json";
+
+/// A shared profile folder (synced directory or git checkout) subscribed to by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSubscription {
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ProfileSubscription {
+    /// Names become part of profile names ("team:work") and of directory names
+    pub fn validate(&self) -> AppResult<()> {
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(AppError::SettingsError(format!(
+                "Invalid subscription name '{}': use letters, digits, '-' and '_'",
+                self.name
+            )));
+        }
+        if !self.path.is_absolute() {
+            return Err(AppError::SettingsError(format!(
+                "Subscription path must be absolute: {}",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// How a subscribed profile compares to the shared directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamState {
+    UpToDate,
+    UpdateAvailable, // 上游已修改
+    New,             // 上游新增，尚未同步
+    Removed,         // 上游已删除，本地仍保留快照
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribedProfileState {
+    pub profile: String,
+    pub state: UpstreamState,
+    pub has_override: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionStatus {
+    pub name: String,
+    pub path: PathBuf,
+    pub enabled: bool,
+    pub error: Option<String>, // 共享目录不可读时的原因
+    pub profiles: Vec<SubscribedProfileState>,
+    pub update_available: bool, // 任一配置与上游不一致
+}
+
+/// Local state of all subscriptions: `<dir>/<subscription>/{upstream,overrides,profiles}/`
+pub struct SubscriptionStore {
+    dir: PathBuf,
+}
+
+impl SubscriptionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn sub_dir(&self, subscription: &str) -> PathBuf {
+        self.dir.join(subscription)
+    }
+
+    pub fn is_synced(&self, subscription: &str) -> bool {
+        self.sub_dir(subscription).join(UPSTREAM_DIR).is_dir()
+    }
+
+    /// Effective profiles of a subscription as (profile name, file) pairs
+    pub fn local_profiles(&self, subscription: &str) -> AppResult<Vec<(String, PathBuf)>> {
+        list_profiles(&self.sub_dir(subscription).join(PROFILES_DIR))
+    }
+
+    /// Compare the snapshots with the shared directory
+    pub fn status(&self, subscription: &ProfileSubscription) -> SubscriptionStatus {
+        let mut status = SubscriptionStatus {
+            name: subscription.name.clone(),
+            path: subscription.path.clone(),
+            enabled: subscription.enabled,
+            error: None,
+            profiles: Vec::new(),
+            update_available: false,
+        };
+        let upstream = match list_profiles(&subscription.path) {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                status.error = Some(e.to_string());
+                return status;
+            }
+        };
+
+        let sub_dir = self.sub_dir(&subscription.name);
+        let snapshots: BTreeMap<String, PathBuf> = list_profiles(&sub_dir.join(UPSTREAM_DIR))
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        let upstream: BTreeMap<String, PathBuf> = upstream.into_iter().collect();
+
+        let mut names: Vec<&String> = snapshots.keys().chain(upstream.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let state = match (snapshots.get(name), upstream.get(name)) {
+                (Some(snapshot), Some(current)) => {
+                    // 重新签名也算上游更新
+                    if fs::read(snapshot).ok() == fs::read(current).ok()
+                        && read_signature(snapshot) == read_signature(current)
+                    {
+                        UpstreamState::UpToDate
+                    } else {
+                        UpstreamState::UpdateAvailable
+                    }
+                }
+                (None, Some(_)) => UpstreamState::New,
+                _ => UpstreamState::Removed,
+            };
+            status.profiles.push(SubscribedProfileState {
+                profile: name.clone(),
+                state,
+                has_override: override_path(&sub_dir, name).exists(),
+            });
+        }
+        status.update_available = status
+            .profiles
+            .iter()
+            .any(|p| p.state != UpstreamState::UpToDate);
+        status
+    }
+
+    /// Take the shared directory's current version of one profile, or of all profiles
+    /// (including new and removed ones), together with its signature. Returns the
+    /// profiles that changed.
+    pub fn sync(
+        &self,
+        subscription: &ProfileSubscription,
+        profile: Option<&str>,
+        trusted_keys: &[TrustedKey],
+    ) -> AppResult<Vec<String>> {
+        let status = self.status(subscription);
+        if let Some(error) = status.error {
+            return Err(AppError::FileSystemError(format!(
+                "Subscription '{}' is not reachable: {}",
+                subscription.name, error
+            )));
+        }
+
+        let sub_dir = self.sub_dir(&subscription.name);
+        fs::create_dir_all(sub_dir.join(UPSTREAM_DIR))?;
+        fs::create_dir_all(sub_dir.join(PROFILES_DIR))?;
+
+        let mut changed = Vec::new();
+        for entry in &status.profiles {
+            if profile.is_some_and(|p| p != entry.profile) || entry.state == UpstreamState::UpToDate {
+                continue;
+            }
+            let snapshot = sub_dir.join(UPSTREAM_DIR).join(profile_file(&entry.profile));
+            if entry.state == UpstreamState::Removed {
+                let _ = fs::remove_file(&snapshot);
+                let _ = fs::remove_file(profile_signing::signature_path(&snapshot));
+                let _ = fs::remove_file(sub_dir.join(PROFILES_DIR).join(profile_file(&entry.profile)));
+            } else {
+                let upstream = subscription.path.join(profile_file(&entry.profile));
+                let content = fs::read_to_string(&upstream)?;
+                parse_object(&content).map_err(|e| {
+                    AppError::ConfigError(format!(
+                        "Shared profile '{}' is invalid: {}",
+                        entry.profile, e
+                    ))
+                })?;
+                // 签名和内容一起读取，快照的签名状态与上游一致
+                let signature = read_signature(&upstream);
+                file_permissions::write_private_file(&snapshot, content.as_bytes())?;
+                match &signature {
+                    Some(signature) => {
+                        fs::write(profile_signing::signature_path(&snapshot), signature)?
+                    }
+                    None => match fs::remove_file(profile_signing::signature_path(&snapshot)) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    },
+                }
+                match self.verify_snapshot(&subscription.name, &entry.profile, trusted_keys) {
+                    SignatureState::Unsigned => log::warn!(
+                        "Shared profile '{}' of '{}' is not signed",
+                        entry.profile,
+                        subscription.name
+                    ),
+                    SignatureState::Invalid(reason) => log::warn!(
+                        "Shared profile '{}' of '{}' has an invalid signature: {}",
+                        entry.profile,
+                        subscription.name,
+                        reason
+                    ),
+                    _ => {}
+                }
+                self.materialize(&subscription.name, &entry.profile)?;
+            }
+            changed.push(entry.profile.clone());
+        }
+
+        if let Some(profile) = profile {
+            if !status.profiles.iter().any(|p| p.profile == profile) {
+                return Err(AppError::ConfigError(format!(
+                    "Subscription '{}' has no profile '{}'",
+                    subscription.name, profile
+                )));
+            }
+        }
+        log::info!(
+            "Synced subscription '{}': {} profiles updated",
+            subscription.name,
+            changed.len()
+        );
+        Ok(changed)
+    }
+
+    /// Signature state of the synced snapshot, which the materialized profile inherits:
+    /// the signature covers what the team published, not the local override on top
+    pub fn verify_snapshot(
+        &self,
+        subscription: &str,
+        profile: &str,
+        trusted_keys: &[TrustedKey],
+    ) -> SignatureState {
+        if trusted_keys.is_empty() {
+            return SignatureState::NotChecked;
+        }
+        let snapshot = self.sub_dir(subscription).join(UPSTREAM_DIR).join(profile_file(profile));
+        match fs::read(&snapshot) {
+            Ok(content) => profile_signing::verify(
+                &profile_signing::signed_payload(&content, None),
+                &profile_signing::signature_path(&snapshot),
+                trusted_keys,
+            ),
+            Err(e) => SignatureState::Invalid(format!("snapshot is not readable: {}", e)),
+        }
+    }
+
+    pub fn read_override(&self, subscription: &str, profile: &str) -> AppResult<Option<String>> {
+        let path = override_path(&self.sub_dir(subscription), profile);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(path)?))
+    }
+
+    /// Replace the local override of a profile; `None` removes it
+    pub fn save_override(
+        &self,
+        subscription: &str,
+        profile: &str,
+        content: Option<&str>,
+    ) -> AppResult<()> {
+        let sub_dir = self.sub_dir(subscription);
+        if !sub_dir.join(UPSTREAM_DIR).join(profile_file(profile)).exists() {
+            return Err(AppError::ConfigError(format!(
+                "Subscription '{}' has no profile '{}'",
+                subscription, profile
+            )));
+        }
+
+        let path = override_path(&sub_dir, profile);
+        match content {
+            Some(content) => {
+                parse_object(content).map_err(|e| {
+                    AppError::ConfigError(format!("Invalid override: {}", e))
+                })?;
+                fs::create_dir_all(sub_dir.join(OVERRIDES_DIR))?;
+                // 覆盖层通常保存个人令牌
+                file_permissions::write_private_file(&path, content.as_bytes())?;
+            }
+            None => match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        self.materialize(subscription, profile)
+    }
+
+    /// Write the snapshot with the override applied as the profile CCCS switches to
+    fn materialize(&self, subscription: &str, profile: &str) -> AppResult<()> {
+        let sub_dir = self.sub_dir(subscription);
+        let snapshot = fs::read_to_string(sub_dir.join(UPSTREAM_DIR).join(profile_file(profile)))?;
+        let mut settings = parse_object(&snapshot).map_err(AppError::ConfigError)?;
+        if let Some(content) = self.read_override(subscription, profile)? {
+            let patch = parse_object(&content).map_err(AppError::ConfigError)?;
+            apply_merge_patch(&mut settings, &patch);
+        }
+
+        fs::create_dir_all(sub_dir.join(PROFILES_DIR))?;
+        let content = serde_json::to_string_pretty(&settings)?;
+        file_permissions::write_private_file(
+            &sub_dir.join(PROFILES_DIR).join(profile_file(profile)),
+            content.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Drop the snapshots and overrides of a subscription that was removed
+    pub fn remove(&self, subscription: &str) -> AppResult<()> {
+        let sub_dir = self.sub_dir(subscription);
+        if sub_dir.exists() {
+            fs::remove_dir_all(sub_dir)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn default_subscriptions_dir() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("subscriptions"))
+}
+
+/// "team:work" for profile "work" of subscription "team"; ':' never occurs in local names
+pub fn qualified_name(subscription: &str, profile: &str) -> String {
+    format!("{}:{}", subscription, profile)
+}
+
+pub fn split_qualified_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(':')
+}
+
+/// RFC 7386 JSON merge patch: objects merge recursively, `null` removes a key and
+/// any other value (arrays included) replaces the target
+pub fn apply_merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let Some(target) = target.as_object_mut() else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            apply_merge_patch(
+                target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                value,
+            );
+        }
+    }
+}
+
+fn profile_file(profile: &str) -> String {
+    format!("{}{}", profile, PROFILE_SUFFIX)
+}
+
+fn read_signature(profile_path: &Path) -> Option<Vec<u8>> {
+    fs::read(profile_signing::signature_path(profile_path)).ok()
+}
+
+fn override_path(sub_dir: &Path, profile: &str) -> PathBuf {
+    sub_dir.join(OVERRIDES_DIR).join(format!("{}.json", profile))
+}
+
+fn parse_object(content: &str) -> Result<serde_json::Value, String> {
+    let value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if !value.is_object() {
+        return Err("settings must be a JSON object".to_string());
+    }
+    Ok(value)
+}
+
+/// `*.settings.json` files of a directory (not `settings.json` itself), sorted by name
+fn list_profiles(dir: &Path) -> AppResult<Vec<(String, PathBuf)>> {
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(PROFILE_SUFFIX))
+        else {
+            continue;
+        };
+        if !name.is_empty() && !name.contains(':') && path.is_file() {
+            profiles.push((name.to_string(), path.clone()));
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn states(status: &SubscriptionStatus) -> Vec<(String, UpstreamState)> {
+        status
+            .profiles
+            .iter()
+            .map(|p| (p.profile.clone(), p.state))
+            .collect()
+    }
+
+    #[test]
+    fn test_sync_and_update_detection() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("work.settings.json"), r#"{"model": "opus"}"#).unwrap();
+        fs::write(shared.join("settings.json"), "{}").unwrap();
+
+        let store = SubscriptionStore::new(temp_dir.path().join("subscriptions"));
+        let subscription = ProfileSubscription {
+            name: "team".to_string(),
+            path: shared.clone(),
+            enabled: true,
+        };
+        assert!(!store.is_synced("team"));
+        assert_eq!(
+            states(&store.status(&subscription)),
+            vec![("work".to_string(), UpstreamState::New)]
+        );
+
+        assert_eq!(store.sync(&subscription, None, &[]).unwrap(), vec!["work"]);
+        assert!(store.is_synced("team"));
+        assert!(!store.status(&subscription).update_available);
+        assert_eq!(store.local_profiles("team").unwrap().len(), 1);
+
+        // Upstream changes are not applied until synced
+        fs::write(shared.join("work.settings.json"), r#"{"model": "sonnet"}"#).unwrap();
+        fs::write(shared.join("review.settings.json"), "{}").unwrap();
+        assert_eq!(
+            states(&store.status(&subscription)),
+            vec![
+                ("review".to_string(), UpstreamState::New),
+                ("work".to_string(), UpstreamState::UpdateAvailable),
+            ]
+        );
+        let (_, path) = &store.local_profiles("team").unwrap()[0];
+        assert!(fs::read_to_string(path).unwrap().contains("opus"));
+
+        assert_eq!(store.sync(&subscription, Some("work"), &[]).unwrap(), vec!["work"]);
+        assert!(fs::read_to_string(path).unwrap().contains("sonnet"));
+        assert!(store.sync(&subscription, Some("missing"), &[]).is_err());
+
+        fs::remove_file(shared.join("work.settings.json")).unwrap();
+        assert_eq!(
+            states(&store.status(&subscription))[1],
+            ("work".to_string(), UpstreamState::Removed)
+        );
+        store.sync(&subscription, None, &[]).unwrap();
+        let names: Vec<String> = store
+            .local_profiles("team")
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["review"]);
+    }
+
+    #[test]
+    fn test_override_layer() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(
+            shared.join("work.settings.json"),
+            r#"{"model": "opus", "env": {"ANTHROPIC_BASE_URL": "https://team", "DEBUG": "1"}}"#,
+        )
+        .unwrap();
+        let store = SubscriptionStore::new(temp_dir.path().join("subscriptions"));
+        let subscription = ProfileSubscription {
+            name: "team".to_string(),
+            path: shared,
+            enabled: true,
+        };
+        store.sync(&subscription, None, &[]).unwrap();
+
+        store
+            .save_override("team", "work", Some(r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "sk-me", "DEBUG": null}}"#))
+            .unwrap();
+        assert!(store.status(&subscription).profiles[0].has_override);
+        let (_, path) = &store.local_profiles("team").unwrap()[0];
+        let effective: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(
+            effective,
+            serde_json::json!({
+                "model": "opus",
+                "env": {"ANTHROPIC_BASE_URL": "https://team", "ANTHROPIC_AUTH_TOKEN": "sk-me"}
+            })
+        );
+
+        assert!(store.save_override("team", "work", Some("[]")).is_err());
+        assert!(store.save_override("team", "other", Some("{}")).is_err());
+        store.save_override("team", "work", None).unwrap();
+        assert_eq!(store.read_override("team", "work").unwrap(), None);
+        assert!(fs::read_to_string(path).unwrap().contains("DEBUG"));
+    }
+
+    #[test]
+    fn test_validate_subscription() {
+        let subscription = |name: &str, path: &str| ProfileSubscription {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+            enabled: true,
+        };
+        let root = if cfg!(windows) { "C:\\team" } else { "/team" };
+        assert!(subscription("team-1", root).validate().is_ok());
+        assert!(subscription("a:b", root).validate().is_err());
+        assert!(subscription("", root).validate().is_err());
+        assert!(subscription("team", "relative").validate().is_err());
+    }
+}
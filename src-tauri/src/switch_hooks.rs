@@ -0,0 +1,396 @@
+// User commands run before and after a profile switch (credential refresh, proxy restart, ...)
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const MAX_TIMEOUT_SECS: u64 = 600;
+// 每个输出流最多保留 64 KB，避免刷屏的命令撑大内存
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+const OUTPUT_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookCommand {
+    pub command: String, // 通过 sh -c（Windows 为 cmd /C）执行
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchHooks {
+    #[serde(default)]
+    pub pre_switch: Vec<HookCommand>,
+    #[serde(default)]
+    pub post_switch: Vec<HookCommand>,
+}
+
+/// Contents of switch_hooks.json: global hooks plus hooks keyed by target profile name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchHooksConfig {
+    #[serde(default)]
+    pub global: SwitchHooks,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, SwitchHooks>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    PreSwitch,
+    PostSwitch,
+}
+
+impl HookStage {
+    fn label(self) -> &'static str {
+        match self {
+            Self::PreSwitch => "pre",
+            Self::PostSwitch => "post",
+        }
+    }
+}
+
+/// What the hook is told about the switch through CCCS_* environment variables
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub old_profile: Option<String>,
+    pub new_profile: String,
+    pub scope: String,
+    pub project_dir: Option<PathBuf>,
+    pub claude_dir: PathBuf,
+}
+
+/// Result of one hook command, kept so the UI can show the captured output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub stage: HookStage,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+    pub error: Option<String>, // 无法启动命令时的错误
+}
+
+impl HookRun {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && !self.timed_out && self.exit_code == Some(0)
+    }
+
+    /// One-line reason for a failed run, ending with the last line of stderr
+    pub fn failure_summary(&self) -> String {
+        let reason = if let Some(error) = &self.error {
+            error.clone()
+        } else if self.timed_out {
+            "timed out".to_string()
+        } else {
+            match self.exit_code {
+                Some(code) => format!("exited with code {}", code),
+                None => "terminated by signal".to_string(),
+            }
+        };
+        match self.stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("'{}' {}: {}", self.command, reason, line.trim()),
+            None => format!("'{}' {}", self.command, reason),
+        }
+    }
+}
+
+pub fn default_hooks_path() -> AppResult<PathBuf> {
+    Ok(crate::settings_service::SettingsService::get_settings_directory()?.join("switch_hooks.json"))
+}
+
+impl SwitchHooksConfig {
+    /// A missing file means no hooks
+    pub fn load(path: &Path) -> AppResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| {
+            AppError::HookError(format!("Failed to read hooks file {:?}: {}", path, e))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            AppError::HookError(format!("Invalid hooks file {:?}: {}", path, e))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        self.validate()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        let all = std::iter::once(&self.global).chain(self.profiles.values());
+        for hook in all.flat_map(|hooks| hooks.pre_switch.iter().chain(&hooks.post_switch)) {
+            if hook.command.trim().is_empty() {
+                return Err(AppError::HookError("Hook command cannot be empty".to_string()));
+            }
+            if let Some(timeout) = hook.timeout_secs {
+                if !(1..=MAX_TIMEOUT_SECS).contains(&timeout) {
+                    return Err(AppError::HookError(format!(
+                        "Hook timeout must be between 1 and {} seconds",
+                        MAX_TIMEOUT_SECS
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hooks for a switch to `profile`: global ones first, then the profile's own
+    pub fn hooks_for(&self, profile: &str, stage: HookStage) -> Vec<HookCommand> {
+        let select = |hooks: &SwitchHooks| match stage {
+            HookStage::PreSwitch => hooks.pre_switch.clone(),
+            HookStage::PostSwitch => hooks.post_switch.clone(),
+        };
+        let mut commands = select(&self.global);
+        if let Some(hooks) = self.profiles.get(profile) {
+            commands.extend(select(hooks));
+        }
+        commands
+    }
+}
+
+/// Run the hooks of one stage in order. Pre-switch hooks stop at the first failure.
+pub fn run_hooks(hooks: &[HookCommand], stage: HookStage, context: &HookContext) -> Vec<HookRun> {
+    let mut runs = Vec::new();
+    for hook in hooks {
+        let run = run_hook(hook, stage, context);
+        if run.succeeded() {
+            log::info!("{}-switch hook '{}' succeeded", stage.label(), hook.command);
+        } else {
+            log::warn!("{}-switch hook failed: {}", stage.label(), run.failure_summary());
+        }
+        let failed = !run.succeeded();
+        runs.push(run);
+        if failed && stage == HookStage::PreSwitch {
+            break;
+        }
+    }
+    runs
+}
+
+fn run_hook(hook: &HookCommand, stage: HookStage, context: &HookContext) -> HookRun {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let mut run = HookRun {
+        stage,
+        command: hook.command.clone(),
+        exit_code: None,
+        timed_out: false,
+        stdout: String::new(),
+        stderr: String::new(),
+        duration_ms: 0,
+        error: None,
+    };
+
+    let mut command = shell_command(&hook.command);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("CCCS_HOOK_STAGE", stage.label())
+        .env("CCCS_OLD_PROFILE", context.old_profile.as_deref().unwrap_or(""))
+        .env("CCCS_NEW_PROFILE", &context.new_profile)
+        .env("CCCS_SCOPE", &context.scope)
+        .env("CCCS_CLAUDE_DIR", &context.claude_dir);
+    if let Some(project_dir) = &context.project_dir {
+        command.env("CCCS_PROJECT_DIR", project_dir);
+        command.current_dir(project_dir);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            run.error = Some(format!("failed to start: {}", e));
+            return run;
+        }
+    };
+
+    // 在线程中读取输出，防止管道写满导致子进程阻塞
+    let stdout = child.stdout.take().map(spawn_reader);
+    let stderr = child.stderr.take().map(spawn_reader);
+
+    match wait_with_timeout(&mut child, timeout) {
+        Ok(Some(status)) => run.exit_code = status.code(),
+        Ok(None) => {
+            run.timed_out = true;
+            kill_process_tree(&mut child);
+        }
+        Err(e) => run.error = Some(format!("failed to wait: {}", e)),
+    }
+
+    // A hook that starts a background process (e.g. a proxy) leaves the pipes open;
+    // take what was written so far instead of waiting for it to exit
+    let deadline = Instant::now() + OUTPUT_GRACE;
+    for reader in stdout.iter().chain(stderr.iter()) {
+        while !reader.1.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    run.stdout = stdout.map(|(buffer, _)| take_output(&buffer)).unwrap_or_default();
+    run.stderr = stderr.map(|(buffer, _)| take_output(&buffer)).unwrap_or_default();
+    run.duration_ms = started.elapsed().as_millis() as u64;
+    run
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Duration,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+type OutputReader = (Arc<Mutex<Vec<u8>>>, thread::JoinHandle<()>);
+
+fn spawn_reader(mut pipe: impl Read + Send + 'static) -> OutputReader {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let shared = Arc::clone(&buffer);
+    let handle = thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(read) = pipe.read(&mut chunk) {
+            if read == 0 {
+                break;
+            }
+            let mut buffer = shared.lock().unwrap();
+            let room = MAX_OUTPUT_BYTES.saturating_sub(buffer.len());
+            buffer.extend_from_slice(&chunk[..read.min(room)]);
+        }
+    });
+    (buffer, handle)
+}
+
+fn take_output(buffer: &Mutex<Vec<u8>>) -> String {
+    String::from_utf8_lossy(&buffer.lock().unwrap()).into_owned()
+}
+
+/// Kill the hook and anything it started; on Unix the hook runs in its own process group
+fn kill_process_tree(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) with a negative pid only sends a signal to the hook's process group
+        unsafe {
+            libc::kill(-(child.id() as i32), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn context() -> HookContext {
+        HookContext {
+            old_profile: Some("personal".to_string()),
+            new_profile: "work".to_string(),
+            scope: "user".to_string(),
+            project_dir: None,
+            claude_dir: PathBuf::from("/tmp"),
+        }
+    }
+
+    fn hook(command: &str, timeout_secs: Option<u64>) -> HookCommand {
+        HookCommand {
+            command: command.to_string(),
+            timeout_secs,
+        }
+    }
+
+    #[test]
+    fn test_run_hooks_env_output_and_timeout() {
+        let runs = run_hooks(
+            &[hook("echo \"$CCCS_OLD_PROFILE->$CCCS_NEW_PROFILE\"; echo oops >&2", None)],
+            HookStage::PostSwitch,
+            &context(),
+        );
+        assert!(runs[0].succeeded());
+        assert_eq!(runs[0].stdout.trim(), "personal->work");
+        assert_eq!(runs[0].stderr.trim(), "oops");
+
+        // A failing pre-switch hook stops the remaining ones
+        let runs = run_hooks(
+            &[hook("echo denied >&2; exit 3", None), hook("true", None)],
+            HookStage::PreSwitch,
+            &context(),
+        );
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].exit_code, Some(3));
+        assert!(runs[0].failure_summary().ends_with("denied"));
+
+        let runs = run_hooks(&[hook("sleep 5", Some(1))], HookStage::PostSwitch, &context());
+        assert!(runs[0].timed_out);
+        assert!(runs[0].duration_ms < 5000);
+
+        // Background processes keeping the output open don't block the hook
+        let runs = run_hooks(&[hook("echo started; sleep 5 &", None)], HookStage::PostSwitch, &context());
+        assert!(runs[0].succeeded());
+        assert_eq!(runs[0].stdout.trim(), "started");
+        assert!(runs[0].duration_ms < 5000);
+    }
+
+    #[test]
+    fn test_hooks_config() {
+        let mut config = SwitchHooksConfig::default();
+        config.global.pre_switch.push(hook("global", None));
+        config.profiles.insert(
+            "work".to_string(),
+            SwitchHooks {
+                pre_switch: vec![hook("aws sso login", Some(120))],
+                post_switch: Vec::new(),
+            },
+        );
+
+        let pre = config.hooks_for("work", HookStage::PreSwitch);
+        assert_eq!(pre.len(), 2);
+        assert_eq!(pre[0].command, "global");
+        assert_eq!(config.hooks_for("other", HookStage::PreSwitch).len(), 1);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("switch_hooks.json");
+        assert_eq!(SwitchHooksConfig::load(&path).unwrap(), SwitchHooksConfig::default());
+        config.save(&path).unwrap();
+        assert_eq!(SwitchHooksConfig::load(&path).unwrap(), config);
+
+        config.global.post_switch.push(hook("x", Some(0)));
+        assert!(config.save(&path).is_err());
+    }
+}
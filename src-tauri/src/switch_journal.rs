@@ -0,0 +1,403 @@
+// Write-ahead journal that makes profile switches recoverable after a crash
+use crate::file_permissions;
+use crate::profile_bundle;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const JOURNAL_FILE: &str = ".cccs-journal.json";
+const JOURNAL_BACKUP_DIR: &str = ".cccs-journal";
+
+/// Temp and write-test files younger than this may belong to a running write
+const STALE_FILE_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalState {
+    Pending,   // 正在修改文件，崩溃后需要回滚
+    Committed, // 所有修改已完成，只剩清理工作
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalFile {
+    pub target: PathBuf,
+    pub backup: Option<PathBuf>, // None 表示修改前文件不存在，回滚时删除
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub id: String,
+    pub operation: String,
+    pub state: JournalState,
+    pub files: Vec<JournalFile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecoveryReport {
+    pub rolled_back: Option<String>, // 被回滚的操作
+    pub completed: Option<String>,   // 已提交、只补做了清理的操作
+    pub removed_files: usize,        // 清理掉的临时文件数量
+}
+
+/// Records every file of a switch before it is modified.
+/// Drop without `commit` or `rollback` leaves the journal for startup recovery.
+pub struct SwitchJournal {
+    claude_dir: PathBuf,
+    backup_dir: PathBuf,
+    record: JournalRecord,
+}
+
+impl SwitchJournal {
+    /// Start a journaled operation; a journal left behind by an earlier failure is rolled back first
+    pub fn begin(claude_dir: &Path, operation: &str) -> AppResult<Self> {
+        if claude_dir.join(JOURNAL_FILE).exists() {
+            log::warn!("Found an unfinished switch journal, recovering before continuing");
+            recover_journal(claude_dir)?;
+        }
+
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_string();
+        let journal = Self {
+            claude_dir: claude_dir.to_path_buf(),
+            backup_dir: claude_dir.join(JOURNAL_BACKUP_DIR).join(&id),
+            record: JournalRecord {
+                id,
+                operation: operation.to_string(),
+                state: JournalState::Pending,
+                files: Vec::new(),
+            },
+        };
+        journal.persist()?;
+        Ok(journal)
+    }
+
+    /// Snapshot `target` and record it before it is written or removed
+    pub fn protect(&mut self, target: &Path) -> AppResult<()> {
+        if self.record.files.iter().any(|f| f.target == target) {
+            return Ok(());
+        }
+
+        let backup = if target.exists() {
+            fs::create_dir_all(&self.backup_dir).map_err(|e| {
+                AppError::FileSystemError(format!("Failed to create journal directory: {}", e))
+            })?;
+            let backup = self.backup_dir.join(self.record.files.len().to_string());
+            let content = fs::read(target).map_err(|e| {
+                AppError::FileSystemError(format!("Failed to snapshot {:?}: {}", target, e))
+            })?;
+            // Snapshots may hold resolved secrets
+            file_permissions::write_private_file(&backup, &content).map_err(|e| {
+                AppError::FileSystemError(format!("Failed to write journal snapshot: {}", e))
+            })?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        self.record.files.push(JournalFile {
+            target: target.to_path_buf(),
+            backup,
+        });
+        self.persist()
+    }
+
+    /// Mark the operation complete and drop its snapshots
+    pub fn commit(mut self) {
+        self.record.state = JournalState::Committed;
+        if let Err(e) = self.persist() {
+            log::warn!("Failed to mark switch journal as committed: {}", e);
+        }
+        finish(&self.claude_dir, &self.record);
+    }
+
+    /// Drop the journal after the caller restored every protected file itself
+    pub fn discard(self) {
+        finish(&self.claude_dir, &self.record);
+    }
+
+    /// Restore every protected file; on failure the journal stays for the next startup
+    pub fn rollback(self) -> AppResult<()> {
+        rollback_record(&self.record)?;
+        finish(&self.claude_dir, &self.record);
+        Ok(())
+    }
+
+    fn persist(&self) -> AppResult<()> {
+        write_journal(&self.claude_dir, &self.record)
+    }
+}
+
+fn write_journal(claude_dir: &Path, record: &JournalRecord) -> AppResult<()> {
+    let path = claude_dir.join(JOURNAL_FILE);
+    let temp_path = claude_dir.join(format!(
+        "{}.{}.{}.tmp",
+        JOURNAL_FILE,
+        record.id,
+        std::process::id()
+    ));
+    let content = serde_json::to_vec_pretty(record)?;
+
+    let written = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(&content)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, &path));
+    written.map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::FileSystemError(format!("Failed to write switch journal: {}", e))
+    })
+}
+
+fn rollback_record(record: &JournalRecord) -> AppResult<()> {
+    let mut failures = Vec::new();
+
+    for file in record.files.iter().rev() {
+        let result = match &file.backup {
+            Some(backup) => restore_file(backup, &file.target),
+            None if file.target.exists() => fs::remove_file(&file.target),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            failures.push(format!("{}: {}", file.target.display(), e));
+        }
+    }
+
+    if failures.is_empty() {
+        log::info!("Rolled back interrupted operation: {}", record.operation);
+        Ok(())
+    } else {
+        Err(AppError::FileSystemError(format!(
+            "Failed to roll back {}: {}",
+            record.operation,
+            failures.join("; ")
+        )))
+    }
+}
+
+fn restore_file(backup: &Path, target: &Path) -> std::io::Result<()> {
+    let content = fs::read(backup)?;
+    let file_name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("restore");
+    let temp_path = target.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    file_permissions::write_private_file(&temp_path, &content)
+        .and_then(|_| file_permissions::preserve_mode(&temp_path, target))
+        .and_then(|_| fs::rename(&temp_path, target))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })
+}
+
+fn finish(claude_dir: &Path, record: &JournalRecord) {
+    let _ = fs::remove_dir_all(claude_dir.join(JOURNAL_BACKUP_DIR).join(&record.id));
+    let _ = fs::remove_dir(claude_dir.join(JOURNAL_BACKUP_DIR));
+    if let Err(e) = fs::remove_file(claude_dir.join(JOURNAL_FILE)) {
+        log::warn!("Failed to remove switch journal: {}", e);
+    }
+}
+
+/// Roll back a pending journal or finish the cleanup of a committed one
+fn recover_journal(claude_dir: &Path) -> AppResult<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+    let path = claude_dir.join(JOURNAL_FILE);
+    if !path.exists() {
+        return Ok(report);
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::FileSystemError(format!("Failed to read switch journal: {}", e)))?;
+    let record: JournalRecord = match serde_json::from_str(&content) {
+        Ok(record) => record,
+        Err(e) => {
+            // The journal is written atomically, so this is not a half-written record
+            log::error!("Discarding unreadable switch journal: {}", e);
+            let _ = fs::remove_file(&path);
+            return Ok(report);
+        }
+    };
+
+    match record.state {
+        JournalState::Pending => {
+            rollback_record(&record)?;
+            report.rolled_back = Some(record.operation.clone());
+        }
+        JournalState::Committed => {
+            report.completed = Some(record.operation.clone());
+        }
+    }
+    finish(claude_dir, &record);
+    Ok(report)
+}
+
+/// Recover an interrupted switch and sweep temp files left behind by crashed writes.
+/// Called once at startup, before any profile is read.
+pub fn recover(claude_dir: &Path) -> AppResult<RecoveryReport> {
+    let mut report = recover_journal(claude_dir)?;
+    report.removed_files = sweep_settings_dir(claude_dir);
+    Ok(report)
+}
+
+/// Sweep a directory holding settings and bundle files (`~/.claude` or `<project>/.claude`)
+pub fn sweep_settings_dir(dir: &Path) -> usize {
+    // Snapshots of a bundle transaction are only needed while it runs
+    let staging_dir = dir.join(profile_bundle::STAGING_DIR);
+    if staging_dir.exists() {
+        let _ = fs::remove_dir_all(&staging_dir);
+    }
+
+    let mut dirs = vec![dir.to_path_buf()];
+    dirs.extend(profile_bundle::ARTIFACT_DIRS.iter().map(|d| dir.join(d)));
+    dirs.iter().map(|dir| sweep_stale_files(dir)).sum()
+}
+
+/// Whether a file name looks like one of our temp files (`.<name>.<nanos>.<pid>.tmp`,
+/// `.<name>.<pid>.tmp`) or write tests (`.write_test_<pid>`, `settings.json.write_test`)
+pub fn is_stale_file_name(name: &str) -> bool {
+    if name.starts_with(".write_test_") || name.ends_with(".write_test") {
+        return true;
+    }
+    let Some(stem) = name.strip_prefix('.').and_then(|n| n.strip_suffix(".tmp")) else {
+        return false;
+    };
+    let mut parts = stem.rsplit('.');
+    parts
+        .next()
+        .is_some_and(|pid| !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()))
+        && parts.next().is_some_and(|rest| !rest.is_empty())
+}
+
+/// Remove stale temp and write-test files directly inside `dir`
+pub fn sweep_stale_files(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut removed = 0;
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let is_candidate = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(is_stale_file_name);
+        if !is_candidate || !path.is_file() {
+            continue;
+        }
+
+        let old_enough = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= STALE_FILE_AGE);
+        if !old_enough {
+            continue;
+        }
+
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("Removed stale file: {}", path.display());
+                removed += 1;
+            }
+            Err(e) => log::warn!("Failed to remove stale file {}: {}", path.display(), e),
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pending_journal_is_rolled_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        let settings = claude_dir.join("settings.json");
+        let claude_md = claude_dir.join("CLAUDE.md");
+        fs::write(&settings, r#"{"theme": "dark"}"#).unwrap();
+
+        let mut journal = SwitchJournal::begin(claude_dir, "switch to work").unwrap();
+        journal.protect(&settings).unwrap();
+        journal.protect(&claude_md).unwrap();
+        fs::write(&settings, r#"{"theme": "light"}"#).unwrap();
+        fs::write(&claude_md, "work").unwrap();
+        drop(journal); // 模拟在提交前崩溃
+
+        let report = recover(claude_dir).unwrap();
+        assert_eq!(report.rolled_back.as_deref(), Some("switch to work"));
+        assert_eq!(fs::read_to_string(&settings).unwrap(), r#"{"theme": "dark"}"#);
+        assert!(!claude_md.exists());
+        assert!(!claude_dir.join(JOURNAL_FILE).exists());
+        assert!(!claude_dir.join(JOURNAL_BACKUP_DIR).exists());
+    }
+
+    #[test]
+    fn test_committed_journal_is_finished() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path();
+        let settings = claude_dir.join("settings.json");
+        fs::write(&settings, "{}").unwrap();
+
+        let mut journal = SwitchJournal::begin(claude_dir, "switch to home").unwrap();
+        journal.protect(&settings).unwrap();
+        fs::write(&settings, r#"{"theme": "light"}"#).unwrap();
+        journal.record.state = JournalState::Committed;
+        journal.persist().unwrap();
+        drop(journal); // 模拟在清理前崩溃
+
+        let report = recover(claude_dir).unwrap();
+        assert_eq!(report.completed.as_deref(), Some("switch to home"));
+        assert_eq!(fs::read_to_string(&settings).unwrap(), r#"{"theme": "light"}"#);
+        assert!(!claude_dir.join(JOURNAL_FILE).exists());
+
+        let journal = SwitchJournal::begin(claude_dir, "noop").unwrap();
+        journal.commit();
+        assert!(!claude_dir.join(JOURNAL_FILE).exists());
+    }
+
+    #[test]
+    fn test_stale_file_names() {
+        assert!(is_stale_file_name(".settings.json.1700000000000000000.4242.tmp"));
+        assert!(is_stale_file_name(".CLAUDE.md.4242.tmp"));
+        assert!(is_stale_file_name(".write_test_4242"));
+        assert!(is_stale_file_name("settings.json.write_test"));
+        assert!(!is_stale_file_name("settings.json"));
+        assert!(!is_stale_file_name(".notes.tmp"));
+        assert!(!is_stale_file_name("work.settings.json"));
+    }
+
+    #[test]
+    fn test_sweep_skips_recent_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let stale = temp_dir.path().join(".settings.json.1.2.tmp");
+        let keep = temp_dir.path().join("work.settings.json");
+        fs::write(&stale, "x").unwrap();
+        fs::write(&keep, "{}").unwrap();
+
+        // Just written, could still belong to a running write
+        assert_eq!(sweep_stale_files(temp_dir.path()), 0);
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_eq!(sweep_stale_files(temp_dir.path()), 1);
+        assert!(!stale.exists());
+        assert!(keep.exists());
+    }
+}
@@ -0,0 +1,158 @@
+// Temporary profile switches that revert automatically when their lease expires
+use crate::file_permissions;
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LEASE_FILE: &str = ".cccs-lease.json";
+
+pub const MIN_LEASE: Duration = Duration::from_secs(60);
+pub const MAX_LEASE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// What the user settings looked like before the lease started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum PreviousState {
+    Profile(String),  // 之前激活的配置名
+    Settings(String), // 之前不匹配任何配置时的 settings.json 原文
+}
+
+/// A switch to `profile` that ends at `expires_at` (unix seconds); stored in the Claude
+/// directory so it survives restarts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchLease {
+    pub profile: String,
+    pub previous: PreviousState,
+    pub started_at: u64,
+    pub expires_at: u64,
+}
+
+/// Lease plus the seconds left, as returned to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaseInfo {
+    pub lease: SwitchLease,
+    pub remaining_secs: u64,
+}
+
+impl SwitchLease {
+    pub fn new(profile: &str, previous: PreviousState, duration: Duration) -> AppResult<Self> {
+        if duration < MIN_LEASE || duration > MAX_LEASE {
+            return Err(AppError::ConfigError(format!(
+                "Temporary switch duration must be between {} and {}",
+                format_remaining(MIN_LEASE.as_secs()),
+                format_remaining(MAX_LEASE.as_secs())
+            )));
+        }
+        let started_at = now_secs();
+        Ok(Self {
+            profile: profile.to_string(),
+            previous,
+            started_at,
+            expires_at: started_at + duration.as_secs(),
+        })
+    }
+
+    fn path(claude_dir: &Path) -> PathBuf {
+        claude_dir.join(LEASE_FILE)
+    }
+
+    pub fn load(claude_dir: &Path) -> AppResult<Option<Self>> {
+        let path = Self::path(claude_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Written private: the previous settings may contain credentials
+    pub fn save(&self, claude_dir: &Path) -> AppResult<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        file_permissions::write_private_file(&Self::path(claude_dir), content.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn clear(claude_dir: &Path) -> AppResult<()> {
+        match fs::remove_file(Self::path(claude_dir)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn remaining_secs(&self, now: u64) -> u64 {
+        self.expires_at.saturating_sub(now)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    pub fn info(&self) -> LeaseInfo {
+        LeaseInfo {
+            remaining_secs: self.remaining_secs(now_secs()),
+            lease: self.clone(),
+        }
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Short remaining time for the tray, e.g. "1h 42m" or "5m"
+pub fn format_remaining(secs: u64) -> String {
+    // 向上取整到分钟，避免最后一分钟显示 0m
+    let minutes = secs.div_ceil(60);
+    match (minutes / (24 * 60), minutes / 60 % 24, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, 0) => format!("{}h", h),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lease_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(SwitchLease::load(temp_dir.path()).unwrap(), None);
+
+        let lease = SwitchLease::new(
+            "opus",
+            PreviousState::Profile("sonnet".to_string()),
+            Duration::from_secs(2 * 60 * 60),
+        )
+        .unwrap();
+        lease.save(temp_dir.path()).unwrap();
+        assert_eq!(SwitchLease::load(temp_dir.path()).unwrap(), Some(lease.clone()));
+
+        assert!(!lease.is_expired(lease.started_at));
+        assert!(lease.is_expired(lease.expires_at));
+        assert_eq!(lease.remaining_secs(lease.started_at + 60), 2 * 60 * 60 - 60);
+
+        SwitchLease::clear(temp_dir.path()).unwrap();
+        SwitchLease::clear(temp_dir.path()).unwrap();
+        assert_eq!(SwitchLease::load(temp_dir.path()).unwrap(), None);
+
+        assert!(SwitchLease::new("opus", PreviousState::Settings("{}".into()), Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(30), "1m");
+        assert_eq!(format_remaining(5 * 60), "5m");
+        assert_eq!(format_remaining(2 * 60 * 60), "2h");
+        assert_eq!(format_remaining(102 * 60), "1h 42m");
+        assert_eq!(format_remaining(26 * 60 * 60), "1d 2h");
+    }
+}
@@ -0,0 +1,361 @@
+// Per-profile actions of the tray menu and the structured menu ids that carry them
+use crate::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const PROFILE_ID_PREFIX: &str = "profile";
+const RECENT_ID_PREFIX: &str = "recent"; // "最近使用"中的条目，与主列表的 id 区分
+// 通知中最多列出的差异键数量
+const MAX_DIFF_KEYS_SHOWN: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfileAction {
+    Switch,
+    Edit,
+    Diff,
+    Duplicate,
+    CopyEnv,
+    Reveal,
+}
+
+impl ProfileAction {
+    /// Order of the entries in a profile's submenu
+    pub const ALL: [ProfileAction; 6] = [
+        ProfileAction::Switch,
+        ProfileAction::Edit,
+        ProfileAction::Diff,
+        ProfileAction::Duplicate,
+        ProfileAction::CopyEnv,
+        ProfileAction::Reveal,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfileAction::Switch => "switch",
+            ProfileAction::Edit => "edit",
+            ProfileAction::Diff => "diff",
+            ProfileAction::Duplicate => "duplicate",
+            ProfileAction::CopyEnv => "copy-env",
+            ProfileAction::Reveal => "reveal",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == action)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProfileAction::Switch => "Switch",
+            ProfileAction::Edit => "Edit…",
+            ProfileAction::Diff => "Diff vs current",
+            ProfileAction::Duplicate => "Duplicate",
+            ProfileAction::CopyEnv => "Copy env as shell",
+            ProfileAction::Reveal if cfg!(target_os = "macos") => "Reveal in Finder",
+            ProfileAction::Reveal if cfg!(windows) => "Show in Explorer",
+            ProfileAction::Reveal => "Show in file manager",
+        }
+    }
+}
+
+/// Menu id `profile:<action>:<profile>`, or `recent:<action>:<profile>` for the
+/// "Recent" section so its items don't share ids with the main list. The profile name
+/// is everything after the second colon, so subscribed profiles (`team:work`) need no
+/// escaping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileMenuId {
+    pub action: ProfileAction,
+    pub profile: String,
+    pub recent: bool,
+}
+
+impl ProfileMenuId {
+    pub fn new(action: ProfileAction, profile: &str) -> Self {
+        Self {
+            action,
+            profile: profile.to_string(),
+            recent: false,
+        }
+    }
+
+    /// Id of an entry in the "Recent" section
+    pub fn recent(action: ProfileAction, profile: &str) -> Self {
+        Self {
+            recent: true,
+            ..Self::new(action, profile)
+        }
+    }
+
+    pub fn to_id(&self) -> String {
+        let prefix = if self.recent { RECENT_ID_PREFIX } else { PROFILE_ID_PREFIX };
+        format!("{}:{}:{}", prefix, self.action.as_str(), self.profile)
+    }
+
+    pub fn parse(id: &str) -> Option<Self> {
+        let mut parts = id.splitn(3, ':');
+        let recent = match parts.next()? {
+            PROFILE_ID_PREFIX => false,
+            RECENT_ID_PREFIX => true,
+            _ => return None,
+        };
+        let action = ProfileAction::parse(parts.next()?)?;
+        let profile = parts.next().filter(|p| !p.is_empty())?;
+        Some(Self {
+            recent,
+            ..Self::new(action, profile)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyChangeKind {
+    Added,   // 只存在于配置中
+    Removed, // 只存在于当前设置中
+    Changed,
+}
+
+/// A settings key (dotted path) whose value differs between the current settings and a profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyChange {
+    pub key: String,
+    pub kind: KeyChangeKind,
+}
+
+/// Keys that would change if the profile were applied; values are left out on purpose
+/// so that resolved secrets never end up in a notification
+pub fn changed_keys(current: &Value, profile: &Value) -> Vec<KeyChange> {
+    let mut old = BTreeMap::new();
+    let mut new = BTreeMap::new();
+    flatten("", current, &mut old);
+    flatten("", profile, &mut new);
+
+    let mut changes = Vec::new();
+    for (key, value) in &new {
+        let kind = match old.get(key) {
+            None => KeyChangeKind::Added,
+            Some(old_value) if old_value != value => KeyChangeKind::Changed,
+            Some(_) => continue,
+        };
+        changes.push(KeyChange { key: key.clone(), kind });
+    }
+    changes.extend(old.keys().filter(|key| !new.contains_key(*key)).map(|key| KeyChange {
+        key: key.clone(),
+        kind: KeyChangeKind::Removed,
+    }));
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+/// Objects are walked key by key; arrays and scalars are compared as a whole
+fn flatten<'a>(prefix: &str, value: &'a Value, out: &mut BTreeMap<String, &'a Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, child, out);
+            }
+        }
+        _ if prefix.is_empty() => {}
+        _ => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+/// "3 settings differ: ~ model, + env.FOO, - permissions.allow"
+pub fn diff_summary(changes: &[KeyChange]) -> String {
+    if changes.is_empty() {
+        return "Identical to the current settings".to_string();
+    }
+    let shown: Vec<String> = changes
+        .iter()
+        .take(MAX_DIFF_KEYS_SHOWN)
+        .map(|change| {
+            let sign = match change.kind {
+                KeyChangeKind::Added => '+',
+                KeyChangeKind::Removed => '-',
+                KeyChangeKind::Changed => '~',
+            };
+            format!("{} {}", sign, change.key)
+        })
+        .collect();
+    let mut summary = match changes.len() {
+        1 => format!("1 setting differs: {}", shown.join(", ")),
+        count => format!("{} settings differ: {}", count, shown.join(", ")),
+    };
+    if changes.len() > MAX_DIFF_KEYS_SHOWN {
+        summary.push_str(&format!(" and {} more", changes.len() - MAX_DIFF_KEYS_SHOWN));
+    }
+    summary
+}
+
+/// Shell commands setting a profile's `env` block: `export KEY='value'`, or
+/// `$env:KEY = 'value'` for PowerShell on Windows. Keys that are not valid
+/// variable names are skipped.
+pub fn shell_exports(env: &serde_json::Map<String, Value>, powershell: bool) -> String {
+    let mut lines = Vec::new();
+    for (key, value) in env {
+        let valid_name = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            log::warn!("Skipping env key '{}': not a valid variable name", key);
+            continue;
+        }
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        lines.push(if powershell {
+            format!("$env:{} = '{}'", key, value.replace('\'', "''"))
+        } else {
+            format!("export {}='{}'", key, value.replace('\'', r"'\''"))
+        });
+    }
+    lines.join("\n")
+}
+
+/// Page of the settings window, focused on a profile when one is given
+pub fn settings_url(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => {
+            let encoded: String = profile
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+                    _ => format!("%{:02X}", b),
+                })
+                .collect();
+            format!("settings.html?profile={}", encoded)
+        }
+        None => "settings.html".to_string(),
+    }
+}
+
+/// Put text on the system clipboard with the platform's command line tool
+pub fn copy_to_clipboard(text: &str) -> AppResult<()> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+
+    for (program, args) in candidates {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        log::warn!("{} exited with {}", program, status);
+    }
+    Err(AppError::TrayError("No clipboard tool is available".to_string()))
+}
+
+/// Show a file or directory selected in the platform's file manager
+pub fn reveal_in_file_manager(path: &Path) -> AppResult<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        // xdg-open 不能选中文件，打开所在目录
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+    command.spawn().map(|_| ()).map_err(|e| {
+        AppError::TrayError(format!("Failed to open file manager for {:?}: {}", path, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_menu_id_round_trip() {
+        for action in ProfileAction::ALL {
+            let id = ProfileMenuId::new(action, "team:work").to_id();
+            assert_eq!(ProfileMenuId::parse(&id), Some(ProfileMenuId::new(action, "team:work")));
+            let id = ProfileMenuId::recent(action, "team:work").to_id();
+            assert_eq!(ProfileMenuId::parse(&id), Some(ProfileMenuId::recent(action, "team:work")));
+        }
+        assert_eq!(ProfileMenuId::new(ProfileAction::CopyEnv, "work").to_id(), "profile:copy-env:work");
+        assert_eq!(ProfileMenuId::recent(ProfileAction::Switch, "work").to_id(), "recent:switch:work");
+        assert_eq!(ProfileMenuId::parse("profile:launch:work"), None);
+        assert_eq!(ProfileMenuId::parse("profile:switch:"), None);
+        assert_eq!(ProfileMenuId::parse("profile_work"), None);
+        assert_eq!(ProfileMenuId::parse("lease_30_work"), None);
+    }
+
+    #[test]
+    fn test_changed_keys() {
+        let current = json!({
+            "model": "opus",
+            "env": {"A": "1", "B": "2"},
+            "permissions": {"allow": ["Read"]}
+        });
+        let profile = json!({
+            "model": "sonnet",
+            "env": {"A": "1", "C": "3"},
+            "permissions": {"allow": ["Read"]}
+        });
+        let changes = changed_keys(&current, &profile);
+        let keys: Vec<(&str, KeyChangeKind)> = changes.iter().map(|c| (c.key.as_str(), c.kind)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("env.B", KeyChangeKind::Removed),
+                ("env.C", KeyChangeKind::Added),
+                ("model", KeyChangeKind::Changed),
+            ]
+        );
+        assert_eq!(diff_summary(&changes), "3 settings differ: - env.B, + env.C, ~ model");
+        assert_eq!(diff_summary(&[]), "Identical to the current settings");
+    }
+
+    #[test]
+    fn test_shell_exports() {
+        let env = json!({"API_KEY": "it's", "TIMEOUT": 30, "bad-name": "x"});
+        let env = env.as_object().unwrap();
+        assert_eq!(shell_exports(env, false), "export API_KEY='it'\\''s'\nexport TIMEOUT='30'");
+        assert_eq!(shell_exports(env, true), "$env:API_KEY = 'it''s'\n$env:TIMEOUT = '30'");
+    }
+
+    #[test]
+    fn test_settings_url() {
+        assert_eq!(settings_url(None), "settings.html");
+        assert_eq!(settings_url(Some("team:my work")), "settings.html?profile=team%3Amy%20work");
+    }
+}
@@ -1,7 +1,11 @@
 // System tray service for CCCS
+use crate::profile_metadata::RecentProfiles;
+use crate::settings_service::SettingsService;
+use crate::switch_lease::{self, SwitchLease};
+use crate::tray_actions::{ProfileAction, ProfileMenuId};
 use crate::{AppError, AppResult, Profile, ProfileStatus};
 use tauri::{
-    menu::{Menu, MenuBuilder, MenuItemBuilder},
+    menu::{CheckMenuItemBuilder, Menu, MenuBuilder, MenuItemBuilder, Submenu, SubmenuBuilder},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     AppHandle, Emitter, Manager,
 };
@@ -10,17 +14,66 @@ pub struct TrayService {
     app_handle: AppHandle,
     current_menu: Option<Menu<tauri::Wry>>,
     tray_id: String,
+    claude_dir: Option<std::path::PathBuf>, // 用于读取临时切换租约
+    recent_profiles_path: Option<std::path::PathBuf>, // 最近切换的配置，显示在菜单顶部
+    tooltip: String, // 不含租约和会话提示的基础文本，定时刷新时复用
+    stale_sessions: Option<String>, // 每次刷新扫描一次进程，菜单和提示复用结果
 }
 
+/// A top-level entry of the profile list: a single profile or a group submenu (indices into the profiles)
+#[derive(Debug, PartialEq, Eq)]
+enum MenuEntry {
+    Profile(usize),
+    Group { name: String, members: Vec<usize> },
+}
+
+/// Durations offered by the "Switch temporarily" submenu, in minutes
+const LEASE_DURATIONS: [(u64, &str); 4] = [
+    (30, "30 minutes"),
+    (60, "1 hour"),
+    (120, "2 hours"),
+    (240, "4 hours"),
+];
+
 impl TrayService {
     pub fn new(app_handle: AppHandle) -> Self {
         Self {
             app_handle,
             current_menu: None,
             tray_id: "cccs_tray".to_string(),
+            claude_dir: None,
+            recent_profiles_path: None,
+            tooltip: String::new(),
+            stale_sessions: None,
         }
     }
     
+    /// Set the Claude directory the temporary switch lease is read from
+    pub fn set_claude_dir(&mut self, claude_dir: std::path::PathBuf) {
+        self.claude_dir = Some(claude_dir);
+    }
+    
+    /// Set the file listing the profiles recently switched to
+    pub fn set_recent_profiles_path(&mut self, path: std::path::PathBuf) {
+        self.recent_profiles_path = Some(path);
+    }
+    
+    fn current_lease(&self) -> Option<SwitchLease> {
+        let claude_dir = self.claude_dir.as_ref()?;
+        SwitchLease::load(claude_dir).ok().flatten()
+    }
+    
+    /// Set the warning about Claude Code sessions that started before the user settings
+    /// last changed; shown in the menu and tooltip until the next refresh
+    pub fn set_stale_sessions_label(&mut self, label: Option<String>) {
+        self.stale_sessions = label;
+    }
+
+    /// The stale session warning from the last refresh
+    pub fn stale_sessions_label(&self) -> Option<String> {
+        self.stale_sessions.clone()
+    }
+    
     /// Create and initialize the system tray icon with enhanced error handling
     pub fn create_tray(&mut self) -> AppResult<()> {
         log::info!("Creating system tray icon");
@@ -356,13 +409,13 @@ impl TrayService {
         // Add profile menu items
         for profile in profiles {
             let menu_text = if profile.is_active {
-                format!("✅ {}", profile.name)
+                Self::profile_label(profile, "✅")
             } else {
-                format!("　  {}", profile.name)  // 全角空格 + 两个普通空格
+                Self::profile_label(profile, "　 ")  // 全角空格 + 两个普通空格
             };
             
             let menu_item = MenuItemBuilder::with_id(
-                format!("profile_{}", profile.name),
+                ProfileMenuId::new(ProfileAction::Switch, &profile.name).to_id(),
                 menu_text
             ).build(&self.app_handle)?;
             
@@ -405,14 +458,17 @@ impl TrayService {
         // Add profile menu items with detailed status
         for (profile, status) in profiles.iter().zip(statuses.iter()) {
             let menu_text = match status {
-                ProfileStatus::FullMatch => format!("✅ {}", profile.name),      // 完全匹配 - 图标前置
-                ProfileStatus::PartialMatch => format!("🔄 {}", profile.name),  // 仅model字段不同 - 图标前置
-                ProfileStatus::NoMatch => format!("　  {}", profile.name),       // 配置不同，全角空格 + 两个普通空格
-                ProfileStatus::Error(_) => format!("❌ {}", profile.name),       // 错误状态 - 图标前置
+                ProfileStatus::FullMatch => Self::profile_label(profile, "✅"),      // 完全匹配 - 图标前置
+                ProfileStatus::PartialMatch => Self::profile_label(profile, "🔄"),  // 仅model字段不同 - 图标前置
+                ProfileStatus::NoMatch => Self::profile_label(profile, "　 "),       // 配置不同，全角空格 + 两个普通空格
+                ProfileStatus::Error(_) => Self::profile_label(profile, "❌"),       // 错误状态 - 图标前置
+                ProfileStatus::NonCompliant(_) => Self::profile_label(profile, "🚫"), // 违反组织策略
+                ProfileStatus::Unsigned => Self::profile_label(profile, "🔓"),        // 缺少签名
+                ProfileStatus::SignatureInvalid(_) => Self::profile_label(profile, "⛔"), // 签名无效
             };
             
             let menu_item = MenuItemBuilder::with_id(
-                format!("profile_{}", profile.name),
+                ProfileMenuId::new(ProfileAction::Switch, &profile.name).to_id(),
                 menu_text
             ).build(&self.app_handle)?;
             
@@ -431,15 +487,67 @@ impl TrayService {
             log::info!("Profiles found, creating profile menu");
             let mut menu_builder = MenuBuilder::new(&self.app_handle);
             
-            // Add profile items using simple text method
-            for (profile, status) in profiles.iter().zip(statuses.iter()) {
-                let menu_text = match status {
-                    ProfileStatus::FullMatch => format!("✅ {}", profile.name),
-                    ProfileStatus::PartialMatch => format!("🔄 {}", profile.name),
-                    ProfileStatus::NoMatch => format!("　  {}", profile.name),
-                    ProfileStatus::Error(_) => format!("❌ {}", profile.name),
-                };
-                menu_builder = menu_builder.text(&format!("profile_{}", profile.name), menu_text);
+            // Running temporary switch with remaining time; clicking reverts it now
+            if let Some(lease) = self.current_lease() {
+                let remaining = switch_lease::format_remaining(lease.remaining_secs(switch_lease::now_secs()));
+                menu_builder = menu_builder
+                    .text("lease_revert", format!("⏳ {} · {} left — Revert now", lease.profile, remaining))
+                    .separator();
+            }
+            
+            // Claude Code sessions that need a restart to pick up the switch
+            if let Some(label) = &self.stale_sessions {
+                let item = MenuItemBuilder::with_id("stale_sessions", format!("⚠️ {}", label))
+                    .enabled(false)
+                    .build(&self.app_handle)?;
+                menu_builder = menu_builder.item(&item).separator();
+            }
+            
+            let count = profiles.len().min(statuses.len());
+            let (profiles, statuses) = (&profiles[..count], &statuses[..count]);
+            
+            // Recently switched profiles, newest first
+            let recent = self.recent_profile_indices(profiles);
+            if !recent.is_empty() {
+                let header = MenuItemBuilder::with_id("recent_header", "Recent")
+                    .enabled(false)
+                    .build(&self.app_handle)?;
+                menu_builder = menu_builder.item(&header);
+                for index in recent {
+                    let profile = &profiles[index];
+                    menu_builder = menu_builder.text(
+                        ProfileMenuId::recent(ProfileAction::Switch, &profile.name).to_id(),
+                        Self::profile_menu_text(profile, &statuses[index]),
+                    );
+                }
+                menu_builder = menu_builder.separator();
+            }
+            
+            // Every profile opens a submenu of actions; grouped profiles sit in a further
+            // submenu where the group's first profile would be
+            for entry in Self::menu_entries(profiles) {
+                match entry {
+                    MenuEntry::Profile(index) => {
+                        menu_builder = menu_builder.item(&self.build_profile_submenu(&profiles[index], &statuses[index])?);
+                    }
+                    MenuEntry::Group { name, members } => {
+                        let label = Self::group_label(
+                            &name,
+                            &members.iter().map(|&i| (&profiles[i], &statuses[i])).collect::<Vec<_>>(),
+                        );
+                        let mut submenu = SubmenuBuilder::new(&self.app_handle, label);
+                        for index in members {
+                            submenu = submenu.item(&self.build_profile_submenu(&profiles[index], &statuses[index])?);
+                        }
+                        menu_builder = menu_builder.item(&submenu.build()?);
+                    }
+                }
+            }
+            
+            menu_builder = menu_builder.item(&self.build_lease_submenu(profiles)?);
+            
+            if let Some(schedules_menu) = self.build_schedules_submenu()? {
+                menu_builder = menu_builder.item(&schedules_menu);
             }
             
             menu_builder
@@ -459,6 +567,167 @@ impl TrayService {
         Ok(())
     }
     
+    /// Status icon, then the profile's icon or color swatch, then its name
+    fn profile_label(profile: &Profile, status_icon: &str) -> String {
+        match profile.metadata.badge() {
+            Some(badge) => format!("{} {} {}", status_icon, badge, profile.name),
+            None => format!("{} {}", status_icon, profile.name),
+        }
+    }
+    
+    fn status_icon(status: &ProfileStatus) -> &'static str {
+        match status {
+            ProfileStatus::FullMatch => "✅",
+            ProfileStatus::PartialMatch => "🔄",
+            ProfileStatus::NoMatch => "　 ", // 全角空格 + 空格，与图标对齐
+            ProfileStatus::Error(_) => "❌",
+            ProfileStatus::NonCompliant(_) => "🚫",
+            ProfileStatus::Unsigned => "🔓",
+            ProfileStatus::SignatureInvalid(_) => "⛔",
+        }
+    }
+    
+    fn profile_menu_text(profile: &Profile, status: &ProfileStatus) -> String {
+        let text = Self::profile_label(profile, Self::status_icon(status));
+        if profile.update_available {
+            format!("{} ⬆ update available", text)
+        } else {
+            text
+        }
+    }
+    
+    /// Switch, Edit, Diff vs current, Duplicate, Copy env as shell and Reveal for one profile
+    fn build_profile_submenu(&self, profile: &Profile, status: &ProfileStatus) -> AppResult<Submenu<tauri::Wry>> {
+        let mut submenu = SubmenuBuilder::new(&self.app_handle, Self::profile_menu_text(profile, status));
+        for action in ProfileAction::ALL {
+            submenu = submenu.text(ProfileMenuId::new(action, &profile.name).to_id(), action.label());
+        }
+        Ok(submenu.build()?)
+    }
+    
+    /// Subscribed profiles are grouped by their shared folder, other profiles by their first tag
+    fn profile_group(profile: &Profile) -> Option<&str> {
+        profile
+            .source
+            .as_deref()
+            .or_else(|| profile.metadata.tags.first().map(String::as_str))
+    }
+    
+    /// Top-level entries in profile order; a group takes the place of its first member
+    fn menu_entries(profiles: &[Profile]) -> Vec<MenuEntry> {
+        let mut entries: Vec<MenuEntry> = Vec::new();
+        for (index, profile) in profiles.iter().enumerate() {
+            let Some(group) = Self::profile_group(profile) else {
+                entries.push(MenuEntry::Profile(index));
+                continue;
+            };
+            let existing = entries.iter_mut().find_map(|entry| match entry {
+                MenuEntry::Group { name, members } if name == group => Some(members),
+                _ => None,
+            });
+            match existing {
+                Some(members) => members.push(index),
+                None => entries.push(MenuEntry::Group {
+                    name: group.to_string(),
+                    members: vec![index],
+                }),
+            }
+        }
+        entries
+    }
+    
+    /// Group name with the most relevant status of its profiles: the active one first, then problems
+    fn group_label(name: &str, members: &[(&Profile, &ProfileStatus)]) -> String {
+        let rank = |status: &ProfileStatus| match status {
+            ProfileStatus::FullMatch => 0,
+            ProfileStatus::PartialMatch => 1,
+            ProfileStatus::SignatureInvalid(_) => 2,
+            ProfileStatus::Error(_) => 3,
+            ProfileStatus::NonCompliant(_) => 4,
+            ProfileStatus::Unsigned => 5,
+            ProfileStatus::NoMatch => 6,
+        };
+        let icon = members
+            .iter()
+            .map(|(_, status)| *status)
+            .min_by_key(|status| rank(status))
+            .map_or(Self::status_icon(&ProfileStatus::NoMatch), Self::status_icon);
+        let mut label = format!("{} {} ({})", icon, name, members.len());
+        if members.iter().any(|(profile, _)| profile.update_available) {
+            label.push_str(" ⬆");
+        }
+        label
+    }
+    
+    /// Indices of the profiles to list under "Recent", newest first
+    fn recent_profile_indices(&self, profiles: &[Profile]) -> Vec<usize> {
+        let Some(path) = &self.recent_profiles_path else {
+            return Vec::new();
+        };
+        let count = match self.app_handle.try_state::<std::sync::Mutex<SettingsService>>() {
+            Some(state) => match state.lock() {
+                Ok(settings) => settings.get_current_settings().recent_profiles_count,
+                Err(_) => return Vec::new(),
+            },
+            None => crate::UserSettings::default_recent_profiles_count(),
+        };
+        match RecentProfiles::load(path) {
+            Ok(recent) => Self::pick_recent(&recent.profiles, profiles, count),
+            Err(e) => {
+                log::warn!("Failed to read recent profiles: {}", e);
+                Vec::new()
+            }
+        }
+    }
+    
+    /// Recent names that still exist, at most `count`
+    fn pick_recent(recent: &[String], profiles: &[Profile], count: usize) -> Vec<usize> {
+        recent
+            .iter()
+            .filter_map(|name| profiles.iter().position(|p| &p.name == name))
+            .take(count)
+            .collect()
+    }
+    
+    /// "Switch temporarily" submenu: one submenu per duration listing the profiles
+    fn build_lease_submenu(&self, profiles: &[Profile]) -> AppResult<Submenu<tauri::Wry>> {
+        let mut submenu = SubmenuBuilder::new(&self.app_handle, "Switch temporarily");
+        for (minutes, label) in LEASE_DURATIONS {
+            let mut duration_menu = SubmenuBuilder::new(&self.app_handle, label);
+            for profile in profiles {
+                duration_menu = duration_menu.text(format!("lease_{}_{}", minutes, profile.name), &profile.name);
+            }
+            submenu = submenu.item(&duration_menu.build()?);
+        }
+        Ok(submenu.build()?)
+    }
+    
+    /// Schedules submenu; a checked item is active, unchecking suspends it
+    fn build_schedules_submenu(&self) -> AppResult<Option<Submenu<tauri::Wry>>> {
+        let schedules = match self.app_handle.try_state::<std::sync::Mutex<SettingsService>>() {
+            Some(state) => match state.lock() {
+                Ok(settings) => settings.get_profile_schedules().to_vec(),
+                Err(_) => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        if schedules.is_empty() {
+            return Ok(None);
+        }
+        
+        let mut submenu = SubmenuBuilder::new(&self.app_handle, "Schedules");
+        for schedule in &schedules {
+            let item = CheckMenuItemBuilder::with_id(
+                format!("schedule_{}", schedule.name),
+                format!("{} → {}", schedule.name, schedule.profile),
+            )
+            .checked(!schedule.suspended)
+            .build(&self.app_handle)?;
+            submenu = submenu.item(&item);
+        }
+        Ok(Some(submenu.build()?))
+    }
+    
     /// Handle menu item click events with error handling
     fn handle_menu_event_safe(app: &AppHandle, event: tauri::menu::MenuEvent) -> AppResult<()> {
         let event_id = event.id().as_ref();
@@ -471,14 +740,34 @@ impl TrayService {
             "exit" => {
                 Self::handle_exit_click(app)
             }
-            id if id.starts_with("profile_") => {
-                let profile_name = id.strip_prefix("profile_").unwrap_or("");
-                Self::handle_profile_click(app, profile_name)
+            "lease_revert" => {
+                app.emit("lease_revert_requested", ())
+                    .map_err(|e| AppError::TrayError(format!("Failed to emit lease revert event: {}", e)))
             }
-            _ => {
-                log::warn!("Unhandled menu event: {}", event_id);
-                Ok(())
+            id if id.starts_with("lease_") => {
+                // lease_<分钟>_<配置名>
+                let rest = id.strip_prefix("lease_").unwrap_or("");
+                match rest.split_once('_').and_then(|(minutes, name)| Some((minutes.parse::<u64>().ok()?, name))) {
+                    Some((minutes, profile_name)) => app.emit("lease_switch_requested", (profile_name, minutes))
+                        .map_err(|e| AppError::TrayError(format!("Failed to emit lease switch event: {}", e))),
+                    None => {
+                        log::warn!("Invalid lease menu id: {}", id);
+                        Ok(())
+                    }
+                }
+            }
+            id if id.starts_with("schedule_") => {
+                let schedule_name = id.strip_prefix("schedule_").unwrap_or("");
+                app.emit("schedule_toggle_requested", schedule_name)
+                    .map_err(|e| AppError::TrayError(format!("Failed to emit schedule toggle event: {}", e)))
             }
+            id => match ProfileMenuId::parse(id) {
+                Some(menu_id) => Self::handle_profile_action(app, &menu_id),
+                None => {
+                    log::warn!("Unhandled menu event: {}", event_id);
+                    Ok(())
+                }
+            },
         }
     }
     
@@ -598,6 +887,20 @@ impl TrayService {
         Ok(())
     }
     
+    /// Switch and Edit are handled like their existing counterparts; the other actions
+    /// need the configuration service and are carried out by the app
+    fn handle_profile_action(app: &AppHandle, menu_id: &ProfileMenuId) -> AppResult<()> {
+        match menu_id.action {
+            ProfileAction::Switch => Self::handle_profile_click(app, &menu_id.profile),
+            ProfileAction::Edit => app
+                .emit("open_settings_window", Some(&menu_id.profile))
+                .map_err(|e| AppError::TrayError(format!("Failed to emit settings event: {}", e))),
+            action => app
+                .emit("profile_action_requested", (action, &menu_id.profile))
+                .map_err(|e| AppError::TrayError(format!("Failed to emit profile action event: {}", e))),
+        }
+    }
+    
     /// Handle profile menu item click
     fn handle_profile_click(app: &AppHandle, profile_name: &str) -> AppResult<()> {
         log::info!("Profile menu clicked: {}", profile_name);
@@ -634,17 +937,17 @@ impl TrayService {
         for profile in profiles {
             let menu_text = if let Some(temp_status) = status_updates.get(&profile.name) {
                 // Show temporary status (e.g., "❕ Profile")
-                format!("{} {}", temp_status, profile.name)
+                Self::profile_label(profile, temp_status)
             } else if profile.is_active {
                 // Show active status
-                format!("✅ {}", profile.name)
+                Self::profile_label(profile, "✅")
             } else {
                 // No status - use full-width space + two normal spaces
-                format!("　  {}", profile.name)
+                Self::profile_label(profile, "　 ")
             };
             
             let menu_item = MenuItemBuilder::with_id(
-                format!("profile_{}", profile.name),
+                ProfileMenuId::new(ProfileAction::Switch, &profile.name).to_id(),
                 menu_text
             ).build(&self.app_handle)?;
             
@@ -684,9 +987,26 @@ impl TrayService {
     }
     
     /// Set tray tooltip
-    pub fn set_tooltip(&self, text: &str) -> AppResult<()> {
+    pub fn set_tooltip(&mut self, text: &str) -> AppResult<()> {
+        self.tooltip = text.to_string();
+        self.refresh_tooltip()
+    }
+    
+    /// Re-apply the tooltip so the lease time and session count stay current
+    pub fn refresh_tooltip(&self) -> AppResult<()> {
+        let mut text = self.tooltip.clone();
+        if let Some(lease) = self.current_lease() {
+            text.push_str(&format!(
+                " (⏳ {}: {})",
+                lease.profile,
+                switch_lease::format_remaining(lease.remaining_secs(switch_lease::now_secs()))
+            ));
+        }
+        if let Some(label) = &self.stale_sessions {
+            text.push_str(&format!("\n⚠️ {}", label));
+        }
         if let Some(tray) = self.app_handle.tray_by_id(&self.tray_id) {
-            tray.set_tooltip(Some(text))?;
+            tray.set_tooltip(Some(&text))?;
         }
         Ok(())
     }
@@ -740,6 +1060,11 @@ mod tests {
             path: PathBuf::from(format!("{}.settings.json", name)),
             content: "{}".to_string(),
             is_active,
+            bundle_dir: None,
+            source: None,
+            update_available: false,
+            signature: Default::default(),
+            metadata: Default::default(),
         }
     }
     
@@ -775,4 +1100,48 @@ mod tests {
         assert_eq!(active_text, "✅ active");
         assert_eq!(inactive_text, "　  inactive");
     }
+    
+    #[test]
+    fn test_menu_entries_group_by_folder_and_tag() {
+        let mut profiles = vec![
+            create_test_profile("home", false),
+            create_test_profile("acme-prod", false),
+            create_test_profile("team:shared", false),
+            create_test_profile("acme-dev", false),
+        ];
+        profiles[1].metadata.tags = vec!["acme".to_string(), "prod".to_string()];
+        profiles[3].metadata.tags = vec!["acme".to_string()];
+        profiles[2].source = Some("team".to_string());
+        
+        assert_eq!(
+            TrayService::menu_entries(&profiles),
+            vec![
+                MenuEntry::Profile(0),
+                MenuEntry::Group { name: "acme".to_string(), members: vec![1, 3] },
+                MenuEntry::Group { name: "team".to_string(), members: vec![2] },
+            ]
+        );
+        
+        let statuses = [ProfileStatus::Unsigned, ProfileStatus::FullMatch];
+        let members: Vec<_> = [&profiles[1], &profiles[3]].into_iter().zip(statuses.iter()).collect();
+        assert_eq!(TrayService::group_label("acme", &members), "✅ acme (2)");
+        assert_eq!(TrayService::group_label("acme", &members[..1]), "🔓 acme (1)");
+    }
+    
+    #[test]
+    fn test_pick_recent() {
+        let profiles = vec![create_test_profile("a", false), create_test_profile("b", false), create_test_profile("c", false)];
+        let recent = vec!["c".to_string(), "deleted".to_string(), "a".to_string(), "b".to_string()];
+        assert_eq!(TrayService::pick_recent(&recent, &profiles, 2), vec![2, 0]);
+        assert!(TrayService::pick_recent(&recent, &profiles, 0).is_empty());
+    }
+    
+    #[test]
+    fn test_profile_label_with_metadata() {
+        let mut profile = create_test_profile("work", false);
+        assert_eq!(TrayService::profile_label(&profile, "　 "), "　  work");
+        
+        profile.metadata.icon = Some("🚀".to_string());
+        assert_eq!(TrayService::profile_label(&profile, "✅"), "✅ 🚀 work");
+    }
 }
\ No newline at end of file
@@ -1,4 +1,9 @@
 // CCCS Types definitions
+use crate::directory_rules::DirectoryRule;
+use crate::profile_metadata::ProfileMetadata;
+use crate::profile_signing::{SignatureState, TrustedKey};
+use crate::schedule_service::ProfileSchedule;
+use crate::subscriptions::ProfileSubscription;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::SystemTime;
@@ -9,6 +14,16 @@ pub struct Profile {
     pub path: PathBuf,
     pub content: String,
     pub is_active: bool,
+    #[serde(default)]
+    pub bundle_dir: Option<PathBuf>, // 目录形式的配置（<name>.profile/），path 指向其中的 settings.json
+    #[serde(default)]
+    pub source: Option<String>, // 来自订阅的配置（只读），值为订阅名
+    #[serde(default)]
+    pub update_available: bool, // 订阅的共享目录中有尚未同步的修改
+    #[serde(default)]
+    pub signature: SignatureState, // 配置了受信任公钥时的签名校验结果
+    #[serde(default)]
+    pub metadata: ProfileMetadata, // 描述、标签、颜色、图标和排序，保存在 CCCS 的 profile_metadata.json
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,12 +34,21 @@ pub struct ProfileInfo {
     pub is_default: bool,
     pub last_modified: SystemTime,
     pub file_size: u64,
+    pub permission_warning: Option<String>, // 含明文密钥且其他用户可读时的警告
+    #[serde(default)]
+    pub source: Option<String>, // 订阅名；订阅的配置只能编辑本地覆盖层
+    #[serde(default)]
+    pub update_available: bool,
+    #[serde(default)]
+    pub metadata: ProfileMetadata,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub is_valid: bool,
     pub errors: Vec<ValidationError>,
+    #[serde(default)]
+    pub warnings: Vec<ValidationError>, // 不阻止保存的警告（例如文件权限）
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +74,20 @@ pub struct UserSettings {
     pub show_notifications: bool,
     #[serde(default = "UserSettings::get_default_ignored_fields")]
     pub ignored_fields: Vec<String>, // 配置比较时要忽略的字段列表
+    #[serde(default)]
+    pub projects: Vec<PathBuf>, // 已登记的项目目录，可将配置应用到其 .claude/ 下
+    #[serde(default)]
+    pub directory_rules: Vec<DirectoryRule>, // 目录规则，按顺序匹配，第一个生效
+    #[serde(default)]
+    pub profile_schedules: Vec<ProfileSchedule>, // 定时切换计划
+    #[serde(default)]
+    pub git_store_enabled: bool, // 将配置的改动和切换记录为 git 提交
+    #[serde(default)]
+    pub subscriptions: Vec<ProfileSubscription>, // 团队共享的只读配置目录
+    #[serde(default)]
+    pub trusted_keys: Vec<TrustedKey>, // 非空时只允许切换到由这些公钥签名的配置
+    #[serde(default = "UserSettings::default_recent_profiles_count")]
+    pub recent_profiles_count: usize, // 托盘"最近使用"中显示的数量，0 表示不显示
 }
 
 impl Default for UserSettings {
@@ -60,11 +98,22 @@ impl Default for UserSettings {
             language: None,
             show_notifications: true,
             ignored_fields: Self::get_default_ignored_fields(),
+            projects: Vec::new(),
+            directory_rules: Vec::new(),
+            profile_schedules: Vec::new(),
+            git_store_enabled: false,
+            subscriptions: Vec::new(),
+            trusted_keys: Vec::new(),
+            recent_profiles_count: Self::default_recent_profiles_count(),
         }
     }
 }
 
 impl UserSettings {
+    pub fn default_recent_profiles_count() -> usize {
+        3
+    }
+    
     /// 获取默认的忽略字段列表
     pub fn get_default_ignored_fields() -> Vec<String> {
         vec![
@@ -107,12 +156,38 @@ impl UserSettings {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretInfo {
+    pub name: String,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub initialized: bool,
+    pub unlocked: bool,
+    pub key_source: Option<String>, // "passphrase" 或 "keyring"
+    pub secret_count: usize,
+    #[serde(default)]
+    pub error: Option<String>, // 金库文件损坏或无法读取
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionFixResult {
+    pub fixed_files: Vec<String>,
+    pub failed_files: Vec<String>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ProfileStatus {
     FullMatch,      // 完全匹配 ✅
     PartialMatch,   // 部分匹配（忽略model字段后匹配）🔄
     NoMatch,        // 不匹配 ❌
     Error(String),  // 错误状态
+    NonCompliant(Vec<String>), // 违反组织策略 🚫，附带违规说明
+    Unsigned,                  // 已配置受信任公钥但配置没有签名 🔓
+    SignatureInvalid(String),  // 签名与内容不符或公钥不受信任 ⛔
 }
 
 #[derive(Debug)]
@@ -8,6 +8,9 @@ pub trait Validator {
     fn get_validator_name(&self) -> &'static str;
 }
 
+/// Rule findings of these types are reported as warnings and don't block saving
+pub const WARNING_TYPES: &[&str] = &["hook_program"];
+
 pub struct JsonValidator {
     rules: Vec<Box<dyn ValidationRule>>,
 }
@@ -42,6 +45,7 @@ impl Validator for JsonFormatValidator {
         Ok(ValidationResult {
             is_valid: errors.is_empty(),
             errors,
+            warnings: Vec::new(),
         })
     }
     
@@ -204,6 +208,7 @@ impl JsonValidator {
             return Ok(ValidationResult {
                 is_valid: false,
                 errors: all_errors,
+                warnings: Vec::new(),
             });
         }
         
@@ -212,14 +217,20 @@ impl JsonValidator {
             .map_err(|e| AppError::ConfigError(format!("Failed to parse JSON: {}", e)))?;
         
         // Run all semantic rules
+        let mut warnings = Vec::new();
         for rule in &self.rules {
-            let rule_errors = rule.validate(&json_value);
+            let (rule_warnings, rule_errors): (Vec<_>, Vec<_>) = rule
+                .validate(&json_value)
+                .into_iter()
+                .partition(|e| WARNING_TYPES.contains(&e.error_type.as_str()));
             all_errors.extend(rule_errors);
+            warnings.extend(rule_warnings);
         }
         
         Ok(ValidationResult {
             is_valid: all_errors.is_empty(),
             errors: all_errors,
+            warnings,
         })
     }
     
@@ -884,6 +884,7 @@ class ContentEditor {
       // Validate JSON
       const validationResult = await invoke("validate_json_content", {
         content: this.currentContent,
+        profileId: globalState.currentProfile,
       });
 
       if (!validationResult.is_valid) {
@@ -896,6 +897,11 @@ class ContentEditor {
         return;
       }
 
+      // Non-blocking warnings, e.g. secrets readable by other users
+      (validationResult.warnings || []).forEach((w) =>
+        showToast(w.message, "warning")
+      );
+
       // Save profile
       await invoke("save_profile", {
         profileId: globalState.currentProfile,